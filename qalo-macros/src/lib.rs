@@ -0,0 +1,111 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Error, FnArg, ItemFn};
+
+/// Exports a Rust function to Qalo scripts.
+///
+/// Next to the function, it generates a module with the same name containing:
+/// - `NAME`, the name of the function in scripts;
+/// - `native`, a wrapper checking the arity and converting the arguments with `FromObject`
+///   and the returned value with `IntoObject`;
+/// - `register`, which adds the wrapper to the global environment of an evaluator;
+/// - `native_function`, which returns the wrapper as a `NativeFunction`, e.g. for `register_module`.
+///
+/// ```ignore
+/// #[qalo::function]
+/// fn clamp(x: i64, lo: i64, hi: i64) -> i64 {
+///     x.max(lo).min(hi)
+/// }
+///
+/// clamp::register(&mut evaluator);
+/// ```
+#[proc_macro_attribute]
+pub fn function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as ItemFn);
+
+    match expand(attr.into(), function) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(attr: TokenStream2, function: ItemFn) -> Result<TokenStream2, Error> {
+    if !attr.is_empty() {
+        return Err(Error::new(
+            attr.span(),
+            "`qalo::function` takes no arguments",
+        ));
+    }
+
+    let signature = &function.sig;
+    if !signature.generics.params.is_empty() {
+        return Err(Error::new(
+            signature.generics.span(),
+            "exported functions can't be generic",
+        ));
+    }
+    if let Some(asyncness) = signature.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            "exported functions can't be async",
+        ));
+    }
+
+    let types = signature
+        .inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Typed(arg) => Ok(&arg.ty),
+            FnArg::Receiver(receiver) => Err(Error::new(
+                receiver.span(),
+                "exported functions can't take `self`",
+            )),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let vis = &function.vis;
+    let ident = &signature.ident;
+    let name = ident.to_string();
+    let arity = types.len();
+    let indices = 0..arity;
+
+    Ok(quote! {
+        #function
+
+        #[doc = concat!("Qalo bindings of [`", #name, "`](fn@", #name, ").")]
+        #vis mod #ident {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub const NAME: &str = #name;
+
+            pub fn native(
+                arguments: ::std::vec::Vec<::qalo::object::Object>,
+            ) -> ::std::result::Result<::qalo::object::Object, ::qalo::object::EvalError> {
+                if arguments.len() != #arity {
+                    return ::std::result::Result::Err(
+                        ::qalo::object::EvalError::FunctionCallWrongArity(
+                            #arity as u8,
+                            arguments.len() as u8,
+                        ),
+                    );
+                }
+
+                let result = super::#ident(#(
+                    <#types as ::qalo::native::FromObject>::from_object(&arguments[#indices])?
+                ),*);
+
+                ::qalo::native::IntoObject::into_object(result)
+            }
+
+            pub fn register(evaluator: &mut ::qalo::evaluator::Evaluator<'_>) {
+                evaluator.register_function(NAME, native);
+            }
+
+            pub fn native_function() -> ::qalo::native::NativeFunction {
+                ::qalo::native::NativeFunction::new(NAME, native)
+            }
+        }
+    })
+}
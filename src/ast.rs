@@ -1,16 +1,31 @@
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fmt,
-    num::{ParseIntError, TryFromIntError},
+    hash::{Hash, Hasher},
+    num::{ParseFloatError, ParseIntError, TryFromIntError},
     rc::Rc,
 };
 
 use thiserror::Error;
 
-use crate::token::{Token, TokenKind};
+use crate::{
+    cache::StableHasher,
+    lexer::is_identifier_like,
+    token::{Span, Token, TokenKind},
+};
 
-#[derive(Debug)]
-pub struct Program(pub Vec<Statement>);
+#[derive(Debug, Clone)]
+pub struct Program(pub Vec<Located>);
+
+impl Program {
+    /// Hash of the syntax tree, which is the same across runs and platforms:
+    /// programs that only differ in their formatting or comments have the same fingerprint.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        self.0.hash(&mut hasher);
+        hasher.finish()
+    }
+}
 
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -21,13 +36,41 @@ impl fmt::Display for Program {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Statement of a program or of a block, with its location in the source.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Located {
+    pub statement: Statement,
+    pub span: Span,
+}
+
+impl fmt::Display for Located {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.statement)
+    }
+}
+
+impl Hash for Located {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the span is left out, like the one of calls
+        self.statement.hash(state);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
-    // TODO: support different types of var statements
     VarStatement {
         kind: TokenKind,
         name: String,
         value: Expression,
+        /// Note of the `@deprecated("...")` attribute marking the declared function as deprecated.
+        deprecated: Option<String>,
+    },
+
+    /// `let` or `var` binding the parts of a value to the identifiers of a pattern, e.g. `let [a, b] = pair;`.
+    DestructureStatement {
+        kind: TokenKind,
+        pattern: Pattern,
+        value: Expression,
     },
 
     ReturnStatement(Option<Expression>),
@@ -39,15 +82,71 @@ pub enum Statement {
 
     ExpressionStatement(Expression),
 
-    BlockStatement(Vec<Statement>),
+    BlockStatement(Vec<Located>),
+
+    ForStatement {
+        label: Option<String>,
+        variable: String,
+        iterable: Expression,
+        body: Box<Statement>,
+    },
+
+    /// Loop checking its condition after each iteration, so that its body runs at least once.
+    DoWhileStatement {
+        label: Option<String>,
+        body: Box<Statement>,
+        condition: Expression,
+    },
+
+    /// Declares a struct, like `struct Point { x, y }`, binding its name to the definition of its fields.
+    StructStatement {
+        name: String,
+        fields: Vec<String>,
+    },
+
+    /// Declares an enum, like `enum Color { Red, Custom(r, g, b) }`, binding its name to its variants.
+    EnumStatement {
+        name: String,
+        variants: Vec<EnumVariant>,
+    },
+
+    /// Runs the body of the first arm whose value is equal to `value`, or the `default` one if none is.
+    SwitchStatement {
+        value: Expression,
+        arms: Vec<SwitchArm>,
+        default: Box<Statement>,
+    },
+
+    /// Exits the loop with the label, or the innermost one:
+    /// a `loop` evaluates to the value of its `break`, if any.
+    BreakStatement {
+        label: Option<String>,
+        value: Option<Expression>,
+    },
+
+    /// Skips to the next iteration of the loop with the label, or of the innermost one.
+    ContinueStatement(Option<String>),
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Statement::VarStatement { kind, name, value } => {
+            Statement::VarStatement {
+                kind,
+                name,
+                value,
+                deprecated,
+            } => {
+                if let Some(note) = deprecated {
+                    write!(f, "@deprecated({note:?}) ")?;
+                }
                 write!(f, "{} {} = {};", kind, name, value)
             }
+            Statement::DestructureStatement {
+                kind,
+                pattern,
+                value,
+            } => write!(f, "{kind} {pattern} = {value};"),
             Statement::ReturnStatement(expr) => {
                 if let Some(expr) = expr {
                     write!(f, "return {expr};")
@@ -64,24 +163,183 @@ impl fmt::Display for Statement {
                 }
                 write!(f, "}}")
             }
+            Statement::ForStatement {
+                label,
+                variable,
+                iterable,
+                body,
+            } => {
+                if let Some(label) = label {
+                    write!(f, "'{label}: ")?;
+                }
+                write!(f, "for {variable} in {iterable} {body}")
+            }
+            Statement::DoWhileStatement {
+                label,
+                body,
+                condition,
+            } => {
+                if let Some(label) = label {
+                    write!(f, "'{label}: ")?;
+                }
+                write!(f, "do {body} while {condition};")
+            }
+            Statement::StructStatement { name, fields } if fields.is_empty() => {
+                write!(f, "struct {name} {{}}")
+            }
+            Statement::StructStatement { name, fields } => {
+                write!(f, "struct {name} {{ {} }}", fields.join(", "))
+            }
+            Statement::EnumStatement { name, variants } if variants.is_empty() => {
+                write!(f, "enum {name} {{}}")
+            }
+            Statement::EnumStatement { name, variants } => {
+                write!(f, "enum {name} {{ {} }}", format_variants(variants))
+            }
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                write!(f, "switch {value} {{")?;
+                for arm in arms {
+                    write!(f, "{arm}")?;
+                }
+                write!(f, "_ => {default}}}")
+            }
+            Statement::BreakStatement { label, value } => {
+                write!(f, "break")?;
+                if let Some(label) = label {
+                    write!(f, " '{label}")?;
+                }
+                if let Some(value) = value {
+                    write!(f, " {value}")?;
+                }
+                write!(f, ";")
+            }
+            Statement::ContinueStatement(label) => match label {
+                Some(label) => write!(f, "continue '{label};"),
+                None => write!(f, "continue;"),
+            },
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+impl Hash for Statement {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // explicit tags, as the values of `mem::discriminant` depend on the compiler
+        state.write_u8(match self {
+            Statement::VarStatement { .. } => 0,
+            Statement::DestructureStatement { .. } => 1,
+            Statement::ReturnStatement(..) => 2,
+            Statement::AssignStatement { .. } => 3,
+            Statement::ExpressionStatement(..) => 4,
+            Statement::BlockStatement(..) => 5,
+            Statement::ForStatement { .. } => 6,
+            Statement::DoWhileStatement { .. } => 7,
+            Statement::StructStatement { .. } => 8,
+            Statement::EnumStatement { .. } => 9,
+            Statement::SwitchStatement { .. } => 10,
+            Statement::BreakStatement { .. } => 11,
+            Statement::ContinueStatement(..) => 12,
+        });
+
+        match self {
+            Statement::VarStatement {
+                kind,
+                name,
+                value,
+                deprecated,
+            } => {
+                kind.hash(state);
+                name.hash(state);
+                value.hash(state);
+                deprecated.hash(state);
+            }
+            Statement::DestructureStatement {
+                kind,
+                pattern,
+                value,
+            } => {
+                kind.hash(state);
+                pattern.hash(state);
+                value.hash(state);
+            }
+            Statement::ReturnStatement(value) => value.hash(state),
+            Statement::AssignStatement { name, value } => {
+                name.hash(state);
+                value.hash(state);
+            }
+            Statement::ExpressionStatement(expression) => expression.hash(state),
+            Statement::BlockStatement(statements) => statements.hash(state),
+            Statement::ForStatement {
+                label,
+                variable,
+                iterable,
+                body,
+            } => {
+                label.hash(state);
+                variable.hash(state);
+                iterable.hash(state);
+                body.hash(state);
+            }
+            Statement::DoWhileStatement {
+                label,
+                body,
+                condition,
+            } => {
+                label.hash(state);
+                body.hash(state);
+                condition.hash(state);
+            }
+            Statement::StructStatement { name, fields } => {
+                name.hash(state);
+                fields.hash(state);
+            }
+            Statement::EnumStatement { name, variants } => {
+                name.hash(state);
+                variants.hash(state);
+            }
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                value.hash(state);
+                arms.hash(state);
+                default.hash(state);
+            }
+            Statement::BreakStatement { label, value } => {
+                label.hash(state);
+                value.hash(state);
+            }
+            Statement::ContinueStatement(label) => label.hash(state),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Identifier(String),
 
-    IntegerLiteral(i32),
+    IntegerLiteral(i64),
+    FloatLiteral(f64),
 
     BooleanLiteral(bool),
+    NullLiteral,
 
     StringLiteral(String),
 
     ArrayLiteral(Vec<Expression>),
 
     // TODO: support different types of keys, as long as they are hashable.
-    MapLiteral(HashMap<String, Expression>),
+    MapLiteral(BTreeMap<String, Expression>),
+
+    /// Builds a value of a struct, like `Point { x: 1, y: 2 }`, with its fields in the order they're written.
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Expression)>,
+    },
 
     BinaryExpression {
         left: Box<Expression>,
@@ -89,9 +347,19 @@ pub enum Expression {
         right: Box<Expression>,
     },
 
+    /// Chained comparison such as `1 < x < 10`: it behaves like `1 < x && x < 10`,
+    /// but every operand is evaluated at most once.
+    ComparisonChain {
+        operands: Vec<Expression>,
+        operators: Vec<TokenKind>,
+    },
+
+    /// Prefix operation like `-x`, or `x?`, the only postfix operator that isn't a call, index or field access.
     UnaryExpression {
         operator: TokenKind,
         value: Box<Expression>,
+        /// Location of the operand in the source, which errors about its type point to.
+        span: Span,
     },
 
     IndexExpression {
@@ -99,13 +367,25 @@ pub enum Expression {
         index: Box<Expression>,
     },
 
+    FieldAccess {
+        value: Box<Expression>,
+        field: String,
+    },
+
     GroupedExpression(Box<Expression>),
 
     CallExpression {
-        path: Box<Expression>,
+        callee: Box<Expression>,
         arguments: Vec<Expression>,
+        /// Arguments matched to the parameters by name, e.g. `x: 1`, after the positional ones.
+        named: Vec<(String, Expression)>,
+        /// Location of the call in the source, from the callee to the closing parenthesis.
+        span: Span,
     },
 
+    /// Boxed, as it would make every expression larger.
+    MethodCall(Box<MethodCall>),
+
     IfExpression {
         condition: Box<Expression>,
         consequence: Box<Statement>,
@@ -114,8 +394,24 @@ pub enum Expression {
 
     FunctionExpression {
         parameters: Vec<String>,
+        /// Parameter collecting the remaining arguments into an array, e.g. `...rest`.
+        rest: Option<String>,
+        body: Box<Statement>,
+    },
+
+    MatchExpression {
+        value: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+
+    /// Repeats its body until a `break` exits it.
+    LoopExpression {
+        label: Option<String>,
         body: Box<Statement>,
     },
+
+    /// Block used as a value, like `{ let a = f(); a * 2 }`, which is the value of its last statement.
+    BlockExpression(Box<Statement>),
 }
 
 impl fmt::Display for Expression {
@@ -123,7 +419,9 @@ impl fmt::Display for Expression {
         match self {
             Expression::Identifier(s) => write!(f, "{s}"),
             Expression::IntegerLiteral(n) => write!(f, "{n}"),
+            Expression::FloatLiteral(n) => write!(f, "{n:?}"),
             Expression::BooleanLiteral(b) => write!(f, "{b}"),
+            Expression::NullLiteral => write!(f, "null"),
             Expression::StringLiteral(s) => write!(f, "\"{s}\""),
             Expression::ArrayLiteral(elements) => {
                 write!(f, "[")?;
@@ -145,6 +443,16 @@ impl fmt::Display for Expression {
                 }
                 write!(f, "}}")
             }
+            Expression::StructLiteral { name, fields } if fields.is_empty() => {
+                write!(f, "{name} {{}}")
+            }
+            Expression::StructLiteral { name, fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!("{field}: {value}"))
+                    .collect();
+                write!(f, "{name} {{ {} }}", fields.join(", "))
+            }
             Expression::BinaryExpression {
                 left,
                 operator,
@@ -152,26 +460,44 @@ impl fmt::Display for Expression {
             } => {
                 write!(f, "({left} {operator} {right})")
             }
-            Expression::UnaryExpression { operator, value } => {
+            Expression::ComparisonChain {
+                operands,
+                operators,
+            } => {
+                write!(f, "(")?;
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " {} ", operators[i - 1])?;
+                    }
+                    write!(f, "{operand}")?;
+                }
+                write!(f, ")")
+            }
+            Expression::UnaryExpression {
+                operator: TokenKind::Question,
+                value,
+                ..
+            } => write!(f, "({value}?)"),
+            Expression::UnaryExpression {
+                operator, value, ..
+            } => {
                 write!(f, "({operator}{value})")
             }
             Expression::IndexExpression { value, index } => {
                 write!(f, "({value}[{index}])")
             }
+            Expression::FieldAccess { value, field } => write!(f, "{value}.{field}"),
             Expression::GroupedExpression(expr) => write!(f, "{expr}"),
-            Expression::CallExpression { path, arguments } => {
-                write!(f, "{path}(")?;
-
-                for (i, arg) in arguments.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, ", ")?;
-                    }
-
-                    write!(f, "{arg}")?;
-                }
-
-                write!(f, ")")
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                write!(f, "{callee}")?;
+                write_arguments(f, arguments, named)
             }
+            Expression::MethodCall(call) => write!(f, "{call}"),
 
             Expression::IfExpression {
                 condition,
@@ -185,21 +511,414 @@ impl fmt::Display for Expression {
                 }
             }
 
-            Expression::FunctionExpression { parameters, body } => {
-                write!(f, "fn(")?;
-                for (i, param) in parameters.iter().enumerate() {
-                    write!(f, "{}", param)?;
-                    if i < parameters.len() - 1 {
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => {
+                write!(f, "fn({}) {}", format_parameters(parameters, rest), body)
+            }
+
+            Expression::MatchExpression { value, arms } => {
+                write!(f, "match {value} {{")?;
+                for arm in arms {
+                    write!(f, "{arm}")?;
+                }
+                write!(f, "}}")
+            }
+
+            Expression::LoopExpression { label, body } => {
+                if let Some(label) = label {
+                    write!(f, "'{label}: ")?;
+                }
+                write!(f, "loop {body}")
+            }
+
+            Expression::BlockExpression(body) => write!(f, "{body}"),
+        }
+    }
+}
+
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // explicit tags, like the ones of statements
+        state.write_u8(match self {
+            Expression::Identifier(..) => 0,
+            Expression::IntegerLiteral(..) => 1,
+            Expression::FloatLiteral(..) => 2,
+            Expression::BooleanLiteral(..) => 3,
+            Expression::NullLiteral => 4,
+            Expression::StringLiteral(..) => 5,
+            Expression::ArrayLiteral(..) => 6,
+            Expression::MapLiteral(..) => 7,
+            Expression::StructLiteral { .. } => 8,
+            Expression::BinaryExpression { .. } => 9,
+            Expression::ComparisonChain { .. } => 10,
+            Expression::UnaryExpression { .. } => 11,
+            Expression::IndexExpression { .. } => 12,
+            Expression::FieldAccess { .. } => 13,
+            Expression::GroupedExpression(..) => 14,
+            Expression::CallExpression { .. } => 15,
+            Expression::MethodCall(..) => 16,
+            Expression::IfExpression { .. } => 17,
+            Expression::FunctionExpression { .. } => 18,
+            Expression::MatchExpression { .. } => 19,
+            Expression::LoopExpression { .. } => 20,
+            Expression::BlockExpression(..) => 21,
+        });
+
+        match self {
+            Expression::Identifier(name) => name.hash(state),
+            Expression::IntegerLiteral(n) => state.write(&n.to_le_bytes()),
+            Expression::FloatLiteral(n) => state.write(&n.to_bits().to_le_bytes()),
+            Expression::BooleanLiteral(b) => b.hash(state),
+            Expression::NullLiteral => {}
+            Expression::StringLiteral(s) => s.hash(state),
+            Expression::ArrayLiteral(elements) => elements.hash(state),
+            Expression::MapLiteral(map) => map.hash(state),
+            Expression::StructLiteral { name, fields } => {
+                name.hash(state);
+                fields.hash(state);
+            }
+            Expression::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                left.hash(state);
+                operator.hash(state);
+                right.hash(state);
+            }
+            Expression::ComparisonChain {
+                operands,
+                operators,
+            } => {
+                operands.hash(state);
+                operators.hash(state);
+            }
+            // the span is left out, like the one of calls
+            Expression::UnaryExpression {
+                operator, value, ..
+            } => {
+                operator.hash(state);
+                value.hash(state);
+            }
+            Expression::IndexExpression { value, index } => {
+                value.hash(state);
+                index.hash(state);
+            }
+            Expression::FieldAccess { value, field } => {
+                value.hash(state);
+                field.hash(state);
+            }
+            Expression::GroupedExpression(expression) => expression.hash(state),
+            // the span is left out, as fingerprints don't depend on formatting
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                callee.hash(state);
+                arguments.hash(state);
+                named.hash(state);
+            }
+            Expression::MethodCall(call) => call.hash(state),
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                condition.hash(state);
+                consequence.hash(state);
+                alternative.hash(state);
+            }
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => {
+                parameters.hash(state);
+                rest.hash(state);
+                body.hash(state);
+            }
+            Expression::MatchExpression { value, arms } => {
+                value.hash(state);
+                arms.hash(state);
+            }
+            Expression::LoopExpression { label, body } => {
+                label.hash(state);
+                body.hash(state);
+            }
+            Expression::BlockExpression(body) => body.hash(state),
+        }
+    }
+}
+
+/// Formats the parameters of a function, like `a, b, ...rest`.
+fn write_arguments(
+    f: &mut fmt::Formatter,
+    arguments: &[Expression],
+    named: &[(String, Expression)],
+) -> fmt::Result {
+    write!(f, "(")?;
+
+    for (i, arg) in arguments.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{arg}")?;
+    }
+
+    for (i, (name, arg)) in named.iter().enumerate() {
+        if i > 0 || !arguments.is_empty() {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{name}: {arg}")?;
+    }
+
+    write!(f, ")")
+}
+
+pub fn format_parameters(parameters: &[String], rest: &Option<String>) -> String {
+    let rest = rest.iter().map(|rest| format!("...{rest}"));
+    parameters
+        .iter()
+        .cloned()
+        .chain(rest)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Whether `name` is read back as an identifier, rather than as a keyword or several tokens.
+fn is_identifier(name: &str) -> bool {
+    is_identifier_like(name) && TokenKind::lookup_identifier(name) == TokenKind::Identifier
+}
+
+/// Call of a method, e.g. `items.len()`: a built-in function taking the receiver as first argument,
+/// chosen by the type of the receiver, or a member of modules, structs and enums.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MethodCall {
+    pub receiver: Expression,
+    pub method: String,
+    pub arguments: Vec<Expression>,
+    pub named: Vec<(String, Expression)>,
+    /// Location of the call in the source, from the receiver to the closing parenthesis.
+    pub span: Span,
+}
+
+impl fmt::Display for MethodCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.receiver, self.method)?;
+        write_arguments(f, &self.arguments, &self.named)
+    }
+}
+
+impl Hash for MethodCall {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the span is left out, like the one of calls
+        self.receiver.hash(state);
+        self.method.hash(state);
+        self.arguments.hash(state);
+        self.named.hash(state);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    /// Condition of `case pattern if guard:`, checked with the bindings of the pattern
+    /// after it matched: the arm is skipped if it's false.
+    pub guard: Option<Expression>,
+    pub body: Statement,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "case {}", self.pattern)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {guard}")?;
+        }
+        write!(f, ": {};", self.body)
+    }
+}
+
+/// Variant of an enum, like `Red` or `Custom(r, g, b)`: variants without fields are values on their own,
+/// the others are built by calling them.
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl fmt::Display for EnumVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.fields.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}({})", self.name, self.fields.join(", "))
+        }
+    }
+}
+
+/// Variants of the enums every program can use without declaring them, `Result` and `Option`,
+/// with their enum and their field. They're also bound to their own names, like `Ok` for `Result.Ok`.
+pub const PRELUDE_VARIANTS: [(&str, &str, Option<&str>); 4] = [
+    ("Result", "Ok", Some("value")),
+    ("Result", "Err", Some("error")),
+    ("Option", "Some", Some("value")),
+    ("Option", "None", None),
+];
+
+/// Enum of a variant of the prelude, like `Result` for `Ok`.
+pub fn prelude_enum(variant: &str) -> Option<&'static str> {
+    PRELUDE_VARIANTS
+        .iter()
+        .find(|(_, name, _)| *name == variant)
+        .map(|(name, ..)| *name)
+}
+
+/// Formats the variants of an enum, like `Red, Custom(r, g, b)`.
+pub fn format_variants(variants: &[EnumVariant]) -> String {
+    variants
+        .iter()
+        .map(EnumVariant::to_string)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Arm of a `switch`, like `1 => "one"`.
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub struct SwitchArm {
+    pub case: Expression,
+    pub body: Statement,
+}
+
+impl fmt::Display for SwitchArm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} => {}, ", self.case, self.body)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub enum Pattern {
+    Wildcard,
+
+    Identifier(String),
+
+    Literal(Expression),
+
+    Array {
+        elements: Vec<Pattern>,
+        /// Pattern binding the remaining elements, e.g. `..rest`.
+        rest: Option<Box<Pattern>>,
+    },
+
+    /// Pattern matching the values of some keys of a hash map, e.g. `{name, age: a}`.
+    Map {
+        entries: Vec<(String, Pattern)>,
+    },
+
+    /// Pattern matching a variant of an enum and its fields, e.g. `Color.Custom(r, _, 0)`.
+    Variant {
+        name: String,
+        variant: String,
+        fields: Vec<Pattern>,
+    },
+
+    Or(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Whether the pattern only binds identifiers, so that it can't fail on a value of the right shape.
+    pub fn binds_only(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Identifier(_) => true,
+            Pattern::Literal(_) | Pattern::Variant { .. } | Pattern::Or(_) => false,
+            Pattern::Array { elements, rest } => {
+                elements.iter().all(Pattern::binds_only)
+                    && rest.as_deref().is_none_or(Pattern::binds_only)
+            }
+            Pattern::Map { entries } => entries.iter().all(|(_, pattern)| pattern.binds_only()),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Identifier(name) => write!(f, "{name}"),
+            Pattern::Literal(expr) => write!(f, "{expr}"),
+            Pattern::Array { elements, rest } => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
                         write!(f, ", ")?;
                     }
+                    write!(f, "{element}")?;
                 }
-                write!(f, ") {}", body)
+                if let Some(rest) = rest {
+                    if !elements.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "..{rest}")?;
+                }
+                write!(f, "]")
+            }
+            Pattern::Map { entries } => {
+                write!(f, "{{")?;
+                for (i, (key, pattern)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match pattern {
+                        // shorthand for binding a key to an identifier with the same name
+                        Pattern::Identifier(name) if name == key => write!(f, "{key}")?,
+                        pattern if is_identifier(key) => write!(f, "{key}: {pattern}")?,
+                        pattern => write!(f, "\"{key}\": {pattern}")?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            Pattern::Variant {
+                name,
+                variant,
+                fields,
+            } => {
+                // the variants of the prelude are written on their own, like `Some(x)`
+                if prelude_enum(variant) != Some(name.as_str()) {
+                    write!(f, "{name}.")?;
+                }
+                write!(f, "{variant}")?;
+                if !fields.is_empty() {
+                    write!(f, "(")?;
+                    for (i, field) in fields.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{field}")?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Pattern::Or(patterns) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{pattern}")?;
+                }
+                Ok(())
             }
         }
     }
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum ParserError {
     #[error("Syntax error: {0}")]
     SyntaxError(String),
@@ -210,9 +929,85 @@ pub enum ParserError {
     #[error("Operator received an invalid operand type: {0:#?}")]
     InvalidOperandType(Rc<Token>),
 
-    #[error("Failed to parse to a 32 bit integer: {0}")]
+    #[error("Failed to parse to a 64 bit integer: {0}")]
     ParseIntError(#[from] ParseIntError),
 
+    #[error("Malformed number literal `{0}`: {1}")]
+    MalformedNumber(String, String),
+
+    #[error("Failed to parse to a 64 bit float: {0}")]
+    ParseFloatError(#[from] ParseFloatError),
+
     #[error("Conversion to int failed: {0}")]
     IntConversionError(#[from] TryFromIntError),
+
+    #[error("Unterminated block comment starting at byte {}, it must be closed with `*/`", .0.start)]
+    UnterminatedComment(Span),
+
+    #[error("Unterminated template placeholder starting at byte {}, it must be closed with `}}}}`", .0.start)]
+    UnterminatedPlaceholder(Span),
+
+    #[error("Only an expression is allowed here, found a statement starting with `{}`", .0.literal)]
+    StatementNotAllowed(Rc<Token>),
+
+    #[error("`{}` can only be used inside a loop, and not from a function defined in it", .0.literal)]
+    OutsideLoop(Rc<Token>),
+
+    #[error("The label `{}` isn't defined by any loop enclosing it", .0.literal)]
+    UndefinedLabel(Rc<Token>),
+
+    #[error("`switch` must end with a default `_` arm, which runs when no other arm matches")]
+    MissingDefaultArm(Rc<Token>),
+
+    #[error("Expressions are nested too deeply, the maximum depth is {0}")]
+    NestingTooDeep(usize),
+
+    #[error("Unexpected identifier `{}`, did you mean the keyword `{1}`?", .0.literal)]
+    KeywordTypo(Rc<Token>, TokenKind),
+
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n"))]
+    Multiple(Vec<ParserError>),
+}
+
+impl ParserError {
+    /// Stable code of the error, described in details by `qalo explain <code>`.
+    /// Multiple errors take the code of the first one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::UnexpectedToken(_) => "E0001",
+            ParserError::SyntaxError(_) => "E0002",
+            ParserError::InvalidOperandType(_) => "E0003",
+            ParserError::MalformedNumber(_, _) => "E0004",
+            ParserError::ParseIntError(_) => "E0005",
+            ParserError::ParseFloatError(_) => "E0006",
+            ParserError::IntConversionError(_) => "E0007",
+            ParserError::UnterminatedComment(_) => "E0008",
+            ParserError::NestingTooDeep(_) => "E0009",
+            ParserError::KeywordTypo(_, _) => "E0010",
+            ParserError::UnterminatedPlaceholder(_) => "E0011",
+            ParserError::StatementNotAllowed(_) => "E0012",
+            ParserError::OutsideLoop(_) => "E0013",
+            ParserError::UndefinedLabel(_) => "E0014",
+            ParserError::MissingDefaultArm(_) => "E0015",
+            ParserError::Multiple(errors) => errors.first().map_or("E0002", ParserError::code),
+        }
+    }
+
+    /// Location of the error in the source, if it's known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParserError::UnexpectedToken(token)
+            | ParserError::InvalidOperandType(token)
+            | ParserError::KeywordTypo(token, _)
+            | ParserError::StatementNotAllowed(token)
+            | ParserError::OutsideLoop(token)
+            | ParserError::UndefinedLabel(token)
+            | ParserError::MissingDefaultArm(token) => Some(token.span),
+            ParserError::UnterminatedComment(span) | ParserError::UnterminatedPlaceholder(span) => {
+                Some(*span)
+            }
+            ParserError::Multiple(errors) => errors.first().and_then(ParserError::span),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,344 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use crate::{
+    ast::Program,
+    environment::Environment,
+    evaluator::Evaluator,
+    object::{EvalError, Object},
+    purity::is_pure_program,
+};
+
+/// FNV-1a hasher: unlike the default hasher of the standard library,
+/// its results are the same across runs, platforms and Rust versions.
+#[derive(Debug, Clone)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // integers are written in little endian, and `usize` as 64 bits, to be independent from the platform
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Results of pure programs (see `is_pure_program`), keyed on the fingerprint of the program and the hash of the environment
+/// they were evaluated in, so that hosts evaluating the same rules over and over can skip the work.
+#[derive(Debug, Default)]
+pub struct EvalCache {
+    results: HashMap<(u64, u64), Vec<Object>>,
+}
+
+impl EvalCache {
+    pub fn new() -> Self {
+        EvalCache::default()
+    }
+
+    /// Evaluates `program` in `env`, reusing the previous result if it was already evaluated
+    /// in an environment with the same values. Programs with effects, and environments holding values
+    /// that can't be hashed, like functions, are always evaluated. Errors aren't cached.
+    pub fn eval(
+        &mut self,
+        program: &Program,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<Vec<Object>, EvalError> {
+        let mut env_hasher = StableHasher::default();
+        let key = (is_pure_program(program, env) && hash_env(&env.borrow(), &mut env_hasher))
+            .then(|| (program.fingerprint(), env_hasher.finish()));
+
+        if let Some(objects) = key.and_then(|key| self.results.get(&key)) {
+            return Ok(objects.clone());
+        }
+
+        let objects = Evaluator::with_env("", env.clone()).eval_parsed_program(program.clone())?;
+
+        // closures would keep the environments they captured alive
+        let plain = objects
+            .iter()
+            .all(|obj| hash_object(obj, &mut StableHasher::default()));
+        if let (Some(key), true) = (key, plain) {
+            self.results.insert(key, objects.clone());
+        }
+
+        Ok(objects)
+    }
+
+    /// Number of cached results.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.results.clear();
+    }
+}
+
+/// Hashes the values of `env` and of the environments enclosing it,
+/// returning false if some of them can't be hashed.
+fn hash_env(env: &Environment, state: &mut StableHasher) -> bool {
+    let mut entries: Vec<(&String, &Object)> = env.store.iter().collect();
+    entries.sort_by_key(|(name, _)| *name);
+
+    write_len(entries.len(), state);
+    let hashed = entries.into_iter().all(|(name, value)| {
+        name.hash(state);
+        hash_object(value, state)
+    });
+
+    match &env.outer {
+        Some(outer) if hashed => hash_env(&outer.borrow(), state),
+        _ => hashed,
+    }
+}
+
+/// Writes a length as 64 bits in little endian, whatever the size of `usize`.
+fn write_len(len: usize, state: &mut StableHasher) {
+    state.write(&(len as u64).to_le_bytes());
+}
+
+/// Hashes plain data, returning false for values that can't be compared by their contents,
+/// like closures and native values.
+fn hash_object(obj: &Object, state: &mut StableHasher) -> bool {
+    match obj {
+        Object::IntegerValue(n) => {
+            state.write_u8(0);
+            state.write(&n.to_le_bytes());
+        }
+        Object::FloatValue(n) => {
+            state.write_u8(1);
+            state.write(&n.to_bits().to_le_bytes());
+        }
+        Object::BooleanValue(b) => {
+            state.write_u8(2);
+            b.hash(state);
+        }
+        Object::StringValue(s) => {
+            state.write_u8(3);
+            s.hash(state);
+        }
+        Object::NullValue => state.write_u8(4),
+        Object::UnitValue => state.write_u8(5),
+        Object::RangeValue {
+            start,
+            end,
+            inclusive,
+        } => {
+            state.write_u8(6);
+            state.write(&start.to_le_bytes());
+            state.write(&end.to_le_bytes());
+            inclusive.hash(state);
+        }
+        Object::BuiltinValue(builtin) => {
+            state.write_u8(7);
+            builtin.to_string().hash(state);
+        }
+        Object::ArrayValue(elements) => {
+            state.write_u8(8);
+            write_len(elements.len(), state);
+            return elements.iter().all(|element| hash_object(element, state));
+        }
+        Object::DequeValue(elements) => {
+            state.write_u8(9);
+            write_len(elements.len(), state);
+            return elements.iter().all(|element| hash_object(element, state));
+        }
+        Object::MapValue(map) => {
+            state.write_u8(10);
+            write_len(map.len(), state);
+            return map.iter().all(|(key, value)| {
+                key.hash(state);
+                hash_object(value, state)
+            });
+        }
+        Object::StructType { name, fields } => {
+            state.write_u8(11);
+            name.hash(state);
+            fields.hash(state);
+        }
+        Object::StructValue { name, fields } => {
+            state.write_u8(12);
+            name.hash(state);
+            write_len(fields.len(), state);
+            return fields.iter().all(|(field, value)| {
+                field.hash(state);
+                hash_object(value, state)
+            });
+        }
+        Object::EnumType { name, variants } => {
+            state.write_u8(13);
+            name.hash(state);
+            variants.hash(state);
+        }
+        Object::EnumValue {
+            name,
+            variant,
+            values,
+        } => {
+            state.write_u8(14);
+            name.hash(state);
+            variant.hash(state);
+            write_len(values.len(), state);
+            return values.iter().all(|value| hash_object(value, state));
+        }
+        Object::VariantConstructor { name, variant } => {
+            state.write_u8(15);
+            name.hash(state);
+            variant.hash(state);
+        }
+        Object::FunctionValue(_)
+        | Object::NativeFunctionValue(_)
+        | Object::NativeValue(_)
+        | Object::WeakValue(_)
+        | Object::TaskValue(_)
+        | Object::ActorValue(_)
+        | Object::ModuleValue { .. } => return false,
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        Parser::new(input).parse_program().unwrap()
+    }
+
+    #[test]
+    fn fingerprint_programs() {
+        let program = parse("let a = {\"x\": 1, \"y\": [2.5, true]}; a[\"x\"] + 1");
+
+        // formatting and comments don't matter
+        assert_eq!(
+            program.fingerprint(),
+            parse("let a = { \"x\": 1, /* y */ \"y\": [2.5, true] };\na[\"x\"] + 1").fingerprint()
+        );
+        // neither does the order of map entries
+        assert_eq!(
+            program.fingerprint(),
+            parse("let a = {\"y\": [2.5, true], \"x\": 1}; a[\"x\"] + 1").fingerprint()
+        );
+        assert_ne!(
+            program.fingerprint(),
+            parse("let a = {\"x\": 1, \"y\": [2.5, true]}; a[\"x\"] + 2").fingerprint()
+        );
+        assert_ne!(
+            parse("let a = 1;").fingerprint(),
+            parse("var a = 1;").fingerprint()
+        );
+        assert_ne!(
+            parse("\"a\" + \"b\"").fingerprint(),
+            parse("\"a\"; \"b\"").fingerprint()
+        );
+    }
+
+    #[test]
+    fn stable_fingerprints() {
+        // a fingerprint can be saved, so it must not change with the compiler or the platform
+        assert_eq!(
+            parse("let a = [1, -2.5, \"x\"]; if (a[0] > 0) { a } else { null }").fingerprint(),
+            0xa450_8d88_e988_3ff6
+        );
+    }
+
+    #[test]
+    fn cache_pure_programs() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.borrow_mut()
+            .set("price".to_owned(), Object::IntegerValue(10));
+        let mut cache = EvalCache::new();
+
+        let rule = parse("let discount = fn(x) { x / 2 }; discount(price)");
+        assert_eq!(
+            cache.eval(&rule, &env).unwrap(),
+            vec![Object::UnitValue, Object::IntegerValue(5)]
+        );
+        // the top-level `let` is visible to the host, so the program isn't cached
+        assert!(cache.is_empty());
+        env.borrow_mut().store.remove("discount");
+
+        let rule = parse("fn(x) { x / 2 }(price) > 3");
+        assert_eq!(
+            cache.eval(&rule, &env).unwrap(),
+            vec![Object::BooleanValue(true)]
+        );
+        assert_eq!(
+            cache.eval(&rule, &env).unwrap(),
+            vec![Object::BooleanValue(true)]
+        );
+        assert_eq!(cache.len(), 1);
+
+        // a different environment is a different entry
+        env.borrow_mut()
+            .set("price".to_owned(), Object::IntegerValue(4));
+        assert_eq!(
+            cache.eval(&rule, &env).unwrap(),
+            vec![Object::BooleanValue(false)]
+        );
+        assert_eq!(cache.len(), 2);
+
+        for effectful in [
+            "println(price)",
+            "var n = 0; fn() { n = 1; }()",
+            "freeze(price)",
+        ] {
+            cache.eval(&parse(effectful), &env).unwrap();
+        }
+        // closures aren't cached
+        cache.eval(&parse("fn(x) { x }"), &env).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        // environments holding closures can't be hashed
+        Evaluator::with_env("let f = fn() { 1 };", env.clone())
+            .eval_program()
+            .unwrap();
+        cache.eval(&rule, &env).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        env.borrow_mut().store.remove("f");
+        assert!(cache.eval(&parse("price / 0"), &env).is_err());
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{Expression, Located, MatchArm, Pattern, Statement},
+    object::BuiltinFunction,
+    token::TokenKind,
+};
+
+/// Identifiers referenced by a function that aren't bound by the function itself,
+/// which are the only ones its closure has to capture.
+/// Built-in functions called by name are left out, as they don't depend on the environment.
+pub fn free_variables(
+    parameters: &[String],
+    rest: &Option<String>,
+    body: &Statement,
+) -> HashSet<String> {
+    let mut collector = Collector {
+        scopes: vec![parameters.iter().chain(rest).cloned().collect()],
+        free: HashSet::new(),
+    };
+    collector.statement(body);
+
+    collector.free
+}
+
+struct Collector {
+    /// Identifiers bound by the function, one set for each nested scope.
+    scopes: Vec<HashSet<String>>,
+    free: HashSet<String>,
+}
+
+impl Collector {
+    fn reference(&mut self, name: &str) {
+        if !self.scopes.iter().any(|scope| scope.contains(name)) {
+            self.free.insert(name.to_owned());
+        }
+    }
+
+    fn bind(&mut self, name: &str) {
+        // there's always at least the scope of the parameters
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned());
+        }
+    }
+
+    fn scoped(&mut self, bindings: HashSet<String>, collect: impl FnOnce(&mut Self)) {
+        self.scopes.push(bindings);
+        collect(self);
+        self.scopes.pop();
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(name) => self.reference(name),
+
+            Expression::IntegerLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::NullLiteral
+            | Expression::StringLiteral(_) => {}
+
+            Expression::ArrayLiteral(elements) => {
+                elements.iter().for_each(|element| self.expression(element))
+            }
+            Expression::MapLiteral(map) => map.values().for_each(|value| self.expression(value)),
+            Expression::StructLiteral { name, fields } => {
+                self.reference(name);
+                fields.iter().for_each(|(_, value)| self.expression(value));
+            }
+            Expression::ComparisonChain { operands, .. } => {
+                operands.iter().for_each(|operand| self.expression(operand))
+            }
+
+            Expression::BinaryExpression { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::IndexExpression { value, index } => {
+                self.expression(value);
+                self.expression(index);
+            }
+            Expression::UnaryExpression { value, .. }
+            | Expression::FieldAccess { value, .. }
+            | Expression::GroupedExpression(value) => self.expression(value),
+
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                match callee.as_ref() {
+                    // built-in functions are searched through before the environment
+                    Expression::Identifier(name)
+                        if BuiltinFunction::lookup_function(name).is_ok() => {}
+                    callee => self.expression(callee),
+                }
+                arguments
+                    .iter()
+                    .chain(named.iter().map(|(_, argument)| argument))
+                    .for_each(|argument| self.expression(argument));
+            }
+
+            Expression::MethodCall(call) => {
+                self.expression(&call.receiver);
+                call.arguments
+                    .iter()
+                    .chain(call.named.iter().map(|(_, argument)| argument))
+                    .for_each(|argument| self.expression(argument));
+            }
+
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.expression(condition);
+                self.statement(consequence);
+                if let Some(alternative) = alternative {
+                    self.statement(alternative);
+                }
+            }
+
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => self.scoped(
+                parameters.iter().chain(rest).cloned().collect(),
+                |collector| collector.statement(body),
+            ),
+
+            Expression::MatchExpression { value, arms } => {
+                self.expression(value);
+                arms.iter().for_each(|arm| self.match_arm(arm));
+            }
+            Expression::LoopExpression { body, .. } | Expression::BlockExpression(body) => {
+                self.statement(body)
+            }
+        }
+    }
+
+    fn match_arm(&mut self, arm: &MatchArm) {
+        let mut bindings = HashSet::new();
+        self.pattern(&arm.pattern, &mut bindings);
+
+        self.scoped(bindings, |collector| {
+            if let Some(guard) = &arm.guard {
+                collector.expression(guard);
+            }
+            collector.statement(&arm.body)
+        });
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, bindings: &mut HashSet<String>) {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Identifier(name) => {
+                bindings.insert(name.clone());
+            }
+            Pattern::Literal(literal) => self.expression(literal),
+            Pattern::Array { elements, rest } => {
+                for element in elements.iter().chain(rest.as_deref()) {
+                    self.pattern(element, bindings);
+                }
+            }
+            Pattern::Map { entries } => entries
+                .iter()
+                .for_each(|(_, pattern)| self.pattern(pattern, bindings)),
+            Pattern::Variant { name, fields, .. } => {
+                // the enum is looked up to check the variant
+                self.reference(name);
+                fields
+                    .iter()
+                    .for_each(|pattern| self.pattern(pattern, bindings));
+            }
+            Pattern::Or(patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.pattern(pattern, bindings)),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VarStatement { name, value, .. } => {
+                // functions can refer to the name they're bound to
+                if matches!(value, Expression::FunctionExpression { .. }) {
+                    self.bind(name);
+                }
+                self.expression(value);
+                self.bind(name);
+            }
+            Statement::DestructureStatement { pattern, value, .. } => {
+                self.expression(value);
+                let mut bindings = HashSet::new();
+                self.pattern(pattern, &mut bindings);
+                bindings.iter().for_each(|name| self.bind(name));
+            }
+            Statement::AssignStatement { name, value } => {
+                self.reference(name);
+                self.expression(value);
+            }
+            Statement::ReturnStatement(value) | Statement::BreakStatement { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::ContinueStatement(_) => {}
+            Statement::StructStatement { name, .. } | Statement::EnumStatement { name, .. } => {
+                self.bind(name)
+            }
+            Statement::DoWhileStatement {
+                body, condition, ..
+            } => {
+                self.statement(body);
+                self.expression(condition);
+            }
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                self.expression(value);
+                for arm in arms {
+                    self.expression(&arm.case);
+                    self.statement(&arm.body);
+                }
+                self.statement(default);
+            }
+            Statement::ExpressionStatement(expression) => self.expression(expression),
+            Statement::BlockStatement(statements) => {
+                self.scoped(hoisted(statements), |collector| {
+                    statements
+                        .iter()
+                        .for_each(|located| collector.statement(&located.statement))
+                })
+            }
+            Statement::ForStatement {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expression(iterable);
+                self.scoped(HashSet::from([variable.clone()]), |collector| {
+                    collector.statement(body)
+                });
+            }
+        }
+    }
+}
+
+/// Names of the functions hoisted by a block, which can be referenced before their declaration.
+fn hoisted(statements: &[Located]) -> HashSet<String> {
+    statements
+        .iter()
+        .filter_map(|located| match &located.statement {
+            Statement::VarStatement {
+                kind: TokenKind::Let,
+                name,
+                value: Expression::FunctionExpression { .. },
+                ..
+            } => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn free_variables_of(input: &str) -> Vec<String> {
+        let mut program = Parser::new(input).parse_program().unwrap();
+        let Some(Statement::ExpressionStatement(Expression::FunctionExpression {
+            parameters,
+            rest,
+            body,
+        })) = program.0.pop().map(|located| located.statement)
+        else {
+            panic!("expected a function: {input}");
+        };
+
+        let mut free: Vec<String> = free_variables(&parameters, &rest, &body)
+            .into_iter()
+            .collect();
+        free.sort();
+        free
+    }
+
+    #[test]
+    fn collect_free_variables() {
+        let tests = vec![
+            ("fn(x) { x + y }", vec!["y"]),
+            ("fn(x, ...rest) { [x, rest, len(rest)] }", vec![]),
+            ("fn() { let a = 1; a + b }", vec!["b"]),
+            ("fn() { let a = a + 1; a }", vec!["a"]),
+            ("fn() { total = total + 1; }", vec!["total"]),
+            ("fn() { let f = fn(n) { f(n - g) }; f(1) }", vec!["g"]),
+            ("fn() { h(); let h = fn() { 1 }; }", vec![]),
+            (
+                "fn() { for i in items { i + step } }",
+                vec!["items", "step"],
+            ),
+            ("fn() { if c { let a = 1; a } else { a } }", vec!["a", "c"]),
+            (
+                "fn(p) { match p { case [a, ..r]: a + k, case {x: b}: b, case _: r } }",
+                vec!["k", "r"],
+            ),
+            (
+                "fn(p) { match p { case [a] if a > lim: a, case _: 0 } }",
+                vec!["lim"],
+            ),
+            ("fn() { let [a, {b}] = pair; a + b }", vec!["pair"]),
+            ("fn() { obj.field(named: v) }", vec!["obj", "v"]),
+            ("fn() { let len = 1; len }", vec![]),
+            ("fn() { len }", vec!["len"]),
+            ("fn() { struct P { x } [P { x: y }, Q {}] }", vec!["Q", "y"]),
+            (
+                "fn(c) { enum E { A } match c { case E.A | F.B(x): x, case _: 0 } }",
+                vec!["F"],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(free_variables_of(input), expected, "{input}");
+        }
+    }
+}
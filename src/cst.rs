@@ -0,0 +1,246 @@
+use crate::{
+    ast::{ParserError, Program},
+    lexer::Lexer,
+    parser::Parser,
+    token::{Span, TokenKind},
+};
+
+/// Lossless parse tree of a source: the parse tree along with every token, comment and whitespace,
+/// at their exact position. Tools like the formatter use it to keep comments,
+/// and codemods to edit the source while leaving the rest of it untouched.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree<'a> {
+    pub source: &'a str,
+    pub program: Program,
+    /// Tokens of the source, ending with `TokenKind::Eof`, which holds the trivia at the end of the source.
+    pub tokens: Vec<CstToken>,
+    /// Spans of the statements and blocks, as recorded by `Parser::spans`.
+    pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstToken {
+    pub kind: TokenKind,
+    pub span: Span,
+    /// Whitespace and comments between the previous token and this one.
+    pub leading: Vec<Trivia>,
+}
+
+/// Part of the source that isn't a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// `// ...`, up to the end of the line.
+    LineComment,
+    /// `/* ... */`, possibly spanning multiple lines.
+    BlockComment,
+}
+
+impl<'a> SyntaxTree<'a> {
+    pub fn parse(source: &'a str) -> Result<Self, ParserError> {
+        let mut parser = Parser::new(source);
+        let program = parser.parse_program()?;
+
+        let mut lexer = Lexer::new(source);
+        let mut spans = vec![];
+        loop {
+            let token = lexer.next_token();
+            spans.push((token.kind.clone(), token.span));
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+        }
+
+        let mut comments = lexer.comments.into_iter().peekable();
+        let mut end = 0;
+        let tokens = spans
+            .into_iter()
+            .map(|(kind, span)| {
+                // the gap before the token is made of comments and of the whitespace around them
+                let mut leading = vec![];
+                let mut start = end;
+                while let Some(comment) = comments.next_if(|comment| comment.end <= span.start) {
+                    push_whitespace(&mut leading, start, comment.start);
+                    let kind = if source[comment.start..].starts_with("//") {
+                        TriviaKind::LineComment
+                    } else {
+                        TriviaKind::BlockComment
+                    };
+                    leading.push(Trivia {
+                        kind,
+                        span: comment,
+                    });
+                    start = comment.end;
+                }
+                push_whitespace(&mut leading, start, span.start);
+                end = span.end;
+
+                CstToken {
+                    kind,
+                    span,
+                    leading,
+                }
+            })
+            .collect();
+
+        Ok(SyntaxTree {
+            source,
+            program,
+            tokens,
+            spans: parser.spans,
+        })
+    }
+
+    pub fn text(&self, span: Span) -> &'a str {
+        &self.source[span.start..span.end]
+    }
+
+    /// Writes the tree back, which gives the source it was parsed from.
+    pub fn to_source(&self) -> String {
+        let mut source = String::with_capacity(self.source.len());
+        for token in &self.tokens {
+            for trivia in &token.leading {
+                source.push_str(self.text(trivia.span));
+            }
+            source.push_str(self.text(token.span));
+        }
+
+        source
+    }
+
+    /// Comments of the source, in the order they appear.
+    pub fn comments(&self) -> impl Iterator<Item = &Trivia> {
+        self.tokens
+            .iter()
+            .flat_map(|token| &token.leading)
+            .filter(|trivia| trivia.kind != TriviaKind::Whitespace)
+    }
+
+    /// Token covering the byte `offset` of the source, if it isn't in some trivia.
+    pub fn token_at(&self, offset: usize) -> Option<&CstToken> {
+        self.tokens
+            .iter()
+            .find(|token| token.span.start <= offset && offset < token.span.end)
+    }
+
+    /// Source where every span is replaced by its text, leaving the rest of it unchanged.
+    /// Edits must not overlap.
+    pub fn edit(&self, edits: impl IntoIterator<Item = (Span, &'a str)>) -> String {
+        let mut edits: Vec<_> = edits.into_iter().collect();
+        edits.sort_by_key(|(span, _)| span.start);
+
+        let mut source = String::with_capacity(self.source.len());
+        let mut end = 0;
+        for (span, text) in edits {
+            source.push_str(&self.source[end..span.start]);
+            source.push_str(text);
+            end = span.end;
+        }
+        source.push_str(&self.source[end..]);
+
+        source
+    }
+}
+
+fn push_whitespace(trivia: &mut Vec<Trivia>, start: usize, end: usize) {
+    if start < end {
+        trivia.push(Trivia {
+            kind: TriviaKind::Whitespace,
+            span: Span { start, end },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless_tree() {
+        let source = "// header\nlet x = 1; /* inline */ let y = [x,  2];\n\n/* end */\n";
+        let tree = SyntaxTree::parse(source).unwrap();
+
+        assert_eq!(tree.to_source(), source);
+        assert_eq!(tree.program.to_string(), "let x = 1;let y = [x, 2];");
+        assert_eq!(
+            tree.comments()
+                .map(|comment| tree.text(comment.span))
+                .collect::<Vec<_>>(),
+            vec!["// header", "/* inline */", "/* end */"]
+        );
+
+        let eof = tree.tokens.last().unwrap();
+        assert_eq!(eof.kind, TokenKind::Eof);
+        assert_eq!(
+            eof.leading
+                .iter()
+                .map(|trivia| trivia.kind)
+                .collect::<Vec<_>>(),
+            vec![
+                TriviaKind::Whitespace,
+                TriviaKind::BlockComment,
+                TriviaKind::Whitespace
+            ]
+        );
+        // statements, with the blocks and statements nested in them
+        assert_eq!(
+            tree.spans
+                .iter()
+                .map(|span| tree.text(*span))
+                .collect::<Vec<_>>(),
+            vec!["let x = 1;", "let y = [x,  2];"]
+        );
+    }
+
+    #[test]
+    fn edit_source() {
+        let source = "let total = add(1, 2); // sum\nadd(total, 3)";
+        let tree = SyntaxTree::parse(source).unwrap();
+
+        // rename `add` to `plus`, leaving the formatting and the comment untouched
+        let edits = tree
+            .tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Identifier && tree.text(token.span) == "add")
+            .map(|token| (token.span, "plus"));
+        assert_eq!(
+            tree.edit(edits),
+            "let total = plus(1, 2); // sum\nplus(total, 3)"
+        );
+
+        let token = tree.token_at(13).unwrap();
+        assert_eq!(tree.text(token.span), "add");
+        assert_eq!(tree.token_at(25), None);
+    }
+
+    #[test]
+    fn statement_spans() {
+        let source = "let f = fn(x) { let y = x; y }; ({\"b\": fn() { 2 }, \"a\": fn() { 1 }})";
+        let tree = SyntaxTree::parse(source).unwrap();
+
+        // the functions of the map are in the order of their keys
+        assert_eq!(
+            tree.spans
+                .iter()
+                .map(|span| tree.text(*span))
+                .collect::<Vec<_>>(),
+            vec![
+                "let f = fn(x) { let y = x; y };",
+                "{ let y = x; y }",
+                "let y = x;",
+                "y",
+                "({\"b\": fn() { 2 }, \"a\": fn() { 1 }})",
+                "{ 1 }",
+                "1",
+                "{ 2 }",
+                "2",
+            ]
+        );
+    }
+}
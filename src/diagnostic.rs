@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use crate::{
+    ast::ParserError, object::EvalError, source_map::Location, token::Span, warning::Warning,
+};
+
+/// Renders what the command line reports about a script on the standard error:
+/// its warnings, then the error it failed with, if any.
+/// Diagnostics with a position quote the line of the source they point to.
+pub fn render_diagnostics(
+    file: &str,
+    source: &str,
+    warnings: &[Warning],
+    err: Option<&EvalError>,
+) -> String {
+    let mut output = String::new();
+    for warning in warnings {
+        let location = Location::in_source(file, source, warning.span().start);
+        writeln!(output, "{location}: warning: {warning}").unwrap();
+        output.push_str(&snippet(source, warning.span()));
+    }
+
+    if let Some(err) = err {
+        writeln!(output, "| Qalo Error {} |", err.code()).unwrap();
+        match err.root_cause() {
+            // the context frames of an error only wrap the parser error when parsing a script
+            EvalError::ParsingError(err) => render_parser_error(&mut output, file, source, err),
+            _ => {
+                writeln!(output, "{err}").unwrap();
+                if let Some(span) = err.span() {
+                    let location = Location::in_source(file, source, span.start);
+                    writeln!(output, " --> {location}").unwrap();
+                    output.push_str(&snippet(source, span));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn render_parser_error(output: &mut String, file: &str, source: &str, err: &ParserError) {
+    if let ParserError::Multiple(errors) = err {
+        for err in errors {
+            render_parser_error(output, file, source, err);
+        }
+        return;
+    }
+
+    writeln!(output, "Parsing error: {err}").unwrap();
+    if let Some(span) = err.span() {
+        let location = Location::in_source(file, source, span.start);
+        writeln!(output, " --> {location}").unwrap();
+        output.push_str(&snippet(source, span));
+    }
+}
+
+/// Quotes the line of `source` where `span` starts, underlining the span up to the end of the line.
+fn snippet(source: &str, span: Span) -> String {
+    let start = Location::in_source("", source, span.start);
+    let end = Location::in_source("", source, span.end.max(span.start));
+    let line = source.lines().nth(start.line - 1).unwrap_or_default();
+
+    let last_column = if end.line == start.line {
+        end.column
+    } else {
+        line.chars().count() + 1
+    };
+    // empty spans, like the end of the file, still point to a column
+    let width = last_column.saturating_sub(start.column).max(1);
+
+    let number = start.line.to_string();
+    let gutter = " ".repeat(number.len());
+    format!(
+        "{gutter} |\n{number} | {line}\n{gutter} | {}{}\n",
+        " ".repeat(start.column - 1),
+        "^".repeat(width)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+
+    fn render(source: &str) -> String {
+        let mut evaluator = Evaluator::new(source);
+        let err = evaluator.eval_program().err();
+        render_diagnostics("a.ql", source, evaluator.warnings(), err.as_ref())
+    }
+
+    #[test]
+    fn render_parser_errors() {
+        assert_eq!(
+            render("let x = 1;\nbreak;"),
+            "| Qalo Error E0013 |\n\
+             Parsing error: `break` can only be used inside a loop, and not from a function defined in it\n \
+             --> a.ql:2:1\n  |\n2 | break;\n  | ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn render_unicode_identifiers() {
+        // columns count characters, not bytes
+        let rendered = render("let größe = 2;\nlet 変数 = größe +;");
+        assert!(
+            rendered
+                .ends_with(" --> a.ql:2:17\n  |\n2 | let 変数 = größe +;\n  |                 ^\n"),
+            "{rendered}"
+        );
+    }
+
+    #[test]
+    fn render_runtime_errors() {
+        assert_eq!(
+            render("let f = fn(x) { x / 0 };\nprintln(f(1))"),
+            "| Qalo Error E1007 |\nDivision by zero isn't allowed\n    while evaluating argument 1 of `println`\n"
+        );
+        assert_eq!(render("1 + 1"), "");
+    }
+
+    #[test]
+    fn render_invalid_operands() {
+        assert_eq!(
+            render("let name = \"Ada\";\nprintln(-name);"),
+            "| Qalo Error E1044 |\nCannot apply `-` to a value of type `string`\n    \
+             while evaluating argument 1 of `println`\n \
+             --> a.ql:2:10\n  |\n2 | println(-name);\n  |          ^^^^\n"
+        );
+    }
+
+    #[test]
+    fn render_warnings() {
+        let source = "@deprecated(\"use g\")\nlet f = fn() { 1 };\nlet x = f();";
+        assert_eq!(
+            render(source),
+            "a.ql:3:9: warning: `f` is deprecated: use g\n  |\n3 | let x = f();\n  |         ^^^\n"
+        );
+    }
+}
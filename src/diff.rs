@@ -0,0 +1,331 @@
+use std::fmt::Write;
+
+use crate::object::Object;
+
+/// Number of unchanged lines shown around the changes of a unified diff.
+const CONTEXT: usize = 3;
+
+/// Line of a diff between two texts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Aligns the lines of `old` and `new` along their longest common subsequence,
+/// listing the removed lines of a change before the added ones.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // the lines shared at both ends are left out of the table, which is quadratic
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lengths of the longest common subsequences of the suffixes of `a` and `b`
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|&line| DiffLine::Same(line))
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(DiffLine::Same(a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if j == b.len()
+            || (i < a.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            lines.push(DiffLine::Removed(a[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(b[j]));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|&line| DiffLine::Same(line)),
+    );
+
+    lines
+}
+
+/// Formats the changes from `old` to `new` as a unified diff of the file at `path`,
+/// like `diff -u` does. Returns an empty string when both texts have the same lines.
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let lines = diff_lines(old, new);
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], DiffLine::Same(_)))
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    // changes closer than twice the context share a hunk
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &i in &changed {
+        match hunks.last_mut() {
+            Some((_, end)) if i <= *end + 2 * CONTEXT => *end = i,
+            _ => hunks.push((i, i)),
+        }
+    }
+
+    let mut diff = format!("--- a/{path}\n+++ b/{path}\n");
+    // line numbers before the start of the current hunk
+    let (mut old_line, mut new_line, mut next) = (0, 0, 0);
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(lines.len());
+        for line in &lines[next..start] {
+            match line {
+                DiffLine::Same(_) => (old_line, new_line) = (old_line + 1, new_line + 1),
+                DiffLine::Removed(_) => old_line += 1,
+                DiffLine::Added(_) => new_line += 1,
+            }
+        }
+
+        let hunk = &lines[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Removed(_)))
+            .count();
+        // empty ranges start at the line before them
+        let old_start = if old_count == 0 {
+            old_line
+        } else {
+            old_line + 1
+        };
+        let new_start = if new_count == 0 {
+            new_line
+        } else {
+            new_line + 1
+        };
+        writeln!(
+            diff,
+            "@@ -{old_start},{old_count} +{new_start},{new_count} @@"
+        )
+        .unwrap();
+        for line in hunk {
+            match line {
+                DiffLine::Same(text) => writeln!(diff, " {text}"),
+                DiffLine::Removed(text) => writeln!(diff, "-{text}"),
+                DiffLine::Added(text) => writeln!(diff, "+{text}"),
+            }
+            .unwrap();
+        }
+
+        (old_line, new_line, next) = (old_line + old_count, new_line + new_count, end);
+    }
+
+    diff
+}
+
+/// Describes where two values differ, one entry per path from the root, written like `value["users"][0].name`,
+/// with the value of the left side after `-` and the one of the right side after `+`.
+/// Arrays, hash maps, structs and enum variants are compared element by element, so only the innermost
+/// differences are listed, and an element missing from a side only has the line of the other.
+/// Returns an empty string when the values are equal.
+pub fn diff_values(left: &Object, right: &Object) -> String {
+    let mut diff = String::new();
+    diff_at("value", left, right, &mut diff);
+    diff
+}
+
+fn diff_at(path: &str, left: &Object, right: &Object, diff: &mut String) {
+    match (left, right) {
+        (Object::ArrayValue(left), Object::ArrayValue(right)) => {
+            diff_elements(path, left.iter(), right.iter(), diff);
+        }
+        (Object::DequeValue(left), Object::DequeValue(right)) => {
+            diff_elements(path, left.iter(), right.iter(), diff);
+        }
+        (Object::MapValue(left), Object::MapValue(right)) => {
+            let mut keys: Vec<&String> = left.keys().chain(right.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = format!("{path}[{key:?}]");
+                match (left.get(key), right.get(key)) {
+                    (Some(left), Some(right)) => diff_at(&path, left, right, diff),
+                    (left, right) => diff_side(&path, left, right, diff),
+                }
+            }
+        }
+        (
+            Object::StructValue { name, fields },
+            Object::StructValue {
+                name: right_name,
+                fields: right_fields,
+            },
+        ) if name == right_name => {
+            for ((field, left), (_, right)) in fields.iter().zip(right_fields) {
+                diff_at(&format!("{path}.{field}"), left, right, diff);
+            }
+        }
+        (
+            Object::EnumValue {
+                name,
+                variant,
+                values,
+            },
+            Object::EnumValue {
+                name: right_name,
+                variant: right_variant,
+                values: right_values,
+            },
+        ) if name == right_name && variant == right_variant => {
+            for (i, (left, right)) in values.iter().zip(right_values).enumerate() {
+                diff_at(&format!("{path}.{i}"), left, right, diff);
+            }
+        }
+        _ if left == right => {}
+        _ => diff_side(path, Some(left), Some(right), diff),
+    }
+}
+
+fn diff_elements<'a>(
+    path: &str,
+    mut left: impl Iterator<Item = &'a Object>,
+    mut right: impl Iterator<Item = &'a Object>,
+    diff: &mut String,
+) {
+    for i in 0.. {
+        match (left.next(), right.next()) {
+            (None, None) => break,
+            (Some(left), Some(right)) => diff_at(&format!("{path}[{i}]"), left, right, diff),
+            (left, right) => diff_side(&format!("{path}[{i}]"), left, right, diff),
+        }
+    }
+}
+
+/// Writes the values of both sides at `path`, leaving out the side missing the element.
+fn diff_side(path: &str, left: Option<&Object>, right: Option<&Object>, diff: &mut String) {
+    writeln!(diff, "{path}").unwrap();
+    if let Some(left) = left {
+        writeln!(diff, "  - {left}").unwrap();
+    }
+    if let Some(right) = right {
+        writeln!(diff, "  + {right}").unwrap();
+    }
+}
+
+/// Colors a unified diff with ANSI escape codes for terminals: removed lines in red, added lines in green,
+/// hunk headers in cyan and file headers in bold.
+pub fn highlight_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            let color = if line.starts_with("---") || line.starts_with("+++") {
+                "1"
+            } else if line.starts_with("@@") {
+                "36"
+            } else if line.starts_with('-') {
+                "31"
+            } else if line.starts_with('+') {
+                "32"
+            } else {
+                return format!("{line}\n");
+            };
+            format!("\x1b[{color}m{line}\x1b[0m\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_changed_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nx\nc\nd\n"),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Added("x"),
+                DiffLine::Same("c"),
+                DiffLine::Added("d"),
+            ]
+        );
+        assert_eq!(diff_lines("", "a"), vec![DiffLine::Added("a")]);
+        assert_eq!(diff_lines("a\n", "a"), vec![DiffLine::Same("a")]);
+    }
+
+    #[test]
+    fn unified_diff_hunks() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "x.ql"), "");
+
+        let old = (1..=12).map(|i| format!("{i}\n")).collect::<String>();
+        let new = old.replacen("2\n", "two\n", 1).replace("11\n", "");
+        assert_eq!(
+            unified_diff(&old, &new, "x.ql"),
+            "--- a/x.ql\n+++ b/x.ql\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -8,5 +8,4 @@\n 8\n 9\n 10\n-11\n 12\n"
+        );
+
+        // an empty file starts before its first line
+        assert_eq!(
+            unified_diff("", "a\n", "x.ql"),
+            "--- a/x.ql\n+++ b/x.ql\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
+
+    #[test]
+    fn diff_nested_values() {
+        let variant = |values| Object::EnumValue {
+            name: "Shape".to_owned(),
+            variant: "Line".to_owned(),
+            values,
+        };
+        let left = Object::ArrayValue(vec![
+            variant(vec![Object::IntegerValue(1), Object::IntegerValue(2)]),
+            Object::NullValue,
+        ]);
+        let right = Object::ArrayValue(vec![variant(vec![
+            Object::IntegerValue(1),
+            Object::FloatValue(2.0),
+        ])]);
+
+        assert_eq!(
+            diff_values(&left, &right),
+            "value[0].1\n  - 2\n  + 2.0\nvalue[1]\n  - null\n"
+        );
+        assert_eq!(diff_values(&left, &left), "");
+    }
+
+    #[test]
+    fn highlight_unified_diff() {
+        assert_eq!(
+            highlight_diff("--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n c\n"),
+            "\x1b[1m--- a/x\x1b[0m\n\x1b[1m+++ b/x\x1b[0m\n\x1b[36m@@ -1 +1 @@\x1b[0m\n\
+             \x1b[31m-a\x1b[0m\n\x1b[32m+b\x1b[0m\n c\n"
+        );
+    }
+}
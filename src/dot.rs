@@ -0,0 +1,394 @@
+use std::fmt::Write;
+
+use crate::ast::{
+    format_parameters, format_variants, Expression, MatchArm, Program, Statement, SwitchArm,
+};
+
+/// Renders the parse tree of a program as a Graphviz DOT graph.
+pub fn program_to_dot(program: &Program) -> String {
+    let mut graph = DotGraph::default();
+
+    let root = graph.node("Program");
+    for located in &program.0 {
+        let child = graph.statement(&located.statement);
+        graph.edge(root, child, None);
+    }
+
+    format!("digraph ast {{\n  node [shape=box];\n{}}}\n", graph.body)
+}
+
+/// Escapes a label so it can be used inside a quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Prefix of the node of a loop with a label.
+fn labeled(label: &Option<String>) -> String {
+    label
+        .as_ref()
+        .map_or(String::new(), |label| format!("'{label}: "))
+}
+
+/// Suffix of the node of a `break` or `continue` to a label.
+fn target(label: &Option<String>) -> String {
+    label
+        .as_ref()
+        .map_or(String::new(), |label| format!(" '{label}"))
+}
+
+#[derive(Debug, Default)]
+struct DotGraph {
+    body: String,
+    next_id: usize,
+}
+
+impl DotGraph {
+    /// Adds a node labeled `label` and returns its id.
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // writing into a `String` can't fail
+        writeln!(self.body, "  n{id} [label=\"{}\"];", escape(label)).unwrap();
+
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<&str>) {
+        match label {
+            Some(label) => writeln!(
+                self.body,
+                "  n{from} -> n{to} [label=\"{}\"];",
+                escape(label)
+            )
+            .unwrap(),
+            None => writeln!(self.body, "  n{from} -> n{to};").unwrap(),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::VarStatement {
+                kind, name, value, ..
+            } => {
+                let id = self.node(&format!("{kind} {name}"));
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                id
+            }
+            Statement::DestructureStatement {
+                kind,
+                pattern,
+                value,
+            } => {
+                let id = self.node(&format!("{kind} {pattern}"));
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                id
+            }
+            Statement::ReturnStatement(expr) => {
+                let id = self.node("Return");
+                if let Some(expr) = expr {
+                    let value = self.expression(expr);
+                    self.edge(id, value, None);
+                }
+                id
+            }
+            Statement::AssignStatement { name, value } => {
+                let id = self.node(&format!("Assign {name}"));
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                id
+            }
+            Statement::ExpressionStatement(expr) => self.expression(expr),
+            Statement::BlockStatement(statements) => {
+                let id = self.node("Block");
+                for located in statements {
+                    let child = self.statement(&located.statement);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Statement::ForStatement {
+                label,
+                variable,
+                iterable,
+                body,
+            } => {
+                let id = self.node(&format!("{}For {variable}", labeled(label)));
+                let iterable = self.expression(iterable);
+                self.edge(id, iterable, Some("iterable"));
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                id
+            }
+            Statement::DoWhileStatement {
+                label,
+                body,
+                condition,
+            } => {
+                let id = self.node(&format!("{}DoWhile", labeled(label)));
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("condition"));
+                id
+            }
+            Statement::StructStatement { name, fields } => {
+                self.node(&format!("Struct {name} {{ {} }}", fields.join(", ")))
+            }
+            Statement::EnumStatement { name, variants } => {
+                self.node(&format!("Enum {name} {{ {} }}", format_variants(variants)))
+            }
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                let id = self.node("Switch");
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                for SwitchArm { case, body } in arms {
+                    let case = self.expression(case);
+                    self.edge(id, case, Some("case"));
+                    let body = self.statement(body);
+                    self.edge(case, body, None);
+                }
+                let default = self.statement(default);
+                self.edge(id, default, Some("default"));
+                id
+            }
+            Statement::BreakStatement { label, value } => {
+                let id = self.node(&format!("Break{}", target(label)));
+                if let Some(expr) = value {
+                    let value = self.expression(expr);
+                    self.edge(id, value, None);
+                }
+                id
+            }
+            Statement::ContinueStatement(label) => self.node(&format!("Continue{}", target(label))),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> usize {
+        match expr {
+            Expression::Identifier(name) => self.node(&format!("Identifier {name}")),
+            Expression::IntegerLiteral(n) => self.node(&format!("Integer {n}")),
+            Expression::FloatLiteral(n) => self.node(&format!("Float {n:?}")),
+            Expression::BooleanLiteral(b) => self.node(&format!("Boolean {b}")),
+            Expression::NullLiteral => self.node("Null"),
+            Expression::StringLiteral(s) => self.node(&format!("String \"{s}\"")),
+            Expression::ArrayLiteral(elements) => {
+                let id = self.node("Array");
+                for (i, element) in elements.iter().enumerate() {
+                    let child = self.expression(element);
+                    self.edge(id, child, Some(&i.to_string()));
+                }
+                id
+            }
+            Expression::StructLiteral { name, fields } => {
+                let id = self.node(&format!("Struct {name}"));
+                for (field, value) in fields {
+                    let child = self.expression(value);
+                    self.edge(id, child, Some(field));
+                }
+                id
+            }
+            Expression::MapLiteral(map) => {
+                let id = self.node("Map");
+                for (key, value) in map {
+                    let child = self.expression(value);
+                    self.edge(id, child, Some(&format!("\"{key}\"")));
+                }
+                id
+            }
+            Expression::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let id = self.node(&format!("Binary {operator}"));
+                let left = self.expression(left);
+                self.edge(id, left, Some("left"));
+                let right = self.expression(right);
+                self.edge(id, right, Some("right"));
+                id
+            }
+            Expression::ComparisonChain {
+                operands,
+                operators,
+            } => {
+                let operators = operators
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                let id = self.node(&format!("Comparison chain {operators}"));
+                for operand in operands {
+                    let child = self.expression(operand);
+                    self.edge(id, child, None);
+                }
+                id
+            }
+            Expression::UnaryExpression {
+                operator, value, ..
+            } => {
+                let id = self.node(&format!("Unary {operator}"));
+                let value = self.expression(value);
+                self.edge(id, value, None);
+                id
+            }
+            Expression::IndexExpression { value, index } => {
+                let id = self.node("Index");
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                let index = self.expression(index);
+                self.edge(id, index, Some("index"));
+                id
+            }
+            Expression::FieldAccess { value, field } => {
+                let id = self.node(&format!("Field {field}"));
+                let value = self.expression(value);
+                self.edge(id, value, None);
+                id
+            }
+            Expression::GroupedExpression(expr) => {
+                let id = self.node("Group");
+                let child = self.expression(expr);
+                self.edge(id, child, None);
+                id
+            }
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                let id = self.node("Call");
+                let callee = self.expression(callee);
+                self.edge(id, callee, Some("callee"));
+                for (i, argument) in arguments.iter().enumerate() {
+                    let child = self.expression(argument);
+                    self.edge(id, child, Some(&format!("arg {i}")));
+                }
+                for (name, argument) in named {
+                    let child = self.expression(argument);
+                    self.edge(id, child, Some(name));
+                }
+                id
+            }
+            Expression::MethodCall(call) => {
+                let id = self.node(&format!("Method {}", call.method));
+                let receiver = self.expression(&call.receiver);
+                self.edge(id, receiver, Some("receiver"));
+                for (i, argument) in call.arguments.iter().enumerate() {
+                    let child = self.expression(argument);
+                    self.edge(id, child, Some(&format!("arg {i}")));
+                }
+                for (name, argument) in &call.named {
+                    let child = self.expression(argument);
+                    self.edge(id, child, Some(name));
+                }
+                id
+            }
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let id = self.node("If");
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("condition"));
+                let consequence = self.statement(consequence);
+                self.edge(id, consequence, Some("then"));
+                if let Some(alternative) = alternative {
+                    let alternative = self.statement(alternative);
+                    self.edge(id, alternative, Some("else"));
+                }
+                id
+            }
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => {
+                let id = self.node(&format!(
+                    "Function ({})",
+                    format_parameters(parameters, rest)
+                ));
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                id
+            }
+            Expression::MatchExpression { value, arms } => {
+                let id = self.node("Match");
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                for MatchArm {
+                    pattern,
+                    guard,
+                    body,
+                } in arms
+                {
+                    let arm = self.node(&format!("Case {pattern}"));
+                    self.edge(id, arm, None);
+                    if let Some(guard) = guard {
+                        let guard = self.expression(guard);
+                        self.edge(arm, guard, Some("guard"));
+                    }
+                    let body = self.statement(body);
+                    self.edge(arm, body, None);
+                }
+                id
+            }
+            Expression::LoopExpression { label, body } => {
+                let id = self.node(&format!("{}Loop", labeled(label)));
+                let body = self.statement(body);
+                self.edge(id, body, Some("body"));
+                id
+            }
+            Expression::BlockExpression(body) => self.statement(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn dot_export() {
+        let mut parser = Parser::new("let a = 1 + 2 * b;");
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(
+            program_to_dot(&program),
+            r#"digraph ast {
+  node [shape=box];
+  n0 [label="Program"];
+  n1 [label="let a"];
+  n2 [label="Binary +"];
+  n3 [label="Integer 1"];
+  n2 -> n3 [label="left"];
+  n4 [label="Binary *"];
+  n5 [label="Integer 2"];
+  n4 -> n5 [label="left"];
+  n6 [label="Identifier b"];
+  n4 -> n6 [label="right"];
+  n2 -> n4 [label="right"];
+  n1 -> n2 [label="value"];
+  n0 -> n1;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn dot_export_escapes_labels() {
+        let mut parser = Parser::new(r#"println("a\b")"#);
+        let program = parser.parse_program().unwrap();
+
+        assert!(program_to_dot(&program).contains(r#"[label="String \"a\\b\""]"#));
+    }
+}
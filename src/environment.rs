@@ -1,25 +1,165 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use crate::object::{EvalError, Object};
+use crate::object::{prelude, EvalError, Object};
 
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct Environment {
     pub store: HashMap<String, Object>,
+    /// Names of the bindings of this environment that can be re-assigned.
+    pub mutable: HashSet<String>,
+    /// Names of the bindings of this environment that have been frozen with `freeze`.
+    pub frozen: HashSet<String>,
+    /// Notes of the bindings of this environment that were declared with `@deprecated`.
+    pub deprecated: HashMap<String, String>,
     pub outer: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn get(&self, name: &str) -> Result<Object, EvalError> {
+        self.with_value(name, Object::clone)
+    }
+
+    /// Calls `read` with the value bound to `name`, without copying it like `get` does.
+    pub fn with_value<T>(
+        &self,
+        name: &str,
+        read: impl FnOnce(&Object) -> T,
+    ) -> Result<T, EvalError> {
         if let Some(obj) = self.store.get(name) {
-            Ok(obj.clone())
+            Ok(read(obj))
         } else if let Some(outer) = &self.outer {
-            Ok(outer.borrow().get(name)?)
+            outer.borrow().with_value(name, read)
         } else {
-            Err(EvalError::IdentifierNotFound(name.to_owned()))
+            prelude(name)
+                .map(|obj| read(&obj))
+                .ok_or_else(|| EvalError::IdentifierNotFound(name.to_owned()))
+        }
+    }
+
+    /// Number of environments enclosing this one.
+    pub fn depth(&self) -> usize {
+        match &self.outer {
+            Some(outer) => outer.borrow().depth() + 1,
+            None => 0,
         }
     }
 
+    /// Binds an immutable identifier in this environment, shadowing any previous binding.
     pub fn set(&mut self, name: String, value: Object) {
+        self.mutable.remove(&name);
+        self.frozen.remove(&name);
+        self.deprecated.remove(&name);
+        self.store.insert(name, value);
+    }
+
+    /// Binds a mutable identifier in this environment, shadowing any previous binding.
+    pub fn set_mutable(&mut self, name: String, value: Object) {
+        self.frozen.remove(&name);
+        self.deprecated.remove(&name);
+        self.mutable.insert(name.clone());
         self.store.insert(name, value);
     }
+
+    /// Re-binds an existing mutable identifier in the environment where it was defined.
+    pub fn assign(&mut self, name: &str, value: Object) -> Result<(), EvalError> {
+        if let Some(obj) = self.store.get_mut(name) {
+            if self.frozen.contains(name) {
+                return Err(EvalError::FrozenAssignment(name.to_owned()));
+            }
+            if !self.mutable.contains(name) {
+                return Err(EvalError::ImmutableAssignment(name.to_owned()));
+            }
+
+            *obj = value;
+            Ok(())
+        } else if let Some(outer) = &self.outer {
+            outer.borrow_mut().assign(name, value)
+        } else {
+            Err(EvalError::IdentifierNotFound(name.to_owned()))
+        }
+    }
+
+    /// Modifies in place the value of an existing mutable identifier, in the environment where it was defined.
+    pub fn update<T>(
+        &mut self,
+        name: &str,
+        update: impl FnOnce(&mut Object) -> Result<T, EvalError>,
+    ) -> Result<T, EvalError> {
+        if let Some(obj) = self.store.get_mut(name) {
+            if self.frozen.contains(name) {
+                return Err(EvalError::FrozenAssignment(name.to_owned()));
+            }
+            if !self.mutable.contains(name) {
+                return Err(EvalError::ImmutableAssignment(name.to_owned()));
+            }
+
+            update(obj)
+        } else if let Some(outer) = &self.outer {
+            outer.borrow_mut().update(name, update)
+        } else {
+            Err(EvalError::IdentifierNotFound(name.to_owned()))
+        }
+    }
+
+    /// Marks a binding of this environment as deprecated, with a note telling what to use instead.
+    pub fn deprecate(&mut self, name: &str, note: String) {
+        self.deprecated.insert(name.to_owned(), note);
+    }
+
+    /// Returns the note of an identifier if the binding it resolves to is deprecated.
+    pub fn deprecation(&self, name: &str) -> Option<String> {
+        if self.store.contains_key(name) {
+            self.deprecated.get(name).cloned()
+        } else {
+            self.outer.as_ref()?.borrow().deprecation(name)
+        }
+    }
+
+    /// Copies the binding of `name` from `env` to this environment, if `env` defines it,
+    /// keeping whether it's frozen or deprecated. Returns whether the binding was copied.
+    pub fn capture(&mut self, name: &str, env: &Environment) -> bool {
+        let Some(obj) = env.store.get(name) else {
+            return false;
+        };
+
+        self.set(name.to_owned(), obj.clone());
+        if env.frozen.contains(name) {
+            self.frozen.insert(name.to_owned());
+        }
+        if let Some(note) = env.deprecated.get(name) {
+            self.deprecate(name, note.clone());
+        }
+        true
+    }
+
+    /// Environment where `name` is bound, searching from `env` outwards.
+    pub fn defining(
+        env: &Rc<RefCell<Environment>>,
+        name: &str,
+    ) -> Option<Rc<RefCell<Environment>>> {
+        if env.borrow().store.contains_key(name) {
+            return Some(env.clone());
+        }
+
+        let outer = env.borrow().outer.clone()?;
+        Environment::defining(&outer, name)
+    }
+
+    /// Makes an existing identifier immutable in the environment where it was defined,
+    /// even if it was declared with `var`, and returns its value.
+    pub fn freeze(&mut self, name: &str) -> Result<Object, EvalError> {
+        if let Some(obj) = self.store.get(name) {
+            self.mutable.remove(name);
+            self.frozen.insert(name.to_owned());
+            Ok(obj.clone())
+        } else if let Some(outer) = &self.outer {
+            outer.borrow_mut().freeze(name)
+        } else {
+            Err(EvalError::IdentifierNotFound(name.to_owned()))
+        }
+    }
 }
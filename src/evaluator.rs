@@ -1,17 +1,88 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    fmt::Debug,
+    io::{BufRead, Write},
+    ops::ControlFlow,
+    rc::{Rc, Weak},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    ast::{Expression, ParserError, Statement},
+    ast::{
+        prelude_enum, Expression, Located, MatchArm, MethodCall, ParserError, Pattern, Program,
+        Statement, SwitchArm,
+    },
+    capture::free_variables,
+    diff::diff_values,
     environment::Environment,
-    object::{BuiltinFunction, Closure, EvalError, Object},
+    event_loop::{EventLoop, InterruptHandle, Poll},
+    events::{EvalEvent, EventSink},
+    features::Features,
+    gc::{self, Collection},
+    keywords::Keywords,
+    log::{LogLevel, LogRecord, LogSink},
+    native::NativeFunction,
+    object::{
+        prelude, BuiltinFunction, Closure, Context, EvalError, Object, Task, Unwind, WeakBinding,
+    },
+    observer::EvalObserver,
+    parallel::{self, parallel_map, MailError, ParallelMapError, Settings},
     parser::Parser,
-    token::TokenKind,
+    purity::is_pure,
+    replay::{Input, InputLog, ReplayError, ReplayMode},
+    sandbox::Sandbox,
+    streams::{Output, SharedBuffer, Streams},
+    suggestion::closest_matches,
+    token::{Span, TokenKind},
+    unicode::graphemes,
+    warning::Warning,
+    VERSION,
 };
 
+/// Name binding the receiver of a method while the built-in function implementing it is called.
+const RECEIVER: &str = "<receiver>";
+
+/// Name binding the function of a partial application in the function it returns.
+const PARTIAL: &str = "<function>";
+
+/// Longest wait of `run_event_loop` between two polls while callbacks wait for tasks.
+const TASK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 #[derive(Debug)]
 pub struct Evaluator<'a> {
     parser: Parser<'a>,
     env: Rc<RefCell<Environment>>,
+    observer: Option<Box<dyn EvalObserver>>,
+    /// Sink receiving the records of `log`, which are written to the stderr stream if `None`.
+    log_sink: Option<Box<dyn LogSink>>,
+    event_sink: Option<Box<dyn EventSink>>,
+    /// Log the lines read and the results of native functions go to, or come from when replaying.
+    replay: Option<ReplayMode>,
+    streams: Streams,
+    /// Channel the tasks and actors started by the evaluator send what they write through,
+    /// which is written to `streams` whenever the evaluator writes, joins a task, gets a reply or polls.
+    task_output: (Sender<Output>, Receiver<Output>),
+    warnings: Vec<Warning>,
+    /// Whether integer overflows are reported as errors, rather than wrapping around.
+    checked_arithmetic: bool,
+    /// Whether conditions accept any value, rather than only booleans.
+    truthy_conditions: bool,
+    /// Every environment created while evaluating, so that they can be cleared when the evaluator
+    /// is dropped: closures stored in the environment they captured form reference cycles.
+    environments: Vec<Weak<RefCell<Environment>>>,
+    /// Whether the environments only kept alive by reference cycles are collected while evaluating.
+    garbage_collection: bool,
+    /// Callbacks scheduled by the program, run by `poll`.
+    event_loop: EventLoop,
+    /// Whether `poll` is running a callback, whose yield points don't poll again.
+    polling: bool,
+    interrupt: InterruptHandle,
+    /// Time after which the evaluation fails at its next yield point.
+    deadline: Option<Instant>,
 }
 
 impl<'a> Evaluator<'a> {
@@ -19,860 +90,6638 @@ impl<'a> Evaluator<'a> {
         let parser = Parser::new(input);
         let env = Rc::new(RefCell::new(Environment::default()));
 
-        Evaluator { parser, env }
+        Evaluator {
+            parser,
+            environments: vec![Rc::downgrade(&env)],
+            env,
+            observer: None,
+            log_sink: None,
+            event_sink: None,
+            replay: None,
+            streams: Streams::default(),
+            task_output: mpsc::channel(),
+            warnings: vec![],
+            checked_arithmetic: false,
+            truthy_conditions: false,
+            garbage_collection: false,
+            event_loop: EventLoop::default(),
+            polling: false,
+            interrupt: InterruptHandle::default(),
+            deadline: None,
+        }
     }
 
-    pub fn eval_program(&mut self) -> Result<Vec<Object>, EvalError> {
-        let program = self.parser.parse_program()?;
-        let mut objects: Vec<Object> = vec![];
-
-        for statement in program.0 {
-            let obj = self.eval_statement(statement)?;
-
-            // unwrap top-level return values
-            if let Object::ReturnValue(inner_obj) = obj {
-                objects.push(*inner_obj);
-            } else {
-                objects.push(obj);
-            }
+    /// Creates an evaluator whose global environment is `env`, shared with the host.
+    /// Unlike the environments created while evaluating, it isn't cleared when the evaluator is dropped.
+    pub fn with_env(input: &'a str, env: Rc<RefCell<Environment>>) -> Self {
+        Evaluator {
+            parser: Parser::new(input),
+            environments: vec![],
+            env,
+            observer: None,
+            log_sink: None,
+            event_sink: None,
+            replay: None,
+            streams: Streams::default(),
+            task_output: mpsc::channel(),
+            warnings: vec![],
+            checked_arithmetic: false,
+            truthy_conditions: false,
+            garbage_collection: false,
+            event_loop: EventLoop::default(),
+            polling: false,
+            interrupt: InterruptHandle::default(),
+            deadline: None,
         }
+    }
 
-        Ok(objects)
+    /// Exposes a Rust function to scripts as a global function called `name`.
+    /// Built-in functions with the same name take precedence over it.
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        function: impl Fn(Vec<Object>) -> Result<Object, EvalError> + 'static,
+    ) {
+        self.register_native_function(NativeFunction::new(name, function));
     }
 
-    fn eval_statement(&mut self, statement: Statement) -> Result<Object, EvalError> {
-        match statement {
-            Statement::VarStatement {
-                kind: _,
-                name,
-                value,
-            } => {
-                let obj = self.eval_expression(value, true)?;
-                self.env.borrow_mut().set(name, obj);
-                Ok(Object::UnitValue)
-            }
-            Statement::ReturnStatement(_) => {
-                // return statements aren't allowed at the top-level scope
-                return Err(EvalError::ReturnOutsideExpression);
-            }
-            Statement::AssignStatement { name, value } => {
-                let obj = self.eval_expression(value, true)?;
-                self.env.borrow_mut().set(name, obj);
-                Ok(Object::UnitValue)
-            }
-            Statement::ExpressionStatement(expr) => Ok(self.eval_expression(expr, true)?),
-            Statement::BlockStatement(statements) => {
-                let inner_env = self.create_enclosed_env();
-                let outer_env = std::mem::replace(&mut self.env, inner_env);
+    /// Like `register_function`, for functions built with `NativeFunction`, e.g. to mark them as deprecated.
+    pub fn register_native_function(&mut self, function: NativeFunction) {
+        self.env
+            .borrow_mut()
+            .set(function.name.clone(), Object::NativeFunctionValue(function));
+    }
+
+    /// Exposes a namespace of Rust functions to scripts, which call them as `name.function(...)`.
+    pub fn register_module(
+        &mut self,
+        name: &str,
+        functions: impl IntoIterator<Item = NativeFunction>,
+    ) {
+        let members: BTreeMap<String, Object> = functions
+            .into_iter()
+            .map(|function| (function.name.clone(), Object::NativeFunctionValue(function)))
+            .collect();
 
-                // save last evaluated object
-                let mut obj = Object::UnitValue;
+        self.emit(|| EvalEvent::ModuleLoaded {
+            name: name.to_owned(),
+            members: members.keys().cloned().collect(),
+        });
+        let module = Object::ModuleValue {
+            name: name.to_owned(),
+            members,
+        };
+        self.env.borrow_mut().set(name.to_owned(), module);
+    }
 
-                for statement in statements {
-                    // handle return statements inside a block
-                    if let Statement::ReturnStatement(expr) = statement {
-                        let expr_eval = if let Some(expr) = expr {
-                            self.eval_expression(expr, true)?
-                        } else {
-                            Object::UnitValue
-                        };
+    /// Lets the program use the aliases of `keywords`, e.g. `func` for `fn`.
+    /// The program is parsed from its start again, so this must be called before evaluating it.
+    pub fn set_keywords(&mut self, keywords: Keywords) {
+        let max_depth = self.parser.max_depth;
+        self.parser = Parser::with_keywords(self.parser.lexer.input(), Rc::new(keywords));
+        self.parser.max_depth = max_depth;
+    }
 
-                        // if the result of the evaluation is a *return value*, keep it to
-                        // propagate it to upper blocks...
-                        if matches!(expr_eval, Object::ReturnValue(_)) {
-                            obj = expr_eval;
-                        } else {
-                            // ...otherwise, wrap the value inside a *return value*
-                            obj = Object::ReturnValue(Box::new(expr_eval));
-                        }
+    /// Installs an observer notified about every evaluated statement.
+    pub fn set_observer(&mut self, observer: Box<dyn EvalObserver>) {
+        self.observer = Some(observer);
+    }
 
-                        break;
-                    }
+    /// Installs the sink receiving the records of the `log` built-in function,
+    /// which writes them on a line of the stderr stream by default.
+    pub fn set_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.log_sink = Some(sink);
+    }
 
-                    // evaluate all other types of statements
-                    obj = self.eval_statement(statement)?;
+    /// Installs the sink receiving the events of the evaluation: the programs it starts, their top-level
+    /// statements and functions, the modules registered from then on and the errors it fails with.
+    pub fn set_event_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.event_sink = Some(sink);
+    }
 
-                    // if the current object is a *return value*, stop evaluating this block
-                    if let Object::ReturnValue(_) = obj {
-                        break;
-                    }
-                }
+    /// Appends the inputs of the run to `log`: the lines `read_line` reads and the results of native functions.
+    pub fn record_inputs(&mut self, log: InputLog) {
+        self.replay = Some(ReplayMode::Recording(log));
+    }
 
-                // go back to the outer environment
-                self.env = outer_env;
+    /// Takes the inputs of the run from a log made by `record_inputs` rather than from stdin
+    /// and native functions, which aren't called. The run fails if it reads them in another order.
+    pub fn replay_inputs(&mut self, log: InputLog) {
+        self.replay = Some(ReplayMode::Replaying(log));
+    }
 
-                // return the last evaluated object
-                Ok(obj)
-            }
+    /// Reads a line of stdin, or the next line of the log when replaying.
+    fn read_input_line(&mut self) -> Result<Option<String>, EvalError> {
+        if let Some(ReplayMode::Replaying(log)) = &self.replay {
+            return match log.pop() {
+                Some(Input::ReadLine(line)) => Ok(line),
+                Some(input) => Err(ReplayError::Diverged(
+                    Input::ReadLine(None).to_string(),
+                    input.to_string(),
+                )
+                .into()),
+                None => Err(ReplayError::Exhausted(Input::ReadLine(None).to_string()).into()),
+            };
         }
+
+        let line = self
+            .streams
+            .read_line()
+            .map_err(|err| EvalError::Io(err.to_string()))?;
+        if let Some(ReplayMode::Recording(log)) = &self.replay {
+            log.push(Input::ReadLine(line.clone()));
+        }
+        Ok(line)
     }
 
-    fn eval_expression(
+    /// Calls a native function, or returns the result of the call from the log when replaying.
+    fn call_native(
         &mut self,
-        expr: Expression,
-        within_statement: bool,
+        name: &str,
+        native: &NativeFunction,
+        arguments: Vec<Object>,
     ) -> Result<Object, EvalError> {
-        let obj = match expr {
-            Expression::IntegerLiteral(lit) => Object::IntegerValue(lit),
-            Expression::BooleanLiteral(lit) => Object::BooleanValue(lit),
-            Expression::StringLiteral(lit) => Object::StringValue(lit),
-            Expression::Identifier(name) => self.env.borrow().get(&name)?,
-            Expression::ArrayLiteral(expressions) => self.eval_array_expression(expressions)?,
-            Expression::MapLiteral(map) => self.eval_map_expression(map)?,
-            Expression::BinaryExpression {
-                left,
-                operator,
-                right,
-            } => self.eval_binary_expression(*left, operator, *right)?,
-            Expression::UnaryExpression { operator, value } => {
-                self.eval_unary_expression(operator, *value)?
-            }
-            Expression::GroupedExpression(expr) => self.eval_expression(*expr, within_statement)?,
-            Expression::CallExpression { path, arguments } => {
-                self.eval_call_expression(*path, arguments)?
-            }
-            Expression::IndexExpression { value, index } => {
-                self.eval_index_expression(*value, *index)?
-            }
-            Expression::IfExpression {
-                condition,
-                consequence,
-                alternative,
-            } => self.eval_if_expression(*condition, *consequence, alternative)?,
-            Expression::FunctionExpression { parameters, body } => {
-                self.eval_function_expression(parameters, *body)?
+        let log = match &self.replay {
+            None => return native.call(arguments),
+            Some(ReplayMode::Recording(log)) => {
+                let result = native.call(arguments);
+                log.push(Input::NativeCall {
+                    name: name.to_owned(),
+                    result: result
+                        .as_ref()
+                        .map(Object::clone)
+                        .map_err(|err| err.to_string()),
+                });
+                return result;
             }
+            Some(ReplayMode::Replaying(log)) => log,
         };
 
-        // unwrap return values
-        if let Object::ReturnValue(ref inner_obj) = obj {
-            if !within_statement {
-                return Ok(*inner_obj.clone());
+        let expected = || {
+            Input::NativeCall {
+                name: name.to_owned(),
+                result: Ok(Object::NullValue),
             }
+            .to_string()
+        };
+        match log.pop() {
+            Some(Input::NativeCall {
+                name: recorded,
+                result,
+            }) if recorded == name => {
+                result.map_err(|message| ReplayError::NativeCallFailed(recorded, message).into())
+            }
+            Some(Input::Unrecorded {
+                name: recorded,
+                type_name,
+            }) if recorded == name => Err(ReplayError::Unreplayable(recorded, type_name).into()),
+            Some(input) => Err(ReplayError::Diverged(expected(), input.to_string()).into()),
+            None => Err(ReplayError::Exhausted(expected()).into()),
         }
+    }
 
-        Ok(obj)
+    /// Reports an event to the event sink, building it only if there's one.
+    fn emit(&mut self, event: impl FnOnce() -> EvalEvent) {
+        if let Some(sink) = &mut self.event_sink {
+            sink.event(&event());
+        }
     }
 
-    fn eval_binary_expression(
-        &mut self,
-        left: Expression,
-        operator: TokenKind,
-        right: Expression,
-    ) -> Result<Object, EvalError> {
-        let left_obj = self.eval_expression(left, false)?;
-        let right_obj = self.eval_expression(right, false)?;
+    /// Reports the error the evaluation failed with to the event sink.
+    fn emit_error<T>(&mut self, result: Result<T, EvalError>) -> Result<T, EvalError> {
+        if let Err(err) = &result {
+            self.emit(|| EvalEvent::ErrorRaised {
+                code: err.code(),
+                message: err.to_string(),
+            });
+        }
 
-        let obj = match (left_obj, right_obj) {
-            (Object::IntegerValue(lhs), Object::IntegerValue(rhs)) => match operator {
-                TokenKind::Plus => Object::IntegerValue(lhs + rhs),
-                TokenKind::Minus => Object::IntegerValue(lhs - rhs),
-                TokenKind::Asterisk => Object::IntegerValue(lhs * rhs),
-                TokenKind::Equal => Object::BooleanValue(lhs == rhs),
-                TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
-                TokenKind::LessThan => Object::BooleanValue(lhs < rhs),
-                TokenKind::GreaterThan => Object::BooleanValue(lhs > rhs),
-                TokenKind::LessThanEqual => Object::BooleanValue(lhs <= rhs),
-                TokenKind::GreaterThanEqual => Object::BooleanValue(lhs >= rhs),
-                TokenKind::Percentage => {
-                    if rhs == 0 {
-                        return Err(EvalError::ModuloByZero);
-                    } else {
-                        Object::IntegerValue(lhs % rhs)
-                    }
-                }
-                TokenKind::Slash => {
-                    if rhs == 0 {
-                        return Err(EvalError::DivisionByZero);
-                    } else {
-                        Object::IntegerValue(lhs / rhs)
-                    }
-                }
-                _ => return Err(EvalError::UnsupportedOperator(operator)),
-            },
+        result
+    }
 
-            (Object::BooleanValue(lhs), Object::BooleanValue(rhs)) => match operator {
-                TokenKind::Equal => Object::BooleanValue(lhs == rhs),
-                TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
-                TokenKind::AndAnd => Object::BooleanValue(lhs && rhs),
-                TokenKind::OrOr => Object::BooleanValue(lhs || rhs),
-                _ => return Err(EvalError::UnsupportedOperator(operator)),
-            },
+    /// Replaces the stream `print` and `println` write to, the standard output of the process by default.
+    pub fn set_stdout(&mut self, stdout: impl Write + 'static) {
+        self.streams.stdout = Box::new(stdout);
+    }
 
-            (Object::StringValue(lhs), Object::StringValue(rhs)) => match operator {
-                TokenKind::Plus => Object::StringValue(lhs + &rhs),
-                _ => return Err(EvalError::UnsupportedOperator(operator)),
-            },
+    /// Replaces the stream receiving the records of `log` when no log sink is installed,
+    /// the standard error of the process by default.
+    pub fn set_stderr(&mut self, stderr: impl Write + 'static) {
+        self.streams.stderr = Box::new(stderr);
+    }
 
-            (lhs, rhs) => {
-                return Err(EvalError::TypeMismatch(format!(
-                    "Cannot perform operation '{operator}' between '{lhs}' and '{rhs}'",
-                )));
-            }
-        };
+    /// Replaces the stream `read_line` reads from, the standard input of the process by default.
+    pub fn set_stdin(&mut self, stdin: impl BufRead + 'static) {
+        self.streams.stdin = Box::new(stdin);
+    }
 
-        Ok(obj)
+    /// Replaces every stream at once, e.g. with the forwarding streams of a task.
+    pub(crate) fn set_streams(&mut self, streams: Streams) {
+        self.streams = streams;
     }
 
-    fn eval_unary_expression(
-        &mut self,
-        operator: TokenKind,
-        value: Expression,
-    ) -> Result<Object, EvalError> {
-        let obj = match operator {
-            TokenKind::Bang => match self.eval_expression(value, false)? {
-                Object::IntegerValue(lit) => Object::IntegerValue(!lit),
-                Object::BooleanValue(lit) => Object::BooleanValue(!lit),
-                _ => return Err(EvalError::UnsupportedOperator(operator)),
-            },
+    /// Appends the output of `print` and `println` to `buffer`, instead of writing it to stdout.
+    pub fn capture_output(&mut self, buffer: Rc<RefCell<String>>) {
+        self.set_stdout(SharedBuffer(buffer));
+    }
 
-            TokenKind::Minus => match self.eval_expression(value, false)? {
-                Object::IntegerValue(lit) => Object::IntegerValue(-lit),
-                _ => return Err(EvalError::UnsupportedOperator(operator)),
-            },
+    /// Capabilities of the evaluator, as reported to scripts by the `features` built-in function.
+    pub fn features(&self) -> Features {
+        Features::new(self.checked_arithmetic, self.truthy_conditions)
+    }
 
-            _ => return Err(EvalError::UnsupportedOperator(operator)),
-        };
+    /// Warnings reported so far, like calls to deprecated functions.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
 
-        Ok(obj)
+    /// By default, integer operations wrap around when they overflow: with checked arithmetic,
+    /// they fail with `EvalError::IntegerOverflow` instead.
+    pub fn set_checked_arithmetic(&mut self, checked: bool) {
+        self.checked_arithmetic = checked;
     }
 
-    fn eval_array_expression(&mut self, expressions: Vec<Expression>) -> Result<Object, EvalError> {
-        let mut objects: Vec<Object> = vec![];
+    /// By default, the conditions of `if`, `do`-`while` and the guards of `case` must be booleans:
+    /// with truthy conditions, `false`, `null`, zero, `""` and `[]` count as false and any other value as true.
+    pub fn set_truthy_conditions(&mut self, truthy: bool) {
+        self.truthy_conditions = truthy;
+    }
 
-        for expr in expressions {
-            objects.push(self.eval_expression(expr, false)?);
+    /// Options shared with the evaluators started on other threads.
+    fn settings(&self) -> Settings {
+        Settings {
+            checked_arithmetic: self.checked_arithmetic,
+            truthy_conditions: self.truthy_conditions,
+            output: self.task_output.0.clone(),
         }
-
-        Ok(Object::ArrayValue(objects))
     }
 
-    fn eval_map_expression(
-        &mut self,
-        expr_map: HashMap<String, Expression>,
-    ) -> Result<Object, EvalError> {
-        let mut map: HashMap<String, Object> = HashMap::new();
-
-        for (key, expr) in expr_map {
-            map.insert(key, self.eval_expression(expr, false)?);
+    /// Writes what the tasks and actors started by the evaluator wrote since the last call to its streams.
+    fn forward_task_output(&mut self) -> Result<(), EvalError> {
+        for output in self.task_output.1.try_iter() {
+            match output {
+                Output::Stdout(text) => self.streams.stdout.write_all(text.as_bytes()),
+                Output::Stderr(text) => self.streams.stderr.write_all(text.as_bytes()),
+            }
+            .map_err(|err| EvalError::Io(err.to_string()))?;
         }
 
-        Ok(Object::MapValue(map))
+        Ok(())
     }
 
-    fn eval_index_expression(
-        &mut self,
-        value: Expression,
-        index: Expression,
-    ) -> Result<Object, EvalError> {
-        let value = self.eval_expression(value, false)?;
-        let index = self.eval_expression(index, false)?;
+    /// Closures stored in the environment they captured form reference cycles, which are only freed
+    /// when the evaluator is dropped. With garbage collection, they are collected while evaluating,
+    /// every time the number of environments doubles, which suits long-running scripts.
+    pub fn set_garbage_collection(&mut self, enabled: bool) {
+        self.garbage_collection = enabled;
+    }
 
-        match value {
-            Object::ArrayValue(objects) => {
-                if let Object::IntegerValue(index) = index {
-                    let id = usize::try_from(index)
-                        .or_else(|err| return Err(ParserError::IntConversionError(err)))?;
+    /// Frees the environments only kept alive by reference cycles, see `gc::collect`.
+    pub fn collect_garbage(&mut self) -> Collection {
+        let collection = gc::collect(&self.environments);
+        self.environments.retain(|env| env.strong_count() > 0);
+        collection
+    }
 
-                    let item = objects
-                        .get(id)
-                        .ok_or(EvalError::IndexOutOfBounds(objects.len(), id))?;
+    /// Runs the callbacks scheduled by the program that are ready, without blocking: the timers
+    /// of `set_timeout` and `set_interval` that are due, then the `when_done` callbacks of the finished tasks.
+    /// Hosts with their own event loop, like games or GUIs, call it on every frame after evaluating the program.
+    /// Fails with the error of the first failing callback, the next ones running on the following polls.
+    pub fn poll(&mut self) -> Result<Poll, EvalError> {
+        let poll = self.poll_callbacks();
+        self.emit_error(poll)
+    }
 
-                    Ok(item.clone())
-                } else {
-                    return Err(EvalError::InvalidIndexType);
-                }
-            }
-            Object::MapValue(map) => {
-                if let Object::StringValue(key) = index {
-                    let item = map.get(&key).ok_or(EvalError::ValueNotFound(key))?;
+    /// Runs the ready callbacks for `poll` and the yield points, whose errors are reported by the evaluation.
+    fn poll_callbacks(&mut self) -> Result<Poll, EvalError> {
+        let polling = std::mem::replace(&mut self.polling, true);
+        let result = self.run_ready_callbacks();
+        self.polling = polling;
 
-                    Ok(item.clone())
-                } else {
-                    return Err(EvalError::InvalidIndexType);
-                }
+        let callbacks = result?;
+        self.forward_task_output()?;
+        Ok(Poll {
+            callbacks,
+            pending: self.event_loop.pending(),
+            next_deadline: self.event_loop.next_deadline(),
+        })
+    }
+
+    /// Runs the callbacks of `poll`, returning how many ran.
+    fn run_ready_callbacks(&mut self) -> Result<usize, EvalError> {
+        let now = Instant::now();
+        let mut callbacks = 0;
+
+        // the timers scheduled by the callbacks run on the next poll at the earliest
+        for id in self.event_loop.due(now) {
+            if let Some(callback) = self.event_loop.fire(id, now) {
+                callbacks += 1;
+                self.call_closure(callback, vec![])
+                    .with_context(|| format!("while running the callback of timer #{id}"))?;
             }
-            _ => return Err(EvalError::InvalidIndexUsage),
         }
+        while let Some((task, callback)) = self.event_loop.finished_task() {
+            callbacks += 1;
+            let result = self.join_task(&task)?;
+            self.call_closure(callback, vec![result])
+                .with_context(|| format!("while running the callback of task #{}", task.id))?;
+        }
+
+        Ok(callbacks)
     }
 
-    fn eval_if_expression(
-        &mut self,
-        condition: Expression,
-        consequence: Statement,
-        alternative: Option<Box<Statement>>,
-    ) -> Result<Object, EvalError> {
-        let obj = match self.eval_expression(condition, false)? {
-            Object::BooleanValue(lit) => {
-                if lit {
-                    self.eval_statement(consequence)?
-                } else if let Some(alt) = alternative {
-                    self.eval_statement(*alt)?
-                } else {
-                    Object::UnitValue
-                }
-            }
-            _ => {
-                return Err(EvalError::TypeMismatch(
-                    "`if` condition must be a boolean".to_owned(),
-                ))
-            }
-        };
+    /// Point where the evaluation yields, at the end of every iteration of a loop and when `yield_now` is called.
+    /// It fails if the host interrupted the evaluation or if its deadline passed,
+    /// and otherwise runs the scheduled callbacks that are ready, unless one of them is already running.
+    fn yield_point(&mut self) -> Result<(), EvalError> {
+        if self.interrupt.take() {
+            return Err(EvalError::Interrupted);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(EvalError::DeadlineExceeded);
+        }
+        if !self.polling && self.event_loop.pending() > 0 {
+            self.poll_callbacks()?;
+        }
 
-        Ok(obj)
+        Ok(())
     }
 
-    fn eval_function_expression(
-        &mut self,
-        parameters: Vec<String>,
-        body: Statement,
-    ) -> Result<Object, EvalError> {
-        let closure = Closure {
-            parameters,
-            body,
-            env: self.create_enclosed_env(),
-        };
+    /// Handle interrupting the evaluation from another thread, e.g. when the user presses Ctrl-C.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
 
-        Ok(Object::FunctionValue(closure))
+    /// Makes the evaluation fail with `EvalError::DeadlineExceeded` at its first yield point after `deadline`,
+    /// bounding the time spent by untrusted scripts. Recursion without loops isn't checked.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
     }
 
-    fn eval_call_expression(
-        &mut self,
-        path: Expression,
-        arguments: Vec<Expression>,
-    ) -> Result<Object, EvalError> {
-        let function = match path {
-            Expression::Identifier(path) => {
-                // built-in functions are searched through before user-defined ones
-                BuiltinFunction::lookup_function(&path).or_else(|_| self.env.borrow().get(&path))?
+    /// Polls until no callback is left, sleeping until the next timer is due,
+    /// like the `qalo` command does after evaluating a program.
+    pub fn run_event_loop(&mut self) -> Result<(), EvalError> {
+        loop {
+            let poll = self.poll()?;
+            if poll.pending == 0 {
+                return Ok(());
             }
-            expr => self.eval_expression(expr, false)?,
-        };
-
-        let obj = match function {
-            Object::FunctionValue(Closure {
-                parameters,
-                body,
-                env,
-            }) => {
-                if parameters.len() != arguments.len() {
-                    return Err(EvalError::FunctionCallWrongArity(
-                        parameters.len() as u8,
-                        arguments.len() as u8,
-                    ));
-                }
 
-                // evaluate arguments in the current scope
-                let arguments = self.eval_call_expression_arguments(arguments)?;
+            let mut wait = poll.next_deadline.map_or(Duration::MAX, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            if self.event_loop.waits_for_tasks() {
+                wait = wait.min(TASK_POLL_INTERVAL);
+            }
+            thread::sleep(wait);
+        }
+    }
 
-                // switch to the closure environment
-                let outer_env = std::mem::replace(&mut self.env, env);
+    pub fn eval_program(&mut self) -> Result<Vec<Object>, EvalError> {
+        let program = self.parser.parse_program().map_err(EvalError::from);
+        let program = self.emit_error(program)?;
+        self.eval_parsed_program(program)
+    }
 
-                // add bindings in the closure environment
-                for (param, arg) in parameters.into_iter().zip(arguments.into_iter()) {
-                    self.env.borrow_mut().set(param, arg);
-                }
+    /// Evaluates a program that has already been parsed, ignoring the input of the evaluator.
+    pub(crate) fn eval_parsed_program(
+        &mut self,
+        program: Program,
+    ) -> Result<Vec<Object>, EvalError> {
+        self.emit(|| EvalEvent::ProgramStarted {
+            statements: program.0.len(),
+        });
+        let objects = self.eval_top_level_statements(program);
+        self.emit_error(objects)
+    }
 
-                // evaluate the closure body
-                let body_obj = self.eval_statement(body)?;
-                // go back to the old environment
-                self.env = outer_env;
+    fn eval_top_level_statements(&mut self, program: Program) -> Result<Vec<Object>, EvalError> {
+        let mut objects: Vec<Object> = vec![];
+        self.hoist_functions(&program.0)?;
 
-                body_obj
+        for (index, statement) in program.0.into_iter().enumerate() {
+            let observed = self
+                .event_sink
+                .is_some()
+                .then(|| statement.statement.clone());
+            // a `return` reaching the top level isn't inside any function
+            let obj = self.eval_located(statement).map_err(outside_function)?;
+            if let Some(statement) = observed {
+                self.emit_statement_events(index, statement, &obj);
             }
+            objects.push(obj);
+        }
+        self.forward_task_output()?;
 
-            Object::BuiltinValue(builtin) => match builtin {
-                BuiltinFunction::Len => {
-                    if arguments.len() != 1 {
-                        return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
-                    }
+        Ok(objects)
+    }
 
-                    let arguments = self.eval_call_expression_arguments(arguments)?;
-                    // unwrapping is fine, this element surely exist because of the previous check
-                    let arg = arguments.get(0).unwrap();
+    fn emit_statement_events(&mut self, index: usize, statement: Statement, value: &Object) {
+        if let Statement::VarStatement {
+            name,
+            value:
+                Expression::FunctionExpression {
+                    parameters, rest, ..
+                },
+            ..
+        } = &statement
+        {
+            let parameters = parameters
+                .iter()
+                .cloned()
+                .chain(rest.iter().map(|rest| format!("...{rest}")))
+                .collect();
+            self.emit(|| EvalEvent::FunctionDefined {
+                name: name.clone(),
+                parameters,
+            });
+        }
 
-                    let length: i32 = match arg {
-                        Object::StringValue(text) => text
-                            .len()
-                            .try_into()
-                            .or_else(|err| return Err(ParserError::IntConversionError(err)))?,
+        self.emit(|| EvalEvent::StatementEvaluated {
+            index,
+            statement: statement.to_string(),
+            value: value.clone(),
+        });
+    }
 
-                        Object::ArrayValue(objects) => objects
-                            .len()
-                            .try_into()
-                            .or_else(|err| return Err(ParserError::IntConversionError(err)))?,
+    /// Evaluates a statement of the program or of a block, notifying the observer with its location.
+    fn eval_located(&mut self, located: Located) -> Result<Object, EvalError> {
+        let Located { statement, span } = located;
+        let Some(observer) = &mut self.observer else {
+            return self.eval_statement(statement);
+        };
 
-                        _ => {
-                            return Err(EvalError::UnsupportedArgumentType(format!(
-                                "`{}` only retrieves the length of strings and arrays",
-                                BuiltinFunction::Len
-                            )));
-                        }
-                    };
+        let depth = self.env.borrow().depth();
+        observer.enter_statement(&statement, span, depth);
 
-                    Object::IntegerValue(length)
-                }
+        let observed = statement.clone();
+        let result = self.eval_statement(statement);
 
-                BuiltinFunction::Append => {
-                    if arguments.len() < 2 {
-                        return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
-                    }
+        if let Some(observer) = &mut self.observer {
+            // statements exiting a function or loop are observed with the value of the exit
+            match &result {
+                Ok(obj) => observer.exit_statement(&observed, span, depth, obj),
+                Err(EvalError::Unwind(unwind)) => {
+                    observer.exit_statement(&observed, span, depth, unwind.value())
+                }
+                Err(_) => {}
+            }
+        }
 
-                    let mut arguments = self.eval_call_expression_arguments(arguments)?;
-                    let (first, rest) = arguments.split_first_mut().unwrap();
+        result
+    }
 
-                    if let Object::ArrayValue(objects) = first {
-                        objects.extend_from_slice(rest);
-                        // return a new array, rather than modifying the existing one
-                        Object::ArrayValue(objects.clone())
-                    } else {
+    fn eval_statement(&mut self, statement: Statement) -> Result<Object, EvalError> {
+        match statement {
+            Statement::VarStatement {
+                kind,
+                name,
+                value,
+                deprecated,
+            } => {
+                let is_function = matches!(value, Expression::FunctionExpression { .. });
+                let obj = self.eval_expression(value)?;
+
+                // functions can call themselves through the name they're bound to,
+                // even after it's shadowed or re-assigned
+                if let (true, Object::FunctionValue(closure)) = (is_function, &obj) {
+                    closure.env.borrow_mut().set(name.clone(), obj.clone());
+                }
+
+                if kind == TokenKind::Var {
+                    self.env.borrow_mut().set_mutable(name.clone(), obj);
+                } else {
+                    self.env.borrow_mut().set(name.clone(), obj);
+                }
+                if let Some(note) = deprecated {
+                    self.env.borrow_mut().deprecate(&name, note);
+                }
+
+                Ok(Object::UnitValue)
+            }
+            Statement::DestructureStatement {
+                kind,
+                pattern,
+                value,
+            } => self.eval_destructure_statement(kind, pattern, value),
+            Statement::ReturnStatement(expr) => {
+                let obj = match expr {
+                    Some(expr) => self.eval_expression(expr)?,
+                    None => Object::UnitValue,
+                };
+
+                Err(Unwind::Return(obj).into())
+            }
+            Statement::AssignStatement { name, value } => {
+                let obj = self.eval_expression(value)?;
+                self.env.borrow_mut().assign(&name, obj)?;
+                Ok(Object::UnitValue)
+            }
+            Statement::ExpressionStatement(expr) => Ok(self.eval_expression(expr)?),
+            Statement::BlockStatement(statements) => self.eval_block_statement(statements),
+            Statement::ForStatement {
+                label,
+                variable,
+                iterable,
+                body,
+            } => self.eval_for_statement(label, variable, iterable, *body),
+            Statement::DoWhileStatement {
+                label,
+                body,
+                condition,
+            } => self.eval_do_while_statement(label, *body, condition),
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => self.eval_switch_statement(value, arms, *default),
+            Statement::StructStatement { name, fields } => {
+                let definition = Object::StructType {
+                    name: name.clone(),
+                    fields,
+                };
+                self.env.borrow_mut().set(name, definition);
+                Ok(Object::UnitValue)
+            }
+            Statement::EnumStatement { name, variants } => {
+                let definition = Object::EnumType {
+                    name: name.clone(),
+                    variants,
+                };
+                self.env.borrow_mut().set(name, definition);
+                Ok(Object::UnitValue)
+            }
+            Statement::BreakStatement { label, value } => self.eval_break_statement(label, value),
+            Statement::ContinueStatement(label) => Err(Unwind::Continue(label).into()),
+        }
+    }
+
+    fn eval_block_statement(&mut self, statements: Vec<Located>) -> Result<Object, EvalError> {
+        let inner_env = self.create_enclosed_env();
+        let outer_env = std::mem::replace(&mut self.env, inner_env);
+
+        // the block evaluates to its last statement
+        let result = self.hoist_functions(&statements).and_then(|()| {
+            let mut obj = Object::UnitValue;
+            for statement in statements {
+                obj = self.eval_located(statement)?;
+            }
+            Ok(obj)
+        });
+
+        // go back to the outer environment, even if the block was exited early
+        self.env = outer_env;
+
+        result
+    }
+
+    fn eval_destructure_statement(
+        &mut self,
+        kind: TokenKind,
+        pattern: Pattern,
+        value: Expression,
+    ) -> Result<Object, EvalError> {
+        let obj = self.eval_expression(value)?;
+        let mut bindings: Vec<(String, Object)> = vec![];
+        destructure(&pattern, obj, &mut bindings)?;
+
+        for (name, obj) in bindings {
+            if kind == TokenKind::Var {
+                self.env.borrow_mut().set_mutable(name, obj);
+            } else {
+                self.env.borrow_mut().set(name, obj);
+            }
+        }
+
+        Ok(Object::UnitValue)
+    }
+
+    /// Binds the functions declared with `let` in a block before evaluating its statements,
+    /// so that they can be called before their declaration and can call each other.
+    /// The declarations are evaluated again when they are reached, in case their names were shadowed.
+    fn hoist_functions(&mut self, statements: &[Located]) -> Result<(), EvalError> {
+        for Located { statement, .. } in statements {
+            if let Statement::VarStatement {
+                kind: TokenKind::Let,
+                value: Expression::FunctionExpression { .. },
+                ..
+            } = statement
+            {
+                self.eval_statement(statement.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn eval_for_statement(
+        &mut self,
+        label: Option<String>,
+        variable: String,
+        iterable: Expression,
+        body: Statement,
+    ) -> Result<Object, EvalError> {
+        // a deque bound to the identifier is iterated as it is, rather than converted to an array first
+        let iterable = match iterable {
+            Expression::Identifier(name) => self.env.borrow().get(&name)?,
+            iterable => self.eval_expression(iterable)?,
+        };
+        let objects: Box<dyn Iterator<Item = Object>> = match iterable {
+            Object::ArrayValue(objects) => Box::new(objects.into_iter()),
+            Object::DequeValue(objects) => Box::new(objects.into_iter()),
+            // ranges are iterated lazily, without building an array first
+            Object::RangeValue {
+                start,
+                end,
+                inclusive,
+            } => {
+                if inclusive {
+                    Box::new((start..=end).map(Object::IntegerValue))
+                } else {
+                    Box::new((start..end).map(Object::IntegerValue))
+                }
+            }
+            other => {
+                return Err(EvalError::TypeMismatch(format!(
+                    "`for` loops can only iterate over arrays and ranges, got '{other}'"
+                )))
+            }
+        };
+
+        for obj in objects {
+            // every iteration binds the variable in a fresh scope
+            let inner_env = self.create_enclosed_env();
+            let outer_env = std::mem::replace(&mut self.env, inner_env);
+            self.env.borrow_mut().set(variable.clone(), obj);
+
+            let body_obj = self.eval_statement(body.clone());
+            self.env = outer_env;
+
+            // `for` loops can't be exited with a value
+            if let ControlFlow::Break(obj) = after_iteration(body_obj, &label)? {
+                return Ok(obj);
+            }
+            self.yield_point()?;
+        }
+
+        Ok(Object::UnitValue)
+    }
+
+    fn eval_do_while_statement(
+        &mut self,
+        label: Option<String>,
+        body: Statement,
+        condition: Expression,
+    ) -> Result<Object, EvalError> {
+        let condition_text = condition.to_string();
+
+        loop {
+            let body_obj = self.eval_statement(body.clone());
+            if let ControlFlow::Break(obj) = after_iteration(body_obj, &label)? {
+                return Ok(obj);
+            }
+            self.yield_point()?;
+
+            let condition = self.eval_expression(condition.clone()).with_context(|| {
+                format!("while evaluating the condition `{condition_text}` of `do`-`while`")
+            })?;
+            if !self.condition(&condition, "`while` condition")? {
+                return Ok(Object::UnitValue);
+            }
+        }
+    }
+
+    /// Compares the value to the ones of the arms in order, like the literal patterns of `match` are:
+    /// the cases after the matching arm aren't evaluated.
+    fn eval_switch_statement(
+        &mut self,
+        value: Expression,
+        arms: Vec<SwitchArm>,
+        default: Statement,
+    ) -> Result<Object, EvalError> {
+        let value = self.eval_expression(value)?;
+
+        for SwitchArm { case, body } in arms {
+            if self.eval_expression(case)? == value {
+                return self.eval_statement(body);
+            }
+        }
+
+        self.eval_statement(default)
+    }
+
+    fn eval_loop_expression(
+        &mut self,
+        label: Option<String>,
+        body: &Statement,
+    ) -> Result<Object, EvalError> {
+        loop {
+            let body_obj = self.eval_statement(body.clone());
+            if let ControlFlow::Break(obj) = after_iteration(body_obj, &label)? {
+                return Ok(obj);
+            }
+            self.yield_point()?;
+        }
+    }
+
+    fn eval_break_statement(
+        &mut self,
+        label: Option<String>,
+        value: Option<Expression>,
+    ) -> Result<Object, EvalError> {
+        let obj = match value {
+            Some(value) => self.eval_expression(value)?,
+            None => Object::UnitValue,
+        };
+
+        Err(Unwind::Break(label, obj).into())
+    }
+
+    fn eval_expression(&mut self, expr: Expression) -> Result<Object, EvalError> {
+        let obj = match expr {
+            Expression::IntegerLiteral(lit) => Object::IntegerValue(lit),
+            Expression::FloatLiteral(lit) => Object::FloatValue(lit),
+            Expression::BooleanLiteral(lit) => Object::BooleanValue(lit),
+            Expression::NullLiteral => Object::NullValue,
+            Expression::StringLiteral(lit) => Object::StringValue(lit),
+            Expression::Identifier(name) => self.env.borrow().get(&name)?.into_array(),
+            Expression::ArrayLiteral(expressions) => self.eval_array_expression(expressions)?,
+            Expression::MapLiteral(map) => self.eval_map_expression(map)?,
+            Expression::StructLiteral { name, fields } => self.eval_struct_literal(name, fields)?,
+            Expression::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => self.eval_binary_expression(*left, operator, *right)?,
+            Expression::ComparisonChain {
+                operands,
+                operators,
+            } => self.eval_comparison_chain(operands, operators)?,
+            Expression::UnaryExpression {
+                operator,
+                value,
+                span,
+            } => self.eval_unary_expression(operator, *value, span)?,
+            Expression::GroupedExpression(expr) => self.eval_expression(*expr)?,
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                span,
+            } => self.eval_call_expression(*callee, arguments, named, span)?,
+            Expression::MethodCall(call) => self.eval_method_call(*call)?,
+            Expression::IndexExpression { value, index } => {
+                self.eval_index_expression(*value, *index)?
+            }
+            Expression::FieldAccess { value, field } => self.eval_field_access(*value, field)?,
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => self.eval_if_expression(*condition, *consequence, alternative)?,
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => self.eval_function_expression(parameters, rest, *body)?,
+            Expression::MatchExpression { value, arms } => {
+                self.eval_match_expression(*value, arms)?
+            }
+            Expression::LoopExpression { label, body } => {
+                self.eval_loop_expression(label, &body)?
+            }
+            // returned rather than unwrapped, which would grow the frame of every nested expression
+            Expression::BlockExpression(body) => return self.eval_statement(*body),
+        };
+
+        Ok(obj)
+    }
+
+    fn eval_binary_expression(
+        &mut self,
+        left: Expression,
+        operator: TokenKind,
+        right: Expression,
+    ) -> Result<Object, EvalError> {
+        let left_obj = self.eval_expression(left)?;
+
+        // logical operators don't evaluate the right side when the left one decides the result
+        match (&operator, &left_obj) {
+            (TokenKind::AndAnd, Object::BooleanValue(false)) => {
+                return Ok(Object::BooleanValue(false))
+            }
+            (TokenKind::OrOr, Object::BooleanValue(true)) => return Ok(Object::BooleanValue(true)),
+            _ => {}
+        }
+
+        let right_obj = self.eval_expression(right)?;
+
+        self.eval_binary_operation(left_obj, operator, right_obj)
+    }
+
+    fn eval_comparison_chain(
+        &mut self,
+        operands: Vec<Expression>,
+        operators: Vec<TokenKind>,
+    ) -> Result<Object, EvalError> {
+        let mut operands = operands.into_iter();
+        // a comparison chain always has at least two operands
+        let mut left_obj = self.eval_expression(operands.next().unwrap())?;
+
+        for (operator, right) in operators.into_iter().zip(operands) {
+            let right_obj = self.eval_expression(right)?;
+
+            match self.eval_binary_operation(left_obj, operator, right_obj.clone())? {
+                Object::BooleanValue(true) => left_obj = right_obj,
+                // stop at the first failed comparison, like `&&` would
+                obj => return Ok(obj),
+            }
+        }
+
+        Ok(Object::BooleanValue(true))
+    }
+
+    fn eval_binary_operation(
+        &self,
+        left_obj: Object,
+        operator: TokenKind,
+        right_obj: Object,
+    ) -> Result<Object, EvalError> {
+        let obj = match (left_obj, right_obj) {
+            (Object::IntegerValue(lhs), Object::IntegerValue(rhs)) => match operator {
+                TokenKind::Plus => {
+                    self.integer_result(lhs.overflowing_add(rhs), || format!("{lhs} + {rhs}"))?
+                }
+                TokenKind::Minus => {
+                    self.integer_result(lhs.overflowing_sub(rhs), || format!("{lhs} - {rhs}"))?
+                }
+                TokenKind::Asterisk => {
+                    self.integer_result(lhs.overflowing_mul(rhs), || format!("{lhs} * {rhs}"))?
+                }
+                TokenKind::Ampersand => Object::IntegerValue(lhs & rhs),
+                TokenKind::Pipe => Object::IntegerValue(lhs | rhs),
+                TokenKind::Caret => Object::IntegerValue(lhs ^ rhs),
+                TokenKind::ShiftLeft | TokenKind::ShiftRight => {
+                    let shift = u32::try_from(rhs)
+                        .ok()
+                        .filter(|shift| *shift < i64::BITS)
+                        .ok_or(EvalError::InvalidShift(rhs))?;
+
+                    // `>>` is an arithmetic shift, keeping the sign of negative numbers
+                    if operator == TokenKind::ShiftLeft {
+                        Object::IntegerValue(lhs << shift)
+                    } else {
+                        Object::IntegerValue(lhs >> shift)
+                    }
+                }
+                TokenKind::DoubleAsterisk => {
+                    let exponent = u64::try_from(rhs).or(Err(EvalError::NegativeExponent(rhs)))?;
+                    self.integer_result(overflowing_pow(lhs, exponent), || {
+                        format!("{lhs} ** {rhs}")
+                    })?
+                }
+                TokenKind::Equal => Object::BooleanValue(lhs == rhs),
+                TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
+                TokenKind::LessThan => Object::BooleanValue(lhs < rhs),
+                TokenKind::GreaterThan => Object::BooleanValue(lhs > rhs),
+                TokenKind::LessThanEqual => Object::BooleanValue(lhs <= rhs),
+                TokenKind::GreaterThanEqual => Object::BooleanValue(lhs >= rhs),
+                TokenKind::DotDot => Object::RangeValue {
+                    start: lhs,
+                    end: rhs,
+                    inclusive: false,
+                },
+                TokenKind::DotDotEqual => Object::RangeValue {
+                    start: lhs,
+                    end: rhs,
+                    inclusive: true,
+                },
+                TokenKind::Percentage => {
+                    if rhs == 0 {
+                        return Err(EvalError::ModuloByZero);
+                    } else {
+                        // `i64::MIN % -1` is 0, but it overflows when computed in Rust
+                        Object::IntegerValue(lhs.wrapping_rem(rhs))
+                    }
+                }
+                TokenKind::Slash => {
+                    if rhs == 0 {
+                        return Err(EvalError::DivisionByZero);
+                    } else {
+                        self.integer_result(lhs.overflowing_div(rhs), || format!("{lhs} / {rhs}"))?
+                    }
+                }
+                _ => return Err(EvalError::UnsupportedOperator(operator)),
+            },
+
+            (Object::FloatValue(lhs), Object::FloatValue(rhs)) => {
+                Self::eval_float_operation(lhs, operator, rhs)?
+            }
+            // integers are promoted to floats when mixed with them
+            (Object::IntegerValue(lhs), Object::FloatValue(rhs)) => {
+                Self::eval_float_operation(lhs as f64, operator, rhs)?
+            }
+            (Object::FloatValue(lhs), Object::IntegerValue(rhs)) => {
+                Self::eval_float_operation(lhs, operator, rhs as f64)?
+            }
+
+            (Object::BooleanValue(lhs), Object::BooleanValue(rhs)) => match operator {
+                TokenKind::Equal => Object::BooleanValue(lhs == rhs),
+                TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
+                TokenKind::AndAnd => Object::BooleanValue(lhs && rhs),
+                TokenKind::OrOr => Object::BooleanValue(lhs || rhs),
+                _ => return Err(EvalError::UnsupportedOperator(operator)),
+            },
+
+            // strings are ordered lexicographically by their codepoints
+            (Object::StringValue(lhs), Object::StringValue(rhs)) => match operator {
+                TokenKind::Plus => Object::StringValue(lhs + &rhs),
+                TokenKind::Equal => Object::BooleanValue(lhs == rhs),
+                TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
+                TokenKind::LessThan => Object::BooleanValue(lhs < rhs),
+                TokenKind::GreaterThan => Object::BooleanValue(lhs > rhs),
+                TokenKind::LessThanEqual => Object::BooleanValue(lhs <= rhs),
+                TokenKind::GreaterThanEqual => Object::BooleanValue(lhs >= rhs),
+                _ => return Err(EvalError::UnsupportedOperator(operator)),
+            },
+
+            // structs and enums are equal if they have the same name, variant and fields
+            (lhs @ Object::StructValue { .. }, rhs @ Object::StructValue { .. })
+            | (lhs @ Object::EnumValue { .. }, rhs @ Object::EnumValue { .. })
+                if matches!(operator, TokenKind::Equal | TokenKind::NotEqual) =>
+            {
+                Object::BooleanValue((lhs == rhs) == (operator == TokenKind::Equal))
+            }
+
+            // null can be compared to any value, but it's only equal to itself
+            (lhs @ Object::NullValue, rhs) | (lhs, rhs @ Object::NullValue) => match operator {
+                TokenKind::Equal => Object::BooleanValue(lhs == rhs),
+                TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
+                _ => {
+                    return Err(EvalError::TypeMismatch(format!(
+                        "Cannot perform operation '{operator}' between '{lhs}' and '{rhs}'",
+                    )))
+                }
+            },
+
+            (lhs, rhs) => {
+                return Err(EvalError::TypeMismatch(format!(
+                    "Cannot perform operation '{operator}' between '{lhs}' and '{rhs}'",
+                )));
+            }
+        };
+
+        Ok(obj)
+    }
+
+    fn eval_float_operation(lhs: f64, operator: TokenKind, rhs: f64) -> Result<Object, EvalError> {
+        let obj = match operator {
+            TokenKind::Plus => Object::FloatValue(lhs + rhs),
+            TokenKind::Minus => Object::FloatValue(lhs - rhs),
+            TokenKind::Asterisk => Object::FloatValue(lhs * rhs),
+            TokenKind::DoubleAsterisk => Object::FloatValue(lhs.powf(rhs)),
+            TokenKind::Equal => Object::BooleanValue(lhs == rhs),
+            TokenKind::NotEqual => Object::BooleanValue(lhs != rhs),
+            TokenKind::LessThan => Object::BooleanValue(lhs < rhs),
+            TokenKind::GreaterThan => Object::BooleanValue(lhs > rhs),
+            TokenKind::LessThanEqual => Object::BooleanValue(lhs <= rhs),
+            TokenKind::GreaterThanEqual => Object::BooleanValue(lhs >= rhs),
+            TokenKind::Percentage => {
+                if rhs == 0.0 {
+                    return Err(EvalError::ModuloByZero);
+                } else {
+                    Object::FloatValue(lhs % rhs)
+                }
+            }
+            TokenKind::Slash => {
+                if rhs == 0.0 {
+                    return Err(EvalError::DivisionByZero);
+                } else {
+                    Object::FloatValue(lhs / rhs)
+                }
+            }
+            _ => return Err(EvalError::UnsupportedOperator(operator)),
+        };
+
+        Ok(obj)
+    }
+
+    /// Turns the result of an `overflowing_*` integer operation into an object, according to `checked_arithmetic`.
+    fn integer_result(
+        &self,
+        (value, overflowed): (i64, bool),
+        operation: impl FnOnce() -> String,
+    ) -> Result<Object, EvalError> {
+        if overflowed && self.checked_arithmetic {
+            Err(EvalError::IntegerOverflow(operation()))
+        } else {
+            Ok(Object::IntegerValue(value))
+        }
+    }
+
+    fn eval_unary_expression(
+        &mut self,
+        operator: TokenKind,
+        value: Expression,
+        span: Span,
+    ) -> Result<Object, EvalError> {
+        let invalid = |operator: TokenKind, operand: &Object| {
+            EvalError::InvalidOperand(operator, operand.type_name().to_owned(), span)
+        };
+        let obj = match operator {
+            TokenKind::Bang => match self.eval_expression(value)? {
+                Object::BooleanValue(lit) => Object::BooleanValue(!lit),
+                Object::IntegerValue(_) => {
+                    return Err(EvalError::TypeMismatch(
+                        "`!` only negates booleans, use `~` to invert the bits of integers"
+                            .to_owned(),
+                    ))
+                }
+                other => return Err(invalid(operator, &other)),
+            },
+
+            TokenKind::Tilde => match self.eval_expression(value)? {
+                Object::IntegerValue(lit) => Object::IntegerValue(!lit),
+                other => return Err(invalid(operator, &other)),
+            },
+
+            TokenKind::Minus => match self.eval_expression(value)? {
+                Object::IntegerValue(lit) => {
+                    self.integer_result(lit.overflowing_neg(), || format!("-({lit})"))?
+                }
+                Object::FloatValue(lit) => Object::FloatValue(-lit),
+                other => return Err(invalid(operator, &other)),
+            },
+
+            TokenKind::Plus => match self.eval_expression(value)? {
+                obj @ (Object::IntegerValue(_) | Object::FloatValue(_)) => obj,
+                other => return Err(invalid(operator, &other)),
+            },
+
+            // `?` unwraps `Ok` and `Some`, and makes the enclosing function return `Err` and `None`
+            TokenKind::Question => match self.eval_expression(value)? {
+                Object::EnumValue {
+                    name,
+                    variant,
+                    mut values,
+                } if prelude_enum(&variant) == Some(name.as_str()) => {
+                    if variant == "Ok" || variant == "Some" {
+                        values.remove(0)
+                    } else {
+                        let obj = Object::EnumValue {
+                            name,
+                            variant,
+                            values,
+                        };
+                        return Err(Unwind::Return(obj).into());
+                    }
+                }
+                other => {
+                    return Err(EvalError::TypeMismatch(format!(
+                        "`?` only works on `Result` and `Option` values, got '{other}'"
+                    )))
+                }
+            },
+
+            _ => return Err(EvalError::UnsupportedOperator(operator)),
+        };
+
+        Ok(obj)
+    }
+
+    fn eval_array_expression(&mut self, expressions: Vec<Expression>) -> Result<Object, EvalError> {
+        let mut objects: Vec<Object> = vec![];
+
+        for expr in expressions {
+            objects.push(self.eval_expression(expr)?);
+        }
+
+        Ok(Object::ArrayValue(objects))
+    }
+
+    fn eval_map_expression(
+        &mut self,
+        expr_map: BTreeMap<String, Expression>,
+    ) -> Result<Object, EvalError> {
+        let mut map: BTreeMap<String, Object> = BTreeMap::new();
+
+        for (key, expr) in expr_map {
+            map.insert(key, self.eval_expression(expr)?);
+        }
+
+        Ok(Object::MapValue(map))
+    }
+
+    /// Builds a value of the struct bound to `name`, checking that all of its fields and only them are given
+    /// before evaluating their values, in the order they're written.
+    fn eval_struct_literal(
+        &mut self,
+        name: String,
+        fields: Vec<(String, Expression)>,
+    ) -> Result<Object, EvalError> {
+        let Object::StructType {
+            name,
+            fields: declared,
+        } = self.env.borrow().get(&name)?
+        else {
+            return Err(EvalError::NotAStruct(name));
+        };
+
+        for (field, _) in &fields {
+            if !declared.contains(field) {
+                let suggestions = closest_matches(field, declared.iter().map(String::as_str));
+                return Err(EvalError::UnknownField(name, field.clone(), suggestions));
+            }
+        }
+        if let Some(missing) = declared
+            .iter()
+            .find(|declared| fields.iter().all(|(field, _)| field != *declared))
+        {
+            return Err(EvalError::MissingField(name, missing.clone()));
+        }
+
+        let mut values = BTreeMap::new();
+        for (field, expr) in fields {
+            let value = self
+                .eval_expression(expr)
+                .with_context(|| format!("while evaluating the field `{field}` of `{name}`"))?;
+            values.insert(field, value);
+        }
+
+        let fields = declared
+            .into_iter()
+            .map(|field| {
+                // unwrapping is fine, every field has a value as checked above
+                let value = values.remove(&field).unwrap();
+                (field, value)
+            })
+            .collect();
+
+        Ok(Object::StructValue { name, fields })
+    }
+
+    fn eval_index_expression(
+        &mut self,
+        value: Expression,
+        index: Expression,
+    ) -> Result<Object, EvalError> {
+        // a binding is indexed in place, as copying its value would take linear time
+        if let Expression::Identifier(name) = value {
+            let index = self.eval_expression(index)?;
+            return self
+                .env
+                .borrow()
+                .with_value(&name, |value| Self::index(value, &index))?;
+        }
+
+        let value = self.eval_expression(value)?;
+        let index = self.eval_expression(index)?;
+        Self::index(&value, &index)
+    }
+
+    fn index(value: &Object, index: &Object) -> Result<Object, EvalError> {
+        match (value, index) {
+            (Object::ArrayValue(objects), Object::IntegerValue(index)) => {
+                let id = usize::try_from(*index).map_err(ParserError::IntConversionError)?;
+
+                let item = objects
+                    .get(id)
+                    .ok_or(EvalError::IndexOutOfBounds(objects.len(), id))?;
+
+                Ok(item.clone())
+            }
+            (Object::DequeValue(objects), Object::IntegerValue(index)) => {
+                let id = usize::try_from(*index).map_err(ParserError::IntConversionError)?;
+
+                let item = objects
+                    .get(id)
+                    .ok_or(EvalError::IndexOutOfBounds(objects.len(), id))?;
+
+                Ok(item.clone())
+            }
+            (Object::ArrayValue(objects), range @ Object::RangeValue { .. }) => {
+                let (start, end) = Self::slice_bounds(range, objects.len())?;
+                Ok(Object::ArrayValue(objects[start..end].to_vec()))
+            }
+            (Object::DequeValue(objects), range @ Object::RangeValue { .. }) => {
+                let (start, end) = Self::slice_bounds(range, objects.len())?;
+                Ok(Object::ArrayValue(
+                    objects.range(start..end).cloned().collect(),
+                ))
+            }
+            // strings are indexed by grapheme clusters, so that "é" is a single element
+            // regardless of how many codepoints or bytes it is made of
+            (Object::StringValue(text), Object::IntegerValue(index)) => {
+                let id = usize::try_from(*index).map_err(ParserError::IntConversionError)?;
+                let graphemes = graphemes(text);
+
+                let item = graphemes
+                    .get(id)
+                    .ok_or(EvalError::IndexOutOfBounds(graphemes.len(), id))?;
+
+                Ok(Object::StringValue((*item).to_owned()))
+            }
+            (Object::StringValue(text), range @ Object::RangeValue { .. }) => {
+                let graphemes = graphemes(text);
+                let (start, end) = Self::slice_bounds(range, graphemes.len())?;
+                Ok(Object::StringValue(graphemes[start..end].concat()))
+            }
+            (Object::MapValue(map), Object::StringValue(key)) => {
+                let Some(item) = map.get(key) else {
+                    let suggestions = closest_matches(key, map.keys().map(String::as_str));
+                    return Err(EvalError::ValueNotFound(key.clone(), suggestions));
+                };
+
+                Ok(item.clone())
+            }
+            (Object::ArrayValue(_) | Object::StringValue(_) | Object::MapValue(_), _) => {
+                Err(EvalError::InvalidIndexType)
+            }
+            _ => Err(EvalError::InvalidIndexUsage),
+        }
+    }
+
+    fn eval_field_access(&mut self, value: Expression, field: String) -> Result<Object, EvalError> {
+        let value = self.eval_expression(value)?;
+        Self::member(value, field)
+    }
+
+    /// Member `field` of a module, enum, struct value or hash map.
+    fn member(value: Object, field: String) -> Result<Object, EvalError> {
+        match value {
+            // sugar for `map["field"]`, which reads better in configurations
+            Object::MapValue(mut map) => {
+                let Some(value) = map.remove(&field) else {
+                    let suggestions = closest_matches(&field, map.keys().map(String::as_str));
+                    return Err(EvalError::ValueNotFound(field, suggestions));
+                };
+
+                Ok(value)
+            }
+            Object::ModuleValue { name, members } => {
+                let Some(member) = members.get(&field) else {
+                    let suggestions = closest_matches(&field, members.keys().map(String::as_str));
+                    return Err(EvalError::MemberNotFound(name, field, suggestions));
+                };
+
+                Ok(member.clone())
+            }
+            Object::EnumType { name, mut variants } => {
+                let Some(i) = variants.iter().position(|variant| variant.name == field) else {
+                    let suggestions = closest_matches(
+                        &field,
+                        variants.iter().map(|variant| variant.name.as_str()),
+                    );
+                    return Err(EvalError::UnknownVariant(name, field, suggestions));
+                };
+                let variant = variants.swap_remove(i);
+
+                // variants with fields are built by calling them
+                if variant.fields.is_empty() {
+                    Ok(Object::EnumValue {
+                        name,
+                        variant: variant.name,
+                        values: vec![],
+                    })
+                } else {
+                    Ok(Object::VariantConstructor { name, variant })
+                }
+            }
+            Object::StructValue { name, fields } => {
+                let Some((_, value)) = fields.iter().find(|(name, _)| *name == field) else {
+                    let suggestions =
+                        closest_matches(&field, fields.iter().map(|(name, _)| name.as_str()));
+                    return Err(EvalError::UnknownField(name, field, suggestions));
+                };
+
+                Ok(value.clone())
+            }
+            value => Err(EvalError::InvalidFieldAccess(
+                value.type_name().to_owned(),
+                field,
+            )),
+        }
+    }
+
+    /// Converts a range used as an index into the bounds of a slice of a `len` elements structure.
+    fn slice_bounds(range: &Object, len: usize) -> Result<(usize, usize), EvalError> {
+        let Object::RangeValue {
+            start,
+            end,
+            inclusive,
+        } = *range
+        else {
+            return Err(EvalError::InvalidIndexType);
+        };
+
+        let start = usize::try_from(start).map_err(ParserError::IntConversionError)?;
+        let end =
+            usize::try_from(end).map_err(ParserError::IntConversionError)? + usize::from(inclusive);
+
+        if end > len {
+            return Err(EvalError::IndexOutOfBounds(len, end - 1));
+        }
+        if start > end {
+            return Err(EvalError::SliceStartAfterEnd(start, end));
+        }
+
+        Ok((start, end))
+    }
+
+    fn eval_if_expression(
+        &mut self,
+        condition: Expression,
+        consequence: Statement,
+        alternative: Option<Box<Statement>>,
+    ) -> Result<Object, EvalError> {
+        let condition_text = condition.to_string();
+        let condition = self.eval_expression(condition).with_context(|| {
+            format!("while evaluating the condition `{condition_text}` of `if`")
+        })?;
+
+        let obj = if self.condition(&condition, "`if` condition")? {
+            self.eval_statement(consequence)?
+        } else if let Some(alt) = alternative {
+            self.eval_statement(*alt)?
+        } else {
+            Object::UnitValue
+        };
+
+        Ok(obj)
+    }
+
+    /// Reads the value of a condition, which must be a boolean unless conditions are truthy.
+    fn condition(&self, value: &Object, what: &str) -> Result<bool, EvalError> {
+        match value {
+            Object::BooleanValue(value) => Ok(*value),
+            value if self.truthy_conditions => Ok(value.is_truthy()),
+            _ => Err(EvalError::TypeMismatch(format!("{what} must be a boolean"))),
+        }
+    }
+
+    fn eval_match_expression(
+        &mut self,
+        value: Expression,
+        arms: Vec<MatchArm>,
+    ) -> Result<Object, EvalError> {
+        let value = self.eval_expression(value)?;
+
+        for MatchArm {
+            pattern,
+            guard,
+            body,
+        } in arms
+        {
+            let mut bindings: Vec<(String, Object)> = vec![];
+
+            if self.match_pattern(&pattern, &value, &mut bindings)? {
+                // bind the matched sub-values in the arm scope
+                let inner_env = self.create_enclosed_env();
+                let outer_env = std::mem::replace(&mut self.env, inner_env);
+                for (name, obj) in bindings {
+                    self.env.borrow_mut().set(name, obj);
+                }
+
+                if let Some(guard) = guard {
+                    match self.eval_guard(guard, &pattern) {
+                        Ok(true) => {}
+                        result => {
+                            self.env = outer_env;
+                            result?;
+                            continue;
+                        }
+                    }
+                }
+
+                let obj = self.eval_statement(body);
+                self.env = outer_env;
+
+                return obj;
+            }
+        }
+
+        Err(EvalError::NoMatchingArm(value.to_string()))
+    }
+
+    /// Evaluates the `if` guard of the arm with `pattern`, which must be a boolean unless conditions are truthy.
+    fn eval_guard(&mut self, guard: Expression, pattern: &Pattern) -> Result<bool, EvalError> {
+        let guard_text = guard.to_string();
+        let guard = self.eval_expression(guard).with_context(|| {
+            format!("while evaluating the guard `{guard_text}` of `case {pattern}`")
+        })?;
+
+        self.condition(&guard, "`if` guard of a `case`")
+    }
+
+    /// Checks whether `value` matches `pattern`, collecting the bindings it introduces.
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Object,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> Result<bool, EvalError> {
+        let matched = match pattern {
+            Pattern::Wildcard => true,
+            Pattern::Identifier(name) => {
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+            Pattern::Literal(expr) => self.eval_expression(expr.clone())? == *value,
+            Pattern::Array { elements, rest } => {
+                let Object::ArrayValue(objects) = value else {
+                    return Ok(false);
+                };
+
+                let length_matches = if rest.is_some() {
+                    objects.len() >= elements.len()
+                } else {
+                    objects.len() == elements.len()
+                };
+
+                if !length_matches {
+                    return Ok(false);
+                }
+
+                for (pattern, obj) in elements.iter().zip(objects) {
+                    if !self.match_pattern(pattern, obj, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                if let Some(rest) = rest {
+                    let rest_obj = Object::ArrayValue(objects[elements.len()..].to_vec());
+                    self.match_pattern(rest, &rest_obj, bindings)?
+                } else {
+                    true
+                }
+            }
+            Pattern::Map { entries } => {
+                let Object::MapValue(map) = value else {
+                    return Ok(false);
+                };
+
+                for (key, pattern) in entries {
+                    let Some(obj) = map.get(key) else {
+                        return Ok(false);
+                    };
+                    if !self.match_pattern(pattern, obj, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                true
+            }
+            Pattern::Variant {
+                name,
+                variant,
+                fields,
+            } => {
+                self.check_variant_pattern(name, variant, fields.len())?;
+                let Object::EnumValue {
+                    name: value_name,
+                    variant: value_variant,
+                    values,
+                } = value
+                else {
+                    return Ok(false);
+                };
+                if value_name != name || value_variant != variant {
+                    return Ok(false);
+                }
+
+                for (pattern, obj) in fields.iter().zip(values) {
+                    if !self.match_pattern(pattern, obj, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                true
+            }
+            Pattern::Or(patterns) => {
+                for pattern in patterns {
+                    // bindings of alternatives that failed to match are discarded
+                    let mut alt_bindings: Vec<(String, Object)> = vec![];
+                    if self.match_pattern(pattern, value, &mut alt_bindings)? {
+                        bindings.extend(alt_bindings);
+                        return Ok(true);
+                    }
+                }
+
+                false
+            }
+        };
+
+        Ok(matched)
+    }
+
+    /// Checks that the pattern `name.variant` with `arity` fields refers to a variant of an enum,
+    /// so that a misspelled pattern is reported rather than never matching.
+    fn check_variant_pattern(
+        &self,
+        name: &str,
+        variant: &str,
+        arity: usize,
+    ) -> Result<(), EvalError> {
+        let Object::EnumType { variants, .. } = self.env.borrow().get(name)? else {
+            return Err(EvalError::NotAnEnum(name.to_owned(), variant.to_owned()));
+        };
+        let Some(definition) = variants.iter().find(|candidate| candidate.name == variant) else {
+            let suggestions = closest_matches(
+                variant,
+                variants.iter().map(|candidate| candidate.name.as_str()),
+            );
+            return Err(EvalError::UnknownVariant(
+                name.to_owned(),
+                variant.to_owned(),
+                suggestions,
+            ));
+        };
+
+        if definition.fields.len() != arity {
+            return Err(EvalError::VariantPatternArity(
+                format!("{name}.{variant}"),
+                definition.fields.len(),
+                arity,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn eval_function_expression(
+        &mut self,
+        parameters: Vec<String>,
+        rest: Option<String>,
+        body: Statement,
+    ) -> Result<Object, EvalError> {
+        let captured = free_variables(&parameters, &rest, &body);
+        let closure = Closure {
+            env: self.create_closure_env(&captured),
+            parameters,
+            rest,
+            body,
+        };
+
+        Ok(Object::FunctionValue(closure))
+    }
+
+    fn eval_call_expression(
+        &mut self,
+        callee: Expression,
+        arguments: Vec<Expression>,
+        named: Vec<(String, Expression)>,
+        span: Span,
+    ) -> Result<Object, EvalError> {
+        let name = callee.to_string();
+        let mut deprecation = None;
+        let function = match callee {
+            Expression::Identifier(name) => {
+                // built-in functions are searched through before user-defined ones
+                BuiltinFunction::lookup_function(&name).or_else(|_| {
+                    let env = self.env.borrow();
+                    deprecation = env.deprecation(&name);
+                    env.get(&name)
+                })?
+            }
+            expr => self.eval_expression(expr)?,
+        };
+
+        self.call_function(function, name, deprecation, arguments, named, span)
+    }
+
+    /// Calls a method: members of modules, structs and enums are called like functions,
+    /// while the methods of other values are the built-in functions taking the receiver as first argument.
+    fn eval_method_call(&mut self, call: MethodCall) -> Result<Object, EvalError> {
+        let MethodCall {
+            receiver,
+            method,
+            arguments,
+            named,
+            span,
+        } = call;
+        let name = format!("{receiver}.{method}");
+        let value = self.eval_expression(receiver)?;
+
+        let methods = BuiltinFunction::methods(&value);
+        let is_member = match &value {
+            Object::ModuleValue { .. } | Object::StructValue { .. } | Object::EnumType { .. } => {
+                true
+            }
+            // functions stored in hash maps can be called too, unless a method has the same name
+            Object::MapValue(map) => {
+                map.contains_key(&method) && !methods.contains(&method.as_str())
+            }
+            _ => false,
+        };
+        if is_member {
+            let function = Self::member(value, method)?;
+            return self.call_function(function, name, None, arguments, named, span);
+        }
+
+        if !methods.contains(&method.as_str()) {
+            let suggestions = closest_matches(&method, methods.iter().copied());
+            return Err(EvalError::UnknownMethod(
+                value.type_name().to_owned(),
+                method,
+                suggestions,
+            ));
+        }
+        if let Some((parameter, _)) = named.first() {
+            return Err(EvalError::UnknownNamedArgument(parameter.clone(), vec![]));
+        }
+        let Object::BuiltinValue(builtin) = BuiltinFunction::lookup_function(&method)? else {
+            unreachable!("methods are built-in functions");
+        };
+
+        // built-in functions take their arguments unevaluated, so the receiver is bound
+        // to a name that identifiers can't have
+        let inner_env = self.create_enclosed_env();
+        let outer_env = std::mem::replace(&mut self.env, inner_env);
+        self.env.borrow_mut().set(RECEIVER.to_owned(), value);
+
+        let mut arguments = arguments;
+        arguments.insert(0, Expression::Identifier(RECEIVER.to_owned()));
+        let result = self.observe_call(&name.clone(), |this| {
+            this.eval_builtin_call(builtin, name, arguments)
+        });
+        self.env = outer_env;
+
+        result
+    }
+
+    /// Calls a function, after warning if it's deprecated. Kept apart from `eval_call_expression`,
+    /// as methods are called the same way.
+    fn call_function(
+        &mut self,
+        function: Object,
+        name: String,
+        mut deprecation: Option<String>,
+        arguments: Vec<Expression>,
+        named: Vec<(String, Expression)>,
+        span: Span,
+    ) -> Result<Object, EvalError> {
+        if let Object::NativeFunctionValue(native) = &function {
+            deprecation = deprecation.or_else(|| native.deprecated.clone());
+        }
+        if let Some(note) = deprecation {
+            self.warn_deprecated_call(&name, note, span);
+        }
+        if arguments.iter().any(Self::is_placeholder) {
+            return self.partially_apply(function, &name, arguments, named, span);
+        }
+
+        let obj = match function {
+            Object::FunctionValue(closure) if !named.is_empty() => {
+                let positional = self.eval_call_expression_arguments(&name, arguments)?;
+                let named = named
+                    .into_iter()
+                    .map(|(parameter, arg)| {
+                        let value = self.eval_expression(arg).with_context(|| {
+                            format!("while evaluating argument `{parameter}` of `{name}`")
+                        })?;
+                        Ok((parameter, value))
+                    })
+                    .collect::<Result<Vec<(String, Object)>, EvalError>>()?;
+
+                let arguments = closure.arrange_arguments(positional, named)?;
+                self.observe_call(&name, |this| this.call_closure(closure, arguments))?
+            }
+            // only user-defined functions have parameter names
+            _ if !named.is_empty() => {
+                return Err(EvalError::UnknownNamedArgument(named[0].0.clone(), vec![]));
+            }
+
+            Object::FunctionValue(closure) => {
+                // check before evaluating the arguments
+                closure.check_arity(arguments.len())?;
+
+                // evaluate arguments in the current scope
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+
+                self.observe_call(&name, |this| this.call_closure(closure, arguments))?
+            }
+
+            Object::BuiltinValue(builtin) => self.observe_call(&name.clone(), |this| {
+                this.eval_builtin_call(builtin, name, arguments)
+            })?,
+
+            Object::NativeFunctionValue(native) => {
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                self.observe_call(&name, |evaluator| {
+                    evaluator.call_native(&name, &native, arguments)
+                })?
+            }
+
+            Object::VariantConstructor {
+                name: enum_name,
+                variant,
+            } => {
+                if arguments.len() != variant.fields.len() {
+                    return Err(EvalError::FunctionCallWrongArity(
+                        variant.fields.len() as u8,
+                        arguments.len() as u8,
+                    ));
+                }
+
+                Object::EnumValue {
+                    name: enum_name,
+                    variant: variant.name,
+                    values: self.eval_call_expression_arguments(&name, arguments)?,
+                }
+            }
+
+            other => {
+                return Err(EvalError::FunctionNotFound(format!(
+                    "`{other}` cannot be called as a function"
+                )));
+            }
+        };
+
+        Ok(obj)
+    }
+
+    fn is_placeholder(argument: &Expression) -> bool {
+        matches!(argument, Expression::Identifier(name) if name == "_")
+    }
+
+    /// Calls a function with `_` in place of some of its arguments, like `add(2, _)`, which makes a function
+    /// taking the missing arguments in order. The other arguments are evaluated right away and bound
+    /// to names that identifiers can't have, and the function makes the call again with all of them,
+    /// so that every kind of function can be partially applied.
+    fn partially_apply(
+        &mut self,
+        function: Object,
+        name: &str,
+        arguments: Vec<Expression>,
+        named: Vec<(String, Expression)>,
+        span: Span,
+    ) -> Result<Object, EvalError> {
+        let mut env = Environment::default();
+        env.set(PARTIAL.to_owned(), function);
+
+        let mut parameters = vec![];
+        let mut bound = vec![];
+        for (i, argument) in arguments.into_iter().enumerate() {
+            let binding = if Self::is_placeholder(&argument) {
+                parameters.push(format!("<_{}>", parameters.len() + 1));
+                parameters[parameters.len() - 1].clone()
+            } else {
+                let value = self
+                    .eval_expression(argument)
+                    .with_context(|| format!("while evaluating argument {} of `{name}`", i + 1))?;
+                env.set(format!("<argument {}>", i + 1), value);
+                format!("<argument {}>", i + 1)
+            };
+            bound.push(Expression::Identifier(binding));
+        }
+        let mut bound_named = vec![];
+        for (parameter, argument) in named {
+            let value = self
+                .eval_expression(argument)
+                .with_context(|| format!("while evaluating argument `{parameter}` of `{name}`"))?;
+            env.set(format!("<{parameter}>"), value);
+            bound_named.push((
+                parameter.clone(),
+                Expression::Identifier(format!("<{parameter}>")),
+            ));
+        }
+
+        let call = Expression::CallExpression {
+            callee: Box::new(Expression::Identifier(PARTIAL.to_owned())),
+            arguments: bound,
+            named: bound_named,
+            span,
+        };
+        Ok(Object::FunctionValue(Closure {
+            parameters,
+            rest: None,
+            body: Statement::ExpressionStatement(call),
+            env: self.track_env(env),
+        }))
+    }
+
+    /// Notifies the observer before and after a function call, once its arguments are evaluated.
+    /// Built-in functions take their arguments unevaluated, so their calls include evaluating them.
+    fn observe_call(
+        &mut self,
+        name: &str,
+        call: impl FnOnce(&mut Self) -> Result<Object, EvalError>,
+    ) -> Result<Object, EvalError> {
+        if let Some(observer) = &mut self.observer {
+            observer.enter_call(name);
+        }
+        let result = call(self);
+        if let Some(observer) = &mut self.observer {
+            observer.exit_call(name);
+        }
+
+        result
+    }
+
+    /// Calls a built-in function. Kept apart from `eval_call_expression`, whose stack frame is part of
+    /// every recursive call of user-defined functions.
+    fn eval_builtin_call(
+        &mut self,
+        builtin: BuiltinFunction,
+        name: String,
+        arguments: Vec<Expression>,
+    ) -> Result<Object, EvalError> {
+        let obj = match builtin {
+            BuiltinFunction::Len => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                // a binding is measured in place, as copying its value would take linear time
+                if let [Expression::Identifier(identifier)] = arguments.as_slice() {
+                    if let Ok(length) = self.env.borrow().with_value(identifier, Self::length) {
+                        return Ok(Object::IntegerValue(length?));
+                    }
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                Object::IntegerValue(Self::length(arguments.first().unwrap())?)
+            }
+
+            BuiltinFunction::Append => {
+                if arguments.len() < 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let mut arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (first, rest) = arguments.split_first_mut().unwrap();
+
+                if let Object::ArrayValue(objects) = first {
+                    objects.extend_from_slice(rest);
+                    // return a new array, rather than modifying the existing one
+                    Object::ArrayValue(objects.clone())
+                } else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Append
+                    )));
+                }
+            }
+
+            BuiltinFunction::PushFront
+            | BuiltinFunction::PushBack
+            | BuiltinFunction::PopFront
+            | BuiltinFunction::PopBack => self.eval_deque_builtin(builtin, arguments)?,
+
+            BuiltinFunction::Rest => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let arg = arguments.first().unwrap();
+
+                if let Object::ArrayValue(objects) = arg {
+                    // return a new array, rather than modifying the existing one
+                    Object::ArrayValue(objects[1..].to_vec())
+                } else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Append
+                    )));
+                }
+            }
+
+            BuiltinFunction::ToArray => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let arg = arguments.first().unwrap();
+
+                match *arg {
+                    Object::RangeValue {
+                        start,
+                        end,
+                        inclusive: true,
+                    } => Object::ArrayValue((start..=end).map(Object::IntegerValue).collect()),
+                    Object::RangeValue {
+                        start,
+                        end,
+                        inclusive: false,
+                    } => Object::ArrayValue((start..end).map(Object::IntegerValue).collect()),
+                    Object::ArrayValue(_) => arg.clone(),
+                    _ => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{}` only works on ranges and arrays",
+                            BuiltinFunction::ToArray
+                        )));
+                    }
+                }
+            }
+
+            BuiltinFunction::Chars | BuiltinFunction::Codepoints | BuiltinFunction::Bytes => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::StringValue(text) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on strings"
+                    )));
+                };
+
+                let elements = match builtin {
+                    BuiltinFunction::Chars => graphemes(text)
+                        .into_iter()
+                        .map(|grapheme| Object::StringValue(grapheme.to_owned()))
+                        .collect(),
+                    BuiltinFunction::Codepoints => text
+                        .chars()
+                        .map(|ch| Object::StringValue(ch.to_string()))
+                        .collect(),
+                    _ => text
+                        .bytes()
+                        .map(|byte| Object::IntegerValue(byte.into()))
+                        .collect(),
+                };
+
+                Object::ArrayValue(elements)
+            }
+
+            BuiltinFunction::Upper | BuiltinFunction::Lower => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::StringValue(text) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on strings"
+                    )));
+                };
+
+                Object::StringValue(match builtin {
+                    BuiltinFunction::Upper => text.to_uppercase(),
+                    _ => text.to_lowercase(),
+                })
+            }
+
+            BuiltinFunction::SplitLines => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::StringValue(text) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on strings"
+                    )));
+                };
+
+                // lines end with `\n` or `\r\n`, and the final line ending is optional
+                Object::ArrayValue(
+                    text.lines()
+                        .map(|line| Object::StringValue(line.to_owned()))
+                        .collect(),
+                )
+            }
+
+            BuiltinFunction::StartsWith | BuiltinFunction::EndsWith => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::StringValue(text), Object::StringValue(affix)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on strings"
+                    )));
+                };
+
+                Object::BooleanValue(if builtin == BuiltinFunction::StartsWith {
+                    text.starts_with(affix.as_str())
+                } else {
+                    text.ends_with(affix.as_str())
+                })
+            }
+
+            BuiltinFunction::PadLeft | BuiltinFunction::PadRight => {
+                if !(2..=3).contains(&arguments.len()) {
+                    return Err(EvalError::FunctionCallArityOutOfRange(
+                        2,
+                        3,
+                        arguments.len() as u8,
+                    ));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::StringValue(text), Object::IntegerValue(width)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` pads a string to an integer width"
+                    )));
+                };
+                let fill = match arguments.get(2) {
+                    None => " ",
+                    Some(Object::StringValue(fill)) if graphemes(fill).len() == 1 => fill,
+                    Some(_) => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` only fills with a single character"
+                        )));
+                    }
+                };
+
+                // the width is counted in grapheme clusters, like the length of strings
+                let missing = usize::try_from(*width)
+                    .unwrap_or(0)
+                    .saturating_sub(graphemes(text).len());
+                let padding = fill.repeat(missing);
+
+                Object::StringValue(if builtin == BuiltinFunction::PadLeft {
+                    padding + text
+                } else {
+                    text.clone() + &padding
+                })
+            }
+
+            BuiltinFunction::IsNull => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                Object::BooleanValue(*arguments.first().unwrap() == Object::NullValue)
+            }
+
+            BuiltinFunction::ParseInt => {
+                if !(1..=2).contains(&arguments.len()) {
+                    return Err(EvalError::FunctionCallArityOutOfRange(
+                        1,
+                        2,
+                        arguments.len() as u8,
+                    ));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+
+                let (Object::StringValue(text), base) = (&arguments[0], arguments.get(1)) else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only parses strings",
+                        BuiltinFunction::ParseInt
+                    )));
+                };
+                let base = match base {
+                    None => 10,
+                    Some(base) => Self::radix(base, BuiltinFunction::ParseInt)?,
+                };
+
+                // the explicit failure value is `null`, rather than an error stopping the script
+                i64::from_str_radix(text, base).map_or(Object::NullValue, Object::IntegerValue)
+            }
+
+            BuiltinFunction::ParseFloat => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::StringValue(text) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only parses strings",
+                        BuiltinFunction::ParseFloat
+                    )));
+                };
+
+                // Rust's parsing doesn't depend on the host locale: the decimal separator is always `.`.
+                // It also reads words like `inf` and `NaN`, which aren't numbers written with digits
+                if !text
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'))
+                {
+                    return Ok(Object::NullValue);
+                }
+                text.parse::<f64>()
+                    .map_or(Object::NullValue, Object::FloatValue)
+            }
+
+            BuiltinFunction::ToBase => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let Object::IntegerValue(number) = arguments[0] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only formats integers",
+                        BuiltinFunction::ToBase
+                    )));
+                };
+                let base = Self::radix(&arguments[1], BuiltinFunction::ToBase)?;
+
+                let mut magnitude = number.unsigned_abs();
+                let mut digits = vec![];
+                loop {
+                    // the remainder is always smaller than `base`, so it fits in an `u32`
+                    let digit = (magnitude % u64::from(base)) as u32;
+                    // unwrapping is fine, the remainder is always a valid digit in `base`
+                    digits.push(char::from_digit(digit, base).unwrap());
+                    magnitude /= u64::from(base);
+                    if magnitude == 0 {
+                        break;
+                    }
+                }
+                if number < 0 {
+                    digits.push('-');
+                }
+
+                Object::StringValue(digits.into_iter().rev().collect())
+            }
+
+            BuiltinFunction::Sorted => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::ArrayValue(objects) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Sorted
+                    )));
+                };
+
+                // return a new array, rather than modifying the existing one
+                let mut sorted = objects.clone();
+                // checked in the order of the array, as sorting compares its elements in any order:
+                // values that can be compared with the first one can be compared with each other
+                if let Some((first, rest)) = objects.split_first() {
+                    for other in rest {
+                        Self::compare_objects(first, other)?;
+                    }
+                }
+                sorted.sort_by(|a, b| Self::compare_objects(a, b).unwrap_or(Ordering::Equal));
+
+                Object::ArrayValue(sorted)
+            }
+
+            BuiltinFunction::Unique => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::ArrayValue(objects) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Unique
+                    )));
+                };
+
+                // keep the first occurrence of every element, preserving their order
+                let mut unique: Vec<Object> = vec![];
+                for object in objects {
+                    if !unique.contains(object) {
+                        unique.push(object.clone());
+                    }
+                }
+
+                Object::ArrayValue(unique)
+            }
+
+            BuiltinFunction::Flatten => {
+                if !(1..=2).contains(&arguments.len()) {
+                    return Err(EvalError::FunctionCallArityOutOfRange(
+                        1,
+                        2,
+                        arguments.len() as u8,
+                    ));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::ArrayValue(objects), depth) = (&arguments[0], arguments.get(1)) else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Flatten
+                    )));
+                };
+                let depth = match depth {
+                    None => 1,
+                    Some(Object::IntegerValue(depth @ 0..)) => *depth as usize,
+                    Some(other) => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{}` only accepts a non-negative integer depth, got {other}",
+                            BuiltinFunction::Flatten
+                        )));
+                    }
+                };
+
+                let mut flattened = vec![];
+                Self::flatten_into(objects, depth, &mut flattened);
+
+                Object::ArrayValue(flattened)
+            }
+
+            BuiltinFunction::Zip => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::ArrayValue(left), Object::ArrayValue(right)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Zip
+                    )));
+                };
+
+                // the result is as long as the shortest array
+                let pairs = left
+                    .iter()
+                    .zip(right)
+                    .map(|(a, b)| Object::ArrayValue(vec![a.clone(), b.clone()]))
+                    .collect();
+
+                Object::ArrayValue(pairs)
+            }
+
+            BuiltinFunction::Enumerate => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::ArrayValue(objects) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{}` only works on arrays",
+                        BuiltinFunction::Enumerate
+                    )));
+                };
+
+                let pairs = objects
+                    .iter()
+                    .enumerate()
+                    .map(|(i, object)| {
+                        let index = i64::try_from(i).map_err(ParserError::IntConversionError)?;
+                        Ok(Object::ArrayValue(vec![
+                            Object::IntegerValue(index),
+                            object.clone(),
+                        ]))
+                    })
+                    .collect::<Result<Vec<Object>, EvalError>>()?;
+
+                Object::ArrayValue(pairs)
+            }
+
+            BuiltinFunction::Pmap => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::ArrayValue(objects), Object::FunctionValue(function)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on an array and a function"
+                    )));
+                };
+                if !is_pure(&arguments[1]) {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` only runs pure functions, use a `for` loop for functions with effects"
+                        )));
+                }
+
+                match parallel_map(function, objects, self.settings()) {
+                    Ok(map) => {
+                        self.environments
+                            .extend(map.environments.iter().map(Rc::downgrade));
+                        Object::ArrayValue(map.results)
+                    }
+                    Err(ParallelMapError::NotPortable(index)) => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                                "`{builtin}` can't send the native value at index {index} to other threads"
+                            )));
+                    }
+                    Err(ParallelMapError::ResultNotPortable(index)) => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` can't send back the native value returned for the element at index {index}"
+                        )));
+                    }
+                    Err(ParallelMapError::Failed(index, code, message)) => {
+                        let err = EvalError::WorkerFailed(code, message);
+                        return Err(err.context(format!(
+                            "while mapping the element at index {index} with `{builtin}`"
+                        )));
+                    }
+                }
+            }
+
+            BuiltinFunction::Spawn => {
+                if arguments.is_empty() {
+                    return Err(EvalError::FunctionCallTooFewArguments(1, 0));
+                }
+
+                let mut arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let Object::FunctionValue(function) = arguments.remove(0) else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only runs functions"
+                    )));
+                };
+                function.check_arity(arguments.len())?;
+
+                let thread = parallel::spawn(&function, &arguments, self.settings())
+                    .map_err(|index| {
+                        EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` can't send the native value of argument {} to another thread",
+                            index + 2
+                        ))
+                    })?;
+                Object::TaskValue(Task::new(thread))
+            }
+
+            BuiltinFunction::Join => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let Object::TaskValue(task) = &arguments[0] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on tasks started by `spawn`"
+                    )));
+                };
+
+                self.join_task(task)?
+            }
+
+            BuiltinFunction::WaitAll => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let not_tasks = || {
+                    EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on an array of tasks started by `spawn`"
+                    ))
+                };
+                let Object::ArrayValue(values) = &arguments[0] else {
+                    return Err(not_tasks());
+                };
+                let tasks = values
+                    .iter()
+                    .map(|value| match value {
+                        Object::TaskValue(task) => Ok(task),
+                        _ => Err(not_tasks()),
+                    })
+                    .collect::<Result<Vec<&Task>, EvalError>>()?;
+
+                // every task is waited for, even after one of them failed
+                let results: Vec<Result<Object, EvalError>> =
+                    tasks.into_iter().map(|task| self.join_task(task)).collect();
+                Object::ArrayValue(results.into_iter().collect::<Result<_, _>>()?)
+            }
+
+            BuiltinFunction::Actor => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let Object::FunctionValue(handler) = &arguments[0] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on a function handling the state and a message"
+                    )));
+                };
+                handler.check_arity(2)?;
+
+                let actor = parallel::start_actor(handler, &arguments[1], self.settings())
+                    .ok_or_else(|| {
+                        EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` can't send a native value to another thread"
+                        ))
+                    })?;
+                Object::ActorValue(actor)
+            }
+
+            BuiltinFunction::Tell | BuiltinFunction::Ask => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let Object::ActorValue(actor) = &arguments[0] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only sends messages to actors started by `actor`"
+                    )));
+                };
+
+                let ask = builtin == BuiltinFunction::Ask;
+                match parallel::send(actor, &arguments[1], ask) {
+                    Ok(Some(reply)) => {
+                        self.forward_task_output()?;
+                        self.environments
+                            .extend(reply.environments.iter().map(Rc::downgrade));
+                        reply.result
+                    }
+                    Ok(None) => Object::UnitValue,
+                    Err(MailError::NotPortable) => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` can't send a native value to another thread"
+                        )));
+                    }
+                    Err(MailError::Failed(message)) => {
+                        return Err(EvalError::ActorFailed(actor.id, message));
+                    }
+                }
+            }
+
+            BuiltinFunction::SetTimeout | BuiltinFunction::SetInterval => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::FunctionValue(callback), Object::IntegerValue(milliseconds)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on a function and a number of milliseconds"
+                    )));
+                };
+                let Ok(milliseconds) = u64::try_from(*milliseconds) else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` can't wait for a negative number of milliseconds, got {milliseconds}"
+                    )));
+                };
+
+                let delay = Duration::from_millis(milliseconds);
+                let period = (builtin == BuiltinFunction::SetInterval).then_some(delay);
+                let id = self.event_loop.schedule(delay, period, callback.clone());
+                Object::IntegerValue(id as i64)
+            }
+
+            BuiltinFunction::ClearTimer => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let Object::IntegerValue(id) = arguments[0] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on the ids of timers"
+                    )));
+                };
+
+                let cleared = usize::try_from(id).is_ok_and(|id| self.event_loop.cancel(id));
+                Object::BooleanValue(cleared)
+            }
+
+            BuiltinFunction::WhenDone => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::TaskValue(task), Object::FunctionValue(callback)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on a task started by `spawn` and a function"
+                    )));
+                };
+                callback.check_arity(1)?;
+
+                self.event_loop.when_done(task.clone(), callback.clone());
+                Object::UnitValue
+            }
+
+            BuiltinFunction::GroupBy | BuiltinFunction::CountBy => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let (Object::ArrayValue(objects), Object::FunctionValue(key_fn)) =
+                    (&arguments[0], &arguments[1])
+                else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on an array and a function"
+                    )));
+                };
+
+                let mut groups: BTreeMap<String, Vec<Object>> = BTreeMap::new();
+                for object in objects {
+                    let key = self.call_closure(key_fn.clone(), vec![object.clone()])?;
+                    let Object::StringValue(key) = key else {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{builtin}` needs the function to return string keys, got {key}"
+                        )));
+                    };
+
+                    groups.entry(key).or_default().push(object.clone());
+                }
+
+                let map = if builtin == BuiltinFunction::GroupBy {
+                    groups
+                        .into_iter()
+                        .map(|(key, group)| (key, Object::ArrayValue(group)))
+                        .collect()
+                } else {
+                    groups
+                        .into_iter()
+                        .map(|(key, group)| {
+                            let count = i64::try_from(group.len())
+                                .map_err(ParserError::IntConversionError)?;
+                            Ok((key, Object::IntegerValue(count)))
+                        })
+                        .collect::<Result<BTreeMap<String, Object>, EvalError>>()?
+                };
+
+                Object::MapValue(map)
+            }
+
+            BuiltinFunction::Clone => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // arrays and maps own their elements, so cloning them copies the whole structure;
+                // closures keep sharing the environment they captured
+                arguments.first().unwrap().clone()
+            }
+
+            BuiltinFunction::Freeze => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                // freezing an identifier prevents any further assignment to it, other values
+                // are temporaries that can't be modified anyway
+                match arguments.into_iter().next().unwrap() {
+                    Expression::Identifier(name) => self.env.borrow_mut().freeze(&name)?,
+                    expr => self.eval_expression(expr)?,
+                }
+            }
+
+            BuiltinFunction::Weak => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let Some(Expression::Identifier(name)) = arguments.into_iter().next() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` refers to a binding, its argument must be an identifier"
+                    )));
+                };
+                let Some(env) = Environment::defining(&self.env, &name) else {
+                    return Err(EvalError::IdentifierNotFound(name));
+                };
+
+                let composite = matches!(
+                    env.borrow().store.get(&name),
+                    Some(
+                        Object::ArrayValue(_)
+                            | Object::DequeValue(_)
+                            | Object::MapValue(_)
+                            | Object::StructValue { .. }
+                            | Object::EnumValue { .. }
+                    )
+                );
+                if !composite {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only refers to arrays, hash maps, structs and enums"
+                    )));
+                }
+
+                Object::WeakValue(WeakBinding {
+                    name,
+                    env: Rc::downgrade(&env),
+                })
+            }
+
+            BuiltinFunction::Upgrade => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let Object::WeakValue(weak) = arguments.first().unwrap() else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only works on values made by `weak`"
+                    )));
+                };
+
+                // the explicit failure value is `null`, like for `parse_int`
+                weak.upgrade().unwrap_or(Object::NullValue)
+            }
+
+            BuiltinFunction::Sizeof | BuiltinFunction::Depth => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let arg = arguments.first().unwrap();
+
+                let value = if builtin == BuiltinFunction::Sizeof {
+                    arg.approximate_size()
+                } else {
+                    arg.nesting_depth()
+                };
+
+                Object::IntegerValue(value.try_into().map_err(ParserError::IntConversionError)?)
+            }
+
+            BuiltinFunction::Fields => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                let names: Vec<&String> = match arguments.first().unwrap() {
+                    Object::MapValue(map) => map.keys().collect(),
+                    Object::StructValue { fields, .. } => {
+                        fields.iter().map(|(name, _)| name).collect()
+                    }
+                    _ => {
+                        return Err(EvalError::UnsupportedArgumentType(format!(
+                            "`{}` only works on hash maps and structs",
+                            BuiltinFunction::Fields
+                        )))
+                    }
+                };
+
+                Object::ArrayValue(
+                    names
+                        .into_iter()
+                        .map(|name| Object::StringValue(name.clone()))
+                        .collect(),
+                )
+            }
+
+            BuiltinFunction::Diff | BuiltinFunction::AssertEq => {
+                if arguments.len() != 2 {
+                    return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                let diff = diff_values(&arguments[0], &arguments[1]);
+                if builtin == BuiltinFunction::Diff {
+                    Object::StringValue(diff)
+                } else if diff.is_empty() {
+                    Object::UnitValue
+                } else {
+                    return Err(EvalError::AssertionFailed(diff));
+                }
+            }
+
+            BuiltinFunction::Type => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                Object::StringValue(arguments.first().unwrap().type_name().to_owned())
+            }
+
+            BuiltinFunction::IsPure => {
+                if arguments.len() != 1 {
+                    return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                // unwrapping is fine, this element surely exist because of the previous check
+                Object::BooleanValue(is_pure(arguments.first().unwrap()))
+            }
+
+            BuiltinFunction::Version => {
+                if !arguments.is_empty() {
+                    return Err(EvalError::FunctionCallWrongArity(0, arguments.len() as u8));
+                }
+
+                Object::StringValue(VERSION.to_owned())
+            }
+            BuiltinFunction::Features => {
+                if !arguments.is_empty() {
+                    return Err(EvalError::FunctionCallWrongArity(0, arguments.len() as u8));
+                }
+
+                self.features().to_object()
+            }
+            BuiltinFunction::YieldNow => {
+                if !arguments.is_empty() {
+                    return Err(EvalError::FunctionCallWrongArity(0, arguments.len() as u8));
+                }
+
+                self.yield_point()?;
+                Object::UnitValue
+            }
+
+            BuiltinFunction::Log => {
+                if !(2..=3).contains(&arguments.len()) {
+                    return Err(EvalError::FunctionCallArityOutOfRange(
+                        2,
+                        3,
+                        arguments.len() as u8,
+                    ));
+                }
+
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+
+                let Object::StringValue(level) = &arguments[0] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` expects the level as a string"
+                    )));
+                };
+                let Some(level) = LogLevel::parse(level) else {
+                    let levels = LogLevel::ALL.map(|level| format!("\"{level}\""));
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` only accepts the levels {}, got \"{level}\"",
+                        levels.join(", ")
+                    )));
+                };
+                let Object::StringValue(message) = &arguments[1] else {
+                    return Err(EvalError::UnsupportedArgumentType(format!(
+                        "`{builtin}` expects the message as a string"
+                    )));
+                };
+
+                let fields = match arguments.get(2) {
+                    None => vec![],
+                    Some(Object::MapValue(map)) => map
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect(),
+                    Some(_) => {
                         return Err(EvalError::UnsupportedArgumentType(format!(
-                            "`{}` only works on arrays",
-                            BuiltinFunction::Append
+                            "`{builtin}` expects the fields as a hash map"
                         )));
                     }
+                };
+
+                let record = LogRecord {
+                    level,
+                    message: message.clone(),
+                    fields,
+                };
+                self.forward_task_output()?;
+                match &mut self.log_sink {
+                    Some(sink) => sink.log(&record),
+                    None => writeln!(self.streams.stderr, "{record}")
+                        .map_err(|err| EvalError::Io(err.to_string()))?,
+                }
+                Object::UnitValue
+            }
+
+            BuiltinFunction::Println => {
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                for arg in arguments {
+                    self.write_output(&format!("{arg}\n"))?;
+                }
+                Object::UnitValue
+            }
+            BuiltinFunction::Print => {
+                let arguments = self.eval_call_expression_arguments(&name, arguments)?;
+                for arg in arguments {
+                    self.write_output(&arg.to_string())?;
+                }
+                Object::UnitValue
+            }
+            BuiltinFunction::ReadLine => {
+                if !arguments.is_empty() {
+                    return Err(EvalError::FunctionCallWrongArity(0, arguments.len() as u8));
+                }
+
+                // what was printed without a newline, like a prompt, is shown before waiting
+                self.streams
+                    .stdout
+                    .flush()
+                    .map_err(|err| EvalError::Io(err.to_string()))?;
+                match self.read_input_line()? {
+                    Some(line) => Object::StringValue(line),
+                    None => Object::NullValue,
+                }
+            }
+        };
+
+        Ok(obj)
+    }
+
+    /// Orders two values of the same type; integers and floats can be compared to each other.
+    /// Length of a value, as returned by `len`.
+    fn length(obj: &Object) -> Result<i64, EvalError> {
+        let length = match obj {
+            Object::StringValue(text) => graphemes(text)
+                .len()
+                .try_into()
+                .map_err(ParserError::IntConversionError)?,
+
+            Object::ArrayValue(objects) => objects
+                .len()
+                .try_into()
+                .map_err(ParserError::IntConversionError)?,
+
+            Object::DequeValue(objects) => objects
+                .len()
+                .try_into()
+                .map_err(ParserError::IntConversionError)?,
+
+            Object::RangeValue {
+                start,
+                end,
+                inclusive,
+            } => {
+                // computed on 128 bits, as the bounds can be as far apart as `i64::MIN..i64::MAX`
+                let length = i128::from(*end) - i128::from(*start) + i128::from(*inclusive);
+                length
+                    .max(0)
+                    .try_into()
+                    .map_err(ParserError::IntConversionError)?
+            }
+
+            _ => {
+                return Err(EvalError::UnsupportedArgumentType(format!(
+                    "`{}` only retrieves the length of strings, arrays and ranges",
+                    BuiltinFunction::Len
+                )));
+            }
+        };
+
+        Ok(length)
+    }
+
+    fn compare_objects(a: &Object, b: &Object) -> Result<Ordering, EvalError> {
+        let ordering = match (a, b) {
+            (Object::IntegerValue(a), Object::IntegerValue(b)) => a.cmp(b),
+            (Object::FloatValue(a), Object::FloatValue(b)) => a.total_cmp(b),
+            (Object::IntegerValue(a), Object::FloatValue(b)) => (*a as f64).total_cmp(b),
+            (Object::FloatValue(a), Object::IntegerValue(b)) => a.total_cmp(&(*b as f64)),
+            (Object::StringValue(a), Object::StringValue(b)) => a.cmp(b),
+            (Object::BooleanValue(a), Object::BooleanValue(b)) => a.cmp(b),
+            _ => {
+                return Err(EvalError::TypeMismatch(format!(
+                    "Cannot compare '{a}' and '{b}'"
+                )))
+            }
+        };
+
+        Ok(ordering)
+    }
+
+    /// Pushes the elements of `objects` into `flattened`, unwrapping nested arrays up to `depth` levels.
+    fn flatten_into(objects: &[Object], depth: usize, flattened: &mut Vec<Object>) {
+        for object in objects {
+            match object {
+                Object::ArrayValue(inner) if depth > 0 => {
+                    Self::flatten_into(inner, depth - 1, flattened)
+                }
+                other => flattened.push(other.clone()),
+            }
+        }
+    }
+
+    /// Validates the base of a number, used by the built-in functions converting from and to strings.
+    fn radix(base: &Object, builtin: BuiltinFunction) -> Result<u32, EvalError> {
+        match *base {
+            Object::IntegerValue(base @ 2..=36) => Ok(base as u32),
+            _ => Err(EvalError::UnsupportedArgumentType(format!(
+                "`{builtin}` only supports integer bases between 2 and 36, got {base}"
+            ))),
+        }
+    }
+
+    /// Waits for a task to finish, failing with its error.
+    fn join_task(&mut self, task: &Task) -> Result<Object, EvalError> {
+        let (result, environments) = task.join();
+        self.environments
+            .extend(environments.iter().map(Rc::downgrade));
+        self.forward_task_output()?;
+
+        result.map_err(|message| EvalError::TaskFailed(task.id, message))
+    }
+
+    /// Calls a user-defined function with arguments that have already been evaluated.
+    pub(crate) fn call_closure(
+        &mut self,
+        closure: Closure,
+        arguments: Vec<Object>,
+    ) -> Result<Object, EvalError> {
+        closure.check_arity(arguments.len())?;
+        let Closure {
+            parameters,
+            rest,
+            body,
+            env,
+        } = closure;
+
+        // switch to a new environment enclosed by the closure one, so that every call
+        // (including recursive ones) has its own bindings
+        let outer_env = std::mem::replace(&mut self.env, env);
+        self.env = self.create_enclosed_env();
+
+        // add bindings in the call environment, collecting the remaining arguments in the rest parameter
+        let mut arguments = arguments.into_iter();
+        for (param, arg) in parameters.into_iter().zip(arguments.by_ref()) {
+            self.env.borrow_mut().set(param, arg);
+        }
+        if let Some(rest) = rest {
+            let rest_arguments = Object::ArrayValue(arguments.collect());
+            self.env.borrow_mut().set(rest, rest_arguments);
+        }
+
+        // evaluate the closure body
+        let body_obj = self.eval_statement(body);
+        // go back to the old environment, even if the body failed
+        self.env = outer_env;
+
+        // `return` exits the function with its value, from any depth of its body
+        match body_obj {
+            Err(EvalError::Unwind(unwind)) => match *unwind {
+                Unwind::Return(obj) => Ok(obj),
+                unwind => Err(unwind.into()),
+            },
+            result => result,
+        }
+    }
+
+    /// Calls `push_front`, `push_back`, `pop_front` or `pop_back`, which modify the array of a `var` in place.
+    /// The array is switched to a deque, so that operations on both of its ends take constant time.
+    fn eval_deque_builtin(
+        &mut self,
+        builtin: BuiltinFunction,
+        arguments: Vec<Expression>,
+    ) -> Result<Object, EvalError> {
+        let pushing = matches!(
+            builtin,
+            BuiltinFunction::PushFront | BuiltinFunction::PushBack
+        );
+        if pushing && arguments.len() < 2 {
+            return Err(EvalError::FunctionCallWrongArity(2, arguments.len() as u8));
+        } else if !pushing && arguments.len() != 1 {
+            return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+        }
+
+        let mut arguments = arguments.into_iter();
+        let Some(Expression::Identifier(name)) = arguments.next() else {
+            return Err(EvalError::UnsupportedArgumentType(format!(
+                "`{builtin}` modifies a `var` in place, its first argument must be an identifier"
+            )));
+        };
+        let elements =
+            self.eval_call_expression_arguments(&builtin.to_string(), arguments.collect())?;
+
+        self.env.borrow_mut().update(&name, |value| {
+            if let Object::ArrayValue(elements) = value {
+                // converting a vector doesn't move its elements
+                *value = Object::DequeValue(std::mem::take(elements).into());
+            }
+            let Object::DequeValue(deque) = value else {
+                return Err(EvalError::UnsupportedArgumentType(format!(
+                    "`{builtin}` only works on arrays"
+                )));
+            };
+
+            let obj = match builtin {
+                BuiltinFunction::PushFront => {
+                    elements
+                        .into_iter()
+                        .rev()
+                        .for_each(|element| deque.push_front(element));
+                    Object::UnitValue
+                }
+                BuiltinFunction::PushBack => {
+                    deque.extend(elements);
+                    Object::UnitValue
+                }
+                BuiltinFunction::PopFront => deque.pop_front().unwrap_or(Object::NullValue),
+                _ => deque.pop_back().unwrap_or(Object::NullValue),
+            };
+
+            Ok(obj)
+        })
+    }
+
+    fn eval_call_expression_arguments(
+        &mut self,
+        function: &str,
+        arguments: Vec<Expression>,
+    ) -> Result<Vec<Object>, EvalError> {
+        arguments
+            .into_iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                self.eval_expression(arg)
+                    .with_context(|| format!("while evaluating argument {} of `{function}`", i + 1))
+            })
+            .collect::<Result<Vec<Object>, EvalError>>()
+    }
+
+    fn write_output(&mut self, text: &str) -> Result<(), EvalError> {
+        self.forward_task_output()?;
+        self.streams
+            .stdout
+            .write_all(text.as_bytes())
+            .map_err(|err| EvalError::Io(err.to_string()))
+    }
+
+    /// Reports the call of a deprecated function, unless it was already reported.
+    fn warn_deprecated_call(&mut self, function: &str, note: String, span: Span) {
+        let reported = self.warnings.iter().any(|warning| {
+            matches!(warning, Warning::DeprecatedCall { function: reported, .. } if reported == function)
+        });
+
+        if !reported {
+            self.warnings.push(Warning::DeprecatedCall {
+                function: function.to_owned(),
+                note,
+                span,
+            });
+        }
+    }
+
+    /// Creates a new environment linked to the outer environment
+    fn create_enclosed_env(&mut self) -> Rc<RefCell<Environment>> {
+        self.track_env(Environment {
+            outer: Some(self.env.clone()),
+            ..Default::default()
+        })
+    }
+
+    /// Creates the environment of a closure referencing the `captured` identifiers.
+    /// Rather than keeping alive every enclosing environment, the immutable bindings it references
+    /// are copied, up to the innermost environment whose bindings can still change for the closure:
+    /// one defining a mutable binding it references, or the current one if some of them aren't bound yet
+    /// (e.g. local recursive functions). The global environment is always kept.
+    fn create_closure_env(&mut self, captured: &HashSet<String>) -> Rc<RefCell<Environment>> {
+        let mut closure_env = Environment::default();
+        let mut pending: HashSet<&str> = captured.iter().map(String::as_str).collect();
+        let mut env = self.env.clone();
+
+        let shared = loop {
+            let outer = env.borrow().outer.clone();
+            let Some(outer) = outer else {
+                // the identifiers that are neither global nor built-in are bound after the closure is created
+                let unbound = {
+                    let global = env.borrow();
+                    pending.iter().any(|name| {
+                        !global.store.contains_key(*name)
+                            && BuiltinFunction::lookup_function(name).is_err()
+                            && prelude(name).is_none()
+                    })
+                };
+                if unbound {
+                    closure_env = Environment::default();
+                    break self.env.clone();
+                }
+                break env;
+            };
+
+            if pending
+                .iter()
+                .any(|name| env.borrow().mutable.contains(*name))
+            {
+                break env;
+            }
+            pending.retain(|name| !closure_env.capture(name, &env.borrow()));
+            env = outer;
+        };
+
+        closure_env.outer = Some(shared);
+        self.track_env(closure_env)
+    }
+
+    /// Shares an environment created while evaluating, so that it's cleared with the evaluator.
+    fn track_env(&mut self, env: Environment) -> Rc<RefCell<Environment>> {
+        let inner_env = Rc::new(RefCell::new(env));
+
+        // forget the environments that have already been freed, keeping the list short
+        if self.environments.len() == self.environments.capacity() {
+            if self.garbage_collection {
+                self.collect_garbage();
+            } else {
+                self.environments.retain(|env| env.strong_count() > 0);
+            }
+        }
+        self.environments.push(Rc::downgrade(&inner_env));
+
+        inner_env
+    }
+}
+
+/// Evaluates an input made of a single expression in `env`, like the formula of a spreadsheet
+/// or a feature-flag rule. Statements are refused, so the input can't bind or re-assign identifiers.
+pub fn eval_expression_str(
+    input: &str,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Object, EvalError> {
+    eval_checked_expression_str(input, env, |_| Ok(()))
+}
+
+/// Like `eval_expression_str`, but the expression is rejected before being evaluated
+/// if it references an identifier the `sandbox` doesn't allow.
+pub fn eval_expression_str_sandboxed(
+    input: &str,
+    env: &Rc<RefCell<Environment>>,
+    sandbox: &Sandbox,
+) -> Result<Object, EvalError> {
+    eval_checked_expression_str(input, env, |expression| sandbox.check(expression))
+}
+
+fn eval_checked_expression_str(
+    input: &str,
+    env: &Rc<RefCell<Environment>>,
+    check: impl FnOnce(&Expression) -> Result<(), EvalError>,
+) -> Result<Object, EvalError> {
+    let mut evaluator = Evaluator::with_env(input, env.clone());
+    let expression = evaluator.parser.parse_standalone_expression()?;
+    check(&expression)?;
+
+    evaluator
+        .eval_expression(expression)
+        .map_err(outside_function)
+}
+
+impl Drop for Evaluator<'_> {
+    /// Clears the environments still alive, breaking reference cycles between closures and
+    /// the environments they captured, so that every value (and native finalizer) gets dropped.
+    fn drop(&mut self) {
+        // the streams may fail, and the output of tasks still running is lost anyway
+        let _ = self.forward_task_output();
+        for env in self.environments.drain(..) {
+            if let Some(env) = env.upgrade() {
+                // take the values out first, dropping them can drop other environments
+                let store = std::mem::take(&mut env.borrow_mut().store);
+                drop(store);
+            }
+        }
+    }
+}
+
+/// Binds the parts of `value` to the identifiers of `pattern`, which only binds identifiers (see `Pattern::binds_only`).
+fn destructure(
+    pattern: &Pattern,
+    value: Object,
+    bindings: &mut Vec<(String, Object)>,
+) -> Result<(), EvalError> {
+    match pattern {
+        Pattern::Wildcard => Ok(()),
+        Pattern::Identifier(name) => {
+            bindings.push((name.clone(), value));
+            Ok(())
+        }
+        Pattern::Array { elements, rest } => {
+            let Object::ArrayValue(mut objects) = value else {
+                return Err(EvalError::DestructuringTypeMismatch(
+                    "an array",
+                    value.type_name().to_owned(),
+                ));
+            };
+
+            match rest {
+                Some(_) if objects.len() < elements.len() => {
+                    return Err(EvalError::DestructuringTooFewElements(
+                        elements.len(),
+                        objects.len(),
+                    ));
+                }
+                None if objects.len() != elements.len() => {
+                    return Err(EvalError::DestructuringLengthMismatch(
+                        elements.len(),
+                        objects.len(),
+                    ));
+                }
+                _ => {}
+            }
+
+            let remaining = objects.split_off(elements.len());
+            for (pattern, obj) in elements.iter().zip(objects) {
+                destructure(pattern, obj, bindings)?;
+            }
+
+            match rest {
+                Some(rest) => destructure(rest, Object::ArrayValue(remaining), bindings),
+                None => Ok(()),
+            }
+        }
+        Pattern::Map { entries } => {
+            let Object::MapValue(mut map) = value else {
+                return Err(EvalError::DestructuringTypeMismatch(
+                    "a hash map",
+                    value.type_name().to_owned(),
+                ));
+            };
+
+            for (key, pattern) in entries {
+                let Some(obj) = map.remove(key) else {
+                    let suggestions = closest_matches(key, map.keys().map(String::as_str));
+                    return Err(EvalError::ValueNotFound(key.clone(), suggestions));
+                };
+                destructure(pattern, obj, bindings)?;
+            }
+
+            Ok(())
+        }
+        // refused by the parser
+        Pattern::Literal(_) | Pattern::Variant { .. } | Pattern::Or(_) => {
+            unreachable!("`{pattern}` can't be destructured")
+        }
+    }
+}
+
+/// Whether a `break` or `continue` to the `exit` label, if any, exits the loop with the given label.
+fn exits(exit: &Option<String>, label: &Option<String>) -> bool {
+    exit.is_none() || exit == label
+}
+
+/// Decides whether the loop with the given label keeps iterating after evaluating its body,
+/// shared by all kinds of loops. Otherwise, it breaks with the value of its `break`.
+/// Errors, `return` and exits of outer loops are propagated.
+fn after_iteration(
+    result: Result<Object, EvalError>,
+    label: &Option<String>,
+) -> Result<ControlFlow<Object>, EvalError> {
+    let Err(EvalError::Unwind(unwind)) = result else {
+        return result.map(|_| ControlFlow::Continue(()));
+    };
+
+    match *unwind {
+        Unwind::Break(exit, value) if exits(&exit, label) => Ok(ControlFlow::Break(value)),
+        Unwind::Continue(exit) if exits(&exit, label) => Ok(ControlFlow::Continue(())),
+        unwind => Err(unwind.into()),
+    }
+}
+
+/// Reports a `return` that reached the top level of a program or expression, outside of any function.
+fn outside_function(err: EvalError) -> EvalError {
+    match err {
+        EvalError::Unwind(_) => EvalError::ReturnOutsideExpression,
+        err => err,
+    }
+}
+
+/// Like `i64::overflowing_pow`, but the exponent isn't limited to an `u32`.
+fn overflowing_pow(mut base: i64, mut exponent: u64) -> (i64, bool) {
+    let mut result: i64 = 1;
+    let mut overflowed = false;
+
+    // exponentiation by squaring
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            let (value, overflow) = result.overflowing_mul(base);
+            result = value;
+            overflowed |= overflow;
+        }
+
+        exponent >>= 1;
+        if exponent > 0 {
+            let (value, overflow) = base.overflowing_mul(base);
+            base = value;
+            overflowed |= overflow;
+        }
+    }
+
+    (result, overflowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventQueue;
+
+    #[test]
+    fn eval_integer_literal() {
+        let input = "5";
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[0];
+        assert_eq!(result, &Object::IntegerValue(5));
+    }
+
+    #[test]
+    fn eval_boolean_literal() {
+        let input = "true";
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[0];
+        assert_eq!(result, &Object::BooleanValue(true));
+    }
+
+    #[test]
+    fn eval_string_literal() {
+        let input = r#""foo""#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[0];
+        assert_eq!(result, &Object::StringValue("foo".to_owned()));
+    }
+
+    #[test]
+    fn eval_float_expressions() {
+        let tests = vec![
+            ("1.5", Object::FloatValue(1.5)),
+            ("1.5 + 2.25", Object::FloatValue(3.75)),
+            ("1 + 0.5", Object::FloatValue(1.5)),
+            ("7.5 / 2", Object::FloatValue(3.75)),
+            ("7.5 % 2", Object::FloatValue(1.5)),
+            ("0.1 * 10 == 1", Object::BooleanValue(true)),
+            ("2.5 < 3", Object::BooleanValue(true)),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, &expected, "{input}");
+        }
+
+        assert_eq!(Object::FloatValue(2.0).to_string(), "2.0");
+
+        let mut evaluator = Evaluator::new("1.5 / 0");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::DivisionByZero)
+        ));
+    }
+
+    #[test]
+    fn builtin_number_conversions() {
+        let tests = vec![
+            (r#"parse_int("42")"#, Object::IntegerValue(42)),
+            (r#"parse_int("-42")"#, Object::IntegerValue(-42)),
+            (r#"parse_int("ff", 16)"#, Object::IntegerValue(255)),
+            (r#"parse_int("101", 2)"#, Object::IntegerValue(5)),
+            (r#"parse_int("12a")"#, Object::NullValue),
+            (r#"parse_int("")"#, Object::NullValue),
+            (r#"parse_int("99999999999999999999")"#, Object::NullValue),
+            (r#"parse_float("3.25")"#, Object::FloatValue(3.25)),
+            (r#"parse_float("-1e3")"#, Object::FloatValue(-1000.0)),
+            (r#"parse_float("3,25")"#, Object::NullValue),
+            (r#"parse_float("inf")"#, Object::NullValue),
+            (r#"parse_float("-infinity")"#, Object::NullValue),
+            (r#"parse_float("NaN")"#, Object::NullValue),
+            (r#"parse_float("e")"#, Object::NullValue),
+            ("to_base(255, 16)", Object::StringValue("ff".to_owned())),
+            ("to_base(-5, 2)", Object::StringValue("-101".to_owned())),
+            ("to_base(0, 36)", Object::StringValue("0".to_owned())),
+            (
+                "to_base(-9223372036854775808, 10)",
+                Object::StringValue("-9223372036854775808".to_owned()),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, &expected, "{input}");
+        }
+
+        for input in [
+            r#"parse_int("1", 1)"#,
+            "to_base(1, 37)",
+            "to_base(\"1\", 2)",
+        ] {
+            let mut evaluator = Evaluator::new(input);
+            assert!(matches!(
+                evaluator.eval_program(),
+                Err(EvalError::UnsupportedArgumentType(_))
+            ));
+        }
+
+        // the base is optional, so the error gives the range of accepted arguments
+        for (input, got) in [("parse_int()", 0), (r#"parse_int("1", 2, 3)"#, 3)] {
+            let err = Evaluator::new(input).eval_program().unwrap_err();
+            assert!(matches!(
+                err,
+                EvalError::FunctionCallArityOutOfRange(1, 2, n) if n == got
+            ));
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Function call with the wrong number of arguments. Expected 1 to 2, got {got}"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn eval_string_comparisons() {
+        let tests = vec![
+            (r#""abc" == "abc""#, true),
+            (r#""abc" != "abd""#, true),
+            (r#""abc" < "abd""#, true),
+            (r#""b" > "abc""#, true),
+            (r#""ab" < "abc""#, true),
+            (r#""" < "a""#, true),
+            (r#""Z" < "a""#, true),
+            (r#""é" > "z""#, true),
+            (r#""a" <= "a" && "a" >= "a""#, true),
+            (r#""a" < "b" < "c""#, true),
+            (r#""abc" == "ABC""#, false),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, &Object::BooleanValue(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_null() {
+        let tests = vec![
+            ("null", Object::NullValue),
+            ("null == null", Object::BooleanValue(true)),
+            ("null != null", Object::BooleanValue(false)),
+            ("null == 0", Object::BooleanValue(false)),
+            ("\"\" != null", Object::BooleanValue(true)),
+            ("is_null(null)", Object::BooleanValue(true)),
+            ("is_null(println())", Object::BooleanValue(false)),
+            (
+                "match null { case null: 1, case _: 2 }",
+                Object::IntegerValue(1),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, &expected, "{input}");
+        }
+
+        let mut evaluator = Evaluator::new("null + 1");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn eval_string_concatenation() {
+        let input = r#"
+            let greet = fn() {
+                let a = "hello";
+                let b = "world";
+                return a + " " + b;
+            };
+
+            greet();
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[1];
+        assert_eq!(result, &Object::StringValue("hello world".to_owned()));
+    }
+
+    #[test]
+    fn eval_boolean_expressions() {
+        let tests = vec![
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 < 2 < 3", true),
+            ("1 < 3 < 2", false),
+            ("3 > 2 >= 2 > 1", true),
+            ("3 > 2 >= 3 > 1", false),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 > 1", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 2", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("false == false", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("false != true", true),
+            ("(1 < 2) == true", true),
+            ("(1 < 2) == false", false),
+            ("(1 > 2) == true", false),
+            ("(1 > 2) == false", true),
+            ("true && true", true),
+            ("true && false", false),
+            ("false && true", false),
+            ("false && false", false),
+            ("true || true", true),
+            ("true || false", true),
+            ("false || true", true),
+            ("false || false", false),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+
+            let expected_obj = match expected {
+                true => &Object::BooleanValue(true),
+                false => &Object::BooleanValue(false),
+            };
+
+            assert_eq!(result, expected_obj);
+        }
+    }
+
+    #[test]
+    fn eval_short_circuit() {
+        let tests = vec![
+            ("false && undefined", &Object::BooleanValue(false)),
+            ("true || 1 / 0 == 1", &Object::BooleanValue(true)),
+            ("false || true && true", &Object::BooleanValue(true)),
+            (
+                "let f = fn() { false }; f() && f()",
+                &Object::BooleanValue(false),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap();
+            assert_eq!(result.last().unwrap(), expected);
+        }
+
+        let mut evaluator = Evaluator::new("true && undefined");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::IdentifierNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn eval_binary_expressions() {
+        let tests = vec![
+            ("2 + 3", &Object::IntegerValue(5)),
+            ("4 - 1", &Object::IntegerValue(3)),
+            ("5 * 6", &Object::IntegerValue(30)),
+            ("10 / 2", &Object::IntegerValue(5)),
+            ("10 % 4", &Object::IntegerValue(2)),
+            ("1 + 10 % 4 * 2", &Object::IntegerValue(5)),
+            ("(1 + 2) * 3", &Object::IntegerValue(9)),
+            ("((10 - (2 + 3)) * 2)", &Object::IntegerValue(10)),
+            ("2 <= 2", &Object::BooleanValue(true)),
+            ("1 >= 2", &Object::BooleanValue(false)),
+            ("1 + 1 <= 3 - 1 == true", &Object::BooleanValue(true)),
+            ("7 == 7", &Object::BooleanValue(true)),
+            ("8 != 9", &Object::BooleanValue(true)),
+            ("true == true", &Object::BooleanValue(true)),
+            ("false != true", &Object::BooleanValue(true)),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn eval_unary_expressions() {
+        let tests = vec![
+            ("-2", &Object::IntegerValue(-2)),
+            ("+2", &Object::IntegerValue(2)),
+            ("-(2)", &Object::IntegerValue(-2)),
+            ("-9223372036854775808", &Object::IntegerValue(i64::MIN)),
+            ("!true", &Object::BooleanValue(false)),
+            ("!false", &Object::BooleanValue(true)),
+            ("~5", &Object::IntegerValue(-6)),
+            ("~~5", &Object::IntegerValue(5)),
+            ("~0", &Object::IntegerValue(-1)),
+            ("!!true", &Object::BooleanValue(true)),
+            ("!!false", &Object::BooleanValue(false)),
+            ("-2.5", &Object::FloatValue(-2.5)),
+            ("+2.5", &Object::FloatValue(2.5)),
+            ("--1.5", &Object::FloatValue(1.5)),
+            ("-(1.5 * 2)", &Object::FloatValue(-3.0)),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, expected);
+        }
+
+        let mut evaluator = Evaluator::new("!5");
+        assert_eq!(
+            evaluator.eval_program().unwrap_err().to_string(),
+            "Type mismatch: `!` only negates booleans, use `~` to invert the bits of integers"
+        );
+
+        let tests = vec![
+            (
+                "!\"yes\"",
+                "Cannot apply `!` to a value of type `string`",
+                1..6,
+            ),
+            ("~1.5", "Cannot apply `~` to a value of type `float`", 1..4),
+            (
+                "let xs = [1];\n-xs",
+                "Cannot apply `-` to a value of type `array`",
+                15..17,
+            ),
+            (
+                "+(null)",
+                "Cannot apply `+` to a value of type `null`",
+                1..7,
+            ),
+        ];
+        for (input, message, span) in tests {
+            let err = Evaluator::new(input).eval_program().unwrap_err();
+            assert_eq!(err.code(), "E1044");
+            assert_eq!(err.to_string(), message);
+            let operand = err.span().unwrap();
+            assert_eq!(operand.start..operand.end, span, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_bitwise_operators() {
+        let tests = vec![
+            ("12 & 10", Ok(8)),
+            ("12 | 10", Ok(14)),
+            ("12 ^ 10", Ok(6)),
+            ("1 << 4", Ok(16)),
+            ("-16 >> 2", Ok(-4)),
+            ("1 << 63", Ok(i64::MIN)),
+            ("1 | 2 ^ 3 & 4 << 1", Ok(3)),
+            ("~0 & 0xFF", Ok(255)),
+            (
+                "1 << 64",
+                Err("Integers can only be shifted by 0 to 63 bits, not 64"),
+            ),
+            (
+                "1 >> -1",
+                Err("Integers can only be shifted by 0 to 63 bits, not -1"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator
+                .eval_program()
+                .map(|mut objects| objects.pop().unwrap())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(Object::IntegerValue).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn eval_if_expression() {
+        let tests = vec![
+            ("if true { 10 }", &Object::IntegerValue(10)),
+            ("if false { 10 }", &Object::UnitValue),
+            ("if 1 < 2 { 10 }", &Object::IntegerValue(10)),
+            ("if 1 > 2 { 10 }", &Object::UnitValue),
+            ("if 1 > 2 { 10 } else { 20 }", &Object::IntegerValue(20)),
+            ("if 1 < 2 { 10 } else { 20 }", &Object::IntegerValue(10)),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[0];
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn eval_truthy_conditions() {
+        let tests = vec![
+            ("if 0 { 1 } else { 2 }", "2"),
+            ("if 0.0 { 1 } else { 2 }", "2"),
+            ("if \"\" { 1 } else { 2 }", "2"),
+            ("if [] { 1 } else { 2 }", "2"),
+            ("if null { 1 } else { 2 }", "2"),
+            ("if [0] { 1 } else { 2 }", "1"),
+            ("if \"no\" { 1 } else { 2 }", "1"),
+            ("if {} { 1 } else { 2 }", "1"),
+            (
+                "var x = 3; var n = 0; do { x = x - 1; n = n + 1; } while x; n",
+                "3",
+            ),
+            (
+                "match 2 { case x if x % 2: \"odd\", case _: \"even\" }",
+                "\"even\"",
+            ),
+            ("join(spawn(fn(x) { if x { 1 } else { 2 } }, \"\"))", "2"),
+        ];
+
+        for (input, expected) in tests {
+            let mut strict = Evaluator::new(input);
+            let err = strict.eval_program().unwrap_err();
+            assert!(
+                err.to_string().contains("must be a boolean"),
+                "{input}: {err}"
+            );
+
+            let mut evaluator = Evaluator::new(input);
+            evaluator.set_truthy_conditions(true);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        // operators still need booleans
+        let mut evaluator = Evaluator::new("if !0 { 1 }");
+        evaluator.set_truthy_conditions(true);
+        assert!(evaluator.eval_program().is_err());
+    }
+
+    #[test]
+    fn eval_standalone_expressions() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.borrow_mut()
+            .set("a".to_owned(), Object::IntegerValue(1));
+        env.borrow_mut()
+            .set("b".to_owned(), Object::IntegerValue(4));
+
+        let tests = vec![
+            ("a + b * 2", Ok(Object::IntegerValue(9))),
+            ("a < b && b < 10;", Ok(Object::BooleanValue(true))),
+            ("{\"limit\": b}[\"limit\"]", Ok(Object::IntegerValue(4))),
+            ("if a > 0 { \"on\" } else { \"off\" }", Ok(Object::StringValue("on".to_owned()))),
+            ("fn(x) { let y = x; y * 2 }(b)", Ok(Object::IntegerValue(8))),
+            (
+                "let c = 1",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `let`"),
+            ),
+            (
+                "a = 2",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `a`"),
+            ),
+            (
+                "a; return b",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `return`"),
+            ),
+            ("c * 2", Err("Identifier not found: c")),
+            ("{ a + 1 }", Ok(Object::IntegerValue(2))),
+            // blocks and loops can't hide statements either
+            (
+                "{ a = 5; a }",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `a`"),
+            ),
+            (
+                "{ let z = 3; z }",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `let`"),
+            ),
+            (
+                "loop { break 4; }",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `loop`"),
+            ),
+            (
+                "if a > 0 { var c = 1; c } else { 0 }",
+                Err("Parsing error: Only an expression is allowed here, found a statement starting with `var`"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = eval_expression_str(input, &env).map_err(|err| err.to_string());
+            assert_eq!(result, expected.map_err(str::to_owned), "{input}");
+        }
+
+        assert!(matches!(
+            eval_expression_str("a b", &env),
+            Err(EvalError::ParsingError(ParserError::UnexpectedToken(_)))
+        ));
+        assert_eq!(env.borrow().get("a").unwrap(), Object::IntegerValue(1));
+    }
+
+    #[test]
+    fn eval_sandboxed_expressions() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.borrow_mut()
+            .set("a".to_owned(), Object::IntegerValue(1));
+        env.borrow_mut()
+            .set("b".to_owned(), Object::IntegerValue(4));
+        let sandbox = Sandbox::new(["b", "len"]);
+
+        assert_eq!(
+            eval_expression_str_sandboxed("len([b, b]) * b", &env, &sandbox).unwrap(),
+            Object::IntegerValue(8)
+        );
+
+        // rejected before anything gets evaluated
+        let err =
+            eval_expression_str_sandboxed("fn() { a = 5; }() + b", &env, &sandbox).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The identifier `a` isn't allowed here. Did you mean \"b\"?"
+        );
+        assert_eq!(err.code(), "E1022");
+        assert_eq!(env.borrow().get("a").unwrap(), Object::IntegerValue(1));
+
+        // built-in functions are called before the bindings with their name, so they must be allowed
+        for input in [
+            "fn(println) { println(\"escaped\") }(1)",
+            "fn() { let println = 0; println(\"escaped\") }()",
+        ] {
+            let err = eval_expression_str_sandboxed(input, &env, &sandbox).unwrap_err();
+            assert!(
+                matches!(&err, EvalError::IdentifierNotAllowed(name, _) if name == "println"),
+                "{input}"
+            );
+        }
+        assert!(matches!(
+            eval_expression_str_sandboxed("{ let println = 0; println(\"x\") }", &env, &sandbox),
+            Err(EvalError::ParsingError(ParserError::StatementNotAllowed(_)))
+        ));
+    }
+
+    #[test]
+    fn eval_exponentiation() {
+        let tests = vec![
+            ("2 ** 10", Ok(Object::IntegerValue(1024))),
+            ("2 ** 3 ** 2", Ok(Object::IntegerValue(512))),
+            ("-2 ** 2", Ok(Object::IntegerValue(-4))),
+            ("(-2) ** 3", Ok(Object::IntegerValue(-8))),
+            ("7 ** 0", Ok(Object::IntegerValue(1))),
+            ("1 ** 9223372036854775807", Ok(Object::IntegerValue(1))),
+            ("2 ** 0.5", Ok(Object::FloatValue(2f64.sqrt()))),
+            ("2.0 ** -1", Ok(Object::FloatValue(0.5))),
+            (
+                "2 ** -1",
+                Err("Integers can't be raised to the negative power -1, use a float base instead"),
+            ),
+            ("2 ** 64", Ok(Object::IntegerValue(0))),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator
+                .eval_program()
+                .map(|mut objects| objects.pop().unwrap())
+                .map_err(|err| err.to_string());
+            assert_eq!(result, expected.map_err(str::to_owned), "{input}");
+        }
+
+        let mut evaluator = Evaluator::new("3 ** 40");
+        evaluator.set_checked_arithmetic(true);
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::IntegerOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn eval_integer_overflow() {
+        let tests = vec![
+            (
+                "9223372036854775807 + 1",
+                i64::MIN,
+                Err("Integer overflow while computing 9223372036854775807 + 1"),
+            ),
+            (
+                "-9223372036854775808 - 1",
+                i64::MAX,
+                Err("Integer overflow while computing -9223372036854775808 - 1"),
+            ),
+            (
+                "4611686018427387904 * 2",
+                i64::MIN,
+                Err("Integer overflow while computing 4611686018427387904 * 2"),
+            ),
+            (
+                "-9223372036854775808 / -1",
+                i64::MIN,
+                Err("Integer overflow while computing -9223372036854775808 / -1"),
+            ),
+            (
+                "let x = -9223372036854775808; -x",
+                i64::MIN,
+                Err("Integer overflow while computing -(-9223372036854775808)"),
+            ),
+            ("-9223372036854775808 % -1", 0, Ok(0)),
+            ("3000000000 * 3", 9000000000, Ok(9000000000)),
+        ];
+
+        for (input, wrapping, checked) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result, Object::IntegerValue(wrapping), "{input}");
+
+            let mut evaluator = Evaluator::new(input);
+            evaluator.set_checked_arithmetic(true);
+            let result = evaluator
+                .eval_program()
+                .map(|mut objects| objects.pop().unwrap())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                checked.map(Object::IntegerValue).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn eval_error_context() {
+        let input = "
+            let check = fn(x) { if x / 0 > 1 { 1 } else { 2 } };
+            append([], check(3));
+        ";
+        let mut evaluator = Evaluator::new(input);
+        let err = evaluator.eval_program().unwrap_err();
+
+        assert!(matches!(err.root_cause(), EvalError::DivisionByZero));
+        assert_eq!(
+            err.frames(),
+            vec![
+                "while evaluating the condition `((x / 0) > 1)` of `if`",
+                "while evaluating argument 2 of `append`",
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "Division by zero isn't allowed
+    while evaluating the condition `((x / 0) > 1)` of `if`
+    while evaluating argument 2 of `append`"
+        );
+    }
+
+    #[test]
+    fn eval_array_expression() {
+        let input = r#"
+            let add = fn(x, y) { return x + y; };
+            [1 + 1, add(2, 2)];
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[1];
+        assert_eq!(
+            result,
+            &Object::ArrayValue(vec![Object::IntegerValue(2), Object::IntegerValue(4)])
+        );
+    }
+
+    #[test]
+    fn eval_map_expression() {
+        let input = r#"
+            let foo = { "temp": 1 + 1, "foo": 4 };
+            foo;
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[1];
+        let mut expected = BTreeMap::new();
+        expected.insert("temp".to_owned(), Object::IntegerValue(2));
+        expected.insert("foo".to_owned(), Object::IntegerValue(4));
+        assert_eq!(result, &Object::MapValue(expected));
+
+        // keys are sorted, whatever the order they were written in
+        let tests = vec![
+            (
+                r#"({"b": 1, "c": 2, "a": 3})"#,
+                r#"{"a": 3, "b": 1, "c": 2}"#,
+            ),
+            (
+                r#"({"c": 2, "a": 3, "b": 1})"#,
+                r#"{"a": 3, "b": 1, "c": 2}"#,
+            ),
+            (
+                r#"var log = []; let f = fn(x) { log = append(log, x); x }; ({"z": f(1), "y": f(2), "x": f(3)}); log"#,
+                "[3, 2, 1]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_index_expression() {
+        let input = r#"
+            let a = [100, 200, 300, 400];
+            1 + a[1 + 1];
+
+            let b = { 
+                "foo": 2,
+                "bar": 4
+            };
+            b["foo"];
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = evaluator.eval_program().unwrap();
+        assert_eq!(&result[1], &Object::IntegerValue(301));
+        assert_eq!(&result[3], &Object::IntegerValue(2));
+    }
+
+    #[test]
+    fn eval_switch_statement() {
+        let tests = vec![
+            (
+                r#"let name = fn(n) { switch n { 1 => "one", 1 + 1 => { "two" } _ => "many" } }; [name(1), name(2), name(3)]"#,
+                r#"["one", "two", "many"]"#,
+            ),
+            // the cases after the matching arm aren't evaluated
+            (r#"switch 1 { 1 => "a", missing => "b", _ => "c" }"#, r#""a""#),
+            // values of different types are never equal
+            (r#"switch "1" { 1 => "int", _ => "other" }"#, r#""other""#),
+            ("switch [1, 2] { [1] => 1, [1, 2] => 2, _ => 0 }", "2"),
+            (
+                "let f = fn(x) { switch x { 0 => { return -1; } _ => {} } x * 2 }; [f(0), f(4)]",
+                "[-1, 8]",
+            ),
+            (
+                "var n = 0; loop { n = n + 1; switch n % 3 { 0 => { break n; } _ => { continue; } } }",
+                "3",
+            ),
+            ("var x = 0; switch x { _ => { x = 5; } } x", "5"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_structs() {
+        let tests = vec![
+            ("struct P { x, y } P { y: 2, x: 1 }", "P { x: 1, y: 2 }"),
+            ("struct P { x, y } let p = P { x: 1, y: [2] }; p.y", "[2]"),
+            (r#"struct P { x } type(P { x: 1 })"#, r#""P""#),
+            ("struct P { x } type(P)", r#""struct""#),
+            (
+                "struct P { b, a } fields(P { a: 1, b: 2 })",
+                r#"["b", "a"]"#,
+            ),
+            ("struct P { x } P { x: 1 } == P { x: 1 }", "true"),
+            (
+                "struct P { x } struct Q { x } P { x: 1 } == Q { x: 1 }",
+                "false",
+            ),
+            ("struct Unit {} Unit {}", "Unit {}"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "let P = 1; P { x: 1 }",
+                "`P` isn't a struct, it can't be built like `P { ... }`",
+            ),
+            (
+                "struct P { x, y } P { x: 1, z: 2 }",
+                "The struct `P` has no field `z`. Did you mean one of \"x\", \"y\"?",
+            ),
+            (
+                "struct P { width } P { width: 1 }.widht",
+                "The struct `P` has no field `widht`. Did you mean \"width\"?",
+            ),
+            (
+                "struct P { x, y } P { x: 1 }",
+                "The field `y` of the struct `P` wasn't given a value",
+            ),
+            ("P { x: 1 }", "Identifier not found: P"),
+            // fields are evaluated in the order they're written
+            (
+                "struct P { x, y } P { y: 1 / 0, x: missing }",
+                "Division by zero isn't allowed",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_enums() {
+        let color = "enum Color { Red, Green, Custom(r, g, b) } ";
+        let tests = vec![
+            ("Color.Red", "Color.Red"),
+            ("Color.Custom(1, 2, 3)", "Color.Custom(1, 2, 3)"),
+            ("Color", "enum Color { Red, Green, Custom(r, g, b) }"),
+            (
+                "[type(Color.Green), type(Color), type(Color.Custom)]",
+                r#"["Color", "enum", "function"]"#,
+            ),
+            ("Color.Red == Color.Red", "true"),
+            ("Color.Red != Color.Green", "true"),
+            ("Color.Custom(1, 2, 3) == Color.Custom(1, 2, 4)", "false"),
+            // constructors are functions like the others
+            (
+                "let make = Color.Custom; make(0, 0, 0)",
+                "Color.Custom(0, 0, 0)",
+            ),
+            (
+                r#"let name = fn(c) {
+                    match c {
+                        case Color.Red: "red",
+                        case Color.Custom(r, 0, 0) if r > 100: "reddish",
+                        case Color.Custom(r, g, b): r + g + b,
+                        case _: "other",
+                    }
+                };
+                [name(Color.Red), name(Color.Custom(200, 0, 0)), name(Color.Custom(1, 2, 3)), name(Color.Green), name(1)]"#,
+                r#"["red", "reddish", 6, "other", "other"]"#,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{color}{input}");
+            let mut evaluator = Evaluator::new(&input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "Color.Gren",
+                "The enum `Color` has no variant `Gren`. Did you mean \"Green\"?",
+            ),
+            (
+                "Color.Custom(1)",
+                "Function call with the wrong number of arguments. Expected 3, got 1",
+            ),
+            (
+                "match Color.Red { case Color.Gren: 1, case _: 2 }",
+                "The enum `Color` has no variant `Gren`. Did you mean \"Green\"?",
+            ),
+            (
+                "match Color.Red { case Color.Custom(r): r, case _: 2 }",
+                "The variant `Color.Custom` has 3 fields, but the pattern has 1",
+            ),
+            (
+                "let Colour = 1; match Color.Red { case Colour.Red: 1, case _: 2 }",
+                "`Colour` isn't an enum, its variants can't be matched like `Colour.Red`",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let input = format!("{color}{input}");
+            let mut evaluator = Evaluator::new(&input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_question_operator() {
+        let half = "let half = fn(n) { if n % 2 == 0 { return Ok(n / 2); } Err(n) }; ";
+        let tests = vec![
+            ("[Ok(1), Err(\"no\"), Some([2]), None]", r#"[Ok(1), Err("no"), Some([2]), None]"#),
+            ("[type(None), type(Ok), type(Option)]", r#"["Option", "function", "enum"]"#),
+            ("Result", "enum Result { Ok(value), Err(error) }"),
+            ("Some(1) == Option.Some(1)", "true"),
+            ("let quarter = fn(n) { Ok(half(half(n)?)?) }; quarter(8)", "Ok(2)"),
+            ("let quarter = fn(n) { Ok(half(half(n)?)?) }; quarter(6)", "Err(3)"),
+            (
+                "let first = fn(xs) { if len(xs) == 0 { return None; } Some(xs[0]) }; \
+                 let sum = fn(a, b) { Some(first(a)? + first(b)?) }; [sum([1], [2]), sum([1], [])]",
+                "[Some(3), None]",
+            ),
+            (
+                "let name = fn(x) { match x { case Ok(v) | Some(v): v, case Err(_): -1, case None: 0 } }; \
+                 [name(Ok(1)), name(Some(2)), name(Err(3)), name(None)]",
+                "[1, 2, -1, 0]",
+            ),
+            // the prelude can be shadowed
+            ("let Some = fn(x) { x }; Some(1)", "1"),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{half}{input}");
+            let mut evaluator = Evaluator::new(&input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            ("half(3)?", "Return statement used outside an expression"),
+            (
+                "let f = fn() { 1? }; f()",
+                "Type mismatch: `?` only works on `Result` and `Option` values, got '1'",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let input = format!("{half}{input}");
+            let mut evaluator = Evaluator::new(&input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_method_calls() {
+        let tests = vec![
+            ("[1, 2, 3].len()", "3"),
+            (r#""héllo".upper()"#, r#""HÉLLO""#),
+            (r#""ÀB".lower()"#, r#""àb""#),
+            ("[3, 1, 2, 1].sorted().unique()", "[1, 2, 3]"),
+            (r#""a,b".starts_with("a")"#, "true"),
+            (r#""7".pad_left(3, "0")"#, r#""007""#),
+            ("(1..4).to_array().len()", "3"),
+            ("255.to_base(16)", r#""ff""#),
+            (r#"let m = {"a": 1}; m.fields()"#, r#"["a"]"#),
+            ("let xs = [1, 2]; xs.append(3)", "[1, 2, 3]"),
+            ("let n = 10; [1, 2].pmap(fn(x) { x * n })", "[10, 20]"),
+            // members of modules, structs and enums are called like functions
+            (
+                "struct P { f } let p = P { f: fn(x) { x + 1 } }; p.f(1)",
+                "2",
+            ),
+            (
+                "enum E { A(x) } let e = E.A(1); match e { case E.A(x): x }",
+                "1",
+            ),
+            // a binding named like the receiver placeholder doesn't exist
+            ("let x = 1; [x].len() + x", "2"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            ("[1].uper()", "Values of type `array` have no method `uper`"),
+            (
+                r#""a".uper()"#,
+                "Values of type `string` have no method `uper`. Did you mean \"upper\"?",
+            ),
+            (
+                "true.len()",
+                "Values of type `boolean` have no method `len`",
+            ),
+            (
+                "[1].len(2)",
+                "Function call with the wrong number of arguments. Expected 1, got 2",
+            ),
+            ("[1].len(x: 2)", "The function has no parameter named `x`"),
+            (
+                "struct P { width } P { width: 1 }.y()",
+                "The struct `P` has no field `y`",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_pipelines() {
+        let tests = vec![
+            ("[3, 1, 2, 1] |> unique() |> sorted()", "[1, 2, 3]"),
+            (
+                "let double = fn(xs) { pmap(xs, fn(x) { x * 2 }) }; [1, 2] |> double |> len()",
+                "2",
+            ),
+            (r#""ab" |> upper() |> pad_left(4, "-")"#, r#""--AB""#),
+            ("let add = fn(a, b) { a - b }; 10 |> add(b: 3)", "7"),
+            ("[1, 2] |> fn(xs) { xs[1] }", "2"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_partial_application() {
+        let tests = vec![
+            ("let sub = fn(a, b) { a - b }; sub(_, 1)(10)", "9"),
+            ("let sub = fn(a, b) { a - b }; sub(10, _)(1)", "9"),
+            ("let sub = fn(a, b) { a - b }; let f = sub(_, _); f(7, 3)", "4"),
+            ("let sub = fn(a, b) { a - b }; let f = sub(_, b: 1); f(3)", "2"),
+            // built-in functions can be partially applied too
+            ("let add3 = append(_, 3); add3([1, 2])", "[1, 2, 3]"),
+            ("pmap([\"1\", \"2\"], pad_left(_, 3, \"0\"))", r#"["001", "002"]"#),
+            // the arguments are evaluated once, when partially applying
+            (
+                "var n = 0; let next = fn() { n = n + 1; n }; let f = append(_, next()); f([]); f([])",
+                "[1]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let mut evaluator = Evaluator::new("let add = fn(a, b) { a + b }; add(1, _)(1, 2)");
+        assert_eq!(
+            evaluator
+                .eval_program()
+                .unwrap_err()
+                .root_cause()
+                .to_string(),
+            "Function call with the wrong number of arguments. Expected 1, got 2"
+        );
+    }
+
+    #[test]
+    fn eval_match_expression() {
+        let input = r#"
+            let describe = fn(x) {
+                match x {
+                    case 1 | 2 | 3: "small",
+                    case "foo" | true: "special",
+                    case []: "empty",
+                    case [first]: first,
+                    case [first, ..rest]: len(rest),
+                    case _: "other"
+                }
+            };
+            describe(2);
+            describe(true);
+            describe([]);
+            describe([7]);
+            describe([7, 8, 9]);
+            describe(100);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[1], &Object::StringValue("small".to_owned()));
+        assert_eq!(&result[2], &Object::StringValue("special".to_owned()));
+        assert_eq!(&result[3], &Object::StringValue("empty".to_owned()));
+        assert_eq!(&result[4], &Object::IntegerValue(7));
+        assert_eq!(&result[5], &Object::IntegerValue(2));
+        assert_eq!(&result[6], &Object::StringValue("other".to_owned()));
+
+        let mut evaluator = Evaluator::new("match 5 { case 1: 1 }");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::NoMatchingArm(_))
+        ));
+    }
+
+    #[test]
+    fn eval_match_guards() {
+        let tests = vec![
+            (
+                r#"let sign = fn(x) { match x { case n if n < 0: "negative", case 0: "zero", case _: "positive" } }; [sign(-2), sign(0), sign(5)]"#,
+                r#"["negative", "zero", "positive"]"#,
+            ),
+            // a failed guard falls through to the next arm, without keeping the bindings of its pattern
+            (
+                "let a = 1; match [5, 2] { case [a, b] if a < b: a, case [_, b]: a + b }",
+                "3",
+            ),
+            (
+                r#"match {"kind": "circle", "r": 2} { case {kind: "circle", r} if r > 1: r * r, case _: 0 }"#,
+                "4",
+            ),
+            (
+                "match [1, 2, 3] { case [x, ..rest] if len(rest) == 2: rest, case _: [] }",
+                "[2, 3]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let mut evaluator = Evaluator::new("match 1 { case x if x: 1, case _: 2 }");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::TypeMismatch(_))
+        ));
+
+        let mut evaluator = Evaluator::new("match [1] { case [x] if x / 0 > 1: 1, case _: 2 }");
+        let err = evaluator.eval_program().unwrap_err();
+        assert!(matches!(err.root_cause(), EvalError::DivisionByZero));
+        assert_eq!(
+            err.frames(),
+            vec!["while evaluating the guard `((x / 0) > 1)` of `case [x]`"]
+        );
+
+        // a guard that never holds leaves the value unmatched
+        let mut evaluator = Evaluator::new("match 5 { case n if n > 10: n }");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::NoMatchingArm(_))
+        ));
+    }
+
+    #[test]
+    fn eval_missing_map_key() {
+        let input = r#"
+            let person = { "name": "foo", "age": 20, "names": [] };
+            person["nmae"];
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let err = evaluator.eval_program().unwrap_err();
+        assert!(matches!(
+            &err,
+            EvalError::ValueNotFound(key, suggestions) if key == "nmae" && suggestions == &["name"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            r#"This map doesn't have a value defined at key "nmae". Did you mean "name"?"#
+        );
+
+        let mut evaluator = Evaluator::new(r#"let m = { "foo": 1 }; m["bar"];"#);
+        assert_eq!(
+            evaluator.eval_program().unwrap_err().to_string(),
+            r#"This map doesn't have a value defined at key "bar""#
+        );
+    }
+
+    #[test]
+    fn eval_map_field_access() {
+        let config = r#"let config = { "server": { "port": 8080, "hosts": ["a", "b"] }, "debug": false, "scale": fn(x) { x * 2 } }; "#;
+        let tests = vec![
+            ("config.server.port", "8080"),
+            ("config.server.hosts[1]", r#""b""#),
+            (r#"config.server["port"] == config["server"].port"#, "true"),
+            ("config.scale(2)", "4"),
+            // methods are looked up before the keys
+            (
+                r#"let m = { "fields": 1 }; [m.fields, m.fields()]"#,
+                r#"[1, ["fields"]]"#,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{config}{input}");
+            let mut evaluator = Evaluator::new(&input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "config.server.prot",
+                r#"This map doesn't have a value defined at key "prot". Did you mean "port"?"#,
+            ),
+            (
+                "config.server.port.value",
+                "Values of type `integer` don't have fields, cannot access `value`",
+            ),
+            (
+                "config.debug()",
+                "Function not found: `false` cannot be called as a function",
+            ),
+            (
+                "config.reload()",
+                "Values of type `map` have no method `reload`",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let input = format!("{config}{input}");
+            let mut evaluator = Evaluator::new(&input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_with_observer() {
+        #[derive(Debug)]
+        struct Recorder(Rc<RefCell<Vec<(String, usize, Object)>>>);
+
+        impl EvalObserver for Recorder {
+            fn exit_statement(
+                &mut self,
+                statement: &Statement,
+                span: Span,
+                depth: usize,
+                value: &Object,
+            ) {
+                self.0.borrow_mut().push((
+                    format!("{}..{} {statement}", span.start, span.end),
+                    depth,
+                    value.clone(),
+                ));
+            }
+        }
+
+        let records = Rc::new(RefCell::new(vec![]));
+        let mut evaluator = Evaluator::new("let a = 1; { a + 1 }");
+        evaluator.set_observer(Box::new(Recorder(records.clone())));
+        evaluator.eval_program().unwrap();
+
+        assert_eq!(
+            *records.borrow(),
+            vec![
+                ("0..10 let a = 1;".to_owned(), 0, Object::UnitValue),
+                ("13..18 (a + 1)".to_owned(), 1, Object::IntegerValue(2)),
+                ("11..20 {(a + 1)}".to_owned(), 0, Object::IntegerValue(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn eval_with_events() {
+        let events = EventQueue::default();
+        let mut evaluator =
+            Evaluator::new("let add = fn(x, ...ys) { x }; var n = add(1); n = n / 0; n");
+        evaluator.set_event_sink(Box::new(events.clone()));
+        evaluator.register_module(
+            "fs",
+            [NativeFunction::new("read", |_| Ok(Object::NullValue))],
+        );
+        assert!(evaluator.eval_program().is_err());
+
+        let events: Vec<String> = events.drain().map(|event| event.to_string()).collect();
+        assert_eq!(
+            events,
+            vec![
+                "module loaded: fs [read]",
+                "program started (4 statements)",
+                "function defined: add(x, ...ys)",
+                "statement 0 evaluated: let add = fn(x, ...ys) {x}; => ()",
+                "statement 1 evaluated: var n = add(1); => ()",
+                "error raised: E1007 Division by zero isn't allowed",
+            ]
+        );
+
+        // errors of callbacks are reported by the poll running them, and parsing errors before any program starts
+        let events = EventQueue::default();
+        let mut evaluator = Evaluator::new("set_timeout(fn() { 1 / 0 }, 0);");
+        evaluator.set_event_sink(Box::new(events.clone()));
+        evaluator.eval_program().unwrap();
+        assert!(evaluator.run_event_loop().is_err());
+        let mut evaluator = Evaluator::new("let = 1;");
+        evaluator.set_event_sink(Box::new(events.clone()));
+        assert!(evaluator.eval_program().is_err());
+
+        let events: Vec<EvalEvent> = events.drain().collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(
+            &events[2],
+            EvalEvent::ErrorRaised { code: "E1007", message }
+                if message.ends_with("while running the callback of timer #1")
+        ));
+        assert!(matches!(
+            &events[3],
+            EvalEvent::ErrorRaised { code: "E0001", .. }
+        ));
+    }
+
+    #[test]
+    fn eval_with_replayed_inputs() {
+        use crate::replay::{InputLog, ReplayError};
+        use std::cell::Cell;
+
+        let input = r#"
+            let name = read_line();
+            println(name, sensor.read(), sensor.read(), read_line());
+        "#;
+        let run = |log: InputLog, record: bool, stdin: &'static str, start: i64| {
+            let output = Rc::new(RefCell::new(String::new()));
+            let mut evaluator = Evaluator::new(input);
+            evaluator.capture_output(output.clone());
+            evaluator.set_stdin(stdin.as_bytes());
+            let count = Cell::new(start);
+            evaluator.register_module(
+                "sensor",
+                [NativeFunction::new("read", move |_| {
+                    count.set(count.get() + 1);
+                    Ok(Object::FloatValue(count.get() as f64 / 2.0))
+                })],
+            );
+            if record {
+                evaluator.record_inputs(log);
+            } else {
+                evaluator.replay_inputs(log);
+            }
+            let result = evaluator.eval_program().map(|_| ());
+            (result, output.take())
+        };
+
+        let log = InputLog::default();
+        let (result, recorded) = run(log.clone(), true, "Ada\n", 0);
+        result.unwrap();
+        assert_eq!(recorded, "\"Ada\"\n0.5\n1.0\nnull\n");
+        assert_eq!(log.len(), 4);
+
+        // stdin and the native function are left alone, the log is read instead
+        let saved = log.to_json_lines();
+        let (result, replayed) = run(InputLog::parse(&saved).unwrap(), false, "Bob\n", 10);
+        result.unwrap();
+        assert_eq!(replayed, recorded);
+
+        // a log cut short, or read in another order, stops the replay
+        let short = saved.lines().take(2).collect::<Vec<_>>().join("\n");
+        let (result, _) = run(InputLog::parse(&short).unwrap(), false, "", 0);
+        let err = result.unwrap_err();
+        assert_eq!(err.code(), "E1043");
+        assert!(matches!(
+            err.root_cause(),
+            EvalError::Replay(ReplayError::Exhausted(input)) if input == "the result of `sensor.read`"
+        ));
+        let swapped = InputLog::default();
+        swapped.push(Input::NativeCall {
+            name: "sensor.read".to_owned(),
+            result: Ok(Object::NullValue),
+        });
+        let (result, _) = run(swapped, false, "", 0);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The replayed run reads a line with `read_line`, but the log has the result of `sensor.read` next"
+        );
+    }
+
+    #[test]
+    fn eval_function_expression() {
+        let input = r#"
+            let foo = fn(x) {
+                let double = fn(y) { y * 2; };
+                return double(x);
+            };
+
+            let bar = foo(3);
+            bar;
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[2];
+        assert_eq!(result, &Object::IntegerValue(6));
+    }
+
+    #[test]
+    fn eval_function_expressions() {
+        let tests = vec![
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("1; fn(x) { x; }(5)", 5),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = &evaluator.eval_program().unwrap()[1];
+            let expected_obj = &Object::IntegerValue(expected);
+            assert_eq!(result, expected_obj);
+        }
+    }
+
+    #[test]
+    fn eval_variadic_functions() {
+        let tests = vec![
+            (
+                "let sum = fn(...nums) { var total = 0; for n in nums { total = total + n; } total }; sum(1, 2, 3)",
+                "6",
+            ),
+            ("let sum = fn(...nums) { len(nums) }; sum()", "0"),
+            ("let f = fn(a, ...rest) { [a, rest] }; f(1)", "[1, []]"),
+            ("let f = fn(a, ...rest) { [a, rest] }; f(1, 2, 3)", "[1, [2, 3]]"),
+            ("fn(a, ...rest) { a }", "fn(a, ...rest) {a}"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let mut evaluator = Evaluator::new("let f = fn(a, b, ...rest) { a }; f(1)");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::FunctionCallTooFewArguments(2, 1))
+        ));
+    }
+
+    #[test]
+    fn eval_destructure_statements() {
+        let tests = vec![
+            ("let [a, b] = [1, 2]; a + b", "3"),
+            (
+                "let [a, b, rest...] = [1, 2, 3, 4]; [a, b, rest]",
+                "[1, 2, [3, 4]]",
+            ),
+            ("let [a, rest...] = [1]; rest", "[]"),
+            (
+                "let [[a, b], _, c] = [[1, 2], 3, 4]; [a, b, c]",
+                "[1, 2, 4]",
+            ),
+            ("var [a, b] = [1, 2]; a = 3; [a, b]", "[3, 2]"),
+            (
+                "let f = fn(pair) { let [x, y] = pair; x * y }; f([3, 4])",
+                "12",
+            ),
+            (
+                "let {name, age: a} = ({\"name\": \"Ada\", \"age\": 36}); [name, a]",
+                "[\"Ada\", 36]",
+            ),
+            (
+                "let {pos: [x, y], \"z index\": z} = ({\"pos\": [1, 2], \"z index\": 3}); x + y + z",
+                "6",
+            ),
+            (
+                "let area = fn(shape) { match shape { case {kind: \"square\", side}: side * side, case {r}: 3 * r * r, case _: 0 } }; [area(({\"kind\": \"square\", \"side\": 2})), area(({\"r\": 1})), area(1)]",
+                "[4, 3, 0]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "let [a, b] = [1, 2, 3];",
+                "Expected an array of 2 elements to destructure, got 3",
+            ),
+            (
+                "let [a, b, rest...] = [1];",
+                "Expected an array of at least 2 elements to destructure, got 1",
+            ),
+            (
+                "let [a, [b, c]] = [1, 2];",
+                "Expected an array to destructure, got a value of type `integer`",
+            ),
+            (
+                "let {name} = [1];",
+                "Expected a hash map to destructure, got a value of type `array`",
+            ),
+            (
+                "let {nme} = ({\"name\": 1});",
+                "This map doesn't have a value defined at key \"nme\". Did you mean \"name\"?",
+            ),
+            (
+                "let [a, b] = [1, 2]; a = 3;",
+                "Cannot assign twice to the immutable identifier `a`, declare it with `var` instead",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let err = Evaluator::new(input).eval_program().unwrap_err();
+            assert_eq!(err.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_keyword_aliases() {
+        let keywords = Keywords::parse("func = fn\nsoit = let\nsi = if\nsinon = else").unwrap();
+        let mut evaluator = Evaluator::new(
+            "soit abs = func(x) { si x < 0 { -x } sinon { x } }; let si_ = 1; abs(-3) + si_",
+        );
+        evaluator.set_keywords(keywords);
+        let result = evaluator.eval_program().unwrap().pop().unwrap();
+        assert_eq!(result, Object::IntegerValue(4));
+
+        // aliases are only keywords for the evaluators they're given to
+        let mut evaluator = Evaluator::new("let func = 1; func");
+        let result = evaluator.eval_program().unwrap().pop().unwrap();
+        assert_eq!(result, Object::IntegerValue(1));
+    }
+
+    #[test]
+    fn eval_block_expressions() {
+        let tests = vec![
+            (
+                "let compute = fn() { 21 }; let x = { let a = compute(); a * 2 }; x",
+                "42",
+            ),
+            ("1 + { let a = 2; a }", "3"),
+            ("let x = {}; x", "{}"),
+            ("let x = { let a = 1; }; x", "()"),
+            // bindings of the block aren't visible after it
+            ("let a = 1; let b = { let a = 2; a * 10 }; a + b", "21"),
+            ("var n = 0; let m = { n = n + 1; n }; n + m", "2"),
+            (
+                "let f = fn(x) { let y = { if x > 0 { return \"positive\"; } x }; y }; f(1)",
+                "\"positive\"",
+            ),
+            ("let x = loop { let y = { break 3; }; }; x", "3"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let mut evaluator = Evaluator::new("let x = { let hidden = 1; hidden }; hidden");
+        assert!(matches!(
+            evaluator.eval_program().unwrap_err().root_cause(),
+            EvalError::IdentifierNotFound(name) if name == "hidden"
+        ));
+    }
+
+    #[test]
+    fn eval_loops() {
+        let tests = vec![
+            ("var i = 0; let x = loop { i = i + 1; if i == 3 { break i * 10; } }; x", "30"),
+            ("loop { break; }", "()"),
+            (
+                "var total = 0; for x in 1..10 { if x % 2 == 0 { continue; } if x > 7 { break; } total = total + x; } total",
+                "16",
+            ),
+            (
+                "var i = 0; var seen = []; loop { i = i + 1; if i > 4 { break seen; } if i == 2 { continue; } seen = append(seen, i); }",
+                "[1, 3, 4]",
+            ),
+            // `break` exits the innermost loop only
+            (
+                "var pairs = []; for a in 1..3 { for b in 1..3 { if b > a { break; } pairs = append(pairs, [a, b]); } } pairs",
+                "[[1, 1], [2, 1], [2, 2]]",
+            ),
+            (
+                "let find = fn(xs, target) { var i = 0; loop { if xs[i] == target { return i; } i = i + 1; } }; find([4, 5, 6], 6)",
+                "2",
+            ),
+            (
+                "var n = 0; loop { n = n + 1; match n { case 5: { break n; }, case _: { continue; } } }",
+                "5",
+            ),
+            // labels exit, or skip to the next iteration of, outer loops
+            (
+                "var pairs = []; 'outer: for a in 1..4 { for b in 1..4 { if b == 2 { continue 'outer; } if a == 3 { break 'outer; } pairs = append(pairs, [a, b]); } } pairs",
+                "[[1, 1], [2, 1]]",
+            ),
+            (
+                "'search: loop { for x in [3, 8, 12] { if x > 5 { break 'search x * 2; } } break -1; }",
+                "16",
+            ),
+            (
+                "var i = 0; var total = 0; 'count: loop { i = i + 1; if i > 3 { break; } for x in 0..10 { if x == i { continue 'count; } total = total + 1; } }; total",
+                "6",
+            ),
+            // the body of `do`-`while` runs at least once
+            ("var n = 10; do { n = n + 1; } while n < 5; n", "11"),
+            (
+                "var n = 0; var odds = []; do { n = n + 1; if n % 2 == 0 { continue; } odds = append(odds, n); } while n < 7; odds",
+                "[1, 3, 5, 7]",
+            ),
+            (
+                "var n = 0; 'outer: do { for x in 0..10 { n = n + x; if n > 20 { break 'outer; } } } while true; n",
+                "21",
+            ),
+            (
+                "let f = fn() { var i = 0; do { i = i + 1; if i == 3 { return i * 100; } } while true; }; f()",
+                "300",
+            ),
+            // an inner loop with the same label shadows the outer one
+            (
+                "var n = 0; 'a: for x in 0..3 { 'a: for y in 0..3 { n = n + 1; break 'a; } } n",
+                "3",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_closure_captures() {
+        let tests = vec![
+            (
+                "let make = fn() { var n = 0; fn() { n = n + 1; n } }; let count = make(); count(); count()",
+                "2",
+            ),
+            (
+                "let f = fn() { let fact = fn(n) { if n < 2 { 1 } else { n * fact(n - 1) } }; fact(5) }; f()",
+                "120",
+            ),
+            ("let f = fn() { g() }; let g = fn() { 1 }; f()", "1"),
+            (
+                "let f = fn(x) { fn() { let inner = fn() { x + len([y]) }; inner() } }; let y = 2; f(1)()",
+                "2",
+            ),
+            // closures see the bindings that were visible when they were created
+            (
+                "let f = fn() { let x = 1; let g = fn() { x }; let x = 2; g() }; f()",
+                "1",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        // only the referenced bindings are kept alive, next to the global environment
+        let mut evaluator =
+            Evaluator::new("let make = fn(x) { let unused = [1, 2, 3]; fn(y) { x + y } }; make(1)");
+        let Some(Object::FunctionValue(closure)) = evaluator.eval_program().unwrap().pop() else {
+            panic!("expected a closure");
+        };
+        let env = closure.env.borrow();
+        assert_eq!(env.store.keys().collect::<Vec<_>>(), vec!["x"]);
+        assert!(env.outer.as_ref().unwrap().borrow().outer.is_none());
+
+        let mut evaluator =
+            Evaluator::new("let f = fn() { var x = 1; freeze(x); fn() { x = 2; } }; f()()");
+        assert_eq!(
+            evaluator.eval_program().unwrap_err().to_string(),
+            "Cannot assign to the identifier `x`, as it has been frozen"
+        );
+    }
+
+    #[test]
+    fn eval_named_arguments() {
+        let tests = vec![
+            ("let draw = fn(x, y) { [x, y] }; draw(y: 2, x: 1)", "[1, 2]"),
+            ("let draw = fn(x, y) { [x, y] }; draw(1, y: 2)", "[1, 2]"),
+            (
+                "let draw = fn(x, ...rest) { [x, rest] }; draw(x: 1)",
+                "[1, []]",
+            ),
+            (
+                "let draw = fn(x, ...rest) { [x, rest] }; draw(1, 2, 3)",
+                "[1, [2, 3]]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "let draw = fn(x, y) { x }; draw(x: 1, yy: 2)",
+                "The function has no parameter named `yy`. Did you mean \"y\"?",
+            ),
+            (
+                "let draw = fn(x, y) { x }; draw(1, x: 2)",
+                "The parameter `x` received more than one argument",
+            ),
+            (
+                "let draw = fn(x, y) { x }; draw(x: 1, x: 2)",
+                "The parameter `x` received more than one argument",
+            ),
+            (
+                "let draw = fn(x, y) { x }; draw(x: 1)",
+                "No argument was passed for the parameter `y`",
+            ),
+            ("len(arr: [1])", "The function has no parameter named `arr`"),
+        ];
+
+        for (input, expected) in errors {
+            let err = Evaluator::new(input).eval_program().unwrap_err();
+            assert_eq!(err.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_recursive_functions() {
+        let tests = vec![
+            (
+                "let fib = fn(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+                610,
+            ),
+            (
+                "let run = fn() {
+                    let fact = fn(n) { if n == 0 { 1 } else { n * fact(n - 1) } };
+                    fact(5)
+                };
+                run()",
+                120,
+            ),
+            // the function still refers to itself once its name is bound to something else
+            (
+                "var count = fn(n) { if n == 0 { 0 } else { 1 + count(n - 1) } };
+                let f = count;
+                count = fn(n) { 100 };
+                f(3)",
+                3,
+            ),
+            // each call has its own bindings
+            (
+                "let adder = fn(x) { fn(y) { x + y } };
+                let add1 = adder(1);
+                let add2 = adder(2);
+                add1(10) * 100 + add2(10)",
+                1112,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result, Object::IntegerValue(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn eval_mutually_recursive_functions() {
+        let tests = vec![
+            (
+                "let is_even = fn(n) { if n == 0 { true } else { is_odd(n - 1) } };
+                let is_odd = fn(n) { if n == 0 { false } else { is_even(n - 1) } };
+                [is_even(10), is_odd(7), is_even(3)]",
+                vec![true, true, false],
+            ),
+            // functions can be called before their declaration, within any block
+            (
+                "let check = fn(n) {
+                    let result = [is_even(n), is_odd(n)];
+                    let is_even = fn(n) { if n == 0 { true } else { is_odd(n - 1) } };
+                    let is_odd = fn(n) { if n == 0 { false } else { is_even(n - 1) } };
+                    result
+                };
+                check(5)",
+                vec![false, true],
+            ),
+            (
+                "let first = [is_odd(1)]; let is_odd = fn(n) { n % 2 == 1 }; first",
+                vec![true],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            let expected = expected.into_iter().map(Object::BooleanValue).collect();
+            assert_eq!(result, Object::ArrayValue(expected), "{input}");
+        }
+
+        // only functions are hoisted
+        let mut evaluator = Evaluator::new("let a = b; let b = 1;");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::IdentifierNotFound(_))
+        ));
+
+        // declarations are bound again when they're reached, even if they were shadowed
+        let mut evaluator = Evaluator::new(
+            "let f = fn() { 1 }; let before = f(); let f = 2; let after = f; let f = fn() { 3 }; [before, after, f()]",
+        );
+        assert_eq!(
+            evaluator.eval_program().unwrap().pop().unwrap(),
+            Object::ArrayValue(vec![
+                Object::IntegerValue(1),
+                Object::IntegerValue(2),
+                Object::IntegerValue(3),
+            ])
+        );
+    }
+
+    #[test]
+    fn eval_block_statement() {
+        let input = r#"
+            let a = 2;
+
+            {
+                let b = 3;
+                b;
+            }
+
+            a;
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[2];
+        assert_eq!(result, &Object::IntegerValue(2));
+    }
+
+    #[test]
+    fn eval_for_statement() {
+        let input = r#"
+            let find = fn(arr, target) {
+                for x in arr {
+                    if x == target {
+                        return true;
+                    }
+                }
+                false
+            };
+            find([1, 2, 3], 2);
+            find([1, 2, 3], 5);
+            for x in [] { x }
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[1], &Object::BooleanValue(true));
+        assert_eq!(&result[2], &Object::BooleanValue(false));
+        assert_eq!(&result[3], &Object::UnitValue);
+
+        let mut evaluator = Evaluator::new("for x in 5 { x }");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn eval_range_expression() {
+        let input = r#"
+            let r = 1..4;
+            r;
+            to_array(r);
+            to_array(1..=3);
+            len(0..10);
+            len(0..=10);
+            len(5..1);
+
+            let last = fn(r) {
+                let result = -1;
+                for i in r {
+                    if i == 2 {
+                        return i * 100;
+                    }
+                }
+                result
+            };
+            last(0..5);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(
+            &result[1],
+            &Object::RangeValue {
+                start: 1,
+                end: 4,
+                inclusive: false
+            }
+        );
+        assert_eq!(
+            &result[2],
+            &Object::ArrayValue(vec![
+                Object::IntegerValue(1),
+                Object::IntegerValue(2),
+                Object::IntegerValue(3)
+            ])
+        );
+        assert_eq!(&result[2], &result[3]);
+        assert_eq!(&result[4], &Object::IntegerValue(10));
+        assert_eq!(&result[5], &Object::IntegerValue(11));
+        assert_eq!(&result[6], &Object::IntegerValue(0));
+        assert_eq!(&result[8], &Object::IntegerValue(200));
+    }
+
+    #[test]
+    fn eval_assign_statement() {
+        let input = r#"
+            var a = 2;
+            a = a + 2;
+            a;
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap()[2];
+        assert_eq!(result, &Object::IntegerValue(4));
+    }
+
+    #[test]
+    fn eval_assign_enclosing_environment() {
+        let input = r#"
+            var sum = 0;
+            for x in [1, 2, 3, 4] {
+                sum = sum + x;
+            }
+            sum;
+
+            let counter = fn() {
+                var count = 0;
+                fn() {
+                    count = count + 1;
+                    count
                 }
+            };
+            let next = counter();
+            next();
+            next();
+
+            var shadowed = 1;
+            {
+                let shadowed = 10;
+            }
+            shadowed;
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[2], &Object::IntegerValue(10));
+        assert_eq!(&result[5], &Object::IntegerValue(1));
+        assert_eq!(&result[6], &Object::IntegerValue(2));
+        assert_eq!(&result[9], &Object::IntegerValue(1));
+
+        let tests = vec![
+            "let a = 1; a = 2;",
+            "var a = 1; { let a = 2; a = 3; }",
+            "let f = fn(x) { x = 2; }; f(1);",
+        ];
+
+        for input in tests {
+            let mut evaluator = Evaluator::new(input);
+            assert!(matches!(
+                evaluator.eval_program(),
+                Err(EvalError::ImmutableAssignment(_))
+            ));
+        }
+
+        let mut evaluator = Evaluator::new("a = 1;");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::IdentifierNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn eval_static_scope() {
+        let input = r#"
+            let i = 5;
+            let foo = fn(i) {
+                i;
+            };
+
+            foo(10);
+            i;
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[2], &Object::IntegerValue(10));
+        assert_eq!(&result[3], &Object::IntegerValue(5));
+    }
+
+    #[test]
+    fn eval_closure() {
+        let input = r#"
+            let newAdder = fn(x) {
+                fn(y) { x + y };
+            };
+
+            let addTwo = newAdder(2);
+            addTwo(2);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[2], &Object::IntegerValue(4));
+    }
+
+    #[test]
+    fn eval_nested_returns() {
+        let input = r#"
+            let add = fn(x, y) { return x + y; };
+
+            let foo = fn() {
+                return add(5 + 5, add(1, 1));
+            };
+
+            let faz = fn() {
+                return 20;
+            };
 
-                BuiltinFunction::Rest => {
-                    if arguments.len() != 1 {
-                        return Err(EvalError::FunctionCallWrongArity(1, arguments.len() as u8));
+            let bar = fn() {
+                let value = if foo() == 12 {
+                    if foo() == 12 {
+                        return faz();
                     }
 
-                    let arguments = self.eval_call_expression_arguments(arguments)?;
-                    // unwrapping is fine, this element surely exist because of the previous check
-                    let arg = arguments.get(0).unwrap();
+                    return 100;
+                } else {
+                    return -1;
+                };
+                value * 2
+            };
+
+            bar();
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[4], &Object::IntegerValue(20));
+    }
+
+    #[test]
+    fn eval_returns_exit_once() {
+        let tests = [
+            ("let f = fn() { let x = if true { return 1; } else { 2 }; x + 10 }; f()", 1),
+            ("let f = fn() { 1 + if true { return 5; } else { 600 } }; f()", 5),
+            ("let f = fn() { len([if true { return 7; } else { 0 }]); 99 }; f()", 7),
+            ("let f = fn() { [1, if true { return 8; } else { 0 }]; 9 }; f()", 8),
+            // the return of a called function doesn't exit the caller
+            ("let f = fn() { let inner = fn() { return 1; }; inner(); 2 }; f()", 2),
+            ("let f = fn() { match 3 { case n if n > 2: { return n; }, case _: 0 }; 4 }; f()", 3),
+            (
+                "let f = fn() { for i in 0..10 { loop { if i == 3 { return i; } break; } }; -1 }; f()",
+                3,
+            ),
+            (
+                "let f = fn() { var total = 0; for i in 0..5 { total = total + i; }; return total; }; f()",
+                10,
+            ),
+            ("let f = fn(n) { if n > 0 { return f(n - 1) + 1; }; 0 }; f(4)", 4),
+        ];
+
+        for (input, expected) in tests {
+            let result = Evaluator::new(input).eval_program().unwrap().pop();
+            assert_eq!(result, Some(Object::IntegerValue(expected)), "{input}");
+        }
+
+        for input in ["return 1;", "{ return 1; }", "if true { return 1; }"] {
+            let err = Evaluator::new(input).eval_program().unwrap_err();
+            assert!(
+                matches!(err, EvalError::ReturnOutsideExpression),
+                "{input}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn eval_call_arbitrary_callee() {
+        let input = r#"
+            let makeAdder = fn(x) { fn(y) { x + y } };
+            makeAdder(2)(3);
+            fn(x) { x * 2 }(5);
+            [fn(x) { x + 1 }][0](1);
+            ({ "double": fn(x) { x * 2 } })["double"](4);
+            (if true { makeAdder(10) } else { makeAdder(20) })(1);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[1], &Object::IntegerValue(5));
+        assert_eq!(&result[2], &Object::IntegerValue(10));
+        assert_eq!(&result[3], &Object::IntegerValue(2));
+        assert_eq!(&result[4], &Object::IntegerValue(8));
+        assert_eq!(&result[5], &Object::IntegerValue(11));
+    }
+
+    #[test]
+    fn eval_function_as_parameter() {
+        let input = r#"
+            let add = fn(x, y) { return x + y; };
+            let sub = fn(x, y) { return x - y; };
+            let applyFunc = fn(a, b, cb) {
+                cb(a, b)
+            };
+            applyFunc(2, 2, add);
+            applyFunc(10, 2, sub);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[3], &Object::IntegerValue(4));
+        assert_eq!(&result[4], &Object::IntegerValue(8));
+    }
+
+    #[test]
+    fn builtin_len() {
+        let input = r#"
+            len("hello");
+            len("");
+            len("héllo 👍🏽");
+            len(bytes("héllo 👍🏽"));
+            len(codepoints("héllo 👍🏽"));
+            len("নমস্কার");
+            "நன்றி"[1];
+            "한국어"[1..3];
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(&result[0], &Object::IntegerValue(5));
+        assert_eq!(&result[1], &Object::IntegerValue(0));
+        assert_eq!(&result[2], &Object::IntegerValue(7));
+        assert_eq!(&result[3], &Object::IntegerValue(15));
+        assert_eq!(&result[4], &Object::IntegerValue(8));
+        // the conjunct স্ক and its vowel sign form a single cluster
+        assert_eq!(&result[5], &Object::IntegerValue(4));
+        assert_eq!(&result[6], &Object::StringValue("ன்".to_owned()));
+        assert_eq!(&result[7], &Object::StringValue("국어".to_owned()));
+
+        // a zero width joiner between letters doesn't join them
+        let mut evaluator = Evaluator::new("len(\"a\u{200D}b\")");
+        let result = evaluator.eval_program().unwrap().pop();
+        assert_eq!(result, Some(Object::IntegerValue(2)));
+    }
+
+    #[test]
+    fn builtin_chars() {
+        let input = "chars(\"e\u{301}👨‍👩‍👧!\"); codepoints(\"e\u{301}\"); bytes(\"é\");";
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+
+        let strings = |items: &[&str]| {
+            Object::ArrayValue(
+                items
+                    .iter()
+                    .map(|item| Object::StringValue((*item).to_owned()))
+                    .collect(),
+            )
+        };
+        assert_eq!(&result[0], &strings(&["e\u{301}", "👨‍👩‍👧", "!"]));
+        assert_eq!(&result[1], &strings(&["e", "\u{301}"]));
+        assert_eq!(
+            &result[2],
+            &Object::ArrayValue(vec![Object::IntegerValue(195), Object::IntegerValue(169)])
+        );
+    }
+
+    #[test]
+    fn builtin_string_helpers() {
+        let tests = vec![
+            ("split_lines(\"a\nb\r\n\nc\n\")", r#"["a", "b", "", "c"]"#),
+            (r#"split_lines("")"#, "[]"),
+            (
+                r#"[starts_with("report.csv", "report"), starts_with("a", "ab"), starts_with("x", "")]"#,
+                "[true, false, true]",
+            ),
+            (
+                r#"[ends_with("report.csv", ".csv"), ends_with("report.csv", "report")]"#,
+                "[true, false]",
+            ),
+            (r#"pad_left("42", 5)"#, r#""   42""#),
+            (r#"pad_right("ab", 4, ".") + "|""#, r#""ab..|""#),
+            (r#"pad_left("7", 3, "0")"#, r#""007""#),
+            // the width is counted in grapheme clusters, and never truncates
+            (r#"pad_left("ñ👍🏽", 3, "·")"#, r#""·ñ👍🏽""#),
+            (r#"pad_right("long", 2)"#, r#""long""#),
+            (r#"pad_left("x", -1)"#, r#""x""#),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        for input in [
+            "split_lines(1)",
+            r#"starts_with("a", 1)"#,
+            r#"pad_left("a", "3")"#,
+            r#"pad_left("a", 3, "ab")"#,
+            r#"pad_right("a", 3, "")"#,
+        ] {
+            let mut evaluator = Evaluator::new(input);
+            assert!(
+                matches!(
+                    evaluator.eval_program(),
+                    Err(EvalError::UnsupportedArgumentType(_))
+                ),
+                "{input}"
+            );
+        }
+        let mut evaluator = Evaluator::new(r#"pad_left("a")"#);
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::FunctionCallArityOutOfRange(2, 3, 1))
+        ));
+    }
 
-                    if let Object::ArrayValue(objects) = arg {
-                        // return a new array, rather than modifying the existing one
-                        Object::ArrayValue(objects[1..].to_vec())
-                    } else {
-                        return Err(EvalError::UnsupportedArgumentType(format!(
-                            "`{}` only works on arrays",
-                            BuiltinFunction::Append
-                        )));
-                    }
-                }
+    #[test]
+    fn builtin_weak_references() {
+        let tests =
+            vec![
+            ("let rows = [1, 2]; let w = weak(rows); upgrade(w)", "[1, 2]"),
+            ("let p = {\"x\": 1}; [weak(p), type(weak(p))]", r#"[weak(p), "weak"]"#),
+            // the value is the current one of the binding
+            ("var xs = [1]; let w = weak(xs); xs = [2]; upgrade(w)", "[2]"),
+            // the binding is gone once its scope is over
+            (
+                "let load = fn(key) { let rows = [key, key * 2]; weak(rows) }; upgrade(load(1))",
+                "null",
+            ),
+            ("let w = if true { let a = [1]; weak(a) }; upgrade(w)", "null"),
+            (
+                r#"var cache = {};
+                let get = fn(key) {
+                    let rows = [key];
+                    cache = {"a": weak(rows)};
+                    rows
+                };
+                [get("a"), upgrade(cache["a"])]"#,
+                r#"[["a"], null]"#,
+            ),
+        ];
 
-                BuiltinFunction::Println => {
-                    let arguments = self.eval_call_expression_arguments(arguments)?;
-                    arguments.iter().for_each(|arg| println!("{arg}"));
-                    Object::UnitValue
-                }
-                BuiltinFunction::Print => {
-                    let arguments = self.eval_call_expression_arguments(arguments)?;
-                    arguments.iter().for_each(|arg| print!("{arg}"));
-                    Object::UnitValue
-                }
-            },
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
 
-            other => {
-                return Err(EvalError::FunctionNotFound(format!(
-                    "`{other}` cannot be called as a function"
-                )));
-            }
-        };
+        let errors = vec![
+            (
+                "weak([1])",
+                "Unsupported argument type for built-in function: `weak` refers to a binding, its argument must be an identifier",
+            ),
+            (
+                "let n = 1; weak(n)",
+                "Unsupported argument type for built-in function: `weak` only refers to arrays, hash maps, structs and enums",
+            ),
+            (
+                "upgrade([1])",
+                "Unsupported argument type for built-in function: `upgrade` only works on values made by `weak`",
+            ),
+            ("weak(missing)", "Identifier not found: missing"),
+        ];
 
-        Ok(obj)
+        for (input, expected) in errors {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
     }
 
-    fn eval_call_expression_arguments(
-        &mut self,
-        arguments: Vec<Expression>,
-    ) -> Result<Vec<Object>, EvalError> {
-        Ok(arguments
-            .into_iter()
-            .map(|arg| self.eval_expression(arg, false))
-            .collect::<Result<Vec<Object>, EvalError>>()?)
+    #[test]
+    fn builtin_tasks() {
+        let tests = vec![
+            (
+                "let sum = fn(n) { var s = 0; for i in 0..n { s = s + i; } s }; wait_all([spawn(sum, 10), spawn(sum, 100)])",
+                "[45, 4950]",
+            ),
+            ("let t = spawn(fn() { [1, 2] }); [join(t), t.join(), type(t)]", r#"[[1, 2], [1, 2], "task"]"#),
+            // the task works on a copy of the values it captured
+            ("var n = 1; let t = spawn(fn() { n = n + 1; n }); [join(t), n]", "[2, 1]"),
+            // closures returned by a task keep working
+            ("let t = spawn(fn(k) { fn(x) { x * k } }, 3); join(t)(2)", "6"),
+            ("wait_all([])", "[]"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            ("join(spawn(fn() { 1 / 0 }))", "failed: Division by zero isn't allowed"),
+            // the first failed task is reported once every task is over
+            (
+                "wait_all([spawn(fn() { 1 }), spawn(fn() { [][1] }), spawn(fn() { 1 / 0 })])",
+                "failed: This structure has 0 elements but the index 1 is out of bounds.",
+            ),
+            ("spawn(fn(a) { a })", "Function call with the wrong number of arguments. Expected 1, got 0"),
+            (
+                "join(1)",
+                "Unsupported argument type for built-in function: `join` only works on tasks started by `spawn`",
+            ),
+            (
+                "wait_all([spawn(fn() { 1 }), 2])",
+                "Unsupported argument type for built-in function: `wait_all` only works on an array of tasks started by `spawn`",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator
+                .eval_program()
+                .unwrap_err()
+                .root_cause()
+                .to_string();
+            assert!(err.ends_with(expected), "{input}: {err}");
+        }
     }
 
-    /// Creates a new environment linked to the outer environment
-    fn create_enclosed_env(&mut self) -> Rc<RefCell<Environment>> {
-        let inner_env = Environment {
-            outer: Some(self.env.clone()),
-            ..Default::default()
-        };
-        Rc::new(RefCell::new(inner_env))
+    #[test]
+    fn eval_task_output() {
+        use crate::streams::SharedBuffer;
+
+        let tests = vec![
+            (
+                "let t = spawn(fn() { println(1); 2 }); println(join(t)); println(3)",
+                "1\n2\n3\n",
+            ),
+            // tasks started by tasks forward their output through them
+            (
+                "join(spawn(fn() { join(spawn(fn() { print(1) })); print(2) })); print(3)",
+                "123",
+            ),
+            (
+                "let a = actor(fn(s, m) { println(m); s }, 0); ask(a, 1); println(2)",
+                "1\n2\n",
+            ),
+            (
+                "when_done(spawn(fn() { print(1) }), fn(n) { print(2) })",
+                "12",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let (stdout, stderr) = (SharedBuffer::default(), SharedBuffer::default());
+            let mut evaluator = Evaluator::new(input);
+            evaluator.set_stdout(stdout.clone());
+            evaluator.set_stderr(stderr.clone());
+            evaluator.eval_program().unwrap();
+            evaluator.run_event_loop().unwrap();
+            assert_eq!(*stdout.0.borrow(), expected, "{input}");
+            assert_eq!(*stderr.0.borrow(), "", "{input}");
+        }
+
+        let stderr = SharedBuffer::default();
+        let mut evaluator = Evaluator::new(r#"join(spawn(fn() { log("info", "task", {}) }))"#);
+        evaluator.set_stderr(stderr.clone());
+        evaluator.eval_program().unwrap();
+        assert_eq!(*stderr.0.borrow(), "[info] task\n");
+
+        let mut evaluator = Evaluator::new("join(spawn(fn() { read_line() }))");
+        evaluator.set_stdin("line".as_bytes());
+        let err = evaluator.eval_program().unwrap_err().to_string();
+        assert!(
+            err.ends_with("tasks and actors can't read the input of the host"),
+            "{err}"
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn poll_scheduled_callbacks() {
+        let input = r#"
+            var ticks = 0;
+            let ticker = set_interval(fn() {
+                ticks = ticks + 1;
+                print(1);
+                if ticks == 3 { clear_timer(ticker); }
+            }, 0);
+            set_timeout(fn() { print(2); }, 0);
+            set_timeout(fn() { print(3); }, 60000);
+            let cancelled = set_timeout(fn() { print(4); }, 0);
+            [clear_timer(cancelled), clear_timer(cancelled)]
+        "#;
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut evaluator = Evaluator::new(input);
+        evaluator.capture_output(output.clone());
+        let result = evaluator.eval_program().unwrap().pop().unwrap();
+        assert_eq!(result.to_string(), "[true, false]");
+        // callbacks only run when the evaluator is polled
+        assert_eq!(*output.borrow(), "");
+
+        let poll = evaluator.poll().unwrap();
+        assert_eq!((poll.callbacks, poll.pending), (2, 2));
+        assert_eq!(*output.borrow(), "12");
+
+        evaluator.poll().unwrap();
+        let poll = evaluator.poll().unwrap();
+        assert_eq!((poll.callbacks, poll.pending), (1, 1));
+        assert!(poll.next_deadline.unwrap() > Instant::now());
+        assert_eq!(*output.borrow(), "1211");
+    }
 
     #[test]
-    fn eval_integer_literal() {
-        let input = "5";
+    fn run_event_loop() {
+        let input = r#"
+            set_timeout(fn() { print(9); }, 20);
+            set_timeout(fn() { print(0); }, 0);
+            let task = spawn(fn() { 2 + 3 });
+            task.when_done(fn(result) { print(result); });
+        "#;
+        let output = Rc::new(RefCell::new(String::new()));
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[0];
-        assert_eq!(result, &Object::IntegerValue(5));
+        evaluator.capture_output(output.clone());
+        evaluator.eval_program().unwrap();
+        evaluator.run_event_loop().unwrap();
+
+        assert!(matches!(output.borrow().as_str(), "059" | "509"));
+        assert_eq!(evaluator.poll().unwrap().pending, 0);
+
+        let tests = [
+            (
+                "set_timeout(fn() { 1 / 0 }, 0);",
+                "while running the callback of timer #1",
+                "Division by zero isn't allowed",
+            ),
+            (
+                "when_done(spawn(fn() { 1 }), fn(n) { n / 0 });",
+                "while running the callback of task",
+                "Division by zero isn't allowed",
+            ),
+        ];
+        for (input, frame, cause) in tests {
+            let mut evaluator = Evaluator::new(input);
+            evaluator.eval_program().unwrap();
+            let err = evaluator.run_event_loop().unwrap_err();
+            assert!(err.frames()[0].starts_with(frame), "{input}: {err}");
+            assert_eq!(err.root_cause().to_string(), cause, "{input}");
+        }
+
+        let errors = [
+            (
+                "set_timeout(fn() { 1 }, -5)",
+                "`set_timeout` can't wait for a negative number of milliseconds, got -5",
+            ),
+            (
+                "set_interval(1, fn() { 1 })",
+                "`set_interval` only works on a function and a number of milliseconds",
+            ),
+            (
+                "when_done(spawn(fn() { 1 }), fn() { 1 })",
+                "Expected 0, got 1",
+            ),
+        ];
+        for (input, expected) in errors {
+            let err = Evaluator::new(input)
+                .eval_program()
+                .unwrap_err()
+                .to_string();
+            assert!(err.ends_with(expected), "{input}: {err}");
+        }
     }
 
     #[test]
-    fn eval_boolean_literal() {
-        let input = "true";
+    fn yield_points() {
+        let tests = [
+            ("set_timeout(fn() { print(9); }, 0); print(0); yield_now(); print(1);", "091"),
+            // every iteration of a loop ends with a yield point
+            ("set_timeout(fn() { print(9); }, 0); for i in 0..3 { print(i); }", "0912"),
+            ("set_timeout(fn() { print(9); }, 0); var i = 0; do { i = i + 1; print(i); } while i < 2;", "192"),
+            // callbacks don't run again while one of them is running
+            (
+                "let t = set_interval(fn() { print(1); yield_now(); loop { break; } }, 0); yield_now(); clear_timer(t);",
+                "1",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let output = Rc::new(RefCell::new(String::new()));
+            let mut evaluator = Evaluator::new(input);
+            evaluator.capture_output(output.clone());
+            evaluator.eval_program().unwrap();
+            assert_eq!(*output.borrow(), expected, "{input}");
+        }
+
+        // interruptions are noticed by the next yield point
+        let mut evaluator = Evaluator::new("var i = 0; loop { i = i + 1; }");
+        let handle = evaluator.interrupt_handle();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            handle.interrupt();
+        });
+        let err = evaluator.eval_program().unwrap_err();
+        interrupter.join().unwrap();
+        assert!(matches!(err, EvalError::Interrupted), "{err}");
+
+        let mut evaluator = Evaluator::new("let f = fn() { for i in 0..10 { yield_now(); } }; f()");
+        evaluator.set_deadline(Some(Instant::now()));
+        let err = evaluator.eval_program().unwrap_err();
+        assert_eq!(err.to_string(), "The evaluation went past its deadline");
+
+        let mut evaluator = Evaluator::new("var i = 0; for x in 0..100 { i = i + x; } i");
+        evaluator.set_deadline(Some(Instant::now() + Duration::from_secs(60)));
+        let result = evaluator.eval_program().unwrap().pop();
+        assert_eq!(result, Some(Object::IntegerValue(4950)));
+    }
+
+    #[test]
+    fn builtin_actors() {
+        let counter = r#"
+            let counter = actor(fn(count, message) {
+                match message { case "inc": count + 1, case _: count }
+            }, 0);
+        "#;
+        let tests = vec![
+            (format!("{counter} ask(counter, \"get\")"), "0"),
+            // messages are handled in the order they were sent
+            (
+                format!("{counter} tell(counter, \"inc\"); counter.tell(\"inc\"); counter.ask(\"get\")"),
+                "2",
+            ),
+            (format!("{counter} [type(counter), tell(counter, \"inc\")]"), r#"["actor", ()]"#),
+            // addresses can be sent to other actors
+            (
+                r#"let log = actor(fn(lines, line) { append(lines, line) }, []);
+                let forward = actor(fn(count, message) { tell(message[0], message[1]); count + 1 }, 0);
+                ask(forward, [log, "a"]);
+                ask(log, "b")"#
+                    .to_owned(),
+                r#"["a", "b"]"#,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(&input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "let a = actor(fn(s, m) { s / m }, 1); ask(a, 0)",
+                "failed: Division by zero isn't allowed",
+            ),
+            // the actor stops handling messages after failing
+            (
+                "let a = actor(fn(s, m) { s / m }, 1); tell(a, 0); ask(a, 1)",
+                "failed: Division by zero isn't allowed",
+            ),
+            (
+                "actor(fn(s) { s }, 1)",
+                "Function call with the wrong number of arguments. Expected 1, got 2",
+            ),
+            (
+                "ask(1, 2)",
+                "Unsupported argument type for built-in function: `ask` only sends messages to actors started by `actor`",
+            ),
+        ];
+
+        for (input, expected) in errors {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator
+                .eval_program()
+                .unwrap_err()
+                .root_cause()
+                .to_string();
+            assert!(err.ends_with(expected), "{input}: {err}");
+        }
+    }
+
+    #[test]
+    fn builtin_deque_helpers() {
+        let tests = vec![
+            (
+                "var q = [1]; push_back(q, 2, 3); push_front(q, -1, 0); q",
+                "[-1, 0, 1, 2, 3]",
+            ),
+            (
+                "var q = [1, 2, 3]; [pop_front(q), pop_back(q), q, type(q), len(q)]",
+                r#"[1, 3, [2], "array", 1]"#,
+            ),
+            ("var q = []; [pop_front(q), pop_back(q)]", "[null, null]"),
+            // the queue is still an array once it's been read
+            (
+                "var q = [1]; push_front(q, 0); let copy = q; push_back(q, 2); [copy, q, q[2]]",
+                "[[0, 1], [0, 1, 2], 2]",
+            ),
+            // breadth-first search
+            (
+                r#"let tree = {"a": ["b", "c"], "b": ["d"], "c": ["e"], "d": [], "e": []};
+                var queue = ["a"];
+                var order = [];
+                loop {
+                    let node = pop_front(queue);
+                    if node == null { break order; }
+                    order = append(order, node);
+                    for child in tree[node] { push_back(queue, child); }
+                }"#,
+                r#"["a", "b", "c", "d", "e"]"#,
+            ),
+            // like assignments, closures modify the binding they captured
+            (
+                "var q = [1, 2]; let f = fn() { push_back(q, 3); }; f(); q",
+                "[1, 2, 3]",
+            ),
+            (
+                "var q = [2, 3]; push_front(q, 1); var sum = 0; for x in q { sum = sum + x; } [len(q), q[0], q[1..3], q.len(), sum]",
+                "[3, 1, [2, 3], 3, 6]",
+            ),
+            (
+                "var q = [2]; push_front(q, 1); [type(q), match q { case [a, ..rest]: rest, case _: 0 }]",
+                r#"["array", [2]]"#,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
+        }
+
+        let errors = vec![
+            (
+                "push_front([1], 0)",
+                "Unsupported argument type for built-in function: `push_front` modifies a `var` in place, its first argument must be an identifier",
+            ),
+            (
+                "var s = \"ab\"; pop_back(s)",
+                "Unsupported argument type for built-in function: `pop_back` only works on arrays",
+            ),
+            (
+                "let q = [1]; pop_front(q)",
+                "Cannot assign twice to the immutable identifier `q`, declare it with `var` instead",
+            ),
+            ("pop_front(missing)", "Identifier not found: missing"),
+        ];
+
+        for (input, expected) in errors {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.root_cause().to_string(), expected, "{input}");
+        }
+
+        // reading the length, an element or every element doesn't convert the deque to an array
+        let mut evaluator = Evaluator::new(
+            "var q = [1]; push_front(q, 0); len(q); q[1]; q[0..1]; for x in q { x; } q.len();",
+        );
+        evaluator.eval_program().unwrap();
+        assert!(matches!(
+            evaluator.env.borrow().store.get("q"),
+            Some(Object::DequeValue(_))
+        ));
+
+        let mut evaluator = Evaluator::new("var q = []; push_back(q)");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::FunctionCallWrongArity(2, 1))
+        ));
+    }
+
+    #[test]
+    fn eval_string_index_and_slices() {
+        let input = r#"
+            let s = "héllo 🇮🇹!";
+            s[1];
+            s[6];
+            s[1..4];
+            s[6..=7];
+            [1, 2, 3, 4][1..3];
+        "#;
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[0];
-        assert_eq!(result, &Object::BooleanValue(true));
+        let result = evaluator.eval_program().unwrap();
+        assert_eq!(&result[1], &Object::StringValue("é".to_owned()));
+        assert_eq!(&result[2], &Object::StringValue("🇮🇹".to_owned()));
+        assert_eq!(&result[3], &Object::StringValue("éll".to_owned()));
+        assert_eq!(&result[4], &Object::StringValue("🇮🇹!".to_owned()));
+        assert_eq!(
+            &result[5],
+            &Object::ArrayValue(vec![Object::IntegerValue(2), Object::IntegerValue(3)])
+        );
+
+        let mut evaluator = Evaluator::new(r#""abc"[1..4];"#);
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::IndexOutOfBounds(3, 3))
+        ));
+        let mut evaluator = Evaluator::new("[1, 2, 3][2..1];");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::SliceStartAfterEnd(2, 1))
+        ));
     }
 
     #[test]
-    fn eval_string_literal() {
-        let input = r#""foo""#;
+    fn builtin_append() {
+        let input = r#"
+            append([1, 2, 3], 100, 200);
+        "#;
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[0];
-        assert_eq!(result, &Object::StringValue("foo".to_owned()));
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(
+            &result[0],
+            &Object::ArrayValue(vec![
+                Object::IntegerValue(1),
+                Object::IntegerValue(2),
+                Object::IntegerValue(3),
+                Object::IntegerValue(100),
+                Object::IntegerValue(200),
+            ])
+        );
     }
 
     #[test]
-    fn eval_string_concatenation() {
+    fn builtin_rest() {
         let input = r#"
-            let greet = fn() {
-                let a = "hello";
-                let b = "world";
-                return a + " " + b;
-            };
+            rest([1, 2, 3]);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = &evaluator.eval_program().unwrap();
+        assert_eq!(
+            &result[0],
+            &Object::ArrayValue(vec![Object::IntegerValue(2), Object::IntegerValue(3)])
+        );
+    }
 
-            greet();
+    #[test]
+    fn builtin_array_operations() {
+        let tests = vec![
+            ("sorted([3, 1, 2])", "[1, 2, 3]"),
+            ("sorted([2.5, 1, 2])", "[1, 2, 2.5]"),
+            (r#"sorted(["b", "a", "c"])"#, r#"["a", "b", "c"]"#),
+            ("sorted([])", "[]"),
+            ("let a = [2, 1]; sorted(a); a", "[2, 1]"),
+            ("unique([1, 2, 1, 3, 2])", "[1, 2, 3]"),
+            (r#"unique(["a", 1, "a", [1], [1]])"#, r#"["a", 1, [1]]"#),
+            ("flatten([1, [2, [3, [4]]]])", "[1, 2, [3, [4]]]"),
+            ("flatten([1, [2, [3, [4]]]], 2)", "[1, 2, 3, [4]]"),
+            ("flatten([1, [2]], 0)", "[1, [2]]"),
+            ("zip([1, 2, 3], [\"a\", \"b\"])", r#"[[1, "a"], [2, "b"]]"#),
+            ("zip([], [1])", "[]"),
+            ("enumerate([\"a\", \"b\"])", r#"[[0, "a"], [1, "b"]]"#),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap();
+            assert_eq!(result.last().unwrap().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn builtin_array_operations_errors() {
+        let tests = vec![
+            ("sorted(1)", "Unsupported argument type for built-in function: `sorted` only works on arrays"),
+            ("unique(\"a\")", "Unsupported argument type for built-in function: `unique` only works on arrays"),
+            ("zip([1], 2)", "Unsupported argument type for built-in function: `zip` only works on arrays"),
+            ("enumerate(1..2)", "Unsupported argument type for built-in function: `enumerate` only works on arrays"),
+            ("flatten([1], -1)", "Unsupported argument type for built-in function: `flatten` only accepts a non-negative integer depth, got -1"),
+            ("sorted([1, \"a\"])", "Type mismatch: Cannot compare '1' and '\"a\"'"),
+            ("sorted([2, 1.5, true, 0])", "Type mismatch: Cannot compare '2' and 'true'"),
+            ("zip([1])", "Function call with the wrong number of arguments. Expected 2, got 1"),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn builtin_group_by() {
+        let input = r#"
+            let parity = fn(x) { if x % 2 == 0 { "even" } else { return "odd"; } };
+            let groups = group_by([1, 2, 3, 4, 5], parity);
+            groups["odd"];
+            groups["even"];
+            let counts = count_by(["a", "bb", "cc", "d", "eee"], fn(s) { to_base(len(s), 10) });
+            counts["1"];
+            counts["2"];
+            counts["3"];
+            group_by([], parity);
         "#;
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[1];
-        assert_eq!(result, &Object::StringValue("hello world".to_owned()));
+        let result = evaluator.eval_program().unwrap();
+        assert_eq!(result[2].to_string(), "[1, 3, 5]");
+        assert_eq!(result[3].to_string(), "[2, 4]");
+        assert_eq!(&result[5], &Object::IntegerValue(2));
+        assert_eq!(&result[6], &Object::IntegerValue(2));
+        assert_eq!(&result[7], &Object::IntegerValue(1));
+        assert_eq!(&result[8], &Object::MapValue(BTreeMap::new()));
+
+        let tests = vec![
+            (
+                "group_by([1], 2)",
+                "Unsupported argument type for built-in function: `group_by` only works on an array and a function",
+            ),
+            (
+                "count_by([1], fn(x) { x })",
+                "Unsupported argument type for built-in function: `count_by` needs the function to return string keys, got 1",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.to_string(), expected, "{input}");
+        }
     }
 
     #[test]
-    fn eval_boolean_expressions() {
+    fn builtin_clone_and_freeze() {
+        let input = r#"
+            var a = [1, [2, 3]];
+            let b = clone(a);
+            a = append(a, 4);
+            b;
+            freeze(a);
+            freeze(1 + 1);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = evaluator.eval_program().unwrap();
+        assert_eq!(result[3].to_string(), "[1, [2, 3]]");
+        assert_eq!(result[4].to_string(), "[1, [2, 3], 4]");
+        assert_eq!(&result[5], &Object::IntegerValue(2));
+
         let tests = vec![
-            ("true", true),
-            ("false", false),
-            ("1 < 2", true),
-            ("1 > 2", false),
-            ("1 < 1", false),
-            ("1 > 1", false),
-            ("1 == 1", true),
-            ("1 != 1", false),
-            ("1 == 2", false),
-            ("1 != 2", true),
-            ("true == true", true),
-            ("false == false", true),
-            ("true == false", false),
-            ("true != false", true),
-            ("false != true", true),
-            ("(1 < 2) == true", true),
-            ("(1 < 2) == false", false),
-            ("(1 > 2) == true", false),
-            ("(1 > 2) == false", true),
-            ("true && true", true),
-            ("true && false", false),
-            ("false && true", false),
-            ("false && false", false),
-            ("true || true", true),
-            ("true || false", true),
-            ("false || true", true),
-            ("false || false", false),
+            (
+                "var a = 1; freeze(a); a = 2;",
+                "Cannot assign to the identifier `a`, as it has been frozen",
+            ),
+            (
+                "var a = 1; let f = fn() { freeze(a) }; f(); a = 2;",
+                "Cannot assign to the identifier `a`, as it has been frozen",
+            ),
+            ("freeze(b)", "Identifier not found: b"),
         ];
 
         for (input, expected) in tests {
             let mut evaluator = Evaluator::new(input);
-            let result = &evaluator.eval_program().unwrap()[0];
+            let err = evaluator.eval_program().unwrap_err();
+            assert_eq!(err.to_string(), expected, "{input}");
+        }
 
-            let expected_obj = match expected {
-                true => &Object::BooleanValue(true),
-                false => &Object::BooleanValue(false),
-            };
+        // shadowing a frozen identifier creates a new binding
+        let mut evaluator = Evaluator::new("var a = 1; freeze(a); var a = 2; a = 3; a");
+        let result = evaluator.eval_program().unwrap();
+        assert_eq!(result.last().unwrap(), &Object::IntegerValue(3));
+    }
+
+    #[test]
+    fn builtin_introspection() {
+        let input = r#"
+            depth(1);
+            depth([]);
+            depth([1, [2, [3]], 4]);
+            depth({ "a": [1], "b": 2 });
+            fields({ "b": 1, "a": { "c": 2 } });
+            sizeof(1) == sizeof(true);
+            sizeof("hello") - sizeof("");
+            sizeof([1, 2]) - sizeof([]) == 2 * sizeof(1);
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        let result = evaluator.eval_program().unwrap();
+        assert_eq!(&result[0], &Object::IntegerValue(0));
+        assert_eq!(&result[1], &Object::IntegerValue(1));
+        assert_eq!(&result[2], &Object::IntegerValue(3));
+        assert_eq!(&result[3], &Object::IntegerValue(2));
+        assert_eq!(result[4].to_string(), r#"["a", "b"]"#);
+        assert_eq!(&result[5], &Object::BooleanValue(true));
+        assert_eq!(&result[6], &Object::IntegerValue(5));
+        assert_eq!(&result[7], &Object::BooleanValue(true));
 
-            assert_eq!(result, expected_obj);
-        }
+        let mut evaluator = Evaluator::new("fields([1])");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::UnsupportedArgumentType(_))
+        ));
     }
 
     #[test]
-    fn eval_binary_expressions() {
+    fn builtin_diff() {
         let tests = vec![
-            ("2 + 3", &Object::IntegerValue(5)),
-            ("4 - 1", &Object::IntegerValue(3)),
-            ("5 * 6", &Object::IntegerValue(30)),
-            ("10 / 2", &Object::IntegerValue(5)),
-            ("7 == 7", &Object::BooleanValue(true)),
-            ("8 != 9", &Object::BooleanValue(true)),
-            ("true == true", &Object::BooleanValue(true)),
-            ("false != true", &Object::BooleanValue(true)),
+            ("diff([1, 2], [1, 2])", ""),
+            ("diff(1, 2)", "value\n  - 1\n  + 2\n"),
+            (
+                r#"diff({ "a": { "b": [1, 2] }, "c": 1 }, { "a": { "b": [1, 3, 4] }, "d": 1 })"#,
+                "value[\"a\"][\"b\"][1]\n  - 2\n  + 3\n\
+                 value[\"a\"][\"b\"][2]\n  + 4\n\
+                 value[\"c\"]\n  - 1\n\
+                 value[\"d\"]\n  + 1\n",
+            ),
+            (
+                "struct P { x, y } diff(P { x: 1, y: [2] }, P { x: 1, y: [3] })",
+                "value.y[0]\n  - 2\n  + 3\n",
+            ),
+            ("diff(Ok([1]), Err(1))", "value\n  - Ok([1])\n  + Err(1)\n"),
         ];
 
         for (input, expected) in tests {
             let mut evaluator = Evaluator::new(input);
-            let result = &evaluator.eval_program().unwrap()[0];
-            assert_eq!(result, expected);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result, Object::StringValue(expected.to_owned()), "{input}");
         }
+
+        let mut evaluator = Evaluator::new("assert_eq([1, 2], [1, 2])");
+        assert_eq!(
+            evaluator.eval_program().unwrap().pop().unwrap(),
+            Object::UnitValue
+        );
+
+        let mut evaluator = Evaluator::new(r#"assert_eq({ "a": 1 }, { "a": "1" })"#);
+        let err = evaluator.eval_program().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The values given to `assert_eq` differ:\nvalue[\"a\"]\n  - 1\n  + \"1\""
+        );
+        assert_eq!(err.code(), "E1042");
     }
 
     #[test]
-    fn eval_unary_expressions() {
+    fn builtin_type() {
         let tests = vec![
-            ("-2", &Object::IntegerValue(-2)),
-            ("!true", &Object::BooleanValue(false)),
-            ("!false", &Object::BooleanValue(true)),
-            ("!5", &Object::IntegerValue(-6)),
-            ("!!5", &Object::IntegerValue(5)),
-            ("!0", &Object::IntegerValue(-1)),
-            ("!!true", &Object::BooleanValue(true)),
-            ("!!false", &Object::BooleanValue(false)),
+            ("type(1)", "integer"),
+            ("type(1.5)", "float"),
+            ("type(\"a\")", "string"),
+            ("type(null)", "null"),
+            ("type([])", "array"),
+            ("type(1..2)", "range"),
+            ("type(fn() { 1 })", "function"),
+            ("type(println())", "unit"),
         ];
 
         for (input, expected) in tests {
             let mut evaluator = Evaluator::new(input);
             let result = &evaluator.eval_program().unwrap()[0];
-            assert_eq!(result, expected);
+            assert_eq!(result, &Object::StringValue(expected.to_owned()), "{input}");
         }
     }
 
     #[test]
-    fn eval_if_expression() {
+    fn builtin_pmap() {
         let tests = vec![
-            ("if true { 10 }", &Object::IntegerValue(10)),
-            ("if false { 10 }", &Object::UnitValue),
-            ("if 1 < 2 { 10 }", &Object::IntegerValue(10)),
-            ("if 1 > 2 { 10 }", &Object::UnitValue),
-            ("if 1 > 2 { 10 } else { 20 }", &Object::IntegerValue(20)),
-            ("if 1 < 2 { 10 } else { 20 }", &Object::IntegerValue(10)),
+            ("pmap([1, 2, 3], fn(x) { x * x })", "[1, 4, 9]"),
+            ("pmap([], fn(x) { x })", "[]"),
+            (
+                "let offset = 1;
+                let fib = fn(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+                pmap(to_array(0..12), fn(n) { fib(n) + offset })",
+                "[1, 2, 2, 3, 4, 6, 9, 14, 22, 35, 56, 90]",
+            ),
+            (
+                "pmap([[1, 2], [3]], fn(pair) { match pair { case [a, b]: ({\"sum\": a + b}), case _: null } })",
+                "[{\"sum\": 3}, null]",
+            ),
+            // the returned closures keep working on this thread
+            (
+                "let adders = pmap([1, 2], fn(x) { fn(y) { x + y } }); adders[1](10)",
+                "12",
+            ),
         ];
 
         for (input, expected) in tests {
             let mut evaluator = Evaluator::new(input);
-            let result = &evaluator.eval_program().unwrap()[0];
-            assert_eq!(result, expected);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
         }
-    }
 
-    #[test]
-    fn eval_array_expression() {
-        let input = r#"
-            let add = fn(x, y) { return x + y; };
-            [1 + 1, add(2, 2)];
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[1];
+        let mut evaluator = Evaluator::new("pmap([1], fn(x) { println(x) })");
+        assert!(matches!(
+            evaluator.eval_program(),
+            Err(EvalError::UnsupportedArgumentType(_))
+        ));
+
+        let mut evaluator = Evaluator::new("pmap([1, 0, 2], fn(x) { 10 / x })");
+        let err = evaluator.eval_program().unwrap_err();
+        // the error of the worker is sent back rather than computed again
+        assert!(matches!(
+            err.root_cause(),
+            EvalError::WorkerFailed("E1007", _)
+        ));
+        assert_eq!(err.code(), "E1007");
         assert_eq!(
-            result,
-            &Object::ArrayValue(vec![Object::IntegerValue(2), Object::IntegerValue(4)])
+            err.to_string(),
+            "Division by zero isn't allowed\n    while mapping the element at index 1 with `pmap`"
         );
-    }
-
-    #[test]
-    fn eval_map_expression() {
-        let input = r#"
-            let foo = { "temp": 1 + 1, "foo": 4 };
-            foo;
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[1];
-        let mut expected = HashMap::new();
-        expected.insert("temp".to_owned(), Object::IntegerValue(2));
-        expected.insert("foo".to_owned(), Object::IntegerValue(4));
-        assert_eq!(result, &Object::MapValue(expected));
-    }
-
-    #[test]
-    fn eval_index_expression() {
-        let input = r#"
-            let a = [100, 200, 300, 400];
-            1 + a[1 + 1];
 
-            let b = { 
-                "foo": 2,
-                "bar": 4
-            };
-            b["foo"];
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = evaluator.eval_program().unwrap();
-        assert_eq!(&result[1], &Object::IntegerValue(301));
-        assert_eq!(&result[3], &Object::IntegerValue(2));
+        // errors raised in functions called by the worker keep their code
+        let mut evaluator =
+            Evaluator::new("let f = fn(x) { len(x) }; pmap([[1], 2], fn(x) { f(x) })");
+        let err = evaluator.eval_program().unwrap_err();
+        assert_eq!(err.code(), "E1005");
+        assert!(
+            err.to_string()
+                .ends_with("\n    while mapping the element at index 1 with `pmap`"),
+            "{err}"
+        );
     }
 
     #[test]
-    fn eval_function_expression() {
-        let input = r#"
-            let foo = fn(x) {
-                let double = fn(y) { y * 2; };
-                return double(x);
-            };
+    fn builtin_is_pure() {
+        let tests = vec![
+            ("is_pure(fn(x) { x * 2 })", true),
+            ("is_pure(fn(x) { println(x) })", false),
+            (
+                "let double = fn(x) { x * 2 }; is_pure(fn(x) { double(x) + 1 })",
+                true,
+            ),
+            (
+                "var total = 0; is_pure(fn(x) { total = total + x; })",
+                false,
+            ),
+            ("is_pure(1)", true),
+        ];
 
-            let bar = foo(3);
-            bar;
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[2];
-        assert_eq!(result, &Object::IntegerValue(6));
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result, Object::BooleanValue(expected), "{input}");
+        }
     }
 
     #[test]
-    fn eval_function_expressions() {
+    fn builtin_version_and_features() {
         let tests = vec![
-            ("let identity = fn(x) { x; }; identity(5);", 5),
-            ("let identity = fn(x) { return x; }; identity(5);", 5),
-            ("let double = fn(x) { x * 2; }; double(5);", 10),
-            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
-            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
-            ("1; fn(x) { x; }(5)", 5),
+            ("version()", format!("{VERSION:?}")),
+            ("features()[\"backend\"]", "\"interpreter\"".to_owned()),
+            ("features()[\"version\"] == version()", "true".to_owned()),
+            ("features()[\"checked_arithmetic\"]", "false".to_owned()),
+            ("type(features()[\"threads\"])", "\"integer\"".to_owned()),
         ];
 
         for (input, expected) in tests {
             let mut evaluator = Evaluator::new(input);
-            let result = &evaluator.eval_program().unwrap()[1];
-            let expected_obj = &Object::IntegerValue(expected);
-            assert_eq!(result, expected_obj);
+            let result = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(result.to_string(), expected, "{input}");
         }
+
+        let mut evaluator = Evaluator::new("features()[\"checked_arithmetic\"]");
+        evaluator.set_checked_arithmetic(true);
+        assert!(evaluator.features().checked_arithmetic);
+        assert_eq!(
+            evaluator.eval_program().unwrap(),
+            vec![Object::BooleanValue(true)]
+        );
+
+        assert!(matches!(
+            Evaluator::new("version(1)").eval_program(),
+            Err(EvalError::FunctionCallWrongArity(0, 1))
+        ));
     }
 
     #[test]
-    fn eval_block_statement() {
-        let input = r#"
-            let a = 2;
+    fn builtin_log() {
+        #[derive(Debug)]
+        struct Collect(Rc<RefCell<Vec<LogRecord>>>);
 
-            {
-                let b = 3;
-                b;
+        impl LogSink for Collect {
+            fn log(&mut self, record: &LogRecord) {
+                self.0.borrow_mut().push(record.clone());
             }
+        }
 
-            a;
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[2];
-        assert_eq!(result, &Object::IntegerValue(2));
+        let records = Rc::new(RefCell::new(vec![]));
+        let mut evaluator = Evaluator::new(
+            r#"log("info", "started"); log("warn", "slow request", {"path": "/users", "ms": 812});"#,
+        );
+        evaluator.set_log_sink(Box::new(Collect(records.clone())));
+        evaluator.eval_program().unwrap();
+
+        assert_eq!(
+            *records.borrow(),
+            vec![
+                LogRecord {
+                    level: LogLevel::Info,
+                    message: "started".to_owned(),
+                    fields: vec![],
+                },
+                LogRecord {
+                    level: LogLevel::Warn,
+                    message: "slow request".to_owned(),
+                    fields: vec![
+                        ("ms".to_owned(), Object::IntegerValue(812)),
+                        ("path".to_owned(), Object::StringValue("/users".to_owned())),
+                    ],
+                },
+            ]
+        );
+
+        let errors = vec![
+            (
+                r#"log("trace", "x")"#,
+                r#"Unsupported argument type for built-in function: `log` only accepts the levels "debug", "info", "warn", "error", got "trace""#,
+            ),
+            (
+                r#"log("info", 1)"#,
+                "Unsupported argument type for built-in function: `log` expects the message as a string",
+            ),
+            (
+                r#"log("info", "x", [1])"#,
+                "Unsupported argument type for built-in function: `log` expects the fields as a hash map",
+            ),
+        ];
+        for (input, expected) in errors {
+            let err = Evaluator::new(input).eval_program().unwrap_err();
+            assert_eq!(err.to_string(), expected, "{input}");
+        }
     }
 
     #[test]
-    fn eval_assign_statement() {
+    fn eval_with_streams() {
+        use crate::streams::SharedBuffer;
+
         let input = r#"
-            let a = 2;
-            a = a + 2;
-            a;
+            var total = 0;
+            var line = read_line();
+            loop {
+                if line == null { break; }
+                total = total + parse_int(line, 10);
+                line = read_line();
+            }
+            print("total: ");
+            println(total);
+            log("info", "done", {"lines": 3});
+            read_line()
         "#;
+        let (stdout, stderr) = (SharedBuffer::default(), SharedBuffer::default());
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap()[2];
-        assert_eq!(result, &Object::IntegerValue(4));
-    }
+        evaluator.set_stdout(stdout.clone());
+        evaluator.set_stderr(stderr.clone());
+        evaluator.set_stdin("1\n2\r\n3".as_bytes());
 
-    #[test]
-    fn eval_static_scope() {
-        let input = r#"
-            let i = 5;
-            let foo = fn(i) {
-                i;
-            };
+        let result = evaluator.eval_program().unwrap().pop();
+        assert_eq!(result, Some(Object::NullValue));
+        assert_eq!(*stdout.0.borrow(), "\"total: \"6\n");
+        assert_eq!(*stderr.0.borrow(), "[info] done lines=3\n");
 
-            foo(10);
-            i;
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
-        assert_eq!(&result[2], &Object::IntegerValue(10));
-        assert_eq!(&result[3], &Object::IntegerValue(5));
+        // failing streams fail the evaluation rather than the process
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut evaluator = Evaluator::new("println(1)");
+        evaluator.set_stdout(Closed);
+        let err = evaluator.eval_program().unwrap_err();
+        assert_eq!(err.code(), "E1041");
+        assert!(err.to_string().starts_with("Input/output error: "), "{err}");
+
+        let err = Evaluator::new("read_line(1)").eval_program().unwrap_err();
+        assert!(matches!(err, EvalError::FunctionCallWrongArity(0, 1)));
     }
 
     #[test]
-    fn eval_closure() {
-        let input = r#"
-            let newAdder = fn(x) {
-                fn(y) { x + y };
-            };
+    fn eval_native_values() {
+        use crate::native::NativeHandle;
 
-            let addTwo = newAdder(2);
-            addTwo(2);
+        struct Connection {
+            url: String,
+        }
+
+        let input = r#"
+            let conn = connect("db://local");
+            type(conn);
+            conn;
+            url(conn);
+            url(1);
         "#;
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
-        assert_eq!(&result[2], &Object::IntegerValue(4));
+        evaluator.register_function("connect", |arguments| match arguments.first() {
+            Some(Object::StringValue(url)) => Ok(Object::NativeValue(NativeHandle::new(
+                "Connection",
+                Connection { url: url.clone() },
+            ))),
+            _ => Err(EvalError::UnsupportedArgumentType(
+                "`connect` needs a url".to_owned(),
+            )),
+        });
+        evaluator.register_function("url", |arguments| match arguments.first() {
+            Some(Object::NativeValue(handle)) => {
+                let conn = handle.downcast_ref::<Connection>()?;
+                Ok(Object::StringValue(conn.url.clone()))
+            }
+            _ => Err(EvalError::UnsupportedArgumentType(
+                "`url` needs a connection".to_owned(),
+            )),
+        });
+
+        let mut statements = evaluator.parser.parse_program().unwrap().0.into_iter();
+        let mut next = || evaluator.eval_located(statements.next().unwrap());
+        next().unwrap();
+        assert_eq!(
+            next().unwrap(),
+            Object::StringValue("Connection".to_owned())
+        );
+        assert_eq!(next().unwrap().to_string(), "<native Connection>");
+        assert_eq!(
+            next().unwrap(),
+            Object::StringValue("db://local".to_owned())
+        );
+        assert!(matches!(next(), Err(EvalError::UnsupportedArgumentType(_))));
+
+        // natives handed back must have the expected Rust type
+        let handle = NativeHandle::new("Number", 1);
+        assert!(handle.downcast_ref::<Connection>().is_err());
+        assert_eq!(handle.downcast_ref::<i32>().unwrap(), &1);
     }
 
     #[test]
-    fn eval_nested_returns() {
-        let input = r#"
-            let add = fn(x, y) { return x + y; };
+    fn eval_native_modules() {
+        use crate::native::NativeFunction;
 
-            let foo = fn() {
-                return add(5 + 5, add(1, 1));
-            };
+        let tests = vec![
+            (
+                r#"fs.read("a.ql")"#,
+                Ok(Object::StringValue("read a.ql".to_owned())),
+            ),
+            (
+                "let read = fs.read; read(\"b\")",
+                Ok(Object::StringValue("read b".to_owned())),
+            ),
+            ("fs.exists()", Ok(Object::BooleanValue(true))),
+            ("fs", Ok(Object::StringValue("module fs".to_owned()))),
+            (
+                "fs.reed",
+                Err("The module `fs` has no member `reed`. Did you mean \"read\"?"),
+            ),
+            (
+                "let x = 1; x.read",
+                Err("Values of type `integer` don't have fields, cannot access `read`"),
+            ),
+        ];
 
-            let faz = fn() {
-                return 20;
-            };
+        for (input, expected) in tests {
+            let mut evaluator = Evaluator::new(input);
+            evaluator.register_module(
+                "fs",
+                [
+                    NativeFunction::new("read", |arguments| {
+                        Ok(Object::StringValue(format!(
+                            "read {}",
+                            arguments[0].as_str()?
+                        )))
+                    }),
+                    NativeFunction::new("exists", |_| Ok(Object::BooleanValue(true))),
+                ],
+            );
 
-            let bar = if foo() == 12 {
-                if foo() == 12 {
-                    return faz();
+            let result = evaluator
+                .eval_program()
+                .map(|mut objects| objects.pop().unwrap())
+                .map_err(|err| err.to_string());
+            let result = match result {
+                Ok(module @ Object::ModuleValue { .. }) => {
+                    Ok(Object::StringValue(module.to_string()))
                 }
-
-                return 100;
-            } else {
-                return -1;
+                result => result,
             };
-
-            bar;
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
-        assert_eq!(&result[4], &Object::IntegerValue(20));
+            assert_eq!(result, expected.map_err(str::to_owned), "{input}");
+        }
     }
 
     #[test]
-    fn eval_function_as_parameter() {
-        let input = r#"
-            let add = fn(x, y) { return x + y; };
-            let sub = fn(x, y) { return x - y; };
-            let applyFunc = fn(a, b, cb) {
-                cb(a, b)
-            };
-            applyFunc(2, 2, add);
-            applyFunc(10, 2, sub);
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
-        assert_eq!(&result[3], &Object::IntegerValue(4));
-        assert_eq!(&result[4], &Object::IntegerValue(8));
-    }
+    fn eval_deprecated_functions() {
+        use crate::native::NativeFunction;
 
-    #[test]
-    fn builtin_len() {
-        let input = r#"
-            len("hello");
-            len("");
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
-        assert_eq!(&result[0], &Object::IntegerValue(5));
-        assert_eq!(&result[1], &Object::IntegerValue(0));
-    }
+        let input = r#"@deprecated("use draw2")
+let draw = fn(x) { x };
+let shadowed = fn() { let draw = fn(x) { x }; draw(0) };
+
+shadowed();
+draw(1);
+draw(2);
+fs.read();
+legacy();"#;
 
-    #[test]
-    fn builtin_append() {
-        let input = r#"
-            append([1, 2, 3], 100, 200);
-        "#;
         let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
+        evaluator.register_native_function(
+            NativeFunction::new("legacy", |_| Ok(Object::NullValue)).deprecated("use modern"),
+        );
+        evaluator.register_module(
+            "fs",
+            [NativeFunction::new("read", |_| Ok(Object::NullValue)).deprecated("use fs.open")],
+        );
+        evaluator.eval_program().unwrap();
+
+        // only the first call of each function is reported, and the shadowing binding isn't deprecated
+        let warnings: Vec<(String, &str)> = evaluator
+            .warnings()
+            .iter()
+            .map(|warning| {
+                (
+                    warning.to_string(),
+                    &input[warning.span().start..warning.span().end],
+                )
+            })
+            .collect();
         assert_eq!(
-            &result[0],
-            &Object::ArrayValue(vec![
-                Object::IntegerValue(1),
-                Object::IntegerValue(2),
-                Object::IntegerValue(3),
-                Object::IntegerValue(100),
-                Object::IntegerValue(200),
-            ])
+            warnings,
+            vec![
+                ("`draw` is deprecated: use draw2".to_owned(), "draw(1)"),
+                (
+                    "`fs.read` is deprecated: use fs.open".to_owned(),
+                    "fs.read()"
+                ),
+                ("`legacy` is deprecated: use modern".to_owned(), "legacy()"),
+            ]
         );
     }
 
     #[test]
-    fn builtin_rest() {
-        let input = r#"
-            rest([1, 2, 3]);
-        "#;
-        let mut evaluator = Evaluator::new(input);
-        let result = &evaluator.eval_program().unwrap();
-        assert_eq!(
-            &result[0],
-            &Object::ArrayValue(vec![Object::IntegerValue(2), Object::IntegerValue(3)])
-        );
+    fn eval_native_finalizers() {
+        use crate::native::NativeHandle;
+        use std::cell::Cell;
+
+        let closed = Rc::new(Cell::new(0));
+        let register = |evaluator: &mut Evaluator| {
+            let closed = closed.clone();
+            evaluator.register_function("open", move |_| {
+                let closed = closed.clone();
+                Ok(Object::NativeValue(NativeHandle::with_finalizer(
+                    "File",
+                    (),
+                    move |_| closed.set(closed.get() + 1),
+                )))
+            });
+        };
+
+        // the value is finalized as soon as nothing refers to it anymore
+        let mut evaluator = Evaluator::new("var f = open(); f = null;");
+        register(&mut evaluator);
+        drop(evaluator.eval_program().unwrap());
+        assert_eq!(closed.get(), 1);
+
+        // or when the evaluator is dropped
+        let mut evaluator = Evaluator::new("var f = open(); let g = f; f = null;");
+        register(&mut evaluator);
+        drop(evaluator.eval_program().unwrap());
+        assert_eq!(closed.get(), 1);
+        drop(evaluator);
+        assert_eq!(closed.get(), 2);
+
+        // even if it's captured by a closure stored in the environment it captured
+        let mut evaluator = Evaluator::new("let f = open(); let read = fn() { f }; read();");
+        register(&mut evaluator);
+        drop(evaluator.eval_program().unwrap());
+        assert_eq!(closed.get(), 2);
+        drop(evaluator);
+        assert_eq!(closed.get(), 3);
     }
 
     #[test]
@@ -0,0 +1,142 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::object::{Closure, Task};
+
+/// Outcome of `Evaluator::poll`, telling the host whether it should keep polling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poll {
+    /// Number of callbacks that ran.
+    pub callbacks: usize,
+    /// Number of timers and `when_done` callbacks still waiting.
+    pub pending: usize,
+    /// When the earliest waiting timer is due, for hosts that sleep until then.
+    pub next_deadline: Option<Instant>,
+}
+
+/// Lets another thread interrupt an evaluation, which fails with `EvalError::Interrupted` at its next yield point.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether an interruption was requested since the last call.
+    pub(crate) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Callback scheduled by `set_timeout` or `set_interval`.
+#[derive(Debug)]
+struct Timer {
+    id: usize,
+    due: Instant,
+    /// Delay between the calls of timers started by `set_interval`.
+    period: Option<Duration>,
+    callback: Closure,
+}
+
+/// Callback waiting for a task to finish, scheduled by `when_done`.
+#[derive(Debug)]
+struct Continuation {
+    task: Task,
+    callback: Closure,
+}
+
+/// Callbacks scheduled by the script, which only run when the host polls the evaluator.
+#[derive(Debug, Default)]
+pub(crate) struct EventLoop {
+    next_id: usize,
+    timers: Vec<Timer>,
+    continuations: Vec<Continuation>,
+}
+
+impl EventLoop {
+    /// Schedules `callback` to be called once `delay` has elapsed, then every `period` if there's one.
+    /// Returns the id of the timer, which cancels it.
+    pub fn schedule(
+        &mut self,
+        delay: Duration,
+        period: Option<Duration>,
+        callback: Closure,
+    ) -> usize {
+        self.next_id += 1;
+        self.timers.push(Timer {
+            id: self.next_id,
+            due: Instant::now() + delay,
+            period,
+            callback,
+        });
+
+        self.next_id
+    }
+
+    /// Stops the timer with the given id, returning whether it was still scheduled.
+    pub fn cancel(&mut self, id: usize) -> bool {
+        let count = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() < count
+    }
+
+    /// Schedules `callback` to be called with the result of `task` once it's finished.
+    pub fn when_done(&mut self, task: Task, callback: Closure) {
+        self.continuations.push(Continuation { task, callback });
+    }
+
+    /// Ids of the timers due at `now`, in the order of their deadlines.
+    pub fn due(&self, now: Instant) -> Vec<usize> {
+        let mut due: Vec<&Timer> = self
+            .timers
+            .iter()
+            .filter(|timer| timer.due <= now)
+            .collect();
+        due.sort_by_key(|timer| (timer.due, timer.id));
+        due.into_iter().map(|timer| timer.id).collect()
+    }
+
+    /// Takes the callback of a due timer, unless it was cancelled in the meantime.
+    /// Repeating timers are scheduled again a period after their deadline, or on the next poll if they fell behind.
+    pub fn fire(&mut self, id: usize, now: Instant) -> Option<Closure> {
+        let index = self.timers.iter().position(|timer| timer.id == id)?;
+        let timer = &mut self.timers[index];
+        match timer.period {
+            Some(period) => {
+                timer.due = (timer.due + period).max(now);
+                Some(timer.callback.clone())
+            }
+            None => Some(self.timers.remove(index).callback),
+        }
+    }
+
+    /// Takes the oldest continuation whose task is finished.
+    pub fn finished_task(&mut self) -> Option<(Task, Closure)> {
+        let index = self
+            .continuations
+            .iter()
+            .position(|continuation| continuation.task.is_finished())?;
+        let Continuation { task, callback } = self.continuations.remove(index);
+
+        Some((task, callback))
+    }
+
+    /// Whether some callbacks wait for tasks, which are checked for completion on every poll.
+    pub fn waits_for_tasks(&self) -> bool {
+        !self.continuations.is_empty()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.timers.len() + self.continuations.len()
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.due).min()
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::{self, Debug},
+    rc::Rc,
+};
+
+use crate::object::Object;
+
+/// High-level step of an evaluation, reported to the event sink of the evaluator.
+/// Unlike the hooks of `EvalObserver`, events only describe the program-level statements,
+/// which is enough to audit what a script did or to replay its session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalEvent {
+    /// The evaluation of a program with `statements` top-level statements started.
+    ProgramStarted { statements: usize },
+    /// A top-level statement was evaluated, with its position in the program and its value.
+    StatementEvaluated {
+        index: usize,
+        statement: String,
+        value: Object,
+    },
+    /// A top-level `let` or `var` statement bound a function, whose rest parameter starts with `...`.
+    FunctionDefined {
+        name: String,
+        parameters: Vec<String>,
+    },
+    /// The host registered a module of native functions with `Evaluator::register_module`.
+    ModuleLoaded { name: String, members: Vec<String> },
+    /// The evaluation failed, while parsing, evaluating a statement or running a callback.
+    ErrorRaised { code: &'static str, message: String },
+}
+
+impl fmt::Display for EvalEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalEvent::ProgramStarted { statements } => {
+                write!(f, "program started ({statements} statements)")
+            }
+            EvalEvent::StatementEvaluated {
+                index,
+                statement,
+                value,
+            } => write!(f, "statement {index} evaluated: {statement} => {value}"),
+            EvalEvent::FunctionDefined { name, parameters } => {
+                write!(f, "function defined: {name}({})", parameters.join(", "))
+            }
+            EvalEvent::ModuleLoaded { name, members } => {
+                write!(f, "module loaded: {name} [{}]", members.join(", "))
+            }
+            EvalEvent::ErrorRaised { code, message } => write!(f, "error raised: {code} {message}"),
+        }
+    }
+}
+
+/// Receives the events of an evaluation as they happen.
+pub trait EventSink: Debug {
+    fn event(&mut self, event: &EvalEvent);
+}
+
+/// Sink keeping the events until the host iterates over them, sharing them with its clones:
+/// install a clone in the evaluator, and `drain` the original between evaluations.
+#[derive(Debug, Clone, Default)]
+pub struct EventQueue(Rc<RefCell<VecDeque<EvalEvent>>>);
+
+impl EventQueue {
+    /// Removes the events received so far, in the order they happened.
+    pub fn drain(&self) -> impl Iterator<Item = EvalEvent> {
+        std::mem::take(&mut *self.0.borrow_mut()).into_iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+}
+
+impl EventSink for EventQueue {
+    fn event(&mut self, event: &EvalEvent) {
+        self.0.borrow_mut().push_back(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drain_event_queue() {
+        let queue = EventQueue::default();
+        let mut sink = queue.clone();
+        sink.event(&EvalEvent::ProgramStarted { statements: 2 });
+        sink.event(&EvalEvent::FunctionDefined {
+            name: "add".to_owned(),
+            parameters: vec!["x".to_owned(), "...rest".to_owned()],
+        });
+
+        assert_eq!(queue.len(), 2);
+        let events: Vec<String> = queue.drain().map(|event| event.to_string()).collect();
+        assert_eq!(
+            events,
+            vec![
+                "program started (2 statements)",
+                "function defined: add(x, ...rest)"
+            ]
+        );
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,772 @@
+/// Every error code, along with the long description printed by `qalo explain <code>`.
+/// Codes starting with `E0` are reported by the parser, those starting with `E1` by the evaluator.
+pub const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        r#"A token appeared where the parser expected something else.
+
+    let = 5;
+
+`let` must be followed by the name of the identifier it binds:
+
+    let x = 5;
+"#,
+    ),
+    (
+        "E0002",
+        r#"The program isn't valid Qalo syntax, for example a construct was left incomplete
+or used where it isn't allowed. The message describes what was expected.
+
+    match x { 1 2 }
+
+Each arm of a `match` needs a `=>` followed by its value:
+
+    match x { 1 => "one", _ => "other" }
+"#,
+    ),
+    (
+        "E0003",
+        r#"An operator was applied to a token it can't operate on.
+
+    let x = -;
+
+Prefix operators like `-` and `!` must be followed by an expression:
+
+    let x = -1;
+"#,
+    ),
+    (
+        "E0004",
+        r#"A number literal contains digits that aren't valid for its base, has no digits at all,
+or doesn't fit in a 64 bit integer.
+
+    let mask = 0xFG;
+    let bits = 0b;
+
+Hexadecimal literals only use the digits `0-9` and `a-f`, octal ones `0-7` and binary ones `0-1`:
+
+    let mask = 0xFF;
+    let bits = 0b0;
+"#,
+    ),
+    (
+        "E0005",
+        r#"An integer literal couldn't be parsed as a 64 bit integer.
+
+Integers range from -9223372036854775808 to 9223372036854775807: use a float for larger values.
+
+    let big = 10000000000000000000.0;
+"#,
+    ),
+    (
+        "E0006",
+        r#"A float literal couldn't be parsed as a 64 bit float.
+
+Floats are written with digits on both sides of the decimal point:
+
+    let half = 0.5;
+"#,
+    ),
+    (
+        "E0007",
+        r#"An integer couldn't be converted to the size needed by an operation,
+for example a negative number was used as an index.
+
+    let xs = [1, 2, 3];
+    xs[-1];
+
+Indices start from 0:
+
+    xs[len(xs) - 1];
+"#,
+    ),
+    (
+        "E0008",
+        r#"A block comment was opened with `/*` but never closed.
+
+    /* the rest of the file is a comment
+
+Close block comments with `*/`:
+
+    /* a comment */
+"#,
+    ),
+    (
+        "E0009",
+        r#"Expressions are nested too deeply for the parser, which protects itself from running out of stack.
+
+    ((((((((((((((((1))))))))))))))))...
+
+Split the expression with intermediate `let` bindings.
+"#,
+    ),
+    (
+        "E0010",
+        r#"An identifier looks like a misspelled keyword.
+
+    lett x = 5;
+
+Use the keyword suggested by the message:
+
+    let x = 5;
+"#,
+    ),
+    (
+        "E0011",
+        r#"A placeholder of a template rendered with `render_template` was opened with `{{`
+but never closed.
+
+    Hello {{ name !
+
+Close placeholders with `}}`:
+
+    Hello {{ name }}!
+"#,
+    ),
+    (
+        "E0012",
+        r#"A statement was used where only a single expression is allowed,
+for example in a formula evaluated with `eval_expression_str`.
+
+    let total = price * quantity; total
+
+Write the expression alone:
+
+    price * quantity
+
+Blocks and the bodies of `if` and `match` can't hold statements either, and loops
+aren't allowed, while the bodies of functions can hold any statement.
+"#,
+    ),
+    (
+        "E0013",
+        r#"`break` or `continue` was used outside of a loop. Functions are a boundary too:
+a function defined inside a loop can't exit it.
+
+    for x in items {
+        let stop = fn() { break; };
+    }
+
+Return a value from the function instead, and exit the loop where it's called:
+
+    for x in items {
+        let should_stop = fn() { true };
+        if should_stop() { break; }
+    }
+"#,
+    ),
+    (
+        "E0014",
+        r#"`break` or `continue` refers to a label that isn't defined by any of the loops enclosing it.
+
+    'outer: for row in rows {
+        for cell in row {
+            if cell == 0 { break 'outter; }
+        }
+    }
+
+Labels are written before `for` and `loop`, like `'outer: for`, and are visible in the body
+of their loop, except from the functions defined in it. Check the spelling of the label.
+"#,
+    ),
+    (
+        "E0015",
+        r#"A `switch` has no default arm. Its last arm must be `_`, which runs when the value
+isn't equal to any of the other arms:
+
+    switch code {
+        200 => "ok",
+        404 => "not found",
+        _ => "unexpected",
+    }
+
+Use an empty block, like `_ => {}`, when nothing has to be done.
+"#,
+    ),
+    (
+        "E1001",
+        r#"An identifier was used without being bound first.
+
+    println(y);
+
+Bind identifiers with `let` or `var` before using them:
+
+    let y = 1;
+    println(y);
+"#,
+    ),
+    (
+        "E1002",
+        r#"A function was called, but no function with that name exists.
+
+    let double = fn(x) { x * 2 };
+    doubel(2);
+
+Check the spelling of the function, or bind it before calling it.
+"#,
+    ),
+    (
+        "E1003",
+        r#"A value doesn't have the type an operation or a function requires.
+
+    if 1 { "one" }
+
+`if` conditions must be booleans:
+
+    if 1 == 1 { "one" }
+"#,
+    ),
+    (
+        "E1004",
+        r#"An operator was applied to values that don't support it.
+
+    true + 1;
+    "a" * 2;
+
+Check the types of the operands, for example with `type(value)`.
+"#,
+    ),
+    (
+        "E1005",
+        r#"A built-in function received an argument of a type it doesn't support.
+
+    len(5);
+
+`len` only retrieves the length of strings, arrays and ranges:
+
+    len("five");
+"#,
+    ),
+    (
+        "E1006",
+        r#"A function was called with the wrong number of arguments.
+
+    let add = fn(a, b) { a + b };
+    add(1);
+
+Pass one argument for each parameter:
+
+    add(1, 2);
+"#,
+    ),
+    (
+        "E1007",
+        r#"An integer was divided by zero.
+
+    let ratio = total / count;
+
+Check the divisor first:
+
+    let ratio = if count == 0 { 0 } else { total / count };
+"#,
+    ),
+    (
+        "E1008",
+        r#"The modulo of an integer by zero was computed.
+
+    let parity = n % 0;
+
+Check the divisor first, as for divisions.
+"#,
+    ),
+    (
+        "E1009",
+        r#"An integer operation overflowed, and checked arithmetic is enabled (`--checked`).
+
+    9223372036854775807 + 1;
+
+Without checked arithmetic the result wraps around. Use floats for values
+outside the range of 64 bit integers.
+"#,
+    ),
+    (
+        "E1010",
+        r#"A `return` statement was used outside of a function.
+
+    return 5;
+
+`return` can only be used in function bodies:
+
+    let five = fn() { return 5; };
+
+The same goes for `?`, which returns `Err` and `None` values from the
+enclosing function.
+"#,
+    ),
+    (
+        "E1011",
+        r#"The index operator was used on a value that can't be indexed.
+
+    let n = 5;
+    n[0];
+
+Only arrays, strings and maps can be indexed.
+"#,
+    ),
+    (
+        "E1012",
+        r#"A value was indexed with an index of the wrong type.
+
+    [1, 2, 3]["first"];
+
+Arrays and strings are indexed with integers or ranges, maps with strings:
+
+    [1, 2, 3][0];
+    {"first": 1}["first"];
+"#,
+    ),
+    (
+        "E1013",
+        r#"An index is past the end of an array or a string.
+
+    let xs = [1, 2, 3];
+    xs[3];
+
+Indices start from 0, so the last element is at `len(xs) - 1`.
+"#,
+    ),
+    (
+        "E1014",
+        r#"A map doesn't contain the requested key.
+
+    let user = {"name": "Ada"};
+    user["nmae"];
+
+The message suggests the closest keys that exist.
+"#,
+    ),
+    (
+        "E1015",
+        r#"A module registered by the host doesn't have the requested member.
+
+    fs.reed("notes.txt");
+
+The message suggests the closest members that exist.
+"#,
+    ),
+    (
+        "E1016",
+        r#"A field was accessed with `.` on a value that doesn't have fields.
+
+    let n = 5;
+    n.value;
+
+Only modules registered by the host, structs, enums and hash maps have fields.
+"#,
+    ),
+    (
+        "E1017",
+        r#"No arm of a `match` expression matched the value.
+
+    match 3 { 1 => "one", 2 => "two" }
+
+Add a catch-all `_` arm:
+
+    match 3 { 1 => "one", 2 => "two", _ => "many" }
+"#,
+    ),
+    (
+        "E1018",
+        r#"An identifier declared with `let` was re-assigned.
+
+    let count = 0;
+    count = 1;
+
+Declare it with `var` to make it mutable:
+
+    var count = 0;
+    count = 1;
+"#,
+    ),
+    (
+        "E1019",
+        r#"An identifier was re-assigned after being frozen with `freeze`.
+
+    var config = {"debug": true};
+    freeze(config);
+    config = {};
+
+Frozen identifiers can't be re-assigned, even if declared with `var`.
+"#,
+    ),
+    (
+        "E1020",
+        r#"An integer was raised to a negative power, whose result is a fraction.
+
+    2 ** -1;
+
+Use a float base to get a float result:
+
+    2.0 ** -1;
+"#,
+    ),
+    (
+        "E1021",
+        r#"An integer was shifted by a negative number of bits, or by more bits than it has.
+
+    1 << 64;
+    1 >> -1;
+
+Integers have 64 bits, so they can be shifted by 0 to 63 bits.
+"#,
+    ),
+    (
+        "E1022",
+        r#"A sandboxed expression referenced an identifier that the host didn't allow.
+
+    // with a sandbox allowing only `price` and `len`
+    println(price);
+
+Hosts evaluating expressions written by users list the identifiers and built-in
+functions they may use. Identifiers bound by the expression itself, like function
+parameters, are always allowed.
+"#,
+    ),
+    (
+        "E1023",
+        r#"A function with a rest parameter was called without enough arguments
+for the parameters before it.
+
+    let log = fn(level, ...messages) { messages };
+    log();
+
+The rest parameter can collect any number of arguments, even none,
+but every other parameter needs one:
+
+    log("info");
+    log("info", "started", "listening");
+"#,
+    ),
+    (
+        "E1024",
+        r#"A named argument doesn't match any parameter of the function.
+
+    let draw = fn(x, y) { [x, y] };
+    draw(x: 1, z: 2);
+
+Use the names of the parameters, or pass the argument by position:
+
+    draw(x: 1, y: 2);
+
+Built-in functions don't have parameter names, so they only take positional arguments.
+"#,
+    ),
+    (
+        "E1025",
+        r#"A parameter received an argument both by position and by name, or was named twice.
+
+    let draw = fn(x, y) { [x, y] };
+    draw(1, x: 2);
+
+Positional arguments fill the first parameters, so name only the following ones:
+
+    draw(1, y: 2);
+"#,
+    ),
+    (
+        "E1026",
+        r#"A function called with named arguments didn't get an argument for every parameter.
+
+    let draw = fn(x, y) { [x, y] };
+    draw(y: 2);
+
+Pass an argument for each parameter, either by position or by name:
+
+    draw(x: 1, y: 2);
+"#,
+    ),
+    (
+        "E1027",
+        r#"An array was destructured into a different number of bindings than it has elements.
+
+    let [x, y] = [1, 2, 3];
+
+Bind every element, or collect the remaining ones with a rest pattern:
+
+    let [x, y, rest...] = [1, 2, 3];
+    let [x, y, _] = [1, 2, 3];
+"#,
+    ),
+    (
+        "E1028",
+        r#"An array destructured with a rest pattern has fewer elements than the bindings before the rest.
+
+    let [first, second, rest...] = [1];
+
+The rest pattern can be empty, but every other binding needs an element.
+"#,
+    ),
+    (
+        "E1029",
+        r#"A value was destructured with a pattern of another type: an array pattern `[...]` needs an array,
+and a hash map pattern `{...}` needs a hash map.
+
+    let [x, y] = "xy";
+
+Convert the value to an array first:
+
+    let [x, y] = chars("xy");
+"#,
+    ),
+    (
+        "E1030",
+        r#"A value was built with the syntax of a struct, but its name isn't bound to a struct declaration.
+
+    let Point = 1;
+    let p = Point { x: 1 };
+
+Declare the struct before building it:
+
+    struct Point { x, y }
+    let p = Point { x: 1, y: 2 };
+"#,
+    ),
+    (
+        "E1031",
+        r#"A field that isn't part of the declaration of a struct was given a value or read.
+
+    struct Point { x, y }
+    let p = Point { x: 1, y: 2, z: 3 };
+    p.z;
+
+Only the fields listed in the declaration can be used; add the field to the declaration if it's needed.
+"#,
+    ),
+    (
+        "E1032",
+        r#"A struct was built without a value for one of its fields.
+
+    struct Point { x, y }
+    let p = Point { x: 1 };
+
+Every field must be given a value, use `null` for the ones that are missing:
+
+    let p = Point { x: 1, y: null };
+"#,
+    ),
+    (
+        "E1033",
+        r#"A variant that isn't part of the declaration of an enum was used, to build a value or in a pattern.
+
+    enum Color { Red, Green }
+    let c = Color.Blue;
+
+Only the variants listed in the declaration exist; add the variant to the declaration if it's needed.
+"#,
+    ),
+    (
+        "E1034",
+        r#"A pattern like `Name.Variant` refers to a name that isn't bound to an enum declaration.
+
+    let Color = 1;
+    match c { case Color.Red: 1, case _: 0 }
+
+Declare the enum before matching its variants:
+
+    enum Color { Red, Green }
+"#,
+    ),
+    (
+        "E1035",
+        r#"A pattern matching a variant of an enum has a different number of fields than the variant.
+
+    enum Shape { Rect(w, h) }
+    match s { case Shape.Rect(w): w, case _: 0 }
+
+Give a pattern to every field, using `_` for the ones that don't matter:
+
+    match s { case Shape.Rect(w, _): w, case _: 0 }
+"#,
+    ),
+    (
+        "E1036",
+        r#"A method was called on a value whose type doesn't have it.
+
+    [3, 1, 2].upper()
+
+Methods are the built-in functions taking the value as first argument, like `len` for
+strings, arrays, maps and ranges, or `upper` and `lower` for strings:
+
+    "abc".upper()
+"#,
+    ),
+    (
+        "E1037",
+        r#"A task started by `spawn` failed, and it was joined with `join` or `wait_all`.
+
+    let task = spawn(fn() { 1 / 0 });
+    join(task)
+
+The error of the task is reported when it's joined, along with the number of the task.
+`wait_all` waits for every task to finish, and reports the first task that failed
+in the order of the array.
+"#,
+    ),
+    (
+        "E1038",
+        r#"The handler of an actor started by `actor` failed, while handling a message sent with `tell` or `ask`.
+
+    let ratio = actor(fn(total, n) { total / n }, 100);
+    ask(ratio, 0)
+
+The actor stops handling messages once its handler fails: the error is reported by `ask`,
+and by every later `tell` or `ask` sent to it.
+"#,
+    ),
+    (
+        "E1039",
+        r#"The host interrupted the evaluation, e.g. because the user pressed Ctrl-C.
+
+    loop {}
+
+Evaluations are interrupted at their next yield point: at the end of an iteration
+of a loop, or when `yield_now()` is called.
+"#,
+    ),
+    (
+        "E1040",
+        r#"The evaluation went past the deadline set by the host, which bounds the time
+spent by a script.
+
+    loop {}
+
+The deadline is checked at the yield points of the evaluation: at the end of an iteration
+of a loop, or when `yield_now()` is called. Recursive functions without loops aren't stopped.
+"#,
+    ),
+    (
+        "E1041",
+        r#"Reading from or writing to a stream of the evaluator failed.
+
+    println("hello");
+
+This happens when the standard output is closed, or when a stream installed
+by the host with `Evaluator::set_stdout`, `set_stderr` or `set_stdin` fails.
+"#,
+    ),
+    (
+        "E1042",
+        r#"The two values given to `assert_eq` aren't equal.
+
+    assert_eq({ "a": [1, 2] }, { "a": [1, 3] })
+
+The error lists where the values differ, like `diff` does: here `value["a"][1]`,
+which is `2` on the left and `3` on the right.
+"#,
+    ),
+    (
+        "E1043",
+        r#"A run replayed from an input log read its inputs differently from the recorded run.
+
+    let name = read_line();
+
+Replaying gives the program the lines `read_line` read and the results of the native
+functions it called, in the order they were recorded. The program must read them
+in the same order, which fails if it or its native functions changed since then.
+Results the log couldn't save, like native handles, can't be replayed either.
+"#,
+    ),
+    (
+        "E1044",
+        r#"A unary operator was applied to a value of a type it doesn't work on.
+
+    let name = "Ada";
+    -name
+
+`-` and `+` work on integers and floats, `~` inverts the bits of integers, and `!`
+negates booleans. The error points to the operand, and names the type of its value.
+"#,
+    ),
+    (
+        "E1045",
+        r#"A slice starts after its end.
+
+    let xs = [1, 2, 3];
+    xs[2..1];
+
+The start of the range is included and its end isn't, unless written with `..=`:
+`xs[1..2]` is `[2]`, and `xs[1..1]` is empty.
+"#,
+    ),
+];
+
+/// Returns the description of an error code, like `E1003`.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluator::Evaluator, parser::Parser};
+
+    #[test]
+    fn explain_codes() {
+        assert!(explain("E1003")
+            .unwrap()
+            .starts_with("A value doesn't have the type"));
+        assert_eq!(explain("e1003"), explain("E1003"));
+        assert_eq!(explain("E9999"), None);
+
+        let mut codes = EXPLANATIONS
+            .iter()
+            .map(|(code, _)| code)
+            .collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), EXPLANATIONS.len());
+    }
+
+    #[test]
+    fn explain_reported_errors() {
+        let tests = vec![
+            ("let = 5;", "E0001"),
+            ("lett x = 5;", "E0010"),
+            ("break;", "E0013"),
+            ("loop { break 'a; }", "E0014"),
+            ("switch 1 { 1 => 2 }", "E0015"),
+            ("0xFG", "E0004"),
+            ("/* open", "E0008"),
+            ("y", "E1001"),
+            ("if 1 { 2 }", "E1003"),
+            ("len(1, 2)", "E1006"),
+            ("fn(a, ...b) { a }()", "E1023"),
+            ("fn(a) { a }(b: 1)", "E1024"),
+            ("fn(a) { a }(1, a: 2)", "E1025"),
+            ("fn(a, b) { a }(a: 1)", "E1026"),
+            ("let [a, b] = [1];", "E1027"),
+            ("let [a, b, c...] = [1];", "E1028"),
+            ("let [a] = 1;", "E1029"),
+            ("let P = 1; P {}", "E1030"),
+            ("struct P { x } P { x: 1 }.y", "E1031"),
+            ("struct P { x } P {}", "E1032"),
+            ("enum E { A } E.B", "E1033"),
+            ("let E = 1; match 1 { case E.A: 1, case _: 0 }", "E1034"),
+            (
+                "enum E { A(x) } match 1 { case E.A: 1, case _: 0 }",
+                "E1035",
+            ),
+            ("[1].upper()", "E1036"),
+            ("join(spawn(fn() { 1 / 0 }))", "E1037"),
+            ("let a = actor(fn(s, m) { s / m }, 1); ask(a, 0)", "E1038"),
+            ("1 / 0", "E1007"),
+            ("[1][3]", "E1013"),
+            ("[1, 2][2..1]", "E1045"),
+            ("let x = 1; x = 2;", "E1018"),
+            // context frames and parsing errors take the code of the wrapped error
+            ("println(1 / 0)", "E1007"),
+        ];
+
+        for (input, expected) in tests {
+            let code = match Parser::new(input).parse_program() {
+                Err(err) => err.code(),
+                Ok(_) => Evaluator::new(input).eval_program().unwrap_err().code(),
+            };
+            assert_eq!(code, expected, "{input}");
+            assert!(explain(code).is_some(), "{code}");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::{fmt, num::NonZeroUsize, thread};
+
+use crate::{object::Object, VERSION};
+
+/// Implementation running the scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Evaluates the parse tree directly.
+    Interpreter,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Interpreter => write!(f, "interpreter"),
+        }
+    }
+}
+
+/// Capabilities of an evaluator, which scripts get from the `features` built-in function
+/// and hosts from `Evaluator::features`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Features {
+    /// Version of the language, which is the version of the crate.
+    pub version: &'static str,
+    pub backend: Backend,
+    /// Whether integer overflows are reported as errors, see `Evaluator::set_checked_arithmetic`.
+    pub checked_arithmetic: bool,
+    /// Whether conditions accept any value, see `Evaluator::set_truthy_conditions`.
+    pub truthy_conditions: bool,
+    /// Maximum number of threads `pmap` spreads its work on.
+    pub threads: usize,
+}
+
+impl Features {
+    pub fn new(checked_arithmetic: bool, truthy_conditions: bool) -> Self {
+        Features {
+            version: VERSION,
+            backend: Backend::Interpreter,
+            checked_arithmetic,
+            truthy_conditions,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    /// Hash map returned to scripts, with a key for every field.
+    pub fn to_object(&self) -> Object {
+        let threads = i64::try_from(self.threads).unwrap_or(i64::MAX);
+
+        Object::MapValue(
+            [
+                ("version", Object::StringValue(self.version.to_owned())),
+                ("backend", Object::StringValue(self.backend.to_string())),
+                (
+                    "checked_arithmetic",
+                    Object::BooleanValue(self.checked_arithmetic),
+                ),
+                (
+                    "truthy_conditions",
+                    Object::BooleanValue(self.truthy_conditions),
+                ),
+                ("threads", Object::IntegerValue(threads)),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_object() {
+        let features = Features {
+            version: "1.2.3",
+            backend: Backend::Interpreter,
+            checked_arithmetic: true,
+            truthy_conditions: false,
+            threads: 4,
+        };
+
+        assert_eq!(
+            features.to_object().to_string(),
+            r#"{"backend": "interpreter", "checked_arithmetic": true, "threads": 4, "truthy_conditions": false, "version": "1.2.3"}"#
+        );
+        assert_eq!(Features::new(false, false).version, VERSION);
+        assert!(Features::new(false, false).threads >= 1);
+    }
+}
@@ -0,0 +1,594 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    ops::Range,
+};
+
+use crate::{
+    ast::{format_parameters, Expression, MatchArm, ParserError, Program, Statement, SwitchArm},
+    cst::SyntaxTree,
+    token::{Span, TokenKind},
+};
+
+/// Width of an indentation level.
+const INDENT: &str = "  ";
+
+/// Formats a program in the canonical style: one statement per line, blocks indented by two spaces,
+/// map entries sorted by key, and parentheses only where the source grouped expressions.
+/// Comments aren't part of the parse tree, so they are dropped: `format_source` keeps them.
+pub fn format_program(program: &Program) -> String {
+    let mut formatter = Formatter::default();
+    formatter.program(program);
+
+    formatter.output
+}
+
+/// Formats a source like `format_program`, keeping its comments. Comments on their own lines stay
+/// before the statement following them, and the ones in the middle of a statement are moved after it.
+pub fn format_source(source: &str) -> Result<String, ParserError> {
+    let tree = SyntaxTree::parse(source)?;
+    let mut formatter = Formatter {
+        source,
+        spans: tree.spans.iter().copied().collect(),
+        comments: tree
+            .comments()
+            .map(|comment| (comment.span.start, comment.span))
+            .collect(),
+        ..Formatter::default()
+    };
+    formatter.program(&tree.program);
+
+    Ok(formatter.output)
+}
+
+#[derive(Debug, Default)]
+struct Formatter<'a> {
+    output: String,
+    depth: usize,
+    /// Source of the program, when its comments are kept.
+    source: &'a str,
+    /// Spans of the statements and blocks left to format, in the order `Parser::spans` records them.
+    spans: VecDeque<Span>,
+    /// Spans of the comments left to write, by their start.
+    comments: BTreeMap<usize, Span>,
+    /// Start of the innermost block being formatted, before which comments belong to outer statements.
+    scope: usize,
+}
+
+impl Formatter<'_> {
+    fn indent(&mut self) {
+        self.output.push_str(&INDENT.repeat(self.depth));
+    }
+
+    fn program(&mut self, program: &Program) {
+        for located in &program.0 {
+            self.item(&located.statement, true);
+            self.output.push('\n');
+        }
+        self.own_line_comments(0..usize::MAX);
+    }
+
+    /// Writes a statement of a program or block on its own line, with the comments attached to it.
+    fn item(&mut self, statement: &Statement, terminated: bool) {
+        let span = self.spans.pop_front().unwrap_or_default();
+        self.own_line_comments(self.scope..span.start);
+
+        self.indent();
+        self.statement(statement, terminated);
+
+        // comments within the statement, which weren't written with the blocks nested in it,
+        // or after it on the same line
+        let mut trailing = self.take_comments(span.start..span.end);
+        if let Some((_, comment)) = self.comments.range(span.end..).next() {
+            if !self.source[span.end..comment.start].contains('\n') {
+                trailing.extend(self.take_comments(comment.start..comment.end));
+            }
+        }
+        for comment in trailing {
+            write!(self.output, " {}", &self.source[comment.start..comment.end]).unwrap();
+        }
+    }
+
+    /// Writes the comments starting in `range` on their own lines.
+    fn own_line_comments(&mut self, range: Range<usize>) {
+        for comment in self.take_comments(range) {
+            self.indent();
+            self.output
+                .push_str(&self.source[comment.start..comment.end]);
+            self.output.push('\n');
+        }
+    }
+
+    fn take_comments(&mut self, range: Range<usize>) -> Vec<Span> {
+        let starts: Vec<usize> = self
+            .comments
+            .range(range)
+            .map(|(start, _)| *start)
+            .collect();
+        starts
+            .iter()
+            .filter_map(|start| self.comments.remove(start))
+            .collect()
+    }
+
+    /// Writes a statement, without indenting it. Expression statements get a semicolon if `terminated`,
+    /// which is needed between statements: `f\n(x)` would be parsed as a call.
+    fn statement(&mut self, statement: &Statement, terminated: bool) {
+        match statement {
+            Statement::VarStatement {
+                kind,
+                name,
+                value,
+                deprecated,
+            } => {
+                if let Some(note) = deprecated {
+                    // writing into a `String` can't fail
+                    writeln!(self.output, "@deprecated({note:?})").unwrap();
+                    self.indent();
+                }
+                write!(self.output, "{kind} {name} = ").unwrap();
+                self.expression(value);
+                self.output.push(';');
+            }
+            Statement::DestructureStatement {
+                kind,
+                pattern,
+                value,
+            } => {
+                write!(self.output, "{kind} {pattern} = ").unwrap();
+                self.expression(value);
+                self.output.push(';');
+            }
+            Statement::ReturnStatement(value) | Statement::BreakStatement { value, .. } => {
+                match statement {
+                    Statement::BreakStatement {
+                        label: Some(label), ..
+                    } => write!(self.output, "break '{label}").unwrap(),
+                    Statement::BreakStatement { .. } => self.output.push_str("break"),
+                    _ => self.output.push_str("return"),
+                }
+                if let Some(value) = value {
+                    self.output.push(' ');
+                    self.expression(value);
+                }
+                self.output.push(';');
+            }
+            Statement::ContinueStatement(label) => match label {
+                Some(label) => write!(self.output, "continue '{label};").unwrap(),
+                None => self.output.push_str("continue;"),
+            },
+            Statement::AssignStatement { name, value } => {
+                write!(self.output, "{name} = ").unwrap();
+                self.expression(value);
+                self.output.push(';');
+            }
+            Statement::ExpressionStatement(expression) => {
+                self.expression(expression);
+                if terminated {
+                    self.output.push(';');
+                }
+            }
+            Statement::BlockStatement(_) => self.block(statement),
+            Statement::ForStatement {
+                label,
+                variable,
+                iterable,
+                body,
+            } => {
+                self.label(label);
+                write!(self.output, "for {variable} in ").unwrap();
+                self.expression(iterable);
+                self.output.push(' ');
+                self.block(body);
+            }
+            Statement::DoWhileStatement {
+                label,
+                body,
+                condition,
+            } => {
+                self.label(label);
+                self.output.push_str("do ");
+                self.block(body);
+                self.output.push_str(" while ");
+                self.expression(condition);
+                self.output.push(';');
+            }
+            Statement::StructStatement { .. } | Statement::EnumStatement { .. } => {
+                write!(self.output, "{statement}").unwrap()
+            }
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                self.output.push_str("switch ");
+                self.expression(value);
+                self.output.push_str(" {\n");
+                self.depth += 1;
+                for SwitchArm { case, body } in arms {
+                    self.indent();
+                    self.expression(case);
+                    self.output.push_str(" => ");
+                    self.arm_body(body);
+                }
+                self.indent();
+                self.output.push_str("_ => ");
+                self.arm_body(default);
+                self.depth -= 1;
+                self.indent();
+                self.output.push('}');
+            }
+        }
+    }
+
+    /// Writes the body of a `match` or `switch` arm, followed by a comma ending its line.
+    fn arm_body(&mut self, body: &Statement) {
+        match body {
+            Statement::ExpressionStatement(expression) => self.expression(expression),
+            body => self.block(body),
+        }
+        self.output.push_str(",\n");
+    }
+
+    /// Writes a block, whose last expression statement is left without semicolon as it's the value of the block.
+    fn block(&mut self, block: &Statement) {
+        let Statement::BlockStatement(statements) = block else {
+            self.output.push_str("{ ");
+            self.statement(block, false);
+            self.output.push_str(" }");
+            return;
+        };
+
+        let span = self.spans.pop_front().unwrap_or_default();
+        let has_comments = self.comments.range(span.start..span.end).next().is_some();
+        if statements.is_empty() && !has_comments {
+            self.output.push_str("{}");
+            return;
+        }
+
+        self.output.push_str("{\n");
+        self.depth += 1;
+        let outer_scope = std::mem::replace(&mut self.scope, span.start);
+        for (i, located) in statements.iter().enumerate() {
+            self.item(&located.statement, i + 1 < statements.len());
+            self.output.push('\n');
+        }
+        self.own_line_comments(span.start..span.end);
+        self.scope = outer_scope;
+        self.depth -= 1;
+        self.indent();
+        self.output.push('}');
+    }
+
+    fn list<'e>(&mut self, expressions: impl IntoIterator<Item = &'e Expression>) {
+        for (i, expression) in expressions.into_iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.expression(expression);
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Expression], named: &[(String, Expression)]) {
+        self.output.push('(');
+        self.list(arguments);
+        for (i, (name, argument)) in named.iter().enumerate() {
+            if i > 0 || !arguments.is_empty() {
+                self.output.push_str(", ");
+            }
+            write!(self.output, "{name}: ").unwrap();
+            self.expression(argument);
+        }
+        self.output.push(')');
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(name) => self.output.push_str(name),
+            Expression::IntegerLiteral(n) => write!(self.output, "{n}").unwrap(),
+            // debug formatting keeps the decimal part, so that `1.0` isn't read back as an integer
+            Expression::FloatLiteral(n) => write!(self.output, "{n:?}").unwrap(),
+            Expression::BooleanLiteral(b) => write!(self.output, "{b}").unwrap(),
+            Expression::NullLiteral => self.output.push_str("null"),
+            Expression::StringLiteral(s) => self.string(s),
+            Expression::ArrayLiteral(elements) => {
+                self.output.push('[');
+                self.list(elements);
+                self.output.push(']');
+            }
+            Expression::StructLiteral { name, fields } if fields.is_empty() => {
+                write!(self.output, "{name} {{}}").unwrap()
+            }
+            Expression::StructLiteral { name, fields } => {
+                write!(self.output, "{name} {{ ").unwrap();
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    write!(self.output, "{field}: ").unwrap();
+                    self.expression(value);
+                }
+                self.output.push_str(" }");
+            }
+            Expression::MapLiteral(map) => {
+                self.output.push('{');
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.string(key);
+                    self.output.push_str(": ");
+                    self.expression(value);
+                }
+                self.output.push('}');
+            }
+            Expression::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left);
+                write!(self.output, " {operator} ").unwrap();
+                self.expression(right);
+            }
+            Expression::ComparisonChain {
+                operands,
+                operators,
+            } => {
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.output, " {} ", operators[i - 1]).unwrap();
+                    }
+                    self.expression(operand);
+                }
+            }
+            Expression::UnaryExpression {
+                operator: TokenKind::Question,
+                value,
+                ..
+            } => {
+                self.expression(value);
+                self.output.push('?');
+            }
+            Expression::UnaryExpression {
+                operator, value, ..
+            } => {
+                write!(self.output, "{operator}").unwrap();
+                self.expression(value);
+            }
+            Expression::IndexExpression { value, index } => {
+                self.expression(value);
+                self.output.push('[');
+                self.expression(index);
+                self.output.push(']');
+            }
+            Expression::FieldAccess { value, field } => {
+                self.expression(value);
+                write!(self.output, ".{field}").unwrap();
+            }
+            Expression::GroupedExpression(value) => {
+                self.output.push('(');
+                self.expression(value);
+                self.output.push(')');
+            }
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                self.expression(callee);
+                self.arguments(arguments, named);
+            }
+            Expression::MethodCall(call) => {
+                self.expression(&call.receiver);
+                write!(self.output, ".{}", call.method).unwrap();
+                self.arguments(&call.arguments, &call.named);
+            }
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.output.push_str("if ");
+                self.expression(condition);
+                self.output.push(' ');
+                self.block(consequence);
+                if let Some(alternative) = alternative {
+                    self.output.push_str(" else ");
+                    self.block(alternative);
+                }
+            }
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => {
+                write!(self.output, "fn({}) ", format_parameters(parameters, rest)).unwrap();
+                self.block(body);
+            }
+            Expression::MatchExpression { value, arms } => {
+                self.output.push_str("match ");
+                self.expression(value);
+                self.output.push_str(" {\n");
+                self.depth += 1;
+                for MatchArm {
+                    pattern,
+                    guard,
+                    body,
+                } in arms
+                {
+                    self.indent();
+                    write!(self.output, "case {pattern}").unwrap();
+                    if let Some(guard) = guard {
+                        self.output.push_str(" if ");
+                        self.expression(guard);
+                    }
+                    self.output.push_str(": ");
+                    self.arm_body(body);
+                }
+                self.depth -= 1;
+                self.indent();
+                self.output.push('}');
+            }
+            Expression::LoopExpression { label, body } => {
+                self.label(label);
+                self.output.push_str("loop ");
+                self.block(body);
+            }
+            Expression::BlockExpression(body) => self.block(body),
+        }
+    }
+
+    fn label(&mut self, label: &Option<String>) {
+        if let Some(label) = label {
+            write!(self.output, "'{label}: ").unwrap();
+        }
+    }
+
+    /// Strings can't contain escape sequences: the ones containing `"` are written as raw strings.
+    fn string(&mut self, s: &str) {
+        if s.contains('"') {
+            write!(self.output, "`{s}`").unwrap();
+        } else {
+            write!(self.output, "\"{s}\"").unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn format(input: &str) -> String {
+        format_program(&Parser::new(input).parse_program().unwrap())
+    }
+
+    #[test]
+    fn format_programs() {
+        let input = r#"let   add=fn(x,y){ x+y };  var total = 0; // running total
+for x in [1,2,3] { total = total + add(x, (x * 2)); }
+@deprecated("use add") let plus = fn(a, ...rest) { return a; };
+let [first, rest...] = [1, 2];
+if total > 10 { println("big") } else { if -total < 0 { ({"b": 2, "a": `say "hi"`}) } }
+match first { case 1 | 2: "small", case [a, ..r] if a>r[0]: { a } case _: null }
+switch first { 1 => { "one" } 2 => "two", _ => null }
+struct Point {x,y} struct Unit {}
+enum Shape {Dot,Circle(r)}
+match Shape.Circle(2) { case Shape.Circle(r)|Shape.Dot: r }
+draw(1, y: 2.0, at: Point{x:1,y:first}, unit: Unit{})
+"ab" . upper ( ).pad_left(4,fill:"-")"#;
+
+        let expected = r#"let add = fn(x, y) {
+  x + y
+};
+var total = 0;
+for x in [1, 2, 3] {
+  total = total + add(x, (x * 2));
+}
+@deprecated("use add")
+let plus = fn(a, ...rest) {
+  return a;
+};
+let [first, ..rest] = [1, 2];
+if total > 10 {
+  println("big")
+} else {
+  if -total < 0 {
+    ({"a": `say "hi"`, "b": 2})
+  }
+};
+match first {
+  case 1 | 2: "small",
+  case [a, ..r] if a > r[0]: {
+    a
+  },
+  case _: null,
+};
+switch first {
+  1 => {
+    "one"
+  },
+  2 => "two",
+  _ => null,
+}
+struct Point { x, y }
+struct Unit {}
+enum Shape { Dot, Circle(r) }
+match Shape.Circle(2) {
+  case Shape.Circle(r) | Shape.Dot: r,
+};
+draw(1, y: 2.0, at: Point { x: 1, y: first }, unit: Unit {});
+"ab".upper().pad_left(4, fill: "-");
+"#;
+        assert_eq!(format(input), expected);
+        // formatting is idempotent
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn format_source_keeps_comments() {
+        let input = r#"// Totals
+let   add=fn(x,y){
+    // add them
+    x+y // the sum
+};
+var total = add(1, /* one */ 2);
+if total > 2 {
+  /* nothing yet */
+}
+let m = ({"b": fn() { 2 // two
+}, "a": fn() { 1 // one
+}});
+/* done */"#;
+
+        let expected = r#"// Totals
+let add = fn(x, y) {
+  // add them
+  x + y // the sum
+};
+var total = add(1, 2); /* one */
+if total > 2 {
+  /* nothing yet */
+};
+let m = ({"a": fn() {
+  1 // one
+}, "b": fn() {
+  2 // two
+}});
+/* done */
+"#;
+        let formatted = format_source(input).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), expected);
+        assert!(format_source("let = 1;").is_err());
+    }
+
+    #[test]
+    fn format_preserves_programs() {
+        let inputs = [
+            include_str!("../examples/map.ql"),
+            include_str!("../examples/reduce.ql"),
+            "let f = fn() { g(1) }; f()\n(2)",
+            "let x = -(1 + 2) * 3 ** -2; x[0](1).y",
+            "if 1 < x <= 10 { x } else { fn() {}() }",
+            "let x = loop { for y in ys { if y { continue; } break; } break 1 + 2; };",
+            "let x = { let a = f(); a * 2 } + { 1 }; let m = {}; g({ \"k\" })",
+            "'a: for x in xs { 'b: loop { if x { continue 'a; } break 'b x; } }",
+            "do { x = x + 1; if x { continue; } } while x < 10 && ok(x);",
+            "switch f(x) { 1 + 1 => { let y = 2; y } [] => [], _ => {} } g()",
+            "struct P { x } for p in [P { x: 1 }] { if p == (P { x: f(P {}) }) { p.x } }",
+            "enum E { A, B(x, y) } match E.B(1, 2) { case E.B(x, _) | E.A: x, case _: E.A }",
+            "let f = fn(x) { let y = g(x)?.value?; Ok(-y?) }; match f(1) { case Ok(x): x, case None: 0 }",
+        ];
+
+        for input in inputs {
+            let program = Parser::new(input).parse_program().unwrap();
+            let formatted = Parser::new(&format_program(&program))
+                .parse_program()
+                .unwrap();
+            assert_eq!(formatted.fingerprint(), program.fingerprint(), "{input}");
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+};
+
+use crate::{environment::Environment, object::Object};
+
+/// Outcome of a garbage collection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Collection {
+    /// Environments only kept alive by reference cycles, whose bindings were dropped.
+    pub collected: usize,
+    /// Environments still reachable after the collection.
+    pub live: usize,
+    /// Approximate number of bytes used by the bindings of the reachable environments.
+    pub live_bytes: usize,
+}
+
+/// Clears the bindings of the environments only kept alive by reference cycles, like a closure stored
+/// in the environment it captured, so that they get dropped.
+///
+/// Rather than being scanned from a set of roots, which would include the values held on the Rust stack,
+/// the environments are traced like CPython does: an environment referenced more times than the other
+/// environments reference it is held from outside, by the evaluator, the host or a value being evaluated,
+/// and everything it reaches is alive. Environments that are currently borrowed are kept as well.
+pub fn collect(environments: &[Weak<RefCell<Environment>>]) -> Collection {
+    let environments: Vec<Rc<RefCell<Environment>>> =
+        environments.iter().filter_map(Weak::upgrade).collect();
+    let index: HashMap<*const RefCell<Environment>, usize> = environments
+        .iter()
+        .enumerate()
+        .map(|(i, env)| (Rc::as_ptr(env), i))
+        .collect();
+
+    // environments referenced by each environment, through its outer one and the closures it binds
+    let mut edges: Vec<Option<Vec<usize>>> = Vec::with_capacity(environments.len());
+    let mut internal = vec![0; environments.len()];
+    for env in &environments {
+        let Ok(env) = env.try_borrow() else {
+            edges.push(None);
+            continue;
+        };
+
+        let mut referenced = vec![];
+        let mut reference = |env: &Rc<RefCell<Environment>>| {
+            if let Some(&i) = index.get(&Rc::as_ptr(env)) {
+                referenced.push(i);
+            }
+        };
+        if let Some(outer) = &env.outer {
+            reference(outer);
+        }
+        env.store
+            .values()
+            .for_each(|value| closure_environments(value, &mut reference));
+
+        referenced.iter().for_each(|&i| internal[i] += 1);
+        edges.push(Some(referenced));
+    }
+
+    // the references of `environments` itself aren't counted
+    let mut pending: Vec<usize> = (0..environments.len())
+        .filter(|&i| edges[i].is_none() || Rc::strong_count(&environments[i]) - 1 > internal[i])
+        .collect();
+    let mut reachable = vec![false; environments.len()];
+    pending.iter().for_each(|&i| reachable[i] = true);
+    while let Some(i) = pending.pop() {
+        for &j in edges[i].iter().flatten() {
+            if !reachable[j] {
+                reachable[j] = true;
+                pending.push(j);
+            }
+        }
+    }
+
+    let mut collection = Collection::default();
+    let mut garbage = vec![];
+    for (env, reachable) in environments.iter().zip(reachable) {
+        if reachable {
+            collection.live += 1;
+            collection.live_bytes += env.try_borrow().map_or(0, |env| {
+                env.store
+                    .iter()
+                    .map(|(name, value)| name.len() + value.approximate_size())
+                    .sum()
+            });
+        } else if let Ok(mut env) = env.try_borrow_mut() {
+            collection.collected += 1;
+            garbage.push(mem::take(&mut env.store));
+        }
+    }
+
+    // the bindings are dropped last, as dropping them can drop environments
+    drop(environments);
+    drop(garbage);
+
+    collection
+}
+
+/// Calls `reference` with the environment of every closure in `value`.
+fn closure_environments(value: &Object, reference: &mut impl FnMut(&Rc<RefCell<Environment>>)) {
+    match value {
+        Object::FunctionValue(closure) => reference(&closure.env),
+        Object::ArrayValue(elements)
+        | Object::EnumValue {
+            values: elements, ..
+        } => elements
+            .iter()
+            .for_each(|element| closure_environments(element, reference)),
+        Object::DequeValue(elements) => elements
+            .iter()
+            .for_each(|element| closure_environments(element, reference)),
+        Object::MapValue(map) | Object::ModuleValue { members: map, .. } => map
+            .values()
+            .for_each(|value| closure_environments(value, reference)),
+        Object::StructValue { fields, .. } => fields
+            .iter()
+            .for_each(|(_, value)| closure_environments(value, reference)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluator::Evaluator, parser::Parser};
+
+    #[test]
+    fn collect_cycles() {
+        // each call leaves a closure stored in the environment it captured
+        let input = r#"
+            let make = fn(n) { var f = fn() { n }; f = fn() { f }; n };
+            let keep = fn() { var g = fn() { 1 }; g = fn() { g }; g };
+            let kept = keep();
+            for i in 0..10 { make(i); }
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        evaluator.eval_program().unwrap();
+
+        let collection = evaluator.collect_garbage();
+        assert!(collection.collected >= 10, "{collection:?}");
+        assert!(collection.live_bytes > 0);
+
+        // the global environment and the closure it binds are still reachable
+        let second = evaluator.collect_garbage();
+        assert_eq!(second.collected, 0);
+        assert_eq!(second.live, collection.live);
+        let program = Parser::new("type(kept())").parse_program().unwrap();
+        assert_eq!(
+            evaluator.eval_parsed_program(program).unwrap(),
+            vec![Object::StringValue("function".to_owned())]
+        );
+    }
+
+    #[test]
+    fn keep_environments_held_from_outside() {
+        let mut evaluator = Evaluator::new(
+            "let counter = fn() { var f = fn() { 0 }; f = fn() { f }; f }; counter()",
+        );
+        // the host holds the closure, whose environment is in a cycle
+        let closure = evaluator.eval_program().unwrap().pop().unwrap();
+
+        evaluator.collect_garbage();
+        let Object::FunctionValue(closure) = closure else {
+            panic!("expected a function");
+        };
+        assert!(closure.env.borrow().get("f").is_ok());
+    }
+
+    #[test]
+    fn collect_while_evaluating() {
+        let input = r#"
+            let make = fn(n) { var f = fn() { n }; f = fn() { f }; n };
+            var total = 0;
+            for i in 0..2000 { total = total + make(i); }
+            total
+        "#;
+        let mut evaluator = Evaluator::new(input);
+        evaluator.set_garbage_collection(true);
+        let result = evaluator.eval_program().unwrap().pop().unwrap();
+        assert_eq!(result, Object::IntegerValue(1999000));
+
+        // the cycles were reclaimed along the way
+        let remaining = evaluator.collect_garbage().collected;
+        let mut without = Evaluator::new(input);
+        without.eval_program().unwrap();
+        assert!(remaining * 100 < without.collect_garbage().collected);
+    }
+}
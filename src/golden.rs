@@ -0,0 +1,138 @@
+use std::{
+    cell::RefCell,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use thiserror::Error;
+
+use crate::{diagnostic::render_diagnostics, diff::unified_diff, evaluator::Evaluator};
+
+/// Extension of the golden file holding the diagnostics of a script, next to it.
+pub const GOLDEN_EXTENSION: &str = "stderr";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GoldenFailure {
+    #[error("The golden file {0} is missing")]
+    Missing(String),
+    #[error("The diagnostics differ from the golden file:\n{0}")]
+    Mismatch(String),
+}
+
+/// Evaluates `source` like the command line does, running the callbacks it schedules,
+/// and renders the diagnostics it reports. What it prints is left out.
+pub fn diagnostics(file: &str, source: &str) -> String {
+    let mut evaluator = Evaluator::new(source);
+    evaluator.capture_output(Rc::new(RefCell::new(String::new())));
+    let result = evaluator
+        .eval_program()
+        .and_then(|_| evaluator.run_event_loop());
+
+    render_diagnostics(file, source, evaluator.warnings(), result.err().as_ref())
+}
+
+/// Compares the diagnostics of the `.ql` scripts of `dir` to their golden files, `name.stderr` for `name.ql`,
+/// in the order of their paths. Scripts are named by their file name, so the golden files don't depend
+/// on where the directory is. With `bless`, the golden files are written instead.
+pub fn check_golden_files(
+    dir: &Path,
+    bless: bool,
+) -> io::Result<Vec<(PathBuf, Result<(), GoldenFailure>)>> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .filter(|path| {
+            path.as_ref()
+                .map_or(true, |path| path.extension().is_some_and(|ext| ext == "ql"))
+        })
+        .collect::<io::Result<_>>()?;
+    scripts.sort();
+
+    let mut results = vec![];
+    for script in scripts {
+        let source = fs::read_to_string(&script)?;
+        let name = script.file_name().unwrap_or_default().to_string_lossy();
+        let actual = diagnostics(&name, &source);
+
+        let golden = script.with_extension(GOLDEN_EXTENSION);
+        let result = if bless {
+            fs::write(&golden, &actual)?;
+            Ok(())
+        } else {
+            match fs::read_to_string(&golden) {
+                Ok(expected) if expected == actual => Ok(()),
+                Ok(expected) => {
+                    let path = golden.file_name().unwrap_or_default().to_string_lossy();
+                    Err(GoldenFailure::Mismatch(unified_diff(
+                        &expected, &actual, &path,
+                    )))
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    Err(GoldenFailure::Missing(golden.display().to_string()))
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        results.push((script, result));
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden_diagnostics() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("diagnostics");
+        // `QALO_BLESS=1 cargo test golden` updates the golden files after a deliberate change
+        let bless = std::env::var_os("QALO_BLESS").is_some();
+        let results = check_golden_files(&dir, bless).unwrap();
+        assert!(!results.is_empty());
+
+        let failures: Vec<String> = results
+            .into_iter()
+            .filter_map(|(path, result)| Some(format!("{}: {}", path.display(), result.err()?)))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn golden_mismatch() {
+        let dir = std::env::temp_dir().join(format!("qalo-golden-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.ql"), "1 / 0").unwrap();
+        fs::write(dir.join("b.ql"), "x").unwrap();
+        fs::write(
+            dir.join("b.stderr"),
+            "| Qalo Error E1001 |\nIdentifier not found: y\n",
+        )
+        .unwrap();
+
+        let results = check_golden_files(&dir, false).unwrap();
+        let blessed = check_golden_files(&dir, true).unwrap();
+        let golden = fs::read_to_string(dir.join("a.stderr"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            results[0].1,
+            Err(GoldenFailure::Missing(
+                dir.join("a.stderr").display().to_string()
+            ))
+        );
+        assert_eq!(
+            results[1].1,
+            Err(GoldenFailure::Mismatch(
+                "--- a/b.stderr\n+++ b/b.stderr\n@@ -1,2 +1,2 @@\n \
+                 | Qalo Error E1001 |\n-Identifier not found: y\n+Identifier not found: x\n"
+                    .to_owned()
+            ))
+        );
+        assert!(blessed.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(
+            golden.unwrap(),
+            "| Qalo Error E1007 |\nDivision by zero isn't allowed\n"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    lexer::Lexer,
+    token::{Span, TokenKind},
+};
+
+/// Semantic category of a token, used by editors to pick a color.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Category {
+    Keyword,
+    Literal,
+    Operator,
+    Punctuation,
+    Identifier,
+    Comment,
+    Invalid,
+}
+
+impl Category {
+    /// Matches token kinds to their category.
+    pub fn from_token_kind(kind: &TokenKind) -> Option<Category> {
+        let category = match kind {
+            TokenKind::Eof => return None,
+            TokenKind::Illegal => Category::Invalid,
+
+            TokenKind::Identifier | TokenKind::Label => Category::Identifier,
+
+            TokenKind::Integer
+            | TokenKind::Float
+            | TokenKind::String
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Null => Category::Literal,
+
+            TokenKind::Assign
+            | TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Bang
+            | TokenKind::Tilde
+            | TokenKind::Question
+            | TokenKind::Asterisk
+            | TokenKind::DoubleAsterisk
+            | TokenKind::Slash
+            | TokenKind::Percentage
+            | TokenKind::Equal
+            | TokenKind::NotEqual
+            | TokenKind::LessThan
+            | TokenKind::GreaterThan
+            | TokenKind::LessThanEqual
+            | TokenKind::GreaterThanEqual
+            | TokenKind::AndAnd
+            | TokenKind::OrOr
+            | TokenKind::Ampersand
+            | TokenKind::Pipe
+            | TokenKind::Pipeline
+            | TokenKind::Caret
+            | TokenKind::ShiftLeft
+            | TokenKind::ShiftRight
+            | TokenKind::DotDot
+            | TokenKind::DotDotEqual
+            | TokenKind::Ellipsis => Category::Operator,
+
+            TokenKind::Comma
+            | TokenKind::Dot
+            | TokenKind::At
+            | TokenKind::Semicolon
+            | TokenKind::Colon
+            | TokenKind::FatArrow
+            | TokenKind::LeftParen
+            | TokenKind::RightParen
+            | TokenKind::LeftBrace
+            | TokenKind::RightBrace
+            | TokenKind::LeftSquare
+            | TokenKind::RightSquare => Category::Punctuation,
+
+            TokenKind::Function
+            | TokenKind::Let
+            | TokenKind::Var
+            | TokenKind::If
+            | TokenKind::Else
+            | TokenKind::Return
+            | TokenKind::For
+            | TokenKind::Do
+            | TokenKind::While
+            | TokenKind::Switch
+            | TokenKind::Struct
+            | TokenKind::Enum
+            | TokenKind::In
+            | TokenKind::Match
+            | TokenKind::Case
+            | TokenKind::Loop
+            | TokenKind::Break
+            | TokenKind::Continue => Category::Keyword,
+        };
+
+        Some(category)
+    }
+}
+
+/// Splits the source into highlighted ranges, in the order they appear.
+/// It never fails: invalid characters are reported with the `Invalid` category.
+pub fn highlight(source: &str) -> Vec<(Span, Category)> {
+    let mut lexer = Lexer::new(source);
+    let mut ranges = vec![];
+
+    loop {
+        let token = lexer.next_token();
+
+        match Category::from_token_kind(&token.kind) {
+            Some(category) => ranges.push((token.span, category)),
+            None => break,
+        }
+    }
+
+    // comments are skipped by the lexer, which only records where they are
+    ranges.extend(
+        lexer
+            .comments
+            .into_iter()
+            .map(|span| (span, Category::Comment)),
+    );
+    ranges.sort_by_key(|(span, _)| span.start);
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_tokens() {
+        let source = r#"let s = "é" + x; /* sum */ # // done"#;
+
+        let ranges: Vec<(&str, Category)> = highlight(source)
+            .into_iter()
+            .map(|(span, category)| (&source[span.start..span.end], category))
+            .collect();
+
+        assert_eq!(
+            ranges,
+            vec![
+                ("let", Category::Keyword),
+                ("s", Category::Identifier),
+                ("=", Category::Operator),
+                ("\"é\"", Category::Literal),
+                ("+", Category::Operator),
+                ("x", Category::Identifier),
+                (";", Category::Punctuation),
+                ("/* sum */", Category::Comment),
+                ("#", Category::Invalid),
+                ("// done", Category::Comment),
+            ]
+        );
+    }
+}
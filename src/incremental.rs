@@ -0,0 +1,313 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    ast::{Located, ParserError, Program, Statement},
+    parser::Parser,
+    token::{Span, TokenKind},
+};
+
+/// Source being edited, like the buffer of an editor, whose top-level statements are parsed separately:
+/// after an edit, only the statements around it are parsed again, which keeps diagnostics fast on large files.
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+    statements: Vec<ParsedStatement>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedStatement {
+    /// Span of the statement in the source. A statement that failed to parse spans until the end of the source,
+    /// as the parser can't know where the next one starts.
+    pub span: Span,
+    pub result: Result<Statement, ParserError>,
+    /// Offset of the source the statement was parsed from, which the spans of its errors
+    /// and of the statements nested in it are relative to.
+    /// Edits before the statement can move it before the start of the source.
+    offset: isize,
+}
+
+/// Amount of work done to apply an edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reparse {
+    /// Statements parsed again, which may be fewer or more than before the edit.
+    pub parsed: usize,
+    /// Statements kept from before the edit, before and after it.
+    pub reused: usize,
+}
+
+impl Document {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let statements = parse_statements(&source, 0, &HashMap::new()).0;
+
+        Document { source, statements }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn statements(&self) -> &[ParsedStatement] {
+        &self.statements
+    }
+
+    /// Replaces the bytes in `range` with `text`, and parses again the statements it may have changed:
+    /// from the statement before the edit, until a statement starts where one started before the edit.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Reparse {
+        self.source.replace_range(range.clone(), text);
+        let delta = text.len() as isize - range.len() as isize;
+        let shift = |offset: usize| offset.saturating_add_signed(delta);
+
+        // text added right after a statement can continue it, like `f` followed by `(x)`
+        let first = self
+            .statements
+            .iter()
+            .rposition(|statement| statement.span.start < range.start)
+            .unwrap_or(0);
+        let start = self
+            .statements
+            .get(first)
+            .map_or(0, |statement| statement.span.start.min(range.start));
+
+        // the source after the edit is unchanged, so the statements starting there can be reused
+        let resumable: HashMap<usize, usize> = self
+            .statements
+            .iter()
+            .enumerate()
+            .skip(first)
+            .filter(|(_, statement)| statement.span.start >= range.end)
+            .map(|(i, statement)| (shift(statement.span.start), i))
+            .collect();
+
+        let (parsed, resume) = parse_statements(&self.source, start, &resumable);
+        let reparse = Reparse {
+            parsed: parsed.len(),
+            reused: first + resume.map_or(0, |i| self.statements.len() - i),
+        };
+
+        let after: Vec<ParsedStatement> = match resume {
+            Some(i) => self
+                .statements
+                .drain(i..)
+                .map(|statement| ParsedStatement {
+                    span: Span {
+                        start: shift(statement.span.start),
+                        end: shift(statement.span.end),
+                    },
+                    offset: statement.offset + delta,
+                    ..statement
+                })
+                .collect(),
+            None => vec![],
+        };
+        self.statements.truncate(first);
+        self.statements.extend(parsed);
+        self.statements.extend(after);
+
+        reparse
+    }
+
+    /// The parsed program, as `Parser::parse_program` would give it for the whole source.
+    pub fn program(&self) -> Result<Program, ParserError> {
+        let mut statements = vec![];
+        let mut errors = vec![];
+        for parsed in &self.statements {
+            match &parsed.result {
+                Ok(statement) => statements.push(Located {
+                    statement: statement.clone(),
+                    span: parsed.span,
+                }),
+                Err(ParserError::Multiple(multiple)) => errors.extend(multiple.iter().cloned()),
+                Err(err) => errors.push(err.clone()),
+            }
+        }
+
+        match errors.len() {
+            0 => Ok(Program(statements)),
+            1 => Err(errors.remove(0)),
+            _ => Err(ParserError::Multiple(errors)),
+        }
+    }
+
+    /// Errors of the statements, with their span in the whole source when they have one.
+    pub fn diagnostics(&self) -> Vec<(Option<Span>, &ParserError)> {
+        let mut diagnostics = vec![];
+        for statement in &self.statements {
+            let errors = match &statement.result {
+                Ok(_) => continue,
+                Err(ParserError::Multiple(errors)) => errors.iter().collect(),
+                Err(err) => vec![err],
+            };
+
+            diagnostics.extend(errors.into_iter().map(|err| {
+                let span = err.span().map(|span| Span {
+                    start: span.start.saturating_add_signed(statement.offset),
+                    end: span.end.saturating_add_signed(statement.offset),
+                });
+                (span, err)
+            }));
+        }
+
+        diagnostics
+    }
+}
+
+/// Parses the statements of `source` from `start`, until one would start at an offset of `resumable`,
+/// whose statement index is returned.
+fn parse_statements(
+    source: &str,
+    start: usize,
+    resumable: &HashMap<usize, usize>,
+) -> (Vec<ParsedStatement>, Option<usize>) {
+    let mut parser = Parser::new(&source[start..]);
+    let mut statements = vec![];
+    let until_end = |from: usize| Span {
+        start: from,
+        end: source.len(),
+    };
+
+    while parser.cur.kind != TokenKind::Eof {
+        let statement_start = start + parser.cur.span.start;
+        if let Some(&i) = resumable.get(&statement_start) {
+            return (statements, Some(i));
+        }
+
+        let result = parser.parse_statement();
+        let failed = result.is_err();
+        let span = if failed {
+            until_end(statement_start)
+        } else {
+            Span {
+                start: statement_start,
+                end: start + parser.cur.span.end,
+            }
+        };
+
+        // errors the parser recovered from, like misspelled keywords
+        let mut errors = std::mem::take(&mut parser.diagnostics);
+        let result = match result {
+            Ok(statement) if errors.is_empty() => Ok(statement),
+            Ok(_) if errors.len() == 1 => Err(errors.remove(0)),
+            Ok(_) => Err(ParserError::Multiple(errors)),
+            Err(err) => Err(err),
+        };
+        statements.push(ParsedStatement {
+            span,
+            result,
+            offset: start as isize,
+        });
+
+        if failed {
+            return (statements, None);
+        }
+        parser.eat_token();
+    }
+
+    // the lexer reads an unterminated comment until the end of the source
+    if let Some(comment) = parser.lexer.unterminated_comment {
+        statements.push(ParsedStatement {
+            span: until_end(start + comment.start),
+            result: Err(ParserError::UnterminatedComment(comment)),
+            offset: start as isize,
+        });
+    }
+
+    (statements, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the document is parsed like its whole source.
+    fn assert_parsed(document: &Document) {
+        let expected = Parser::new(document.source())
+            .parse_program()
+            .map(|program| program.to_string());
+        let program = document.program().map(|program| program.to_string());
+
+        match expected {
+            Ok(expected) => assert_eq!(program.ok(), Some(expected)),
+            Err(_) => assert!(program.is_err(), "{}", document.source()),
+        }
+    }
+
+    #[test]
+    fn reparse_edited_statements() {
+        let mut document = Document::new("let a = 1;\nlet b = 2;\nlet c = 3;\nlet d = 4;\n");
+        assert_eq!(document.statements().len(), 4);
+
+        // only the edited statement is parsed again
+        let reparse = document.edit(30..31, "30");
+        assert_eq!(
+            document.source(),
+            "let a = 1;\nlet b = 2;\nlet c = 30;\nlet d = 4;\n"
+        );
+        assert_eq!(
+            reparse,
+            Reparse {
+                parsed: 1,
+                reused: 3
+            }
+        );
+        assert_eq!(document.statements()[3].span, Span { start: 34, end: 44 });
+        assert_parsed(&document);
+
+        // an edit can merge statements...
+        let reparse = document.edit(19..30, "");
+        assert_eq!(document.source(), "let a = 1;\nlet b = 30;\nlet d = 4;\n");
+        assert_eq!(reparse.parsed, 1);
+        assert_eq!(document.statements().len(), 3);
+        assert_parsed(&document);
+
+        // ...or add new ones
+        let reparse = document.edit(0..0, "let z = 0; ");
+        assert_eq!(
+            reparse,
+            Reparse {
+                parsed: 1,
+                reused: 3
+            }
+        );
+        assert_eq!(document.statements().len(), 4);
+        assert_parsed(&document);
+
+        // the statement before an edit is parsed again, as the edit can continue it
+        let mut document = Document::new("f;\n(y);");
+        document.edit(1..2, "");
+        assert_eq!(document.statements().len(), 1);
+        assert_eq!(document.program().unwrap().to_string(), "f(y)");
+    }
+
+    #[test]
+    fn reparse_errors() {
+        let mut document = Document::new("let a = 1;\nlet b = ;\nlet c = 3;");
+        let diagnostics = document.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].1.code(), "E0001");
+        assert_eq!(diagnostics[0].0, Some(Span { start: 19, end: 20 }));
+        // the statements following an error can't be told apart
+        assert_eq!(document.statements().len(), 2);
+
+        document.edit(19..19, "2");
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.statements().len(), 3);
+        assert_parsed(&document);
+
+        document.edit(22..22, "/* unterminated ");
+        let diagnostics = document.diagnostics();
+        assert!(matches!(
+            diagnostics[0].1,
+            ParserError::UnterminatedComment(_)
+        ));
+        assert_eq!(diagnostics[0].0, Some(Span { start: 22, end: 48 }));
+
+        // misspelled keywords are reported along with the errors after them
+        document.edit(0..3, "lett");
+        let diagnostics = document.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].1.code(), "E0010");
+        assert_eq!(diagnostics[0].0, Some(Span { start: 0, end: 4 }));
+        assert_parsed(&document);
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt::{self, Write};
+
+use thiserror::Error;
+
+/// JSON value, as exchanged with hosts over the `serve --jsonrpc` protocol.
+/// Objects keep the order of their members.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid JSON at byte {offset}: {message}")]
+pub struct JsonError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Json {
+    pub fn parse(input: &str) -> Result<Json, JsonError> {
+        let mut parser = JsonParser { input, offset: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.offset < input.len() {
+            return Err(parser.error("unexpected data after the value"));
+        }
+
+        Ok(value)
+    }
+
+    /// Value of the member `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            // JSON has no representation for them
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(elements) => {
+                f.write_char('[')?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> JsonError {
+        JsonError {
+            offset: self.offset,
+            message: message.to_owned(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.offset..].chars().next()
+    }
+
+    fn eat(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("expected `{expected}`")));
+        }
+        self.offset += expected.len_utf8();
+
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => self.keyword(),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self) -> Result<Json, JsonError> {
+        let rest = &self.input[self.offset..];
+        let (keyword, value) = [
+            ("null", Json::Null),
+            ("true", Json::Bool(true)),
+            ("false", Json::Bool(false)),
+        ]
+        .into_iter()
+        .find(|(keyword, _)| rest.starts_with(keyword))
+        .ok_or_else(|| self.error("expected a value"))?;
+
+        self.offset += keyword.len();
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.offset;
+        while matches!(self.peek(), Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+            self.offset += 1;
+        }
+
+        self.input[start..self.offset]
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| JsonError {
+                offset: start,
+                message: "malformed number".to_owned(),
+            })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            match self.eat() {
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.eat() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    s.push(c);
+                }
+                Some(c) if c.is_control() => {
+                    return Err(self.error("control characters must be escaped"))
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// Reads the hex digits of a `\u` escape, and of the low surrogate following a high surrogate.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+
+        if !self.input[self.offset..].starts_with("\\u") {
+            return Err(self.error("unpaired surrogate"));
+        }
+        self.offset += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("unpaired surrogate"));
+        }
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .input
+            .get(self.offset..self.offset + 4)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let n =
+            u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.offset += 4;
+
+        Ok(n)
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.expect('[')?;
+        let mut elements = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.offset += 1;
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.offset += 1,
+                Some(']') => {
+                    self.offset += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.expect('{')?;
+        let mut members = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.offset += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.offset += 1,
+                Some('}') => {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_json() {
+        let value = Json::parse(
+            r#" {"a": [1, -2.5e1, true, null], "b": "x\n\"y\" \u00e9\ud83d\ude00", "c": {}} "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            Json::Object(vec![
+                (
+                    "a".to_owned(),
+                    Json::Array(vec![
+                        Json::Number(1.0),
+                        Json::Number(-25.0),
+                        Json::Bool(true),
+                        Json::Null
+                    ])
+                ),
+                ("b".to_owned(), Json::from("x\n\"y\" é😀")),
+                ("c".to_owned(), Json::Object(vec![])),
+            ])
+        );
+        assert_eq!(value.get("b").and_then(Json::as_str), Some("x\n\"y\" é😀"));
+        assert_eq!(value.get("d"), None);
+
+        let errors = vec![
+            ("", "Invalid JSON at byte 0: unexpected end of input"),
+            ("[1, 2", "Invalid JSON at byte 5: expected `,` or `]`"),
+            ("{\"a\" 1}", "Invalid JSON at byte 5: expected `:`"),
+            ("\"abc", "Invalid JSON at byte 4: unterminated string"),
+            (
+                "1 2",
+                "Invalid JSON at byte 2: unexpected data after the value",
+            ),
+            ("nul", "Invalid JSON at byte 0: expected a value"),
+            ("\"\\ud83d\"", "Invalid JSON at byte 7: unpaired surrogate"),
+        ];
+
+        for (input, expected) in errors {
+            assert_eq!(
+                Json::parse(input).unwrap_err().to_string(),
+                expected,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn serialize_json() {
+        let value = Json::Object(vec![
+            ("n".to_owned(), Json::from(3)),
+            ("f".to_owned(), Json::Number(0.5)),
+            ("s".to_owned(), Json::from("a\"b\\c\nd\u{1}")),
+            ("o".to_owned(), Json::from(None::<&str>)),
+            (
+                "a".to_owned(),
+                Json::Array(vec![Json::Bool(false), Json::Number(f64::NAN)]),
+            ),
+        ]);
+
+        let serialized = value.to_string();
+        assert_eq!(
+            serialized,
+            r#"{"n":3,"f":0.5,"s":"a\"b\\c\nd\u0001","o":null,"a":[false,null]}"#
+        );
+        // escaped strings are read back unchanged
+        assert_eq!(Json::parse(&serialized).unwrap().get("s"), value.get("s"));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    lexer::is_identifier_like,
+    object::BuiltinFunction,
+    token::{TokenKind, KEYWORDS},
+};
+
+/// Keyword table of the lexer: the built-in keywords, plus the aliases added by the host,
+/// e.g. `func` for `fn` or localized keywords.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keywords {
+    aliases: HashMap<String, TokenKind>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum KeywordError {
+    #[error("`{0}` isn't a keyword that can be aliased")]
+    UnknownKeyword(String),
+    #[error("`{0}` can't be used as an alias, as it isn't a valid identifier")]
+    InvalidAlias(String),
+    #[error("`{0}` is already a keyword")]
+    ReservedAlias(String),
+    #[error("`{0}` is the name of a built-in function, which would become unusable")]
+    BuiltinAlias(String),
+    #[error(
+        "`{alias}` is already an alias of `{existing}`, it can't be an alias of `{keyword}` too"
+    )]
+    ConflictingAlias {
+        alias: String,
+        existing: TokenKind,
+        keyword: TokenKind,
+    },
+    #[error("Line {0} of the keyword table should look like `alias = keyword`")]
+    MalformedLine(usize),
+}
+
+impl Keywords {
+    pub fn new() -> Self {
+        Keywords::default()
+    }
+
+    /// Reads a keyword table made of `alias = keyword` lines.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(table: &str) -> Result<Self, KeywordError> {
+        let mut keywords = Keywords::new();
+
+        for (i, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (alias, keyword) = line
+                .split_once('=')
+                .ok_or(KeywordError::MalformedLine(i + 1))?;
+            keywords.alias(alias.trim(), keyword.trim())?;
+        }
+
+        Ok(keywords)
+    }
+
+    /// Makes `alias` a synonym of the built-in `keyword`.
+    /// Aliases can't shadow keywords, built-in functions or other aliases.
+    pub fn alias(&mut self, alias: &str, keyword: &str) -> Result<(), KeywordError> {
+        let kind = TokenKind::lookup_identifier(keyword);
+        if kind == TokenKind::Identifier {
+            return Err(KeywordError::UnknownKeyword(keyword.to_owned()));
+        }
+
+        // the alias must be lexed as a single identifier
+        if !is_identifier_like(alias) {
+            return Err(KeywordError::InvalidAlias(alias.to_owned()));
+        }
+
+        if TokenKind::lookup_identifier(alias) != TokenKind::Identifier {
+            return Err(KeywordError::ReservedAlias(alias.to_owned()));
+        }
+        if BuiltinFunction::lookup_function(alias).is_ok() {
+            return Err(KeywordError::BuiltinAlias(alias.to_owned()));
+        }
+        match self.aliases.get(alias) {
+            Some(existing) if *existing != kind => Err(KeywordError::ConflictingAlias {
+                alias: alias.to_owned(),
+                existing: existing.clone(),
+                keyword: kind,
+            }),
+            _ => {
+                self.aliases.insert(alias.to_owned(), kind);
+                Ok(())
+            }
+        }
+    }
+
+    /// Kind of the token for `identifier`, which is `TokenKind::Identifier` unless it's a keyword or an alias.
+    pub fn lookup(&self, identifier: &str) -> TokenKind {
+        match self.aliases.get(identifier) {
+            Some(kind) => kind.clone(),
+            None => TokenKind::lookup_identifier(identifier),
+        }
+    }
+
+    /// Every word recognized as a keyword, with its kind: the built-in keywords, then the aliases sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TokenKind)> {
+        let mut aliases: Vec<_> = self.aliases.iter().collect();
+        aliases.sort_by_key(|(alias, _)| *alias);
+
+        KEYWORDS
+            .iter()
+            .map(|(keyword, kind)| (*keyword, kind))
+            .chain(
+                aliases
+                    .into_iter()
+                    .map(|(alias, kind)| (alias.as_str(), kind)),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_aliases() {
+        let mut keywords = Keywords::new();
+        keywords.alias("func", "fn").unwrap();
+        keywords.alias("si", "if").unwrap();
+        // aliasing the same keyword twice is harmless
+        keywords.alias("func", "fn").unwrap();
+
+        assert_eq!(keywords.lookup("func"), TokenKind::Function);
+        assert_eq!(keywords.lookup("fn"), TokenKind::Function);
+        assert_eq!(keywords.lookup("si"), TokenKind::If);
+        assert_eq!(keywords.lookup("sinon"), TokenKind::Identifier);
+        assert_eq!(
+            keywords.iter().skip(KEYWORDS.len()).collect::<Vec<_>>(),
+            vec![("func", &TokenKind::Function), ("si", &TokenKind::If)]
+        );
+
+        let errors = vec![
+            (
+                ("f", "function"),
+                KeywordError::UnknownKeyword("function".to_owned()),
+            ),
+            (
+                ("my-fn", "fn"),
+                KeywordError::InvalidAlias("my-fn".to_owned()),
+            ),
+            (("", "fn"), KeywordError::InvalidAlias("".to_owned())),
+            (
+                ("let", "var"),
+                KeywordError::ReservedAlias("let".to_owned()),
+            ),
+            (("len", "fn"), KeywordError::BuiltinAlias("len".to_owned())),
+            (
+                ("func", "let"),
+                KeywordError::ConflictingAlias {
+                    alias: "func".to_owned(),
+                    existing: TokenKind::Function,
+                    keyword: TokenKind::Let,
+                },
+            ),
+        ];
+
+        for ((alias, keyword), expected) in errors {
+            assert_eq!(keywords.alias(alias, keyword), Err(expected), "{alias}");
+        }
+        // failed aliases leave the table unchanged
+        assert_eq!(keywords.lookup("func"), TokenKind::Function);
+    }
+
+    #[test]
+    fn parse_keyword_table() {
+        let keywords = Keywords::parse("# French\nsoit = let\n\nsi = if\nsinon=else\n").unwrap();
+        assert_eq!(keywords.lookup("soit"), TokenKind::Let);
+        assert_eq!(keywords.lookup("sinon"), TokenKind::Else);
+
+        assert_eq!(
+            Keywords::parse("soit = let\nsi if"),
+            Err(KeywordError::MalformedLine(2))
+        );
+        assert_eq!(
+            Keywords::parse("a = fn\na = let").unwrap_err().to_string(),
+            "`a` is already an alias of `fn`, it can't be an alias of `let` too"
+        );
+    }
+}
@@ -1,25 +1,61 @@
-use crate::token::{Token, TokenKind};
+use std::rc::Rc;
 
-#[derive(Debug)]
+use crate::{
+    keywords::Keywords,
+    token::{Span, Token, TokenKind},
+};
+
+/// Whether an identifier can start with `ch`: identifiers follow UAX #31,
+/// starting with a `XID_Start` character or an underscore.
+pub fn is_identifier_start(ch: char) -> bool {
+    ch == '_' || unicode_ident::is_xid_start(ch)
+}
+
+/// Whether `ch` can follow the first character of an identifier: a `XID_Continue` character,
+/// which includes digits, combining marks and the underscore.
+pub fn is_identifier_continue(ch: char) -> bool {
+    unicode_ident::is_xid_continue(ch)
+}
+
+/// Whether `name` is lexed as a single identifier or keyword.
+pub fn is_identifier_like(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_identifier_start) && chars.all(is_identifier_continue)
+}
+
+#[derive(Debug, Clone)]
 pub struct Lexer<'a> {
     input: &'a str,
-    /// Current position in input (points to current char)
+    /// Current byte position in input (points to current char)
     cur: usize,
-    /// Next position in input (after current char)
+    /// Next byte position in input (after current char)
     next: usize,
     /// Current char under examination
     ch: char,
+    /// Spans of the comments skipped so far, in the order they appear
+    pub comments: Vec<Span>,
+    /// Span of a block comment missing its closing `*/`, which swallows the rest of the input
+    pub unterminated_comment: Option<Span>,
+    keywords: Rc<Keywords>,
 }
 
 const EOF_CHAR: char = '\0';
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
+        Lexer::with_keywords(input, Rc::default())
+    }
+
+    /// Creates a lexer recognizing the aliases of `keywords` as well as the built-in keywords.
+    pub fn with_keywords(input: &'a str, keywords: Rc<Keywords>) -> Self {
         let mut lexer = Self {
             input,
             cur: 0,
             next: 0,
             ch: EOF_CHAR,
+            comments: vec![],
+            unterminated_comment: None,
+            keywords,
         };
 
         lexer.eat_char();
@@ -27,13 +63,17 @@ impl<'a> Lexer<'a> {
         lexer
     }
 
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
     /// Give the next character.
     pub fn peek_char(&mut self) -> char {
-        if self.next >= self.input.chars().count() {
+        if self.next >= self.input.len() {
             // reached EOF
             EOF_CHAR
         } else {
-            self.input.chars().nth(self.next).unwrap_or(EOF_CHAR)
+            self.input[self.next..].chars().next().unwrap_or(EOF_CHAR)
         }
     }
 
@@ -41,7 +81,7 @@ impl<'a> Lexer<'a> {
     pub fn eat_char(&mut self) {
         self.ch = self.peek_char();
         self.cur = self.next;
-        self.next += 1;
+        self.next += self.ch.len_utf8();
     }
 
     pub fn skip_whitespace(&mut self) {
@@ -50,220 +90,280 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    pub fn eat_identifier(&mut self) -> &str {
+    /// Skips whitespace and comments, recording the span of every comment.
+    pub fn skip_trivia(&mut self) {
+        loop {
+            self.skip_whitespace();
+
+            let start = self.cur;
+            match (self.ch, self.peek_char()) {
+                ('/', '/') => {
+                    while self.ch != '\n' && self.ch != EOF_CHAR {
+                        self.eat_char();
+                    }
+                }
+                ('/', '*') => {
+                    // skip `/*`, so that `/*/` isn't considered a complete comment
+                    self.eat_char();
+                    self.eat_char();
+
+                    while !(self.ch == '*' && self.peek_char() == '/') {
+                        if self.ch == EOF_CHAR {
+                            self.unterminated_comment = Some(Span {
+                                start,
+                                end: self.input.len(),
+                            });
+                            break;
+                        }
+                        self.eat_char();
+                    }
+
+                    // skip `*/`
+                    if self.ch != EOF_CHAR {
+                        self.eat_char();
+                        self.eat_char();
+                    }
+                }
+                _ => return,
+            }
+
+            self.comments.push(Span {
+                start,
+                end: self.cur.min(self.input.len()),
+            });
+        }
+    }
+
+    pub fn eat_identifier(&mut self) -> &'a str {
         let start = self.cur;
 
-        while self.ch.is_alphanumeric() || self.ch == '_' {
+        while is_identifier_continue(self.ch) {
             self.eat_char();
         }
 
         &self.input[start..self.cur]
     }
 
-    // TODO: add support for different types of numbers; only `i32`s are supported currently.
+    /// Reads an integer or a float; a dot is part of the number only if a digit follows it,
+    /// so that `1..2` is still lexed as a range.
     pub fn eat_number(&mut self) -> &str {
         let start = self.cur;
 
-        while self.ch.is_ascii_digit() {
+        // hexadecimal, octal and binary integers: the digits are validated by the parser,
+        // so any alphanumeric char is included to report malformed literals as a whole
+        if self.ch == '0' && matches!(self.peek_char(), 'x' | 'o' | 'b') {
+            self.eat_char();
+            self.eat_char();
+            self.eat_digits(char::is_alphanumeric);
+
+            return &self.input[start..self.cur];
+        }
+
+        self.eat_digits(|ch| ch.is_ascii_digit());
+
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
             self.eat_char();
+            self.eat_digits(|ch| ch.is_ascii_digit());
         }
 
         &self.input[start..self.cur]
     }
 
+    /// Reads the chars matching `is_digit`, along with the `_` separators placed between them.
+    fn eat_digits(&mut self, is_digit: impl Fn(char) -> bool) {
+        while is_digit(self.ch) || (self.ch == '_' && is_digit(self.peek_char())) {
+            self.eat_char();
+        }
+    }
+
     pub fn eat_string(&mut self) -> &str {
+        // TODO: add support for escape characters
+        self.eat_delimited('"')
+    }
+
+    /// Reads a raw string delimited by backticks: it can span multiple lines and contain `"`.
+    pub fn eat_raw_string(&mut self) -> &str {
+        self.eat_delimited('`')
+    }
+
+    /// Reads everything up to the closing `delimiter`, which is excluded from the literal.
+    fn eat_delimited(&mut self, delimiter: char) -> &str {
         let start = self.cur + 1;
 
         loop {
             self.eat_char();
 
-            // TODO: add support for escape characters
-            if self.ch == '"' || self.ch == EOF_CHAR {
+            if self.ch == delimiter || self.ch == EOF_CHAR {
                 break;
             }
         }
 
-        &self.input[start..self.cur]
+        &self.input[start..self.cur.min(self.input.len())]
     }
 
     /// Retrieve the current token and advance position in the input string.
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        self.skip_trivia();
+
+        let start = self.cur;
+        let (kind, literal) = self.read_token();
+
+        Token {
+            kind,
+            literal,
+            span: Span {
+                start,
+                end: self.cur.min(self.input.len()),
+            },
+        }
+    }
 
+    /// Read the kind and literal of the token starting at the current char.
+    fn read_token(&mut self) -> (TokenKind, String) {
         let token = match self.ch {
             '=' => {
                 if self.peek_char() == '=' {
                     self.eat_char();
-                    Token {
-                        kind: TokenKind::Equal,
-                        literal: "==".to_owned(),
-                    }
+                    (TokenKind::Equal, "==".to_owned())
+                } else if self.peek_char() == '>' {
+                    self.eat_char();
+                    (TokenKind::FatArrow, "=>".to_owned())
                 } else {
-                    Token {
-                        kind: TokenKind::Assign,
-                        literal: "=".to_owned(),
-                    }
+                    (TokenKind::Assign, "=".to_owned())
                 }
             }
             '!' => {
                 if self.peek_char() == '=' {
                     self.eat_char();
-                    Token {
-                        kind: TokenKind::NotEqual,
-                        literal: "!=".to_owned(),
-                    }
+                    (TokenKind::NotEqual, "!=".to_owned())
                 } else {
-                    Token {
-                        kind: TokenKind::Bang,
-                        literal: "!".to_owned(),
-                    }
+                    (TokenKind::Bang, "!".to_owned())
                 }
             }
             '<' => {
                 if self.peek_char() == '=' {
                     self.eat_char();
-                    Token {
-                        kind: TokenKind::LessThanEqual,
-                        literal: "<=".to_owned(),
-                    }
+                    (TokenKind::LessThanEqual, "<=".to_owned())
+                } else if self.peek_char() == '<' {
+                    self.eat_char();
+                    (TokenKind::ShiftLeft, "<<".to_owned())
                 } else {
-                    Token {
-                        kind: TokenKind::LessThan,
-                        literal: "<".to_owned(),
-                    }
+                    (TokenKind::LessThan, "<".to_owned())
                 }
             }
             '>' => {
                 if self.peek_char() == '=' {
                     self.eat_char();
-                    Token {
-                        kind: TokenKind::GreaterThanEqual,
-                        literal: ">=".to_owned(),
-                    }
+                    (TokenKind::GreaterThanEqual, ">=".to_owned())
+                } else if self.peek_char() == '>' {
+                    self.eat_char();
+                    (TokenKind::ShiftRight, ">>".to_owned())
                 } else {
-                    Token {
-                        kind: TokenKind::GreaterThan,
-                        literal: ">".to_owned(),
-                    }
+                    (TokenKind::GreaterThan, ">".to_owned())
                 }
             }
             '&' => {
                 if self.peek_char() == '&' {
                     self.eat_char();
-                    Token {
-                        kind: TokenKind::AndAnd,
-                        literal: "&&".to_owned(),
-                    }
+                    (TokenKind::AndAnd, "&&".to_owned())
                 } else {
-                    Token {
-                        kind: TokenKind::Illegal,
-                        literal: self.ch.to_string(),
-                    }
+                    (TokenKind::Ampersand, "&".to_owned())
                 }
             }
             '|' => {
                 if self.peek_char() == '|' {
                     self.eat_char();
-                    Token {
-                        kind: TokenKind::OrOr,
-                        literal: "||".to_owned(),
-                    }
+                    (TokenKind::OrOr, "||".to_owned())
+                } else if self.peek_char() == '>' {
+                    self.eat_char();
+                    (TokenKind::Pipeline, "|>".to_owned())
                 } else {
-                    Token {
-                        kind: TokenKind::Illegal,
-                        literal: self.ch.to_string(),
+                    (TokenKind::Pipe, "|".to_owned())
+                }
+            }
+            '.' => {
+                if self.peek_char() == '.' {
+                    self.eat_char();
+
+                    if self.peek_char() == '=' {
+                        self.eat_char();
+                        (TokenKind::DotDotEqual, "..=".to_owned())
+                    } else if self.peek_char() == '.' {
+                        self.eat_char();
+                        (TokenKind::Ellipsis, "...".to_owned())
+                    } else {
+                        (TokenKind::DotDot, "..".to_owned())
                     }
+                } else {
+                    (TokenKind::Dot, ".".to_owned())
                 }
             }
-            '+' => Token {
-                kind: TokenKind::Plus,
-                literal: "+".to_owned(),
-            },
-            '-' => Token {
-                kind: TokenKind::Minus,
-                literal: "-".to_owned(),
-            },
-            '/' => Token {
-                kind: TokenKind::Slash,
-                literal: "/".to_owned(),
-            },
-            '*' => Token {
-                kind: TokenKind::Asterisk,
-                literal: "*".to_owned(),
-            },
-            '%' => Token {
-                kind: TokenKind::Percentage,
-                literal: "%".to_owned(),
-            },
-            '(' => Token {
-                kind: TokenKind::LeftParen,
-                literal: "(".to_owned(),
-            },
-            ')' => Token {
-                kind: TokenKind::RightParen,
-                literal: ")".to_owned(),
-            },
-            '{' => Token {
-                kind: TokenKind::LeftBrace,
-                literal: "{".to_owned(),
-            },
-            '}' => Token {
-                kind: TokenKind::RightBrace,
-                literal: "}".to_owned(),
-            },
-            '[' => Token {
-                kind: TokenKind::LeftSquare,
-                literal: "[".to_owned(),
-            },
-            ']' => Token {
-                kind: TokenKind::RightSquare,
-                literal: "]".to_owned(),
-            },
-            ':' => Token {
-                kind: TokenKind::Colon,
-                literal: ":".to_owned(),
-            },
-            ';' => Token {
-                kind: TokenKind::Semicolon,
-                literal: ";".to_owned(),
-            },
-            ',' => Token {
-                kind: TokenKind::Comma,
-                literal: ",".to_owned(),
-            },
+            '+' => (TokenKind::Plus, "+".to_owned()),
+            '-' => (TokenKind::Minus, "-".to_owned()),
+            '/' => (TokenKind::Slash, "/".to_owned()),
+            '*' => {
+                if self.peek_char() == '*' {
+                    self.eat_char();
+                    (TokenKind::DoubleAsterisk, "**".to_owned())
+                } else {
+                    (TokenKind::Asterisk, "*".to_owned())
+                }
+            }
+            '%' => (TokenKind::Percentage, "%".to_owned()),
+            '^' => (TokenKind::Caret, "^".to_owned()),
+            '~' => (TokenKind::Tilde, "~".to_owned()),
+            '?' => (TokenKind::Question, "?".to_owned()),
+            '@' => (TokenKind::At, "@".to_owned()),
+            '(' => (TokenKind::LeftParen, "(".to_owned()),
+            ')' => (TokenKind::RightParen, ")".to_owned()),
+            '{' => (TokenKind::LeftBrace, "{".to_owned()),
+            '}' => (TokenKind::RightBrace, "}".to_owned()),
+            '[' => (TokenKind::LeftSquare, "[".to_owned()),
+            ']' => (TokenKind::RightSquare, "]".to_owned()),
+            ':' => (TokenKind::Colon, ":".to_owned()),
+            ';' => (TokenKind::Semicolon, ";".to_owned()),
+            ',' => (TokenKind::Comma, ",".to_owned()),
             '"' => {
                 let literal = self.eat_string().to_owned();
 
-                Token {
-                    kind: TokenKind::String,
-                    literal,
+                (TokenKind::String, literal)
+            }
+            '`' => {
+                let literal = self.eat_raw_string().to_owned();
+
+                (TokenKind::String, literal)
+            }
+            '\'' => {
+                let next = self.peek_char();
+                if is_identifier_start(next) {
+                    let start = self.cur;
+                    self.eat_char();
+                    self.eat_identifier();
+
+                    return (TokenKind::Label, self.input[start..self.cur].to_owned());
                 }
+
+                (TokenKind::Illegal, "'".to_owned())
             }
-            EOF_CHAR => Token {
-                kind: TokenKind::Eof,
-                literal: "".to_owned(),
-            },
+            EOF_CHAR => (TokenKind::Eof, "".to_owned()),
             _ => {
-                if self.ch.is_alphabetic() || self.ch == '_' {
+                if is_identifier_start(self.ch) {
                     let literal = self.eat_identifier();
-                    let kind = TokenKind::lookup_identifier(literal);
+                    let kind = self.keywords.lookup(literal);
 
-                    return Token {
-                        kind,
-                        literal: literal.to_owned(),
-                    };
+                    return (kind, literal.to_owned());
                 } else if self.ch.is_ascii_digit() {
-                    let literal = self.eat_number().to_owned();
-
-                    return Token {
-                        kind: TokenKind::Integer,
-                        literal,
+                    // digit separators are only there for readability
+                    let literal = self.eat_number().replace('_', "");
+                    let kind = if literal.contains('.') {
+                        TokenKind::Float
+                    } else {
+                        TokenKind::Integer
                     };
+
+                    return (kind, literal);
                 } else {
-                    Token {
-                        kind: TokenKind::Illegal,
-                        literal: self.ch.to_string(),
-                    }
+                    (TokenKind::Illegal, self.ch.to_string())
                 }
             }
         };
@@ -399,4 +499,396 @@ mod tests {
 
         test_tokenization_iter(input, tests)
     }
+
+    #[test]
+    fn next_token4() {
+        let input = r#"
+            case 1 | 2: x
+            case [first, ..rest]: first
+        "#;
+
+        let tests = vec![
+            (TokenKind::Case, "case"),
+            (TokenKind::Integer, "1"),
+            (TokenKind::Pipe, "|"),
+            (TokenKind::Integer, "2"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::Case, "case"),
+            (TokenKind::LeftSquare, "["),
+            (TokenKind::Identifier, "first"),
+            (TokenKind::Comma, ","),
+            (TokenKind::DotDot, ".."),
+            (TokenKind::Identifier, "rest"),
+            (TokenKind::RightSquare, "]"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Identifier, "first"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token5() {
+        let input = "1..10; 1..=10";
+
+        let tests = vec![
+            (TokenKind::Integer, "1"),
+            (TokenKind::DotDot, ".."),
+            (TokenKind::Integer, "10"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Integer, "1"),
+            (TokenKind::DotDotEqual, "..="),
+            (TokenKind::Integer, "10"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_ellipsis() {
+        let input = "fn(a, ...rest) 1...2";
+
+        let tests = vec![
+            (TokenKind::Function, "fn"),
+            (TokenKind::LeftParen, "("),
+            (TokenKind::Identifier, "a"),
+            (TokenKind::Comma, ","),
+            (TokenKind::Ellipsis, "..."),
+            (TokenKind::Identifier, "rest"),
+            (TokenKind::RightParen, ")"),
+            (TokenKind::Integer, "1"),
+            (TokenKind::Ellipsis, "..."),
+            (TokenKind::Integer, "2"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_question() {
+        let input = "f()?.x?";
+
+        let tests = vec![
+            (TokenKind::Identifier, "f"),
+            (TokenKind::LeftParen, "("),
+            (TokenKind::RightParen, ")"),
+            (TokenKind::Question, "?"),
+            (TokenKind::Dot, "."),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::Question, "?"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_attribute() {
+        let input = r#"@deprecated("x")"#;
+
+        let tests = vec![
+            (TokenKind::At, "@"),
+            (TokenKind::Identifier, "deprecated"),
+            (TokenKind::LeftParen, "("),
+            (TokenKind::String, "x"),
+            (TokenKind::RightParen, ")"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_loop_keywords() {
+        let input = "loop { continue; break x; } do {} while switch x { _ => 1 } struct enum";
+
+        let tests = vec![
+            (TokenKind::Loop, "loop"),
+            (TokenKind::LeftBrace, "{"),
+            (TokenKind::Continue, "continue"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Break, "break"),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::RightBrace, "}"),
+            (TokenKind::Do, "do"),
+            (TokenKind::LeftBrace, "{"),
+            (TokenKind::RightBrace, "}"),
+            (TokenKind::While, "while"),
+            (TokenKind::Switch, "switch"),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::LeftBrace, "{"),
+            (TokenKind::Identifier, "_"),
+            (TokenKind::FatArrow, "=>"),
+            (TokenKind::Integer, "1"),
+            (TokenKind::RightBrace, "}"),
+            (TokenKind::Struct, "struct"),
+            (TokenKind::Enum, "enum"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_labels() {
+        let input = "'outer: for x in xs { break 'outer; } ' x";
+
+        let tests = vec![
+            (TokenKind::Label, "'outer"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::For, "for"),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::In, "in"),
+            (TokenKind::Identifier, "xs"),
+            (TokenKind::LeftBrace, "{"),
+            (TokenKind::Break, "break"),
+            (TokenKind::Label, "'outer"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::RightBrace, "}"),
+            (TokenKind::Illegal, "'"),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_pipeline() {
+        let input = "xs |> f(1) | g || h";
+
+        let tests = vec![
+            (TokenKind::Identifier, "xs"),
+            (TokenKind::Pipeline, "|>"),
+            (TokenKind::Identifier, "f"),
+            (TokenKind::LeftParen, "("),
+            (TokenKind::Integer, "1"),
+            (TokenKind::RightParen, ")"),
+            (TokenKind::Pipe, "|"),
+            (TokenKind::Identifier, "g"),
+            (TokenKind::OrOr, "||"),
+            (TokenKind::Identifier, "h"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_bitwise_operators() {
+        let input = "a & b | c ^ ~d << 1 >> 2 && e";
+
+        let tests = vec![
+            (TokenKind::Identifier, "a"),
+            (TokenKind::Ampersand, "&"),
+            (TokenKind::Identifier, "b"),
+            (TokenKind::Pipe, "|"),
+            (TokenKind::Identifier, "c"),
+            (TokenKind::Caret, "^"),
+            (TokenKind::Tilde, "~"),
+            (TokenKind::Identifier, "d"),
+            (TokenKind::ShiftLeft, "<<"),
+            (TokenKind::Integer, "1"),
+            (TokenKind::ShiftRight, ">>"),
+            (TokenKind::Integer, "2"),
+            (TokenKind::AndAnd, "&&"),
+            (TokenKind::Identifier, "e"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_exponentiation() {
+        let input = "2 ** 3 * 4 *** 5";
+
+        let tests = vec![
+            (TokenKind::Integer, "2"),
+            (TokenKind::DoubleAsterisk, "**"),
+            (TokenKind::Integer, "3"),
+            (TokenKind::Asterisk, "*"),
+            (TokenKind::Integer, "4"),
+            (TokenKind::DoubleAsterisk, "**"),
+            (TokenKind::Asterisk, "*"),
+            (TokenKind::Integer, "5"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_floats() {
+        let input = "3.14 1.0..2 7.";
+
+        let tests = vec![
+            (TokenKind::Float, "3.14"),
+            (TokenKind::Float, "1.0"),
+            (TokenKind::DotDot, ".."),
+            (TokenKind::Integer, "2"),
+            (TokenKind::Integer, "7"),
+            (TokenKind::Dot, "."),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_radix_integers() {
+        let input = "0x1F 0o17 0b1010 0xZZ 0b 0.5";
+
+        let tests = vec![
+            (TokenKind::Integer, "0x1F"),
+            (TokenKind::Integer, "0o17"),
+            (TokenKind::Integer, "0b1010"),
+            (TokenKind::Integer, "0xZZ"),
+            (TokenKind::Integer, "0b"),
+            (TokenKind::Float, "0.5"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_digit_separators() {
+        let input = "1_000_000 3_000.141_5 0xFF_FF 0b1010_1010 1__0 2_";
+
+        let tests = vec![
+            (TokenKind::Integer, "1000000"),
+            (TokenKind::Float, "3000.1415"),
+            (TokenKind::Integer, "0xFFFF"),
+            (TokenKind::Integer, "0b10101010"),
+            // separators must be placed between digits
+            (TokenKind::Integer, "1"),
+            (TokenKind::Identifier, "__0"),
+            (TokenKind::Integer, "2"),
+            (TokenKind::Identifier, "_"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_raw_strings() {
+        let input = "`say \"hi\"\n  \\d+` + ``;";
+
+        let tests = vec![
+            (TokenKind::String, "say \"hi\"\n  \\d+"),
+            (TokenKind::Plus, "+"),
+            (TokenKind::String, ""),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests)
+    }
+
+    #[test]
+    fn next_token_comments() {
+        let input = "1 // one\n/* two\n 2 */ 3 /**/ 4 / 5 //";
+
+        let tests = vec![
+            (TokenKind::Integer, "1"),
+            (TokenKind::Integer, "3"),
+            (TokenKind::Integer, "4"),
+            (TokenKind::Slash, "/"),
+            (TokenKind::Integer, "5"),
+            (TokenKind::Eof, ""),
+        ];
+
+        test_tokenization_iter(input, tests);
+
+        let mut lexer = Lexer::new(input);
+        while lexer.next_token().kind != TokenKind::Eof {}
+        let comments: Vec<&str> = lexer
+            .comments
+            .iter()
+            .map(|span| &input[span.start..span.end])
+            .collect();
+        assert_eq!(comments, vec!["// one", "/* two\n 2 */", "/**/", "//"]);
+        assert_eq!(lexer.unterminated_comment, None);
+
+        let mut lexer = Lexer::new("1 /* 2 */ /*/ 3");
+        assert_eq!(lexer.next_token().kind, TokenKind::Integer);
+        assert_eq!(lexer.next_token().kind, TokenKind::Eof);
+        assert_eq!(
+            lexer.unterminated_comment,
+            Some(Span { start: 10, end: 15 })
+        );
+    }
+
+    #[test]
+    fn next_token_unicode_identifiers() {
+        // decomposed accents, Devanagari vowel signs and viramas are combining marks
+        let input = "let π = 3; let 変数_1 = π; 'ループ: loop {} café नमस्ते x\u{301} _a ½ €";
+        let mut lexer = Lexer::new(input);
+
+        let expected = vec![
+            (TokenKind::Let, "let"),
+            (TokenKind::Identifier, "π"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Integer, "3"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Let, "let"),
+            (TokenKind::Identifier, "変数_1"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Identifier, "π"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Label, "'ループ"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Loop, "loop"),
+            (TokenKind::LeftBrace, "{"),
+            (TokenKind::RightBrace, "}"),
+            (TokenKind::Identifier, "café"),
+            (TokenKind::Identifier, "नमस्ते"),
+            (TokenKind::Identifier, "x\u{301}"),
+            (TokenKind::Identifier, "_a"),
+            // numbers and symbols aren't identifiers, even if they're alphanumeric or letter-like
+            (TokenKind::Illegal, "½"),
+            (TokenKind::Illegal, "€"),
+            (TokenKind::Eof, ""),
+        ];
+
+        for (kind, source) in expected {
+            let tok = lexer.next_token();
+            assert_eq!(tok.kind, kind, "{source}");
+            assert_eq!(tok.span.end - tok.span.start, source.len(), "{source}");
+            assert_eq!(&input[tok.span.start..tok.span.end], source);
+        }
+
+        assert!(is_identifier_like("größe"));
+        assert!(!is_identifier_like("1st"));
+        assert!(!is_identifier_like("a-b"));
+        assert!(!is_identifier_like(""));
+    }
+
+    #[test]
+    fn token_spans() {
+        let input = "let héllo = \"ü\";";
+        let mut lexer = Lexer::new(input);
+
+        let expected = vec![
+            (TokenKind::Let, "let"),
+            (TokenKind::Identifier, "héllo"),
+            (TokenKind::Assign, "="),
+            (TokenKind::String, "\"ü\""),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Eof, ""),
+        ];
+
+        for (kind, source) in expected {
+            let tok = lexer.next_token();
+            assert_eq!(tok.kind, kind);
+            assert_eq!(&input[tok.span.start..tok.span.end], source);
+        }
+    }
 }
@@ -1,7 +1,49 @@
+// lets the code generated by `qalo_macros` refer to this crate as `::qalo`, even from within it
+extern crate self as qalo;
+
 pub mod ast;
+pub mod cache;
+pub mod capture;
+pub mod cst;
+pub mod diagnostic;
+pub mod diff;
+pub mod dot;
 pub mod environment;
 pub mod evaluator;
+pub mod event_loop;
+pub mod events;
+pub mod explain;
+pub mod features;
+pub mod formatter;
+pub mod gc;
+pub mod golden;
+pub mod highlight;
+pub mod incremental;
+pub mod json;
+pub mod keywords;
 pub mod lexer;
+pub mod log;
+pub mod manifest;
+pub mod native;
+pub mod notebook;
 pub mod object;
+pub mod observer;
+mod parallel;
 pub mod parser;
+pub mod purity;
+pub mod repl;
+pub mod replay;
+pub mod sandbox;
+pub mod source_map;
+pub mod spec;
+pub mod streams;
+pub mod suggestion;
+pub mod template;
 pub mod token;
+pub mod unicode;
+pub mod warning;
+
+pub use qalo_macros::function;
+
+/// Version of the crate, which is also the version of the language it implements.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
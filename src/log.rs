@@ -0,0 +1,105 @@
+use std::fmt::{self, Debug};
+
+use crate::object::Object;
+
+/// Severity of a log record, from the least to the most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    /// Matches the names used by scripts, like `"warn"`.
+    pub fn parse(name: &str) -> Option<LogLevel> {
+        LogLevel::ALL
+            .into_iter()
+            .find(|level| level.to_string() == name)
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogLevel::Debug => write!(f, "debug"),
+            LogLevel::Info => write!(f, "info"),
+            LogLevel::Warn => write!(f, "warn"),
+            LogLevel::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Structured record emitted by the `log` built-in function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub message: String,
+    /// Sorted by name, like the keys of hash maps.
+    pub fields: Vec<(String, Object)>,
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.level, self.message)?;
+        for (name, value) in &self.fields {
+            write!(f, " {name}={value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Receives the records logged by scripts, so that hosts can forward them to their own logging pipeline.
+pub trait LogSink: Debug {
+    fn log(&mut self, record: &LogRecord);
+}
+
+/// Writes every record on a line of the standard error, like `[info] started port=8080`.
+#[derive(Debug, Default)]
+pub struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn log(&mut self, record: &LogRecord) {
+        eprintln!("{record}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_log_levels() {
+        for level in LogLevel::ALL {
+            assert_eq!(LogLevel::parse(&level.to_string()), Some(level));
+        }
+        assert_eq!(LogLevel::parse("WARN"), None);
+        assert_eq!(LogLevel::parse("trace"), None);
+        assert!(LogLevel::Debug < LogLevel::Error);
+    }
+
+    #[test]
+    fn display_log_records() {
+        let record = LogRecord {
+            level: LogLevel::Warn,
+            message: "slow request".to_owned(),
+            fields: vec![
+                ("ms".to_owned(), Object::IntegerValue(812)),
+                ("path".to_owned(), Object::StringValue("/users".to_owned())),
+            ],
+        };
+        assert_eq!(
+            record.to_string(),
+            "[warn] slow request ms=812 path=\"/users\""
+        );
+    }
+}
@@ -1,22 +1,237 @@
-use std::{env, error::Error, fs, process};
+use std::{
+    cell::RefCell,
+    env,
+    error::Error,
+    fs,
+    io::{self, IsTerminal},
+    path::Path,
+    process,
+    rc::Rc,
+};
 
-use qalo::evaluator::Evaluator;
+use qalo::{
+    diagnostic::render_diagnostics,
+    diff::{highlight_diff, unified_diff},
+    dot::program_to_dot,
+    evaluator::Evaluator,
+    explain::explain,
+    formatter::format_source,
+    keywords::Keywords,
+    manifest::Manifest,
+    notebook,
+    observer::{ProfileObserver, TraceObserver},
+    parser::Parser,
+    repl,
+    replay::InputLog,
+    spec::run_specs,
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let files = env::args()
-        .into_iter()
-        .filter(|file| file.ends_with(".ql"))
-        .collect::<Vec<String>>();
+    if env::args().nth(1).as_deref() == Some("explain") {
+        let code = env::args().nth(2).unwrap_or_default();
+        match explain(&code) {
+            Some(explanation) => print!("{explanation}"),
+            None => {
+                eprintln!("Unknown error code `{code}`");
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if env::args().nth(1).as_deref() == Some("serve") {
+        if !env::args().any(|arg| arg == "--jsonrpc") {
+            eprintln!("Usage: qalo serve --jsonrpc");
+            process::exit(1);
+        }
+        notebook::serve(io::stdin().lock(), io::stdout())?;
+        return Ok(());
+    }
+
+    if env::args().nth(1).as_deref() == Some("spec") {
+        let dir = env::args().nth(2).unwrap_or_else(|| "spec".to_owned());
+        let results = run_specs(Path::new(&dir)).unwrap_or_else(|err| {
+            eprintln!("Failed to read the specs in {dir}: {err}");
+            process::exit(1);
+        });
+        let mut failed = 0;
+        for (path, result) in &results {
+            if let Err(failure) = result {
+                println!("FAIL {}: {failure}", path.display());
+                failed += 1;
+            }
+        }
+        println!("{} passed, {failed} failed", results.len() - failed);
+
+        process::exit(i32::from(failed > 0));
+    }
+
+    if env::args().nth(1).as_deref() == Some("fmt") {
+        let check = env::args().any(|arg| arg == "--check");
+        let mut unformatted = false;
+        for file in env::args().skip(2).filter(|arg| arg.ends_with(".ql")) {
+            let source = fs::read_to_string(&file).expect("Failed to read a file");
+            let formatted = format_source(&source).unwrap_or_else(|err| {
+                eprintln!("{file}: | Qalo Error {} |\n{err}", err.code());
+                process::exit(1);
+            });
+            if formatted == source {
+                continue;
+            }
+
+            if !check {
+                fs::write(&file, formatted).expect("Failed to write a file");
+                continue;
+            }
+            unformatted = true;
+            match unified_diff(&source, &formatted, &file) {
+                // only the line endings differ
+                diff if diff.is_empty() => eprintln!("{file}: the line endings would change"),
+                diff if io::stdout().is_terminal() => print!("{}", highlight_diff(&diff)),
+                diff => print!("{diff}"),
+            }
+        }
+
+        process::exit(i32::from(unformatted));
+    }
+
+    // `ast --dot` prints the parse tree of each file as a Graphviz graph, without evaluating it
+    if env::args().nth(1).as_deref() == Some("ast") {
+        let files: Vec<String> = env::args()
+            .skip(2)
+            .filter(|arg| arg.ends_with(".ql"))
+            .collect();
+        if !env::args().any(|arg| arg == "--dot") || files.is_empty() {
+            eprintln!("Usage: qalo ast --dot <file.ql>... [--keywords <table>]");
+            process::exit(1);
+        }
+        let keywords = env::args()
+            .skip_while(|arg| arg != "--keywords")
+            .nth(1)
+            .map_or_else(Keywords::new, |path| read_keywords(&path));
+
+        for file in files {
+            let source = fs::read_to_string(&file).expect("Failed to read a file");
+            let mut parser = Parser::with_keywords(&source, Rc::new(keywords.clone()));
+            let program = parser.parse_program().unwrap_or_else(|err| {
+                eprint!(
+                    "{}",
+                    render_diagnostics(&file, &source, &[], Some(&err.into()))
+                );
+                process::exit(1);
+            });
+            print!("{}", program_to_dot(&program));
+        }
+
+        return Ok(());
+    }
+
+    // `run` evaluates the entry point of the project in the given directory, with the settings of its manifest
+    let manifest = (env::args().nth(1).as_deref() == Some("run")).then(|| {
+        let root = env::args().nth(2).filter(|arg| !arg.starts_with("--"));
+        Manifest::load(Path::new(root.as_deref().unwrap_or("."))).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            process::exit(1);
+        })
+    });
+
+    let trace = env::args().any(|arg| arg == "--trace");
+    let checked = env::args().any(|arg| arg == "--checked")
+        || manifest
+            .as_ref()
+            .is_some_and(|manifest| manifest.checked_arithmetic);
+    let truthy = env::args().any(|arg| arg == "--truthy")
+        || manifest
+            .as_ref()
+            .is_some_and(|manifest| manifest.truthy_conditions);
+    let gc = env::args().any(|arg| arg == "--gc")
+        || manifest
+            .as_ref()
+            .is_some_and(|manifest| manifest.garbage_collection);
+    let profile = env::args().skip_while(|arg| arg != "--profile").nth(1);
+    let record = env::args().skip_while(|arg| arg != "--record").nth(1);
+    let replay = env::args().skip_while(|arg| arg != "--replay").nth(1);
+    // a single log covers the inputs of every file, in the order they're run
+    let inputs = match &replay {
+        Some(path) => {
+            let text = fs::read_to_string(path).expect("Failed to read the input log");
+            InputLog::parse(&text).unwrap_or_else(|err| {
+                eprintln!("{path}: {err}");
+                process::exit(1);
+            })
+        }
+        None => InputLog::default(),
+    };
+    // a single profile covers every file
+    let profiler = Rc::new(RefCell::new(ProfileObserver::new()));
+    let keyword_table = env::args()
+        .skip_while(|arg| arg != "--keywords")
+        .nth(1)
+        .or_else(|| {
+            let path = manifest.as_ref()?.keywords.as_ref()?;
+            Some(path.display().to_string())
+        });
+    let keywords = keyword_table.map_or_else(Keywords::new, |path| read_keywords(&path));
+    let files = match &manifest {
+        Some(manifest) => vec![manifest.entry.display().to_string()],
+        None => env::args()
+            .filter(|file| file.ends_with(".ql"))
+            .collect::<Vec<String>>(),
+    };
+
+    if files.is_empty() {
+        repl::run(io::stdin().lock(), io::stdout())?;
+        return Ok(());
+    }
 
     for file in files {
-        let source = fs::read_to_string(file).expect("Failed to read a file");
+        let source = fs::read_to_string(&file).expect("Failed to read a file");
 
         let mut evaluator = Evaluator::new(&source);
-        evaluator.eval_program().unwrap_or_else(|err| {
-            eprintln!("| Qalo Error |\n{err}");
+        evaluator.set_keywords(keywords.clone());
+        if trace {
+            evaluator.set_observer(Box::new(TraceObserver::new(&source)));
+        } else if profile.is_some() {
+            evaluator.set_observer(Box::new(profiler.clone()));
+        }
+        evaluator.set_checked_arithmetic(checked);
+        evaluator.set_truthy_conditions(truthy);
+        evaluator.set_garbage_collection(gc);
+        if replay.is_some() {
+            evaluator.replay_inputs(inputs.clone());
+        } else if record.is_some() {
+            evaluator.record_inputs(inputs.clone());
+        }
+        // the callbacks scheduled by the program run once it's evaluated
+        let result = evaluator
+            .eval_program()
+            .and_then(|_| evaluator.run_event_loop());
+        if let Some(path) = &profile {
+            fs::write(path, profiler.borrow().folded()).expect("Failed to write the profile");
+        }
+        // written after each file, for the log to hold the inputs of a run that fails
+        if let Some(path) = &record {
+            fs::write(path, inputs.to_json_lines()).expect("Failed to write the input log");
+        }
+
+        let err = result.err();
+        eprint!(
+            "{}",
+            render_diagnostics(&file, &source, evaluator.warnings(), err.as_ref())
+        );
+        if err.is_some() {
             process::exit(1);
-        });
+        }
     }
 
     Ok(())
 }
+
+/// Reads the keyword aliases of the table at `path`, exiting if it's invalid.
+fn read_keywords(path: &str) -> Keywords {
+    let table = fs::read_to_string(path).expect("Failed to read the keyword table");
+    Keywords::parse(&table).unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        process::exit(1);
+    })
+}
@@ -0,0 +1,250 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::suggestion::{closest_matches, format_suggestions};
+
+/// Keys a manifest can set.
+const KEYS: [&str; 3] = ["entry", "keywords", "capabilities"];
+
+/// Capabilities a manifest can enable, named like the fields of the `features` built-in function.
+const CAPABILITIES: [&str; 3] = [
+    "checked_arithmetic",
+    "truthy_conditions",
+    "garbage_collection",
+];
+
+/// Settings of a project, read from the `qalo.toml` file at its root, so that `qalo run`
+/// evaluates its entry point the way the project expects without passing flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Script evaluated by `qalo run`.
+    pub entry: PathBuf,
+    /// Keyword table read like the `--keywords` flag does.
+    pub keywords: Option<PathBuf>,
+    /// See `Evaluator::set_checked_arithmetic`.
+    pub checked_arithmetic: bool,
+    /// See `Evaluator::set_truthy_conditions`.
+    pub truthy_conditions: bool,
+    /// See `Evaluator::set_garbage_collection`.
+    pub garbage_collection: bool,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ManifestError {
+    #[error("Failed to read {0}: {1}")]
+    Unreadable(String, String),
+    #[error("Line {0} of the manifest should look like `key = value`")]
+    MalformedLine(usize),
+    #[error("Line {0} of the manifest has an unknown key `{1}`{}", format_suggestions(.2))]
+    UnknownKey(usize, String, Vec<String>),
+    #[error("Line {0} of the manifest sets `{1}` again")]
+    DuplicateKey(usize, String),
+    #[error("Line {0} of the manifest should set `{1}` to {2}")]
+    InvalidValue(usize, String, &'static str),
+    #[error("Line {0} of the manifest enables an unknown capability `{1}`{}", format_suggestions(.2))]
+    UnknownCapability(usize, String, Vec<String>),
+    #[error("The manifest must set the `entry` script")]
+    MissingEntry,
+}
+
+impl Manifest {
+    /// Name of the manifest file at the root of a project.
+    pub const FILE: &'static str = "qalo.toml";
+
+    /// Reads a manifest written in a subset of TOML: `key = value` lines, whose values are strings
+    /// or arrays of strings written on a single line. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(source: &str) -> Result<Self, ManifestError> {
+        let mut manifest = Manifest::default();
+        let mut seen = vec![];
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let number = i + 1;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(ManifestError::MalformedLine(number))?;
+            let (key, value) = (key.trim(), value.trim());
+            if !KEYS.contains(&key) {
+                let suggestions = closest_matches(key, KEYS);
+                return Err(ManifestError::UnknownKey(
+                    number,
+                    key.to_owned(),
+                    suggestions,
+                ));
+            }
+            if seen.contains(&key) {
+                return Err(ManifestError::DuplicateKey(number, key.to_owned()));
+            }
+            seen.push(key);
+
+            match key {
+                "entry" | "keywords" => {
+                    let path = string(value).ok_or_else(|| {
+                        ManifestError::InvalidValue(number, key.to_owned(), "a string")
+                    })?;
+                    if key == "entry" {
+                        manifest.entry = PathBuf::from(path);
+                    } else {
+                        manifest.keywords = Some(PathBuf::from(path));
+                    }
+                }
+                _ => {
+                    let capabilities = strings(value).ok_or_else(|| {
+                        ManifestError::InvalidValue(number, key.to_owned(), "an array of strings")
+                    })?;
+                    for capability in capabilities {
+                        match capability {
+                            "checked_arithmetic" => manifest.checked_arithmetic = true,
+                            "truthy_conditions" => manifest.truthy_conditions = true,
+                            "garbage_collection" => manifest.garbage_collection = true,
+                            _ => {
+                                return Err(ManifestError::UnknownCapability(
+                                    number,
+                                    capability.to_owned(),
+                                    closest_matches(capability, CAPABILITIES),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !seen.contains(&"entry") {
+            return Err(ManifestError::MissingEntry);
+        }
+
+        Ok(manifest)
+    }
+
+    /// Reads the manifest of the project at `root`, resolving its paths from there.
+    pub fn load(root: &Path) -> Result<Self, ManifestError> {
+        let path = root.join(Self::FILE);
+        let source = fs::read_to_string(&path).map_err(|err| {
+            ManifestError::Unreadable(path.display().to_string(), err.to_string())
+        })?;
+
+        let mut manifest = Self::parse(&source)?;
+        manifest.entry = root.join(&manifest.entry);
+        manifest.keywords = manifest.keywords.map(|keywords| root.join(keywords));
+
+        Ok(manifest)
+    }
+}
+
+/// Contents of a string written between double quotes, which can't contain escapes.
+fn string(value: &str) -> Option<&str> {
+    let contents = value.strip_prefix('"')?.strip_suffix('"')?;
+    (!contents.contains(['"', '\\'])).then_some(contents)
+}
+
+/// Strings of an array written between brackets, with an optional trailing comma.
+fn strings(value: &str) -> Option<Vec<&str>> {
+    let elements = value.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let elements = elements.strip_suffix(',').unwrap_or(elements);
+    if elements.trim().is_empty() {
+        return Some(vec![]);
+    }
+
+    elements
+        .split(',')
+        .map(|element| string(element.trim()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_manifest() {
+        let source = r#"
+            # the script run by `qalo run`
+            entry = "src/main.ql"
+            keywords = "keywords.txt"
+            capabilities = ["checked_arithmetic", "truthy_conditions", "garbage_collection",]
+        "#;
+        assert_eq!(
+            Manifest::parse(source),
+            Ok(Manifest {
+                entry: PathBuf::from("src/main.ql"),
+                keywords: Some(PathBuf::from("keywords.txt")),
+                checked_arithmetic: true,
+                truthy_conditions: true,
+                garbage_collection: true,
+            })
+        );
+        assert_eq!(
+            Manifest::parse("entry = \"main.ql\"\ncapabilities = []"),
+            Ok(Manifest {
+                entry: PathBuf::from("main.ql"),
+                ..Manifest::default()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_invalid_manifest() {
+        let tests = [
+            (
+                "entry",
+                "Line 1 of the manifest should look like `key = value`",
+            ),
+            (
+                "entri = \"main.ql\"",
+                "Line 1 of the manifest has an unknown key `entri`. Did you mean \"entry\"?",
+            ),
+            (
+                "entry = \"a.ql\"\nentry = \"b.ql\"",
+                "Line 2 of the manifest sets `entry` again",
+            ),
+            (
+                "entry = main.ql",
+                "Line 1 of the manifest should set `entry` to a string",
+            ),
+            (
+                "entry = \"main.ql\"\ncapabilities = \"gc\"",
+                "Line 2 of the manifest should set `capabilities` to an array of strings",
+            ),
+            (
+                "entry = \"main.ql\"\ncapabilities = [\"checked_arithmetics\"]",
+                "Line 2 of the manifest enables an unknown capability `checked_arithmetics`. \
+                 Did you mean \"checked_arithmetic\"?",
+            ),
+            (
+                "capabilities = []",
+                "The manifest must set the `entry` script",
+            ),
+        ];
+
+        for (source, expected) in tests {
+            assert_eq!(
+                Manifest::parse(source).unwrap_err().to_string(),
+                expected,
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn load_manifest() {
+        let root = std::env::temp_dir().join(format!("qalo-manifest-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(Manifest::FILE), "entry = \"main.ql\"").unwrap();
+
+        let manifest = Manifest::load(&root);
+        let missing = Manifest::load(&root.join("missing"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(manifest.unwrap().entry, root.join("main.ql"));
+        assert!(matches!(missing, Err(ManifestError::Unreadable(..))));
+    }
+}
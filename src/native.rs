@@ -0,0 +1,397 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    rc::Rc,
+};
+
+use crate::{
+    ast::ParserError,
+    object::{EvalError, Object},
+};
+
+/// Signature of the Rust functions that hosts expose to scripts.
+pub type NativeFn = dyn Fn(Vec<Object>) -> Result<Object, EvalError>;
+
+type FinalizerFn = dyn FnOnce(&dyn Any);
+
+/// Opaque Rust value handed to scripts, like a database connection or a file handle.
+/// Scripts can only pass it around: native functions get it back through `downcast_ref`.
+#[derive(Clone)]
+pub struct NativeHandle {
+    /// Name shown to scripts by `type()` and when the value is printed.
+    pub type_name: &'static str,
+    pub value: Rc<dyn Any>,
+    /// Only held to be dropped: it is shared by all the copies of the handle,
+    /// so that the finalizer runs once the last one is gone.
+    _finalizer: Option<Rc<Finalizer>>,
+}
+
+impl NativeHandle {
+    pub fn new<T: Any>(type_name: &'static str, value: T) -> Self {
+        NativeHandle {
+            type_name,
+            value: Rc::new(value),
+            _finalizer: None,
+        }
+    }
+
+    /// Creates a handle running `finalizer` once scripts can no longer reach the value,
+    /// or at the latest when the evaluator is dropped. Useful to release resources like connections.
+    pub fn with_finalizer<T: Any>(
+        type_name: &'static str,
+        value: T,
+        finalizer: impl FnOnce(&T) + 'static,
+    ) -> Self {
+        let value: Rc<dyn Any> = Rc::new(value);
+        let callback: Box<FinalizerFn> = Box::new(move |value| {
+            // the value is always a `T`, as it's the one given to this function
+            if let Some(value) = value.downcast_ref::<T>() {
+                finalizer(value);
+            }
+        });
+
+        NativeHandle {
+            type_name,
+            value: value.clone(),
+            _finalizer: Some(Rc::new(Finalizer {
+                value,
+                callback: Some(callback),
+            })),
+        }
+    }
+
+    /// Returns the wrapped value if it's a `T`, otherwise a type mismatch error.
+    pub fn downcast_ref<T: Any>(&self) -> Result<&T, EvalError> {
+        self.value.downcast_ref::<T>().ok_or_else(|| {
+            EvalError::TypeMismatch(format!(
+                "Expected a native value of type `{}`, got `{}`",
+                std::any::type_name::<T>(),
+                self.type_name
+            ))
+        })
+    }
+}
+
+struct Finalizer {
+    value: Rc<dyn Any>,
+    callback: Option<Box<FinalizerFn>>,
+}
+
+impl Drop for Finalizer {
+    fn drop(&mut self) {
+        if let Some(callback) = self.callback.take() {
+            callback(self.value.as_ref());
+        }
+    }
+}
+
+impl fmt::Debug for NativeHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeHandle({})", self.type_name)
+    }
+}
+
+/// Two handles are equal only if they share the same value.
+impl PartialEq for NativeHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl fmt::Display for NativeHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native {}>", self.type_name)
+    }
+}
+
+/// Rust function registered by the host with `Evaluator::register_function`.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub function: Rc<NativeFn>,
+    /// Note telling scripts what to use instead, if the function is deprecated.
+    pub deprecated: Option<String>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        function: impl Fn(Vec<Object>) -> Result<Object, EvalError> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.to_owned(),
+            function: Rc::new(function),
+            deprecated: None,
+        }
+    }
+
+    /// Marks the function as deprecated: scripts calling it get a warning with `note`.
+    pub fn deprecated(mut self, note: &str) -> Self {
+        self.deprecated = Some(note.to_owned());
+        self
+    }
+
+    pub fn call(&self, arguments: Vec<Object>) -> Result<Object, EvalError> {
+        (self.function)(arguments)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "native function {}", self.name)
+    }
+}
+
+/// Conversion from script values to Rust values, used for the arguments of `#[qalo::function]`s.
+pub trait FromObject<'a>: Sized {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError>;
+}
+
+impl<'a> FromObject<'a> for i64 {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_int()
+    }
+}
+
+impl<'a> FromObject<'a> for i32 {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        Ok(i32::try_from(object.as_int()?).map_err(ParserError::IntConversionError)?)
+    }
+}
+
+impl<'a> FromObject<'a> for f64 {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_float()
+    }
+}
+
+impl<'a> FromObject<'a> for bool {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_bool()
+    }
+}
+
+impl<'a> FromObject<'a> for &'a str {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_str()
+    }
+}
+
+impl<'a> FromObject<'a> for String {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_str().map(str::to_owned)
+    }
+}
+
+impl<'a> FromObject<'a> for &'a [Object] {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_array()
+    }
+}
+
+impl<'a> FromObject<'a> for Vec<Object> {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_array().map(<[Object]>::to_vec)
+    }
+}
+
+impl<'a> FromObject<'a> for &'a BTreeMap<String, Object> {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        object.as_map()
+    }
+}
+
+impl<'a> FromObject<'a> for &'a Object {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        Ok(object)
+    }
+}
+
+impl<'a> FromObject<'a> for Object {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        Ok(object.clone())
+    }
+}
+
+/// `null` becomes `None`, any other value is converted to `T`.
+impl<'a, T: FromObject<'a>> FromObject<'a> for Option<T> {
+    fn from_object(object: &'a Object) -> Result<Self, EvalError> {
+        match object {
+            Object::NullValue => Ok(None),
+            _ => T::from_object(object).map(Some),
+        }
+    }
+}
+
+/// Conversion from Rust values to script values, used for the result of `#[qalo::function]`s.
+pub trait IntoObject {
+    fn into_object(self) -> Result<Object, EvalError>;
+}
+
+impl IntoObject for i64 {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::IntegerValue(self))
+    }
+}
+
+impl IntoObject for i32 {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::IntegerValue(self.into()))
+    }
+}
+
+impl IntoObject for f64 {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::FloatValue(self))
+    }
+}
+
+impl IntoObject for bool {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::BooleanValue(self))
+    }
+}
+
+impl IntoObject for String {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::StringValue(self))
+    }
+}
+
+impl IntoObject for &str {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::StringValue(self.to_owned()))
+    }
+}
+
+impl IntoObject for Vec<Object> {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::ArrayValue(self))
+    }
+}
+
+impl IntoObject for BTreeMap<String, Object> {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::MapValue(self))
+    }
+}
+
+impl IntoObject for HashMap<String, Object> {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::MapValue(self.into_iter().collect()))
+    }
+}
+
+impl IntoObject for Object {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(self)
+    }
+}
+
+impl IntoObject for () {
+    fn into_object(self) -> Result<Object, EvalError> {
+        Ok(Object::UnitValue)
+    }
+}
+
+/// `None` becomes `null`.
+impl<T: IntoObject> IntoObject for Option<T> {
+    fn into_object(self) -> Result<Object, EvalError> {
+        match self {
+            Some(value) => value.into_object(),
+            None => Ok(Object::NullValue),
+        }
+    }
+}
+
+/// Errors are propagated to the script.
+impl<T: IntoObject> IntoObject for Result<T, EvalError> {
+    fn into_object(self) -> Result<Object, EvalError> {
+        self?.into_object()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+
+    #[crate::function]
+    fn clamp(x: i64, lo: i64, hi: i64) -> i64 {
+        x.max(lo).min(hi)
+    }
+
+    #[crate::function]
+    fn greet(name: &str, excited: Option<bool>) -> String {
+        let mark = if excited == Some(true) { "!" } else { "." };
+        format!("Hello {name}{mark}")
+    }
+
+    #[crate::function]
+    fn checked_div(a: i64, b: i64) -> Result<i64, EvalError> {
+        a.checked_div(b).ok_or(EvalError::DivisionByZero)
+    }
+
+    fn eval(input: &str) -> Result<Object, EvalError> {
+        let mut evaluator = Evaluator::new(input);
+        clamp::register(&mut evaluator);
+        greet::register(&mut evaluator);
+        checked_div::register(&mut evaluator);
+
+        Ok(evaluator.eval_program()?.pop().unwrap())
+    }
+
+    #[test]
+    fn exported_functions() {
+        // the original function is still available to Rust code
+        assert_eq!(clamp(5, 0, 3), 3);
+        assert_eq!(clamp::NAME, "clamp");
+
+        assert_eq!(eval("clamp(12, 0, 10)").unwrap(), Object::IntegerValue(10));
+        assert_eq!(
+            eval(r#"greet("Ada", true)"#).unwrap(),
+            Object::StringValue("Hello Ada!".to_owned())
+        );
+        assert_eq!(
+            eval(r#"greet("Ada", null)"#).unwrap(),
+            Object::StringValue("Hello Ada.".to_owned())
+        );
+        assert_eq!(eval("checked_div(7, 2)").unwrap(), Object::IntegerValue(3));
+
+        let mut evaluator = Evaluator::new("math.clamp(-4, 0, 10)");
+        evaluator.register_module("math", [clamp::native_function()]);
+        assert_eq!(
+            evaluator.eval_program().unwrap().pop().unwrap(),
+            Object::IntegerValue(0)
+        );
+
+        let tests = vec![
+            (
+                "clamp(1, 2)",
+                "Function call with the wrong number of arguments. Expected 3, got 2",
+            ),
+            (
+                r#"clamp("1", 2, 3)"#,
+                "Type mismatch: Expected integer, got string \"1\"",
+            ),
+            ("checked_div(1, 0)", "Division by zero isn't allowed"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap_err().to_string(), expected, "{input}");
+        }
+    }
+}
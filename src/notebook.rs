@@ -0,0 +1,302 @@
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use crate::{
+    evaluator::Evaluator,
+    json::Json,
+    object::{EvalError, Object},
+    parser::Parser,
+    source_map::Location,
+    token::Span,
+};
+
+/// Cells of code evaluated one after the other in the same environment, like in a notebook.
+#[derive(Debug)]
+pub struct Notebook {
+    evaluator: Evaluator<'static>,
+    output: Rc<RefCell<String>>,
+}
+
+impl Default for Notebook {
+    fn default() -> Self {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut evaluator = Evaluator::new("");
+        evaluator.capture_output(output.clone());
+        // the requests come from the standard input, which cells can't read
+        evaluator.set_stdin(io::empty());
+
+        Notebook { evaluator, output }
+    }
+}
+
+/// Outcome of the evaluation of a cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellResult {
+    /// Value of the last statement of the cell, unless it failed or has no value.
+    pub value: Option<Object>,
+    /// Output of `print` and `println`, including the one written before an error.
+    pub stdout: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// Position in the cell, for the diagnostics that know where they come from.
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Notebook {
+    pub fn new() -> Self {
+        Notebook::default()
+    }
+
+    /// Evaluates a cell. Its bindings stay visible to the next cells, even if it fails after defining them.
+    pub fn eval_cell(&mut self, code: &str) -> CellResult {
+        let reported_warnings = self.evaluator.warnings().len();
+        let locate = |span: Span| Location::in_source("cell", code, span.start);
+
+        let result = Parser::interactive(code)
+            .parse_program()
+            .map_err(EvalError::from)
+            .and_then(|program| self.evaluator.eval_parsed_program(program));
+
+        let mut diagnostics: Vec<Diagnostic> = self.evaluator.warnings()[reported_warnings..]
+            .iter()
+            .map(|warning| Diagnostic {
+                severity: Severity::Warning,
+                code: None,
+                message: warning.to_string(),
+                location: Some(locate(warning.span())),
+            })
+            .collect();
+
+        let value = match result {
+            Ok(mut objects) => objects.pop().filter(|obj| *obj != Object::UnitValue),
+            Err(err) => {
+                let span = match err.root_cause() {
+                    EvalError::ParsingError(err) => err.span(),
+                    _ => None,
+                };
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    code: Some(err.code()),
+                    message: err.to_string(),
+                    location: span.map(locate),
+                });
+                None
+            }
+        };
+
+        CellResult {
+            value,
+            stdout: self.output.take(),
+            diagnostics,
+        }
+    }
+}
+
+impl From<CellResult> for Json {
+    fn from(result: CellResult) -> Self {
+        let diagnostics = result.diagnostics.into_iter().map(Json::from).collect();
+
+        Json::Object(vec![
+            (
+                "value".to_owned(),
+                result.value.as_ref().map(Object::to_string).into(),
+            ),
+            (
+                "type".to_owned(),
+                result.value.as_ref().map(Object::type_name).into(),
+            ),
+            ("stdout".to_owned(), result.stdout.into()),
+            ("diagnostics".to_owned(), Json::Array(diagnostics)),
+        ])
+    }
+}
+
+impl From<Diagnostic> for Json {
+    fn from(diagnostic: Diagnostic) -> Self {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let (line, column) = diagnostic
+            .location
+            .map(|location| (location.line, location.column))
+            .unzip();
+
+        Json::Object(vec![
+            ("severity".to_owned(), severity.into()),
+            ("code".to_owned(), diagnostic.code.into()),
+            ("message".to_owned(), diagnostic.message.into()),
+            ("line".to_owned(), line.into()),
+            ("column".to_owned(), column.into()),
+        ])
+    }
+}
+
+/// Serves a notebook over JSON-RPC 2.0, reading a request per line from `input`
+/// and writing a response per line to `output`.
+/// The `eval` method evaluates the cell in its `code` parameter, and `shutdown` ends the session.
+pub fn serve(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut notebook = Notebook::new();
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, response, shutdown) = match Json::parse(&line) {
+            Ok(request) => {
+                let id = request.get("id").cloned();
+                let shutdown = request.get("method").and_then(Json::as_str) == Some("shutdown");
+                (id, handle(&mut notebook, &request), shutdown)
+            }
+            Err(err) => (Some(Json::Null), Err((-32700, err.to_string())), false),
+        };
+
+        // requests without id are notifications, which don't get a response
+        if let Some(id) = id {
+            let outcome = match response {
+                Ok(result) => ("result".to_owned(), result),
+                Err((code, message)) => (
+                    "error".to_owned(),
+                    Json::Object(vec![
+                        ("code".to_owned(), Json::Number(code.into())),
+                        ("message".to_owned(), message.into()),
+                    ]),
+                ),
+            };
+            let response = Json::Object(vec![
+                ("jsonrpc".to_owned(), "2.0".into()),
+                ("id".to_owned(), id),
+                outcome,
+            ]);
+            writeln!(output, "{response}")?;
+            output.flush()?;
+        }
+
+        if shutdown {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Result of a request, or the code and message of its error.
+fn handle(notebook: &mut Notebook, request: &Json) -> Result<Json, (i32, String)> {
+    let method = request
+        .get("method")
+        .and_then(Json::as_str)
+        .ok_or((-32600, "Requests must have a `method` string".to_owned()))?;
+
+    match method {
+        "eval" => {
+            let code = request
+                .get("params")
+                .and_then(|params| params.get("code"))
+                .and_then(Json::as_str)
+                .ok_or((
+                    -32602,
+                    "`eval` expects a `code` string parameter".to_owned(),
+                ))?;
+
+            Ok(notebook.eval_cell(code).into())
+        }
+        "shutdown" => Ok(Json::Null),
+        method => Err((-32601, format!("Unknown method `{method}`"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_cells() {
+        let mut notebook = Notebook::new();
+
+        let result = notebook.eval_cell("let x = 2; println(\"x is\", x); x * 3");
+        assert_eq!(result.value, Some(Object::IntegerValue(6)));
+        assert_eq!(result.stdout, "\"x is\"\n2\n");
+        assert!(result.diagnostics.is_empty());
+
+        // the environment is kept between cells, and the output isn't repeated
+        let result = notebook.eval_cell("var y = x + 1;");
+        assert_eq!(result.value, None);
+        assert_eq!(result.stdout, "");
+
+        let result = notebook.eval_cell("print(y); y / 0");
+        assert_eq!(result.value, None);
+        assert_eq!(result.stdout, "3");
+        assert_eq!(
+            result.diagnostics,
+            vec![Diagnostic {
+                severity: Severity::Error,
+                code: Some("E1007"),
+                message: "Division by zero isn't allowed".to_owned(),
+                location: None,
+            }]
+        );
+
+        let result = notebook.eval_cell("let z = 1;\nlet = 2;");
+        assert_eq!(result.diagnostics[0].code, Some("E0001"));
+        assert_eq!(
+            result.diagnostics[0].location,
+            Some(Location {
+                file: "cell".to_owned(),
+                line: 2,
+                column: 5,
+            })
+        );
+
+        let result = notebook.eval_cell(
+            "@deprecated(\"use g\") let f = fn() { 1 };\nlet g = fn() { f() }; g() + g()",
+        );
+        assert_eq!(result.value, Some(Object::IntegerValue(2)));
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].severity, Severity::Warning);
+        assert_eq!(result.diagnostics[0].message, "`f` is deprecated: use g");
+    }
+
+    #[test]
+    fn serve_requests() {
+        let input = r#"{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"code": "let a = [1, 2]; println(len(a)); a"}}
+{"jsonrpc": "2.0", "method": "eval", "params": {"code": "var b = 1;"}}
+{"jsonrpc": "2.0", "id": "two", "method": "eval", "params": {"code": "b + c"}}
+{"jsonrpc": "2.0", "id": 3, "method": "eval", "params": {}}
+{"jsonrpc": "2.0", "id": 4, "method": "run"}
+{"jsonrpc": "2.0", "id": 5
+{"jsonrpc": "2.0", "id": 6, "method": "shutdown"}
+{"jsonrpc": "2.0", "id": 7, "method": "eval", "params": {"code": "1"}}
+"#;
+
+        let mut output = vec![];
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            r#"{"jsonrpc":"2.0","id":1,"result":{"value":"[1, 2]","type":"array","stdout":"2\n","diagnostics":[]}}"#,
+            r#"{"jsonrpc":"2.0","id":"two","result":{"value":null,"type":null,"stdout":"","diagnostics":[{"severity":"error","code":"E1001","message":"Identifier not found: c","line":null,"column":null}]}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"error":{"code":-32602,"message":"`eval` expects a `code` string parameter"}}"#,
+            r#"{"jsonrpc":"2.0","id":4,"error":{"code":-32601,"message":"Unknown method `run`"}}"#,
+            r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Invalid JSON at byte 26: expected `,` or `}`"}}"#,
+            r#"{"jsonrpc":"2.0","id":6,"result":null}"#,
+        ];
+        assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+    }
+}
@@ -1,31 +1,266 @@
-use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt, mem,
+    rc::{Rc, Weak},
+    sync::{mpsc::Sender, Arc, OnceLock},
+};
 
 use thiserror::Error;
 
 use crate::{
-    ast::{ParserError, Statement},
+    ast::{
+        format_parameters, format_variants, prelude_enum, EnumVariant, ParserError, Statement,
+        PRELUDE_VARIANTS,
+    },
     environment::Environment,
-    token::TokenKind,
+    native::{NativeFunction, NativeHandle},
+    parallel::{Mail, TaskThread},
+    replay::ReplayError,
+    suggestion::{closest_matches, format_suggestions},
+    token::{Span, TokenKind},
 };
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Object {
-    IntegerValue(i32),
+    IntegerValue(i64),
+    FloatValue(f64),
     BooleanValue(bool),
+    NullValue,
     StringValue(String),
     ArrayValue(Vec<Object>),
-    MapValue(HashMap<String, Object>),
-    ReturnValue(Box<Object>),
+    /// Array stored as a double-ended queue, which the binding of a `var` switches to when it's used
+    /// with `push_front` or `pop_front`. `len`, indexing and `for` loops read it in place, while it's
+    /// converted to an array when the binding is read as a value.
+    DequeValue(VecDeque<Object>),
+    MapValue(BTreeMap<String, Object>),
+    RangeValue {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
     FunctionValue(Closure),
     BuiltinValue(BuiltinFunction),
+    NativeFunctionValue(NativeFunction),
+    NativeValue(NativeHandle),
+    /// Reference to the binding of an array, hash map, struct or enum value, made by `weak(x)`.
+    WeakValue(WeakBinding),
+    /// Function running on its own thread, started by `spawn(f)`.
+    TaskValue(Task),
+    /// Address of an actor started by `actor(handler, state)`, to send it messages.
+    ActorValue(Actor),
+    /// Definition of a struct, bound to its name by `struct Point { x, y }`.
+    StructType {
+        name: String,
+        fields: Vec<String>,
+    },
+    /// Value of a struct, with its fields in the order of the definition.
+    StructValue {
+        name: String,
+        fields: Vec<(String, Object)>,
+    },
+    /// Definition of an enum, bound to its name by `enum Color { Red, Custom(r, g, b) }`.
+    EnumType {
+        name: String,
+        variants: Vec<EnumVariant>,
+    },
+    /// Variant of an enum, with the values of its fields.
+    EnumValue {
+        name: String,
+        variant: String,
+        values: Vec<Object>,
+    },
+    /// Variant of an enum with fields, like `Color.Custom`, called with their values to build it.
+    VariantConstructor {
+        name: String,
+        variant: EnumVariant,
+    },
+    /// Namespace of native functions registered with `Evaluator::register_module`.
+    ModuleValue {
+        name: String,
+        members: BTreeMap<String, Object>,
+    },
     UnitValue,
 }
 
+impl Object {
+    /// Builds the error returned by the `as_*` helpers when the value has another type.
+    fn type_mismatch(&self, expected: &str) -> EvalError {
+        let found = match self {
+            // the value would only repeat the type name
+            Object::NullValue | Object::UnitValue => self.type_name().to_owned(),
+            _ => format!("{} {self}", self.type_name()),
+        };
+
+        EvalError::TypeMismatch(format!("Expected {expected}, got {found}"))
+    }
+
+    pub fn as_int(&self) -> Result<i64, EvalError> {
+        match self {
+            Object::IntegerValue(value) => Ok(*value),
+            _ => Err(self.type_mismatch("integer")),
+        }
+    }
+
+    /// Integers are converted to floats, like in mixed arithmetic operations.
+    pub fn as_float(&self) -> Result<f64, EvalError> {
+        match self {
+            Object::FloatValue(value) => Ok(*value),
+            Object::IntegerValue(value) => Ok(*value as f64),
+            _ => Err(self.type_mismatch("float")),
+        }
+    }
+
+    /// Whether the value counts as true in the conditions of an evaluator with truthy conditions:
+    /// only `false`, `null`, zero, the empty string and the empty array count as false.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(
+            self,
+            Object::BooleanValue(false) | Object::NullValue | Object::IntegerValue(0)
+        ) && !matches!(self, Object::FloatValue(value) if *value == 0.0)
+            && !matches!(self, Object::StringValue(value) if value.is_empty())
+            && !matches!(self, Object::ArrayValue(elements) if elements.is_empty())
+    }
+
+    pub fn as_bool(&self) -> Result<bool, EvalError> {
+        match self {
+            Object::BooleanValue(value) => Ok(*value),
+            _ => Err(self.type_mismatch("boolean")),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, EvalError> {
+        match self {
+            Object::StringValue(value) => Ok(value),
+            _ => Err(self.type_mismatch("string")),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&[Object], EvalError> {
+        match self {
+            Object::ArrayValue(elements) => Ok(elements),
+            _ => Err(self.type_mismatch("array")),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&BTreeMap<String, Object>, EvalError> {
+        match self {
+            Object::MapValue(map) => Ok(map),
+            _ => Err(self.type_mismatch("map")),
+        }
+    }
+
+    /// Returns the Rust value of a native handle, checking that it's a `T`.
+    pub fn as_native<T: Any>(&self) -> Result<&T, EvalError> {
+        match self {
+            Object::NativeValue(handle) => handle.downcast_ref(),
+            _ => Err(self.type_mismatch("native value")),
+        }
+    }
+
+    /// Converts a deque to the array with the same elements, leaving other values unchanged.
+    pub fn into_array(self) -> Object {
+        match self {
+            Object::DequeValue(elements) => Object::ArrayValue(elements.into()),
+            obj => obj,
+        }
+    }
+
+    /// Name of the type of the value, as returned by the `type` built-in function.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Object::IntegerValue(_) => "integer",
+            Object::FloatValue(_) => "float",
+            Object::BooleanValue(_) => "boolean",
+            Object::NullValue => "null",
+            Object::StringValue(_) => "string",
+            Object::ArrayValue(_) | Object::DequeValue(_) => "array",
+            Object::MapValue(_) => "map",
+            Object::RangeValue { .. } => "range",
+            Object::FunctionValue(_)
+            | Object::BuiltinValue(_)
+            | Object::NativeFunctionValue(_)
+            | Object::VariantConstructor { .. } => "function",
+            Object::NativeValue(handle) => handle.type_name,
+            Object::WeakValue(_) => "weak",
+            Object::TaskValue(_) => "task",
+            Object::ActorValue(_) => "actor",
+            Object::ModuleValue { .. } => "module",
+            Object::StructType { .. } => "struct",
+            Object::EnumType { .. } => "enum",
+            // values of a struct or enum have the type it defines
+            Object::StructValue { name, .. } | Object::EnumValue { name, .. } => name,
+            Object::UnitValue => "unit",
+        }
+    }
+
+    /// Approximate number of bytes used by the value, including the heap memory of its elements.
+    /// Closures only account for their own size, not for the environment they captured.
+    pub fn approximate_size(&self) -> usize {
+        let heap = match self {
+            Object::StringValue(text) => text.len(),
+            Object::ArrayValue(elements) => elements.iter().map(Object::approximate_size).sum(),
+            Object::DequeValue(elements) => elements.iter().map(Object::approximate_size).sum(),
+            Object::MapValue(map) => map
+                .iter()
+                .map(|(key, value)| mem::size_of::<String>() + key.len() + value.approximate_size())
+                .sum(),
+            Object::StructValue { fields, .. } => fields
+                .iter()
+                .map(|(field, value)| {
+                    mem::size_of::<String>() + field.len() + value.approximate_size()
+                })
+                .sum(),
+            Object::EnumValue { values, .. } => values.iter().map(Object::approximate_size).sum(),
+            _ => 0,
+        };
+
+        mem::size_of::<Object>() + heap
+    }
+
+    /// Number of nested arrays, maps, structs and enum variants with fields:
+    /// `0` for scalars, `1` for `[1, 2]`, `2` for `[[1], 2]`...
+    pub fn nesting_depth(&self) -> usize {
+        match self {
+            Object::ArrayValue(elements) => {
+                1 + elements
+                    .iter()
+                    .map(Object::nesting_depth)
+                    .max()
+                    .unwrap_or(0)
+            }
+            Object::DequeValue(elements) => {
+                1 + elements
+                    .iter()
+                    .map(Object::nesting_depth)
+                    .max()
+                    .unwrap_or(0)
+            }
+            Object::MapValue(map) => 1 + map.values().map(Object::nesting_depth).max().unwrap_or(0),
+            Object::StructValue { fields, .. } => {
+                1 + fields
+                    .iter()
+                    .map(|(_, value)| value.nesting_depth())
+                    .max()
+                    .unwrap_or(0)
+            }
+            Object::EnumValue { values, .. } if !values.is_empty() => {
+                1 + values.iter().map(Object::nesting_depth).max().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+}
+
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Object::IntegerValue(value) => write!(f, "{value}"),
+            // debug formatting keeps the decimal part, so that `1.0` isn't shown as `1`
+            Object::FloatValue(value) => write!(f, "{value:?}"),
             Object::BooleanValue(value) => write!(f, "{value}"),
+            Object::NullValue => write!(f, "null"),
             Object::StringValue(value) => write!(f, "\"{value}\""),
             Object::ArrayValue(elements) => {
                 write!(f, "[")?;
@@ -37,6 +272,16 @@ impl fmt::Display for Object {
                 }
                 write!(f, "]")
             }
+            Object::DequeValue(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            }
             Object::MapValue(map) => {
                 write!(f, "{{")?;
                 for (i, (key, value)) in map.iter().enumerate() {
@@ -47,24 +292,258 @@ impl fmt::Display for Object {
                 }
                 write!(f, "}}")
             }
+            Object::RangeValue {
+                start,
+                end,
+                inclusive,
+            } => {
+                if *inclusive {
+                    write!(f, "{start}..={end}")
+                } else {
+                    write!(f, "{start}..{end}")
+                }
+            }
             Object::FunctionValue(value) => write!(f, "{value}"),
-            Object::ReturnValue(value) => write!(f, "return {value}"),
             Object::BuiltinValue(value) => write!(f, "built-in function {value}"),
+            Object::NativeFunctionValue(value) => write!(f, "{value}"),
+            Object::NativeValue(value) => write!(f, "{value}"),
+            Object::WeakValue(weak) => write!(f, "weak({})", weak.name),
+            Object::TaskValue(task) => write!(f, "task #{}", task.id),
+            Object::ActorValue(actor) => write!(f, "actor #{}", actor.id),
+            Object::ModuleValue { name, .. } => write!(f, "module {name}"),
+            Object::StructType { name, fields } if fields.is_empty() => {
+                write!(f, "struct {name} {{}}")
+            }
+            Object::StructType { name, fields } => {
+                write!(f, "struct {name} {{ {} }}", fields.join(", "))
+            }
+            Object::StructValue { name, fields } if fields.is_empty() => write!(f, "{name} {{}}"),
+            Object::StructValue { name, fields } => {
+                write!(f, "{name} {{ ")?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{field}: {value}")?;
+                }
+                write!(f, " }}")
+            }
+            Object::EnumType { name, variants } if variants.is_empty() => {
+                write!(f, "enum {name} {{}}")
+            }
+            Object::EnumType { name, variants } => {
+                write!(f, "enum {name} {{ {} }}", format_variants(variants))
+            }
+            Object::EnumValue {
+                name,
+                variant,
+                values,
+            } => {
+                // the variants of the prelude are written on their own, like `Some(1)`
+                if prelude_enum(variant) != Some(name.as_str()) {
+                    write!(f, "{name}.")?;
+                }
+                write!(f, "{variant}")?;
+                if !values.is_empty() {
+                    write!(f, "(")?;
+                    for (i, value) in values.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{value}")?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Object::VariantConstructor { name, variant } => write!(f, "variant {name}.{variant}"),
             Object::UnitValue => write!(f, "()"),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Binding referenced by a weak value. It doesn't keep alive the environment of the binding,
+/// so that values only cached through it are dropped along with their scope.
+#[derive(Debug, Clone)]
+pub struct WeakBinding {
+    pub name: String,
+    pub env: Weak<RefCell<Environment>>,
+}
+
+impl WeakBinding {
+    /// Current value of the binding, unless its environment was dropped.
+    pub fn upgrade(&self) -> Option<Object> {
+        let env = self.env.upgrade()?;
+        let value = env.borrow().get(&self.name).ok();
+        value.map(Object::into_array)
+    }
+}
+
+impl PartialEq for WeakBinding {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.env.ptr_eq(&other.env)
+    }
+}
+
+/// Handle of a task, shared by its copies. The result of the task is kept once it's joined,
+/// so that joining it again gives the same result.
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub id: usize,
+    state: Rc<RefCell<TaskState>>,
+}
+
+#[derive(Debug)]
+enum TaskState {
+    Running(TaskThread),
+    Finished(Result<Object, String>),
+}
+
+impl Task {
+    pub(crate) fn new(thread: TaskThread) -> Self {
+        Task {
+            id: thread.id,
+            state: Rc::new(RefCell::new(TaskState::Running(thread))),
+        }
+    }
+
+    /// Waits for the task to finish, returning its result or the message of its error,
+    /// along with the environments of the closures restored from the result.
+    pub(crate) fn join(&self) -> (Result<Object, String>, Vec<Rc<RefCell<Environment>>>) {
+        let mut state = self.state.borrow_mut();
+        let mut environments = vec![];
+        if matches!(*state, TaskState::Running(_)) {
+            let finished = TaskState::Finished(Ok(Object::NullValue));
+            let TaskState::Running(thread) = mem::replace(&mut *state, finished) else {
+                unreachable!("the task is running");
+            };
+            *state = TaskState::Finished(thread.join().map(|joined| {
+                environments = joined.environments;
+                joined.result
+            }));
+        }
+
+        let TaskState::Finished(result) = &*state else {
+            unreachable!("the task was joined");
+        };
+        (result.clone(), environments)
+    }
+
+    /// Whether the task is over, so that joining it doesn't block.
+    pub(crate) fn is_finished(&self) -> bool {
+        match &*self.state.borrow() {
+            TaskState::Running(thread) => thread.is_finished(),
+            TaskState::Finished(_) => true,
+        }
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+/// Address of an actor, running on its own thread. Its copies share the mailbox of the actor,
+/// which stops once every copy is dropped.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub id: usize,
+    pub(crate) mailbox: Sender<Mail>,
+    /// Error the handler of the actor failed with, after which it doesn't handle messages anymore.
+    pub(crate) failure: Arc<OnceLock<String>>,
+}
+
+impl PartialEq for Actor {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Closure {
     pub parameters: Vec<String>,
+    pub rest: Option<String>,
     pub body: Statement,
     pub env: Rc<RefCell<Environment>>,
 }
 
+impl Closure {
+    /// Checks that the closure can be called with this number of arguments:
+    /// the rest parameter, if any, collects any number of them.
+    pub fn check_arity(&self, arguments: usize) -> Result<(), EvalError> {
+        let parameters = self.parameters.len();
+
+        match self.rest {
+            Some(_) if arguments < parameters => Err(EvalError::FunctionCallTooFewArguments(
+                parameters as u8,
+                arguments as u8,
+            )),
+            None if arguments != parameters => Err(EvalError::FunctionCallWrongArity(
+                parameters as u8,
+                arguments as u8,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Closure {
+    /// Orders the arguments of a call with named arguments like the parameters they're matched to,
+    /// after the positional ones.
+    pub fn arrange_arguments(
+        &self,
+        positional: Vec<Object>,
+        named: Vec<(String, Object)>,
+    ) -> Result<Vec<Object>, EvalError> {
+        let mut slots: Vec<Option<Object>> = vec![None; self.parameters.len()];
+        let mut remaining = vec![];
+
+        for (i, argument) in positional.into_iter().enumerate() {
+            match slots.get_mut(i) {
+                Some(slot) => *slot = Some(argument),
+                None => remaining.push(argument),
+            }
+        }
+
+        for (name, argument) in named {
+            let Some(i) = self
+                .parameters
+                .iter()
+                .position(|parameter| *parameter == name)
+            else {
+                let suggestions =
+                    closest_matches(&name, self.parameters.iter().map(String::as_str));
+                return Err(EvalError::UnknownNamedArgument(name, suggestions));
+            };
+            if slots[i].is_some() {
+                return Err(EvalError::DuplicateArgument(name));
+            }
+
+            slots[i] = Some(argument);
+        }
+
+        let mut arguments = slots
+            .into_iter()
+            .zip(&self.parameters)
+            .map(|(slot, parameter)| {
+                slot.ok_or_else(|| EvalError::MissingArgument(parameter.clone()))
+            })
+            .collect::<Result<Vec<Object>, EvalError>>()?;
+        arguments.extend(remaining);
+
+        Ok(arguments)
+    }
+}
+
 impl fmt::Display for Closure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "fn({}) {}", self.parameters.join(", "), self.body)
+        write!(
+            f,
+            "fn({}) {}",
+            format_parameters(&self.parameters, &self.rest),
+            self.body
+        )
     }
 }
 
@@ -72,9 +551,62 @@ impl fmt::Display for Closure {
 pub enum BuiltinFunction {
     Len,
     Append,
+    PushFront,
+    PushBack,
+    PopFront,
+    PopBack,
     Rest,
     Println,
     Print,
+    ToArray,
+    Chars,
+    Codepoints,
+    Bytes,
+    SplitLines,
+    StartsWith,
+    EndsWith,
+    PadLeft,
+    PadRight,
+    Upper,
+    Lower,
+    IsNull,
+    ParseInt,
+    ParseFloat,
+    ToBase,
+    Sorted,
+    Unique,
+    Flatten,
+    Zip,
+    Enumerate,
+    GroupBy,
+    CountBy,
+    Clone,
+    Freeze,
+    Weak,
+    Upgrade,
+    Sizeof,
+    Depth,
+    Fields,
+    Diff,
+    AssertEq,
+    Type,
+    IsPure,
+    Pmap,
+    Spawn,
+    Join,
+    WaitAll,
+    Actor,
+    Tell,
+    Ask,
+    SetTimeout,
+    SetInterval,
+    ClearTimer,
+    WhenDone,
+    YieldNow,
+    ReadLine,
+    Log,
+    Version,
+    Features,
 }
 
 impl BuiltinFunction {
@@ -83,12 +615,106 @@ impl BuiltinFunction {
         match identifier {
             "len" => Ok(Object::BuiltinValue(BuiltinFunction::Len)),
             "append" => Ok(Object::BuiltinValue(BuiltinFunction::Append)),
+            "push_front" => Ok(Object::BuiltinValue(BuiltinFunction::PushFront)),
+            "push_back" => Ok(Object::BuiltinValue(BuiltinFunction::PushBack)),
+            "pop_front" => Ok(Object::BuiltinValue(BuiltinFunction::PopFront)),
+            "pop_back" => Ok(Object::BuiltinValue(BuiltinFunction::PopBack)),
             "rest" => Ok(Object::BuiltinValue(BuiltinFunction::Rest)),
             "println" => Ok(Object::BuiltinValue(BuiltinFunction::Println)),
             "print" => Ok(Object::BuiltinValue(BuiltinFunction::Print)),
+            "to_array" => Ok(Object::BuiltinValue(BuiltinFunction::ToArray)),
+            "chars" => Ok(Object::BuiltinValue(BuiltinFunction::Chars)),
+            "codepoints" => Ok(Object::BuiltinValue(BuiltinFunction::Codepoints)),
+            "bytes" => Ok(Object::BuiltinValue(BuiltinFunction::Bytes)),
+            "split_lines" => Ok(Object::BuiltinValue(BuiltinFunction::SplitLines)),
+            "starts_with" => Ok(Object::BuiltinValue(BuiltinFunction::StartsWith)),
+            "ends_with" => Ok(Object::BuiltinValue(BuiltinFunction::EndsWith)),
+            "pad_left" => Ok(Object::BuiltinValue(BuiltinFunction::PadLeft)),
+            "pad_right" => Ok(Object::BuiltinValue(BuiltinFunction::PadRight)),
+            "upper" => Ok(Object::BuiltinValue(BuiltinFunction::Upper)),
+            "lower" => Ok(Object::BuiltinValue(BuiltinFunction::Lower)),
+            "is_null" => Ok(Object::BuiltinValue(BuiltinFunction::IsNull)),
+            "parse_int" => Ok(Object::BuiltinValue(BuiltinFunction::ParseInt)),
+            "parse_float" => Ok(Object::BuiltinValue(BuiltinFunction::ParseFloat)),
+            "to_base" => Ok(Object::BuiltinValue(BuiltinFunction::ToBase)),
+            "sorted" => Ok(Object::BuiltinValue(BuiltinFunction::Sorted)),
+            "unique" => Ok(Object::BuiltinValue(BuiltinFunction::Unique)),
+            "flatten" => Ok(Object::BuiltinValue(BuiltinFunction::Flatten)),
+            "zip" => Ok(Object::BuiltinValue(BuiltinFunction::Zip)),
+            "enumerate" => Ok(Object::BuiltinValue(BuiltinFunction::Enumerate)),
+            "group_by" => Ok(Object::BuiltinValue(BuiltinFunction::GroupBy)),
+            "count_by" => Ok(Object::BuiltinValue(BuiltinFunction::CountBy)),
+            "clone" => Ok(Object::BuiltinValue(BuiltinFunction::Clone)),
+            "freeze" => Ok(Object::BuiltinValue(BuiltinFunction::Freeze)),
+            "weak" => Ok(Object::BuiltinValue(BuiltinFunction::Weak)),
+            "upgrade" => Ok(Object::BuiltinValue(BuiltinFunction::Upgrade)),
+            "sizeof" => Ok(Object::BuiltinValue(BuiltinFunction::Sizeof)),
+            "depth" => Ok(Object::BuiltinValue(BuiltinFunction::Depth)),
+            "fields" => Ok(Object::BuiltinValue(BuiltinFunction::Fields)),
+            "diff" => Ok(Object::BuiltinValue(BuiltinFunction::Diff)),
+            "assert_eq" => Ok(Object::BuiltinValue(BuiltinFunction::AssertEq)),
+            "type" => Ok(Object::BuiltinValue(BuiltinFunction::Type)),
+            "is_pure" => Ok(Object::BuiltinValue(BuiltinFunction::IsPure)),
+            "pmap" => Ok(Object::BuiltinValue(BuiltinFunction::Pmap)),
+            "spawn" => Ok(Object::BuiltinValue(BuiltinFunction::Spawn)),
+            "join" => Ok(Object::BuiltinValue(BuiltinFunction::Join)),
+            "wait_all" => Ok(Object::BuiltinValue(BuiltinFunction::WaitAll)),
+            "actor" => Ok(Object::BuiltinValue(BuiltinFunction::Actor)),
+            "tell" => Ok(Object::BuiltinValue(BuiltinFunction::Tell)),
+            "ask" => Ok(Object::BuiltinValue(BuiltinFunction::Ask)),
+            "set_timeout" => Ok(Object::BuiltinValue(BuiltinFunction::SetTimeout)),
+            "set_interval" => Ok(Object::BuiltinValue(BuiltinFunction::SetInterval)),
+            "clear_timer" => Ok(Object::BuiltinValue(BuiltinFunction::ClearTimer)),
+            "when_done" => Ok(Object::BuiltinValue(BuiltinFunction::WhenDone)),
+            "yield_now" => Ok(Object::BuiltinValue(BuiltinFunction::YieldNow)),
+            "read_line" => Ok(Object::BuiltinValue(BuiltinFunction::ReadLine)),
+            "log" => Ok(Object::BuiltinValue(BuiltinFunction::Log)),
+            "version" => Ok(Object::BuiltinValue(BuiltinFunction::Version)),
+            "features" => Ok(Object::BuiltinValue(BuiltinFunction::Features)),
             _ => Err(EvalError::IdentifierNotFound(identifier.to_owned())),
         }
     }
+
+    /// Names of the built-in functions callable as methods of `receiver`, e.g. `"abc".len()`,
+    /// which get the receiver as first argument.
+    pub fn methods(receiver: &Object) -> &'static [&'static str] {
+        match receiver {
+            Object::StringValue(_) => &[
+                "len",
+                "chars",
+                "codepoints",
+                "bytes",
+                "split_lines",
+                "starts_with",
+                "ends_with",
+                "pad_left",
+                "pad_right",
+                "upper",
+                "lower",
+                "parse_int",
+                "parse_float",
+            ],
+            Object::ArrayValue(_) | Object::DequeValue(_) => &[
+                "len",
+                "append",
+                "rest",
+                "sorted",
+                "unique",
+                "flatten",
+                "zip",
+                "enumerate",
+                "group_by",
+                "count_by",
+                "pmap",
+            ],
+            Object::MapValue(_) => &["fields"],
+            Object::RangeValue { .. } => &["len", "to_array"],
+            Object::IntegerValue(_) => &["to_base"],
+            Object::TaskValue(_) => &["join", "when_done"],
+            Object::ActorValue(_) => &["tell", "ask"],
+            _ => &[],
+        }
+    }
 }
 
 impl fmt::Display for BuiltinFunction {
@@ -96,13 +722,132 @@ impl fmt::Display for BuiltinFunction {
         match self {
             BuiltinFunction::Len => write!(f, "len"),
             BuiltinFunction::Append => write!(f, "push"),
+            BuiltinFunction::PushFront => write!(f, "push_front"),
+            BuiltinFunction::PushBack => write!(f, "push_back"),
+            BuiltinFunction::PopFront => write!(f, "pop_front"),
+            BuiltinFunction::PopBack => write!(f, "pop_back"),
             BuiltinFunction::Rest => write!(f, "rest"),
             BuiltinFunction::Println => write!(f, "println"),
             BuiltinFunction::Print => write!(f, "print"),
+            BuiltinFunction::ToArray => write!(f, "to_array"),
+            BuiltinFunction::Chars => write!(f, "chars"),
+            BuiltinFunction::Codepoints => write!(f, "codepoints"),
+            BuiltinFunction::Bytes => write!(f, "bytes"),
+            BuiltinFunction::SplitLines => write!(f, "split_lines"),
+            BuiltinFunction::StartsWith => write!(f, "starts_with"),
+            BuiltinFunction::EndsWith => write!(f, "ends_with"),
+            BuiltinFunction::PadLeft => write!(f, "pad_left"),
+            BuiltinFunction::PadRight => write!(f, "pad_right"),
+            BuiltinFunction::Upper => write!(f, "upper"),
+            BuiltinFunction::Lower => write!(f, "lower"),
+            BuiltinFunction::IsNull => write!(f, "is_null"),
+            BuiltinFunction::ParseInt => write!(f, "parse_int"),
+            BuiltinFunction::ParseFloat => write!(f, "parse_float"),
+            BuiltinFunction::ToBase => write!(f, "to_base"),
+            BuiltinFunction::Sorted => write!(f, "sorted"),
+            BuiltinFunction::Unique => write!(f, "unique"),
+            BuiltinFunction::Flatten => write!(f, "flatten"),
+            BuiltinFunction::Zip => write!(f, "zip"),
+            BuiltinFunction::Enumerate => write!(f, "enumerate"),
+            BuiltinFunction::GroupBy => write!(f, "group_by"),
+            BuiltinFunction::CountBy => write!(f, "count_by"),
+            BuiltinFunction::Clone => write!(f, "clone"),
+            BuiltinFunction::Freeze => write!(f, "freeze"),
+            BuiltinFunction::Weak => write!(f, "weak"),
+            BuiltinFunction::Upgrade => write!(f, "upgrade"),
+            BuiltinFunction::Sizeof => write!(f, "sizeof"),
+            BuiltinFunction::Depth => write!(f, "depth"),
+            BuiltinFunction::Fields => write!(f, "fields"),
+            BuiltinFunction::Diff => write!(f, "diff"),
+            BuiltinFunction::AssertEq => write!(f, "assert_eq"),
+            BuiltinFunction::Type => write!(f, "type"),
+            BuiltinFunction::IsPure => write!(f, "is_pure"),
+            BuiltinFunction::Pmap => write!(f, "pmap"),
+            BuiltinFunction::Spawn => write!(f, "spawn"),
+            BuiltinFunction::Join => write!(f, "join"),
+            BuiltinFunction::WaitAll => write!(f, "wait_all"),
+            BuiltinFunction::Actor => write!(f, "actor"),
+            BuiltinFunction::Tell => write!(f, "tell"),
+            BuiltinFunction::Ask => write!(f, "ask"),
+            BuiltinFunction::SetTimeout => write!(f, "set_timeout"),
+            BuiltinFunction::SetInterval => write!(f, "set_interval"),
+            BuiltinFunction::ClearTimer => write!(f, "clear_timer"),
+            BuiltinFunction::WhenDone => write!(f, "when_done"),
+            BuiltinFunction::YieldNow => write!(f, "yield_now"),
+            BuiltinFunction::ReadLine => write!(f, "read_line"),
+            BuiltinFunction::Log => write!(f, "log"),
+            BuiltinFunction::Version => write!(f, "version"),
+            BuiltinFunction::Features => write!(f, "features"),
         }
     }
 }
 
+/// Values every program can use without declaring them, unless it shadows them: the `Result` and `Option` enums,
+/// and their variants bound to their own names, like `Ok` for `Result.Ok`.
+pub fn prelude(name: &str) -> Option<Object> {
+    if let Some((enum_name, variant, field)) = PRELUDE_VARIANTS
+        .iter()
+        .find(|(_, variant, _)| *variant == name)
+    {
+        let obj = match field {
+            Some(field) => Object::VariantConstructor {
+                name: (*enum_name).to_owned(),
+                variant: EnumVariant {
+                    name: (*variant).to_owned(),
+                    fields: vec![(*field).to_owned()],
+                },
+            },
+            None => Object::EnumValue {
+                name: (*enum_name).to_owned(),
+                variant: (*variant).to_owned(),
+                values: vec![],
+            },
+        };
+        return Some(obj);
+    }
+
+    let variants: Vec<EnumVariant> = PRELUDE_VARIANTS
+        .iter()
+        .filter(|(enum_name, ..)| *enum_name == name)
+        .map(|(_, variant, field)| EnumVariant {
+            name: (*variant).to_owned(),
+            fields: field.iter().map(|field| (*field).to_owned()).collect(),
+        })
+        .collect();
+    (!variants.is_empty()).then(|| Object::EnumType {
+        name: name.to_owned(),
+        variants,
+    })
+}
+
+/// Exit of a function or loop, made by `return`, `break` or `continue`. It leaves the expressions and
+/// statements being evaluated like errors do, until it reaches the function or loop it exits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unwind {
+    /// Exits the innermost function, which evaluates to the value.
+    Return(Object),
+    /// Exits the loops up to the one with the label or the innermost one, which evaluates to the value.
+    Break(Option<String>, Object),
+    /// Skips the rest of the body of the loop with the label or of the innermost one.
+    Continue(Option<String>),
+}
+
+impl Unwind {
+    /// Value of the statement that made the exit.
+    pub fn value(&self) -> &Object {
+        match self {
+            Unwind::Return(value) | Unwind::Break(_, value) => value,
+            Unwind::Continue(_) => &Object::UnitValue,
+        }
+    }
+}
+
+impl From<Unwind> for EvalError {
+    fn from(unwind: Unwind) -> Self {
+        EvalError::Unwind(Box::new(unwind))
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum EvalError {
     #[error("Identifier not found: {0}")]
@@ -111,6 +856,15 @@ pub enum EvalError {
     #[error("Type mismatch: {0}")]
     TypeMismatch(String),
 
+    #[error("Integer overflow while computing {0}")]
+    IntegerOverflow(String),
+
+    #[error("Integers can't be raised to the negative power {0}, use a float base instead")]
+    NegativeExponent(i64),
+
+    #[error("Integers can only be shifted by 0 to 63 bits, not {0}")]
+    InvalidShift(i64),
+
     #[error("Modulo of zero isn't allowed")]
     ModuloByZero,
 
@@ -123,6 +877,31 @@ pub enum EvalError {
     #[error("Function call with the wrong number of arguments. Expected {0}, got {1}")]
     FunctionCallWrongArity(u8, u8),
 
+    #[error("Function call with too few arguments. Expected at least {0}, got {1}")]
+    FunctionCallTooFewArguments(u8, u8),
+
+    /// Call of a built-in function with optional parameters, with the accepted range of arguments.
+    #[error("Function call with the wrong number of arguments. Expected {0} to {1}, got {2}")]
+    FunctionCallArityOutOfRange(u8, u8, u8),
+
+    #[error("The function has no parameter named `{0}`{}", format_suggestions(.1))]
+    UnknownNamedArgument(String, Vec<String>),
+
+    #[error("The parameter `{0}` received more than one argument")]
+    DuplicateArgument(String),
+
+    #[error("No argument was passed for the parameter `{0}`")]
+    MissingArgument(String),
+
+    #[error("Expected an array of {0} elements to destructure, got {1}")]
+    DestructuringLengthMismatch(usize, usize),
+
+    #[error("Expected an array of at least {0} elements to destructure, got {1}")]
+    DestructuringTooFewElements(usize, usize),
+
+    #[error("Expected {0} to destructure, got a value of type `{1}`")]
+    DestructuringTypeMismatch(&'static str, String),
+
     #[error("Return statement used outside an expression")]
     ReturnOutsideExpression,
 
@@ -135,7 +914,7 @@ pub enum EvalError {
     #[error("Unsupported argument type for built-in function: {0}")]
     UnsupportedArgumentType(String),
 
-    #[error("Only arrays can be accessed through the index operator")]
+    #[error("Only arrays, strings and maps can be accessed through the index operator")]
     InvalidIndexUsage,
 
     #[error("This structure cannot be accessed with such type.")]
@@ -144,6 +923,257 @@ pub enum EvalError {
     #[error("This structure has {0} elements but the index {1} is out of bounds.")]
     IndexOutOfBounds(usize, usize),
 
-    #[error("This map doesn't have a value defined at key {0}")]
-    ValueNotFound(String),
+    #[error("The slice starts at index {0}, after its end at index {1}")]
+    SliceStartAfterEnd(usize, usize),
+
+    #[error("This map doesn't have a value defined at key \"{0}\"{}", format_suggestions(.1))]
+    ValueNotFound(String, Vec<String>),
+
+    #[error("The module `{0}` has no member `{1}`{}", format_suggestions(.2))]
+    MemberNotFound(String, String, Vec<String>),
+
+    #[error("The identifier `{0}` isn't allowed here{}", format_suggestions(.1))]
+    IdentifierNotAllowed(String, Vec<String>),
+
+    #[error("Values of type `{0}` don't have fields, cannot access `{1}`")]
+    InvalidFieldAccess(String, String),
+
+    #[error("`{0}` isn't a struct, it can't be built like `{0} {{ ... }}`")]
+    NotAStruct(String),
+
+    #[error("The struct `{0}` has no field `{1}`{}", format_suggestions(.2))]
+    UnknownField(String, String, Vec<String>),
+
+    #[error("The field `{1}` of the struct `{0}` wasn't given a value")]
+    MissingField(String, String),
+
+    #[error("The enum `{0}` has no variant `{1}`{}", format_suggestions(.2))]
+    UnknownVariant(String, String, Vec<String>),
+
+    #[error("`{0}` isn't an enum, its variants can't be matched like `{0}.{1}`")]
+    NotAnEnum(String, String),
+
+    #[error("The variant `{0}` has {1} fields, but the pattern has {2}")]
+    VariantPatternArity(String, usize, usize),
+
+    #[error("Values of type `{0}` have no method `{1}`{}", format_suggestions(.2))]
+    UnknownMethod(String, String, Vec<String>),
+
+    #[error("The task #{0} failed: {1}")]
+    TaskFailed(usize, String),
+
+    #[error("The actor #{0} failed: {1}")]
+    ActorFailed(usize, String),
+
+    /// Error raised on another thread, like the workers of `pmap`, with its code and message.
+    #[error("{1}")]
+    WorkerFailed(&'static str, String),
+
+    #[error("The evaluation was interrupted")]
+    Interrupted,
+
+    #[error("The evaluation went past its deadline")]
+    DeadlineExceeded,
+
+    #[error("Input/output error: {0}")]
+    Io(String),
+
+    #[error("The values given to `assert_eq` differ:\n{}", .0.trim_end())]
+    AssertionFailed(String),
+
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+
+    /// The span is the one of the operand, which the diagnostics point to.
+    #[error("Cannot apply `{0}` to a value of type `{1}`")]
+    InvalidOperand(TokenKind, String, Span),
+
+    #[error("No match arm matched the value {0}")]
+    NoMatchingArm(String),
+
+    #[error(
+        "Cannot assign twice to the immutable identifier `{0}`, declare it with `var` instead"
+    )]
+    ImmutableAssignment(String),
+
+    #[error("Cannot assign to the identifier `{0}`, as it has been frozen")]
+    FrozenAssignment(String),
+
+    /// Not a failure: the evaluation is leaving a function or loop. The parser only allows `break` and
+    /// `continue` inside loops, so only a `return` outside of functions can be reported.
+    #[error("Return statement used outside an expression")]
+    Unwind(Box<Unwind>),
+
+    /// Wraps an error with a description of what was being evaluated when it happened.
+    #[error("{source}\n    {context}")]
+    Context {
+        context: String,
+        source: Box<EvalError>,
+    },
+}
+
+impl EvalError {
+    /// Stable code of the error, described in details by `qalo explain <code>`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvalError::IdentifierNotFound(_) => "E1001",
+            EvalError::FunctionNotFound(_) => "E1002",
+            EvalError::TypeMismatch(_) => "E1003",
+            EvalError::UnsupportedOperator(_) => "E1004",
+            EvalError::UnsupportedArgumentType(_) => "E1005",
+            EvalError::FunctionCallWrongArity(_, _)
+            | EvalError::FunctionCallArityOutOfRange(_, _, _) => "E1006",
+            EvalError::DivisionByZero => "E1007",
+            EvalError::ModuloByZero => "E1008",
+            EvalError::IntegerOverflow(_) => "E1009",
+            EvalError::ReturnOutsideExpression | EvalError::Unwind(_) => "E1010",
+            EvalError::InvalidIndexUsage => "E1011",
+            EvalError::InvalidIndexType => "E1012",
+            EvalError::IndexOutOfBounds(_, _) => "E1013",
+            EvalError::ValueNotFound(_, _) => "E1014",
+            EvalError::MemberNotFound(_, _, _) => "E1015",
+            EvalError::InvalidFieldAccess(_, _) => "E1016",
+            EvalError::NoMatchingArm(_) => "E1017",
+            EvalError::ImmutableAssignment(_) => "E1018",
+            EvalError::FrozenAssignment(_) => "E1019",
+            EvalError::NegativeExponent(_) => "E1020",
+            EvalError::InvalidShift(_) => "E1021",
+            EvalError::IdentifierNotAllowed(_, _) => "E1022",
+            EvalError::FunctionCallTooFewArguments(_, _) => "E1023",
+            EvalError::UnknownNamedArgument(_, _) => "E1024",
+            EvalError::DuplicateArgument(_) => "E1025",
+            EvalError::MissingArgument(_) => "E1026",
+            EvalError::DestructuringLengthMismatch(_, _) => "E1027",
+            EvalError::DestructuringTooFewElements(_, _) => "E1028",
+            EvalError::DestructuringTypeMismatch(_, _) => "E1029",
+            EvalError::NotAStruct(_) => "E1030",
+            EvalError::UnknownField(_, _, _) => "E1031",
+            EvalError::MissingField(_, _) => "E1032",
+            EvalError::UnknownVariant(_, _, _) => "E1033",
+            EvalError::NotAnEnum(_, _) => "E1034",
+            EvalError::VariantPatternArity(_, _, _) => "E1035",
+            EvalError::UnknownMethod(_, _, _) => "E1036",
+            EvalError::TaskFailed(_, _) => "E1037",
+            EvalError::ActorFailed(_, _) => "E1038",
+            EvalError::Interrupted => "E1039",
+            EvalError::DeadlineExceeded => "E1040",
+            EvalError::Io(_) => "E1041",
+            EvalError::AssertionFailed(_) => "E1042",
+            EvalError::Replay(_) => "E1043",
+            EvalError::InvalidOperand(_, _, _) => "E1044",
+            EvalError::SliceStartAfterEnd(_, _) => "E1045",
+            EvalError::WorkerFailed(code, _) => code,
+            EvalError::ParsingError(err) => err.code(),
+            EvalError::Context { source, .. } => source.code(),
+        }
+    }
+
+    /// Wraps the error with a frame describing what was being evaluated, like `while evaluating argument 2 of push`.
+    /// Exits of functions and loops are left unwrapped, for them to be caught.
+    pub fn context(self, context: impl Into<String>) -> Self {
+        if let EvalError::Unwind(_) = self {
+            return self;
+        }
+
+        EvalError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The innermost error, stripped of all its context frames.
+    pub fn root_cause(&self) -> &EvalError {
+        match self {
+            EvalError::Context { source, .. } => source.root_cause(),
+            err => err,
+        }
+    }
+
+    /// Location in the source of what the error is about, for the errors which know it.
+    pub fn span(&self) -> Option<Span> {
+        match self.root_cause() {
+            EvalError::InvalidOperand(_, _, span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// The context frames attached to the error, from the innermost to the outermost.
+    pub fn frames(&self) -> Vec<&str> {
+        match self {
+            EvalError::Context { context, source } => {
+                let mut frames = source.frames();
+                frames.push(context.as_str());
+                frames
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Attaches context frames to the error of a `Result`, similarly to `anyhow::Context`.
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T, EvalError>;
+
+    /// Like `context`, but the frame is only built if there's an error.
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, EvalError>;
+}
+
+impl<T> Context<T> for Result<T, EvalError> {
+    fn context(self, context: impl Into<String>) -> Result<T, EvalError> {
+        self.map_err(|err| err.context(context))
+    }
+
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, EvalError> {
+        self.map_err(|err| err.context(context()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_extraction() {
+        assert_eq!(Object::IntegerValue(-3).as_int().unwrap(), -3);
+        assert_eq!(Object::IntegerValue(2).as_float().unwrap(), 2.0);
+        assert_eq!(Object::FloatValue(2.5).as_float().unwrap(), 2.5);
+        assert!(Object::BooleanValue(true).as_bool().unwrap());
+        assert_eq!(Object::StringValue("a".to_owned()).as_str().unwrap(), "a");
+        assert_eq!(
+            Object::ArrayValue(vec![Object::NullValue])
+                .as_array()
+                .unwrap(),
+            &[Object::NullValue]
+        );
+        assert!(Object::MapValue(BTreeMap::new())
+            .as_map()
+            .unwrap()
+            .is_empty());
+
+        let handle = Object::NativeValue(NativeHandle::new("Counter", 7u8));
+        assert_eq!(handle.as_native::<u8>().unwrap(), &7);
+
+        let tests = vec![
+            (
+                Object::StringValue("1".to_owned()).as_int().unwrap_err(),
+                "Type mismatch: Expected integer, got string \"1\"",
+            ),
+            (
+                Object::FloatValue(1.5).as_int().unwrap_err(),
+                "Type mismatch: Expected integer, got float 1.5",
+            ),
+            (
+                Object::NullValue.as_array().unwrap_err(),
+                "Type mismatch: Expected array, got null",
+            ),
+            (
+                Object::IntegerValue(1).as_native::<u8>().unwrap_err(),
+                "Type mismatch: Expected native value, got integer 1",
+            ),
+        ];
+
+        for (err, expected) in tests {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
 }
@@ -0,0 +1,264 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{Debug, Write},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{ast::Statement, object::Object, source_map::Location, token::Span};
+
+/// Hooks called by the evaluator while it runs a program, for the statements of the program and of its blocks.
+/// `span` is the location of the statement in the source, and `depth` is the number of environments
+/// enclosing the current one (0 at the program-level).
+pub trait EvalObserver: Debug {
+    /// Called right before a statement is evaluated.
+    fn enter_statement(&mut self, _statement: &Statement, _span: Span, _depth: usize) {}
+
+    /// Called after a statement has been successfully evaluated.
+    fn exit_statement(
+        &mut self,
+        _statement: &Statement,
+        _span: Span,
+        _depth: usize,
+        _value: &Object,
+    ) {
+    }
+
+    /// Called right before a function is called, with the called expression as `name`, like `fib` or `xs.sorted`.
+    fn enter_call(&mut self, _name: &str) {}
+
+    /// Called after a function call, even if it failed.
+    fn exit_call(&mut self, _name: &str) {}
+}
+
+/// Lets the host keep a handle on an observer installed in an evaluator, to read its results afterwards.
+impl<O: EvalObserver> EvalObserver for Rc<RefCell<O>> {
+    fn enter_statement(&mut self, statement: &Statement, span: Span, depth: usize) {
+        self.borrow_mut().enter_statement(statement, span, depth);
+    }
+
+    fn exit_statement(&mut self, statement: &Statement, span: Span, depth: usize, value: &Object) {
+        self.borrow_mut()
+            .exit_statement(statement, span, depth, value);
+    }
+
+    fn enter_call(&mut self, name: &str) {
+        self.borrow_mut().enter_call(name);
+    }
+
+    fn exit_call(&mut self, name: &str) {
+        self.borrow_mut().exit_call(name);
+    }
+}
+
+/// Prints every evaluated statement to stderr, after the line and column where it starts in `source`,
+/// with its environment depth and the produced value.
+#[derive(Debug)]
+pub struct TraceObserver {
+    source: String,
+}
+
+impl TraceObserver {
+    pub fn new(source: &str) -> Self {
+        TraceObserver {
+            source: source.to_owned(),
+        }
+    }
+
+    fn line(&self, statement: &Statement, span: Span, depth: usize, value: &Object) -> String {
+        let Location { line, column, .. } = Location::in_source("", &self.source, span.start);
+        format!(
+            "{line}:{column} {}[depth {depth}] {statement} => {value}",
+            "  ".repeat(depth)
+        )
+    }
+}
+
+impl EvalObserver for TraceObserver {
+    fn exit_statement(&mut self, statement: &Statement, span: Span, depth: usize, value: &Object) {
+        eprintln!("{}", self.line(statement, span, depth, value));
+    }
+}
+
+/// Time spent in a function, over all of its calls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub calls: usize,
+    /// Time spent in the function itself, excluding the functions it called.
+    pub own: Duration,
+}
+
+/// Measures the time spent in every function, to find where a program spends its time.
+#[derive(Debug)]
+pub struct ProfileObserver {
+    stack: Vec<String>,
+    last: Instant,
+    stacks: BTreeMap<Vec<String>, Duration>,
+    functions: BTreeMap<String, FunctionStats>,
+}
+
+impl Default for ProfileObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfileObserver {
+    /// Name of the bottom frame of every stack, standing for the statements outside of functions.
+    pub const ROOT: &'static str = "program";
+
+    pub fn new() -> Self {
+        Self {
+            stack: vec![],
+            last: Instant::now(),
+            stacks: BTreeMap::new(),
+            functions: BTreeMap::new(),
+        }
+    }
+
+    /// The number of calls and the time spent in each function, by name.
+    pub fn functions(&self) -> &BTreeMap<String, FunctionStats> {
+        &self.functions
+    }
+
+    /// The time spent in each call stack in the folded format read by flamegraph tools,
+    /// like `program;fib;fib 120`: one line per stack, with its frames separated by semicolons
+    /// and followed by the microseconds spent in its last frame.
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for (stack, elapsed) in &self.stacks {
+            folded.push_str(Self::ROOT);
+            for frame in stack {
+                folded.push(';');
+                folded.push_str(frame);
+            }
+            writeln!(folded, " {}", elapsed.as_micros()).unwrap();
+        }
+
+        folded
+    }
+
+    /// Charges the time elapsed since the last event to the current stack.
+    fn sample(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+
+        *self.stacks.entry(self.stack.clone()).or_default() += elapsed;
+        if let Some(function) = self.stack.last() {
+            self.functions.entry(function.clone()).or_default().own += elapsed;
+        }
+    }
+}
+
+impl EvalObserver for ProfileObserver {
+    fn enter_statement(&mut self, _statement: &Statement, _span: Span, _depth: usize) {
+        self.sample();
+    }
+
+    fn exit_statement(
+        &mut self,
+        _statement: &Statement,
+        _span: Span,
+        _depth: usize,
+        _value: &Object,
+    ) {
+        self.sample();
+    }
+
+    fn enter_call(&mut self, name: &str) {
+        self.sample();
+        // semicolons separate the frames, and anonymous functions are named after their code
+        let name = name.replace(';', ",").replace('\n', " ");
+        self.functions.entry(name.clone()).or_default().calls += 1;
+        self.stack.push(name);
+    }
+
+    fn exit_call(&mut self, _name: &str) {
+        self.sample();
+        self.stack.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::Evaluator;
+
+    #[derive(Debug)]
+    struct Lines(TraceObserver, Rc<RefCell<Vec<String>>>);
+
+    impl EvalObserver for Lines {
+        fn exit_statement(
+            &mut self,
+            statement: &Statement,
+            span: Span,
+            depth: usize,
+            value: &Object,
+        ) {
+            let line = self.0.line(statement, span, depth, value);
+            self.1.borrow_mut().push(line);
+        }
+    }
+
+    #[test]
+    fn trace_lines() {
+        let input = "let x = 1;\nif x > 0 {\n  x + 1\n}";
+        let lines = Rc::new(RefCell::new(vec![]));
+        let mut evaluator = Evaluator::new(input);
+        evaluator.set_observer(Box::new(Lines(TraceObserver::new(input), lines.clone())));
+        evaluator.eval_program().unwrap();
+
+        assert_eq!(
+            *lines.borrow(),
+            vec![
+                "1:1 [depth 0] let x = 1; => ()",
+                "3:3   [depth 1] (x + 1) => 2",
+                "2:1 [depth 0] if (x > 0) {(x + 1)} => 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_folded_stacks() {
+        let input = r#"
+            let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };
+            let square = fn(x) { x * x };
+            square(fib(3));
+            [3, 1, 2].sorted();
+        "#;
+        let profiler = Rc::new(RefCell::new(ProfileObserver::new()));
+        let mut evaluator = Evaluator::new(input);
+        evaluator.set_observer(Box::new(profiler.clone()));
+        evaluator.eval_program().unwrap();
+
+        let profiler = profiler.borrow();
+        let folded = profiler.folded();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks,
+            vec![
+                "program",
+                "program;[3, 1, 2].sorted",
+                "program;fib",
+                "program;fib;fib",
+                "program;fib;fib;fib",
+                "program;square",
+            ]
+        );
+
+        let calls: Vec<(&str, usize)> = profiler
+            .functions()
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats.calls))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![("[3, 1, 2].sorted", 1), ("fib", 5), ("square", 1)]
+        );
+    }
+}
@@ -0,0 +1,674 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    num::NonZeroUsize,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+        Arc, OnceLock,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    ast::{EnumVariant, Statement},
+    environment::Environment,
+    evaluator::Evaluator,
+    object::{Actor, BuiltinFunction, Closure, Object},
+    streams::{Output, Streams},
+};
+
+/// Options of an evaluator, which the evaluators it starts on other threads share.
+#[derive(Debug, Clone)]
+pub(crate) struct Settings {
+    pub checked_arithmetic: bool,
+    pub truthy_conditions: bool,
+    /// Where the evaluators on other threads send what they write, for the evaluator that started them.
+    pub output: Sender<Output>,
+}
+
+impl Settings {
+    /// Evaluator calling functions in `env`, with these options.
+    fn evaluator(self, env: Rc<RefCell<Environment>>) -> Evaluator<'static> {
+        let mut evaluator = Evaluator::with_env("", env);
+        evaluator.set_checked_arithmetic(self.checked_arithmetic);
+        evaluator.set_truthy_conditions(self.truthy_conditions);
+        evaluator.set_streams(Streams::forwarded(self.output));
+        evaluator
+    }
+}
+
+/// Copy of a value that can be sent to another thread.
+/// Closures refer to their environment through its index in a `Snapshot`.
+#[derive(Debug, Clone)]
+enum Portable {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Null,
+    String(String),
+    Array(Vec<Portable>),
+    Map(BTreeMap<String, Portable>),
+    StructType {
+        name: String,
+        fields: Vec<String>,
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, Portable)>,
+    },
+    EnumType {
+        name: String,
+        variants: Vec<EnumVariant>,
+    },
+    Enum {
+        name: String,
+        variant: String,
+        values: Vec<Portable>,
+    },
+    VariantConstructor {
+        name: String,
+        variant: EnumVariant,
+    },
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
+    Builtin(BuiltinFunction),
+    Function(PortableClosure),
+    /// Addresses of actors can be sent as they are, to let actors talk to each other.
+    Actor(Actor),
+    Unit,
+}
+
+#[derive(Debug, Clone)]
+struct PortableClosure {
+    parameters: Vec<String>,
+    rest: Option<String>,
+    body: Statement,
+    env: usize,
+}
+
+#[derive(Debug, Default)]
+struct PortableEnv {
+    store: HashMap<String, Portable>,
+    mutable: HashSet<String>,
+    frozen: HashSet<String>,
+    deprecated: HashMap<String, String>,
+    outer: Option<usize>,
+}
+
+/// Copy of the environments reachable from some ported values, which can refer to each other.
+#[derive(Debug, Default)]
+struct Snapshot {
+    envs: Vec<PortableEnv>,
+}
+
+impl Snapshot {
+    /// Rebuilds every environment of the snapshot. They are returned so that they can be cleared
+    /// once they aren't needed anymore, as recursive closures form reference cycles with them.
+    fn restore_envs(&self) -> Vec<Rc<RefCell<Environment>>> {
+        let envs: Vec<Rc<RefCell<Environment>>> = self
+            .envs
+            .iter()
+            .map(|_| Rc::new(RefCell::new(Environment::default())))
+            .collect();
+
+        for (env, portable) in envs.iter().zip(&self.envs) {
+            *env.borrow_mut() = Environment {
+                store: portable
+                    .store
+                    .iter()
+                    .map(|(name, value)| (name.clone(), restore(value, &envs)))
+                    .collect(),
+                mutable: portable.mutable.clone(),
+                frozen: portable.frozen.clone(),
+                deprecated: portable.deprecated.clone(),
+                outer: portable.outer.map(|outer| envs[outer].clone()),
+            };
+        }
+
+        envs
+    }
+}
+
+/// Builds a `Snapshot` out of the environments of the captured values.
+#[derive(Debug, Default)]
+struct Capture {
+    snapshot: Snapshot,
+    indices: HashMap<*const RefCell<Environment>, usize>,
+    /// Keeps the captured environments alive, so that their addresses aren't reused by other ones.
+    captured: Vec<Rc<RefCell<Environment>>>,
+}
+
+impl Capture {
+    /// Returns `None` for native values, which can't leave the thread they were created in.
+    fn value(&mut self, obj: &Object) -> Option<Portable> {
+        let value = match obj {
+            Object::IntegerValue(n) => Portable::Integer(*n),
+            Object::FloatValue(n) => Portable::Float(*n),
+            Object::BooleanValue(b) => Portable::Boolean(*b),
+            Object::NullValue => Portable::Null,
+            Object::StringValue(s) => Portable::String(s.clone()),
+            Object::ArrayValue(elements) => Portable::Array(
+                elements
+                    .iter()
+                    .map(|element| self.value(element))
+                    .collect::<Option<_>>()?,
+            ),
+            // restored as an array, like it's read from the environment
+            Object::DequeValue(elements) => Portable::Array(
+                elements
+                    .iter()
+                    .map(|element| self.value(element))
+                    .collect::<Option<_>>()?,
+            ),
+            Object::MapValue(map) => Portable::Map(
+                map.iter()
+                    .map(|(key, value)| Some((key.clone(), self.value(value)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            Object::StructType { name, fields } => Portable::StructType {
+                name: name.clone(),
+                fields: fields.clone(),
+            },
+            Object::StructValue { name, fields } => Portable::Struct {
+                name: name.clone(),
+                fields: fields
+                    .iter()
+                    .map(|(field, value)| Some((field.clone(), self.value(value)?)))
+                    .collect::<Option<_>>()?,
+            },
+            Object::EnumType { name, variants } => Portable::EnumType {
+                name: name.clone(),
+                variants: variants.clone(),
+            },
+            Object::EnumValue {
+                name,
+                variant,
+                values,
+            } => Portable::Enum {
+                name: name.clone(),
+                variant: variant.clone(),
+                values: values
+                    .iter()
+                    .map(|value| self.value(value))
+                    .collect::<Option<_>>()?,
+            },
+            Object::VariantConstructor { name, variant } => Portable::VariantConstructor {
+                name: name.clone(),
+                variant: variant.clone(),
+            },
+            Object::RangeValue {
+                start,
+                end,
+                inclusive,
+            } => Portable::Range {
+                start: *start,
+                end: *end,
+                inclusive: *inclusive,
+            },
+            Object::BuiltinValue(builtin) => Portable::Builtin(builtin.clone()),
+            Object::FunctionValue(closure) => Portable::Function(self.closure(closure)),
+            Object::ActorValue(actor) => Portable::Actor(actor.clone()),
+            Object::UnitValue => Portable::Unit,
+            Object::NativeFunctionValue(_)
+            | Object::NativeValue(_)
+            | Object::WeakValue(_)
+            | Object::TaskValue(_)
+            | Object::ModuleValue { .. } => return None,
+        };
+
+        Some(value)
+    }
+
+    fn closure(&mut self, closure: &Closure) -> PortableClosure {
+        PortableClosure {
+            parameters: closure.parameters.clone(),
+            rest: closure.rest.clone(),
+            body: closure.body.clone(),
+            env: self.env(&closure.env),
+        }
+    }
+
+    /// Bindings to native values are left out: pure functions can't refer to them anyway.
+    fn env(&mut self, env: &Rc<RefCell<Environment>>) -> usize {
+        if let Some(index) = self.indices.get(&Rc::as_ptr(env)) {
+            return *index;
+        }
+
+        // reserve the index first, as closures stored in the environment can refer to it
+        let index = self.snapshot.envs.len();
+        self.snapshot.envs.push(PortableEnv::default());
+        self.indices.insert(Rc::as_ptr(env), index);
+        self.captured.push(env.clone());
+
+        let env = env.borrow();
+        let store = env
+            .store
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), self.value(value)?)))
+            .collect();
+        let outer = env.outer.as_ref().map(|outer| self.env(outer));
+
+        self.snapshot.envs[index] = PortableEnv {
+            store,
+            mutable: env.mutable.clone(),
+            frozen: env.frozen.clone(),
+            deprecated: env.deprecated.clone(),
+            outer,
+        };
+
+        index
+    }
+}
+
+fn restore(value: &Portable, envs: &[Rc<RefCell<Environment>>]) -> Object {
+    match value {
+        Portable::Integer(n) => Object::IntegerValue(*n),
+        Portable::Float(n) => Object::FloatValue(*n),
+        Portable::Boolean(b) => Object::BooleanValue(*b),
+        Portable::Null => Object::NullValue,
+        Portable::String(s) => Object::StringValue(s.clone()),
+        Portable::Array(elements) => Object::ArrayValue(
+            elements
+                .iter()
+                .map(|element| restore(element, envs))
+                .collect(),
+        ),
+        Portable::Map(map) => Object::MapValue(
+            map.iter()
+                .map(|(key, value)| (key.clone(), restore(value, envs)))
+                .collect(),
+        ),
+        Portable::StructType { name, fields } => Object::StructType {
+            name: name.clone(),
+            fields: fields.clone(),
+        },
+        Portable::Struct { name, fields } => Object::StructValue {
+            name: name.clone(),
+            fields: fields
+                .iter()
+                .map(|(field, value)| (field.clone(), restore(value, envs)))
+                .collect(),
+        },
+        Portable::EnumType { name, variants } => Object::EnumType {
+            name: name.clone(),
+            variants: variants.clone(),
+        },
+        Portable::Enum {
+            name,
+            variant,
+            values,
+        } => Object::EnumValue {
+            name: name.clone(),
+            variant: variant.clone(),
+            values: values.iter().map(|value| restore(value, envs)).collect(),
+        },
+        Portable::VariantConstructor { name, variant } => Object::VariantConstructor {
+            name: name.clone(),
+            variant: variant.clone(),
+        },
+        Portable::Range {
+            start,
+            end,
+            inclusive,
+        } => Object::RangeValue {
+            start: *start,
+            end: *end,
+            inclusive: *inclusive,
+        },
+        Portable::Builtin(builtin) => Object::BuiltinValue(builtin.clone()),
+        Portable::Function(closure) => Object::FunctionValue(restore_closure(closure, envs)),
+        Portable::Actor(actor) => Object::ActorValue(actor.clone()),
+        Portable::Unit => Object::UnitValue,
+    }
+}
+
+fn restore_closure(closure: &PortableClosure, envs: &[Rc<RefCell<Environment>>]) -> Closure {
+    Closure {
+        parameters: closure.parameters.clone(),
+        rest: closure.rest.clone(),
+        body: closure.body.clone(),
+        env: envs[closure.env].clone(),
+    }
+}
+
+/// Why a parallel map failed.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ParallelMapError {
+    /// The element at this index holds a native value, which can't be sent to another thread.
+    NotPortable(usize),
+    /// The result of the function on the element at this index holds a native value.
+    ResultNotPortable(usize),
+    /// Calling the function on the element at this index failed, with the code and message of the error.
+    Failed(usize, &'static str, String),
+}
+
+/// Results of a parallel map, along with the environments of the closures they contain.
+pub(crate) struct ParallelMap {
+    pub results: Vec<Object>,
+    pub environments: Vec<Rc<RefCell<Environment>>>,
+}
+
+/// Calls `function`, which must be pure, on every element on a pool of threads, returning the results in order.
+/// Every thread evaluates the function in its own copy of the environment it captured.
+pub(crate) fn parallel_map(
+    function: &Closure,
+    elements: &[Object],
+    settings: Settings,
+) -> Result<ParallelMap, ParallelMapError> {
+    let mut capture = Capture::default();
+    let function = capture.closure(function);
+    let elements = elements
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            capture
+                .value(element)
+                .ok_or(ParallelMapError::NotPortable(index))
+        })
+        .collect::<Result<Vec<Portable>, ParallelMapError>>()?;
+    let snapshot = capture.snapshot;
+
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(elements.len())
+        .max(1);
+    let chunk_size = elements.len().div_ceil(workers).max(1);
+
+    let chunks = thread::scope(|scope| {
+        let handles: Vec<_> = elements
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, elements)| {
+                let (snapshot, function, settings) = (&snapshot, &function, settings.clone());
+                scope.spawn(move || {
+                    map_chunk(snapshot, function, elements, chunk * chunk_size, settings)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect::<Result<Vec<(Snapshot, Vec<Portable>)>, ParallelMapError>>()
+    })?;
+
+    let mut map = ParallelMap {
+        results: Vec::with_capacity(elements.len()),
+        environments: vec![],
+    };
+    for (snapshot, results) in chunks {
+        let envs = snapshot.restore_envs();
+        map.results
+            .extend(results.iter().map(|result| restore(result, &envs)));
+        map.environments.extend(envs);
+    }
+
+    Ok(map)
+}
+
+/// Maps the elements of a chunk, which starts at the index `offset`, within a worker thread, stopping at the first
+/// one that fails. Errors hold values which can't be sent to other threads, so their code and message are sent instead.
+fn map_chunk(
+    snapshot: &Snapshot,
+    function: &PortableClosure,
+    elements: &[Portable],
+    offset: usize,
+    settings: Settings,
+) -> Result<(Snapshot, Vec<Portable>), ParallelMapError> {
+    let envs = snapshot.restore_envs();
+    let closure = restore_closure(function, &envs);
+
+    let mut evaluator = settings.evaluator(closure.env.clone());
+
+    let mut capture = Capture::default();
+    let results = elements
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            let arguments = vec![restore(element, &envs)];
+            let result = evaluator
+                .call_closure(closure.clone(), arguments)
+                .map_err(|err| {
+                    ParallelMapError::Failed(offset + index, err.code(), err.to_string())
+                })?;
+            capture
+                .value(&result)
+                .ok_or(ParallelMapError::ResultNotPortable(offset + index))
+        })
+        .collect::<Result<Vec<Portable>, ParallelMapError>>();
+
+    // break the reference cycles between the environments and the closures they hold
+    drop(evaluator);
+    drop(closure);
+    for env in envs {
+        env.borrow_mut().store.clear();
+    }
+
+    results.map(|results| (capture.snapshot, results))
+}
+
+/// Number of tasks spawned so far, by every evaluator, which identifies the next one.
+static TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// What a task sends back: its result, or the message of the error it failed with.
+type TaskOutput = Result<(Snapshot, Portable), String>;
+
+/// Thread running a task started by `spawn`.
+#[derive(Debug)]
+pub struct TaskThread {
+    pub(crate) id: usize,
+    handle: JoinHandle<TaskOutput>,
+}
+
+impl TaskThread {
+    /// Whether the task is over, so that joining it doesn't block.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the task to finish, returning its result or the message of its error.
+    pub(crate) fn join(self) -> Result<TaskResult, String> {
+        let (snapshot, result) = self
+            .handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        let envs = snapshot.restore_envs();
+
+        Ok(TaskResult {
+            result: restore(&result, &envs),
+            environments: envs,
+        })
+    }
+}
+
+/// Result of a task, along with the environments of the closures it contains.
+pub(crate) struct TaskResult {
+    pub result: Object,
+    pub environments: Vec<Rc<RefCell<Environment>>>,
+}
+
+/// Starts calling `function` with `arguments` on its own thread, which evaluates it in its own copy
+/// of the environment it captured. Fails with the index of the first argument holding a native value.
+pub(crate) fn spawn(
+    function: &Closure,
+    arguments: &[Object],
+    settings: Settings,
+) -> Result<TaskThread, usize> {
+    let mut capture = Capture::default();
+    let function = capture.closure(function);
+    let arguments = arguments
+        .iter()
+        .enumerate()
+        .map(|(index, argument)| capture.value(argument).ok_or(index))
+        .collect::<Result<Vec<Portable>, usize>>()?;
+    let snapshot = capture.snapshot;
+
+    let handle = thread::spawn(move || run_task(&snapshot, &function, &arguments, settings));
+    Ok(TaskThread {
+        id: TASKS.fetch_add(1, Ordering::Relaxed) + 1,
+        handle,
+    })
+}
+
+/// Calls the function of a task within its thread.
+fn run_task(
+    snapshot: &Snapshot,
+    function: &PortableClosure,
+    arguments: &[Portable],
+    settings: Settings,
+) -> TaskOutput {
+    let envs = snapshot.restore_envs();
+    let closure = restore_closure(function, &envs);
+    let arguments = arguments
+        .iter()
+        .map(|argument| restore(argument, &envs))
+        .collect();
+
+    let mut evaluator = settings.evaluator(closure.env.clone());
+
+    let mut capture = Capture::default();
+    let result = match evaluator.call_closure(closure.clone(), arguments) {
+        Ok(result) => capture.value(&result).ok_or_else(|| {
+            "its result holds a native value, which can't be sent to another thread".to_owned()
+        }),
+        Err(err) => Err(err.to_string()),
+    };
+
+    // break the reference cycles between the environments and the closures they hold
+    drop(evaluator);
+    drop(closure);
+    for env in envs {
+        env.borrow_mut().store.clear();
+    }
+
+    result.map(|result| (capture.snapshot, result))
+}
+
+/// Number of actors started so far, by every evaluator, which identifies the next one.
+static ACTORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Message sent to an actor, with the sender of the reply for `ask`.
+#[derive(Debug)]
+pub struct Mail {
+    snapshot: Snapshot,
+    message: Portable,
+    reply: Option<Sender<TaskOutput>>,
+}
+
+/// Why a message couldn't be handled by an actor.
+pub(crate) enum MailError {
+    /// The message holds a native value, which can't be sent to another thread.
+    NotPortable,
+    /// The handler of the actor failed with this error, on this message or a previous one,
+    /// or its new state couldn't be sent back.
+    Failed(String),
+}
+
+/// Starts an actor on its own thread, which calls `handler` with its state and each message it receives,
+/// the result becoming its new state. Returns `None` if the state holds a native value.
+pub(crate) fn start_actor(handler: &Closure, state: &Object, settings: Settings) -> Option<Actor> {
+    let mut capture = Capture::default();
+    let handler = capture.closure(handler);
+    let state = capture.value(state)?;
+    let snapshot = capture.snapshot;
+
+    let (mailbox, mails) = mpsc::channel::<Mail>();
+    let failure = Arc::new(OnceLock::new());
+    let failed = failure.clone();
+    thread::spawn(move || {
+        let envs = snapshot.restore_envs();
+        let handler = restore_closure(&handler, &envs);
+        let mut state = restore(&state, &envs);
+        let mut evaluator = settings.evaluator(handler.env.clone());
+
+        // the mailbox is closed once every copy of the address is dropped
+        for mail in mails {
+            let result = match failed.get() {
+                Some(failure) => Err(String::clone(failure)),
+                None => {
+                    let message = restore(&mail.message, &mail.snapshot.restore_envs());
+                    match evaluator.call_closure(handler.clone(), vec![state.clone(), message]) {
+                        Ok(result) => Ok(result),
+                        Err(err) => Err(failed.get_or_init(|| err.to_string()).clone()),
+                    }
+                }
+            };
+            if let Ok(result) = &result {
+                state = result.clone();
+            }
+
+            if let Some(reply) = mail.reply {
+                let mut capture = Capture::default();
+                let reply_value = result.and_then(|result| {
+                    capture.value(&result).ok_or_else(|| {
+                        "its state holds a native value, which can't be sent to another thread"
+                            .to_owned()
+                    })
+                });
+                // the asking thread may be gone
+                let _ = reply.send(reply_value.map(|result| (capture.snapshot, result)));
+            }
+        }
+
+        // break the reference cycles between the environments and the closures they hold
+        drop(evaluator);
+        drop(handler);
+        drop(state);
+        for env in envs {
+            env.borrow_mut().store.clear();
+        }
+    });
+
+    Some(Actor {
+        id: ACTORS.fetch_add(1, Ordering::Relaxed) + 1,
+        mailbox,
+        failure,
+    })
+}
+
+/// Sends a message to an actor. With `ask`, waits for the actor to handle it and returns its new state.
+pub(crate) fn send(
+    actor: &Actor,
+    message: &Object,
+    ask: bool,
+) -> Result<Option<TaskResult>, MailError> {
+    if let Some(failure) = actor.failure.get() {
+        return Err(MailError::Failed(failure.clone()));
+    }
+
+    let mut capture = Capture::default();
+    let message = capture.value(message).ok_or(MailError::NotPortable)?;
+    let (reply, replies) = mpsc::channel();
+    let mail = Mail {
+        snapshot: capture.snapshot,
+        message,
+        reply: ask.then_some(reply),
+    };
+    // the thread of an actor only ends once its mailbox is closed
+    actor
+        .mailbox
+        .send(mail)
+        .expect("the actor is still running");
+    if !ask {
+        return Ok(None);
+    }
+
+    let (snapshot, result) = replies
+        .recv()
+        .expect("the actor replies to every message")
+        .map_err(MailError::Failed)?;
+    let envs = snapshot.restore_envs();
+    Ok(Some(TaskResult {
+        result: restore(&result, &envs),
+        environments: envs,
+    }))
+}
@@ -1,9 +1,14 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{collections::BTreeMap, rc::Rc};
 
 use crate::{
-    ast::{Expression, ParserError, Program, Statement},
+    ast::{
+        prelude_enum, EnumVariant, Expression, Located, MatchArm, MethodCall, ParserError, Pattern,
+        Program, Statement, SwitchArm,
+    },
+    keywords::Keywords,
     lexer::Lexer,
-    token::{Token, TokenKind},
+    suggestion::levenshtein,
+    token::{Span, Token, TokenKind},
 };
 
 /// Represents the binding power of a token.
@@ -22,22 +27,122 @@ pub struct Parser<'a> {
     pub lexer: Lexer<'a>,
     pub cur: Rc<Token>,
     pub next: Rc<Token>,
+    /// Errors the parser recovered from, reported once the whole program is parsed.
+    pub diagnostics: Vec<ParserError>,
+    /// Maximum nesting of expressions and blocks, protecting against stack overflows.
+    pub max_depth: usize,
+    /// Whether a line break ends statements like a semicolon, as in interactive sessions.
+    /// Otherwise, semicolons can only be left out at the end of a block or of the program.
+    pub newline_terminates: bool,
+    depth: usize,
+    /// Kinds and labels of the loops enclosing the statement being parsed, from the outermost one,
+    /// within the innermost function: `break` and `continue` can't exit a function.
+    loops: Vec<(TokenKind, Option<String>)>,
+    /// Spans of the blocks and of the statements of the program and of the blocks, in the order they start,
+    /// except that the ones in map literals are ordered by key, like the entries of `Expression::MapLiteral`.
+    /// Tools use them to find what the comments of the source are attached to.
+    pub spans: Vec<Span>,
+    /// Set while parsing the head of `if`, `match`, `switch` and `for`, where `Name {` starts their block
+    /// rather than a struct literal, unless it's inside delimiters like parentheses.
+    no_struct_literals: bool,
+    /// Set while parsing a standalone expression, outside of the bodies of its functions:
+    /// statements are refused even inside blocks, and loops too.
+    expressions_only: bool,
+}
+
+/// Default value of `Parser::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Positional and named arguments of a call.
+type CallArguments = (Vec<Expression>, Vec<(String, Expression)>);
+
+/// Keywords that can start a statement, checked when recovering from typos.
+const STATEMENT_KEYWORDS: [TokenKind; 9] = [
+    TokenKind::Let,
+    TokenKind::Var,
+    TokenKind::Return,
+    TokenKind::For,
+    TokenKind::Match,
+    TokenKind::Switch,
+    TokenKind::Struct,
+    TokenKind::Enum,
+    TokenKind::If,
+];
+
+/// Parses the literal of an integer token, written in decimal or with a `0x`, `0o` or `0b` prefix.
+/// `negative` is set when the literal follows a minus sign, so that `i64::MIN` can be written.
+pub fn parse_integer(literal: &str, negative: bool) -> Result<i64, ParserError> {
+    let (radix, name, digits) = match literal.get(..2) {
+        Some("0x") => (16, "hexadecimal", &literal[2..]),
+        Some("0o") => (8, "octal", &literal[2..]),
+        Some("0b") => (2, "binary", &literal[2..]),
+        _ => (10, "decimal", literal),
+    };
+
+    let malformed = |reason: String| ParserError::MalformedNumber(literal.to_owned(), reason);
+
+    if digits.is_empty() {
+        return Err(malformed(format!(
+            "{name} literals need at least one digit"
+        )));
+    }
+    if let Some(invalid) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+        return Err(malformed(format!("`{invalid}` isn't a valid {name} digit")));
+    }
+
+    // the magnitude is parsed as an `i128`, as `-i64::MIN` doesn't fit in an `i64`
+    let magnitude = i128::from_str_radix(digits, radix)
+        .map_err(|_| malformed("the number doesn't fit in a 64 bit integer".to_owned()))?;
+    let value = if negative { -magnitude } else { magnitude };
+
+    i64::try_from(value)
+        .map_err(|_| malformed("the number doesn't fit in a 64 bit integer".to_owned()))
+}
+
+fn is_struct_name(name: &str) -> bool {
+    name.starts_with(char::is_uppercase)
+}
+
+fn repeated_field(name: &str, field: &str) -> ParserError {
+    ParserError::SyntaxError(format!("The field `{field}` of `{name}` is repeated"))
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
-        let lexer = Lexer::new(input);
+        Parser::with_keywords(input, Rc::default())
+    }
+
+    /// Creates a parser for interactive sessions, where a line break ends statements like a semicolon.
+    pub fn interactive(input: &'a str) -> Self {
+        let mut parser = Parser::new(input);
+        parser.newline_terminates = true;
+        parser
+    }
+
+    /// Creates a parser accepting the aliases of `keywords` in place of the keywords they stand for.
+    pub fn with_keywords(input: &'a str, keywords: Rc<Keywords>) -> Self {
+        let lexer = Lexer::with_keywords(input, keywords);
 
         let mut parser = Self {
             lexer,
             cur: Rc::new(Token {
                 kind: TokenKind::Eof,
                 literal: "".to_owned(),
+                span: Span::default(),
             }),
             next: Rc::new(Token {
                 kind: TokenKind::Eof,
                 literal: "".to_owned(),
+                span: Span::default(),
             }),
+            diagnostics: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
+            newline_terminates: false,
+            depth: 0,
+            loops: vec![],
+            spans: vec![],
+            no_struct_literals: false,
+            expressions_only: false,
         };
 
         // consume two tokens to set `cur` and `next` correctly
@@ -59,6 +164,59 @@ impl<'a> Parser<'a> {
         self.cur = std::mem::replace(&mut self.next, self.lexer.next_token().into());
     }
 
+    /// Runs a recursive parsing function, failing instead of overflowing the stack
+    /// when the source is nested deeper than `max_depth`.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParserError>,
+    ) -> Result<T, ParserError> {
+        if self.depth >= self.max_depth {
+            return Err(ParserError::NestingTooDeep(self.max_depth));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Parses the head of `if`, `match`, `switch` or `for`, which is followed by a block.
+    fn parse_condition(&mut self) -> Result<Expression, ParserError> {
+        let restricted = std::mem::replace(&mut self.no_struct_literals, true);
+        let condition = self.parse_expression(0, false);
+        self.no_struct_literals = restricted;
+
+        condition
+    }
+
+    /// Runs a parsing function for the inside of delimiters, where struct literals are always allowed.
+    fn delimited<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParserError>,
+    ) -> Result<T, ParserError> {
+        let restricted = std::mem::replace(&mut self.no_struct_literals, false);
+        let result = parse(self);
+        self.no_struct_literals = restricted;
+
+        result
+    }
+
+    /// Parses the body of a loop of the given kind, where `break` and `continue` are allowed.
+    fn loop_body(
+        &mut self,
+        kind: TokenKind,
+        label: Option<String>,
+    ) -> Result<Statement, ParserError> {
+        self.expect_token(TokenKind::LeftBrace)?;
+
+        self.loops.push((kind, label));
+        let body = self.parse_block_statement();
+        self.loops.pop();
+
+        body
+    }
+
     pub fn expect_token(&mut self, token_kind: TokenKind) -> Result<Rc<Token>, ParserError> {
         if self.next.kind != token_kind {
             return Err(ParserError::UnexpectedToken(self.next.clone()));
@@ -68,22 +226,174 @@ impl<'a> Parser<'a> {
         Ok(self.cur.clone())
     }
 
+    /// Whether the statement being parsed can end before the next token: at a semicolon,
+    /// at the end of its block or of the program, and at a line break when `newline_terminates` is set.
+    fn at_statement_end(&self) -> bool {
+        matches!(
+            self.next.kind,
+            TokenKind::Semicolon | TokenKind::RightBrace | TokenKind::Eof
+        ) || (self.newline_terminates && self.newline_before_next())
+    }
+
+    /// Whether a line break separates the current token from the next one.
+    fn newline_before_next(&self) -> bool {
+        self.lexer
+            .input()
+            .get(self.cur.span.end..self.next.span.start)
+            .is_some_and(|between| between.contains('\n'))
+    }
+
+    /// Ends a statement like `let` or `return`, consuming its semicolon,
+    /// which can be left out where `at_statement_end` allows it.
+    fn end_statement(&mut self) -> Result<(), ParserError> {
+        if self.next.kind == TokenKind::Semicolon {
+            self.eat_token();
+        } else if !self.at_statement_end() {
+            return Err(ParserError::UnexpectedToken(self.next.clone()));
+        }
+
+        Ok(())
+    }
+
     pub fn parse_program(&mut self) -> Result<Program, ParserError> {
-        let mut statements: Vec<Statement> = vec![];
+        let mut statements: Vec<Located> = vec![];
 
         while self.cur.kind != TokenKind::Eof {
-            statements.push(self.parse_statement()?);
+            match self.spanned(Self::parse_statement) {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    self.diagnostics.push(err);
+                    break;
+                }
+            }
+            self.eat_token();
+        }
+
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        // the unterminated comment is likely the cause of any other error, so it's reported first
+        if let Some(span) = self.lexer.unterminated_comment {
+            diagnostics.insert(0, ParserError::UnterminatedComment(span));
+        }
+
+        match diagnostics.len() {
+            0 => Ok(Program(statements)),
+            1 => Err(diagnostics.remove(0)),
+            _ => Err(ParserError::Multiple(diagnostics)),
+        }
+    }
+
+    /// Parses an input made of a single expression, optionally followed by a semicolon,
+    /// refusing statements like `let`, `return` or assignments, and loops. Blocks, and the bodies of `if`
+    /// and `match`, can only hold expressions too, while the bodies of functions can hold any statement.
+    pub fn parse_standalone_expression(&mut self) -> Result<Expression, ParserError> {
+        if let Some(span) = self.lexer.unterminated_comment {
+            return Err(ParserError::UnterminatedComment(span));
+        }
+
+        self.expressions_only = true;
+        let expression = self.parse_only_expression();
+        self.expressions_only = false;
+
+        expression
+    }
+
+    fn parse_only_expression(&mut self) -> Result<Expression, ParserError> {
+        if self.starts_statement() {
+            return Err(ParserError::StatementNotAllowed(self.cur.clone()));
+        }
+
+        let expression = self.parse_expression(0, true)?;
+
+        if self.next.kind == TokenKind::Semicolon {
+            self.eat_token();
+        }
+        if self.next.kind != TokenKind::Eof {
             self.eat_token();
+            return Err(if self.starts_statement() {
+                ParserError::StatementNotAllowed(self.cur.clone())
+            } else {
+                ParserError::UnexpectedToken(self.cur.clone())
+            });
+        }
+
+        Ok(expression)
+    }
+
+    /// Whether the current token starts a statement other than an expression.
+    fn starts_statement(&self) -> bool {
+        matches!(
+            self.cur.kind,
+            TokenKind::Let
+                | TokenKind::Var
+                | TokenKind::Return
+                | TokenKind::For
+                | TokenKind::Do
+                | TokenKind::Loop
+                | TokenKind::Label
+                | TokenKind::Switch
+                | TokenKind::Struct
+                | TokenKind::Enum
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::At
+        ) || (self.cur.kind == TokenKind::Identifier && self.next.kind == TokenKind::Assign)
+    }
+
+    /// Finds the statement keyword that an identifier is likely a typo of (e.g. `lett` or `retun`).
+    /// Only identifiers followed by an operand are considered, since that's never valid code.
+    fn keyword_typo(&self) -> Option<TokenKind> {
+        if self.cur.kind != TokenKind::Identifier || self.cur.literal.chars().count() < 3 {
+            return None;
+        }
+
+        if !matches!(
+            self.next.kind,
+            TokenKind::Identifier
+                | TokenKind::Integer
+                | TokenKind::Float
+                | TokenKind::String
+                | TokenKind::True
+                | TokenKind::False
+                | TokenKind::Null
+        ) {
+            return None;
         }
 
-        Ok(Program(statements))
+        STATEMENT_KEYWORDS
+            .into_iter()
+            .find(|keyword| levenshtein(&self.cur.literal, &keyword.to_string()) == 1)
     }
 
     pub fn parse_statement(&mut self) -> Result<Statement, ParserError> {
+        if let Some(keyword) = self.keyword_typo() {
+            self.diagnostics
+                .push(ParserError::KeywordTypo(self.cur.clone(), keyword.clone()));
+
+            // recover by pretending the keyword was written correctly
+            self.cur = Rc::new(Token {
+                kind: keyword.clone(),
+                literal: keyword.to_string(),
+                span: self.cur.span,
+            });
+        }
+
+        if self.expressions_only && self.starts_statement() {
+            return Err(ParserError::StatementNotAllowed(self.cur.clone()));
+        }
+
         match self.cur.kind {
-            TokenKind::Let => self.parse_var_statement(),
+            TokenKind::Let | TokenKind::Var => self.parse_var_statement(),
             TokenKind::Return => self.parse_return_statement(),
             TokenKind::LeftBrace => self.parse_block_statement(),
+            TokenKind::For => self.parse_for_statement(None),
+            TokenKind::Do => self.parse_do_while_statement(None),
+            TokenKind::Switch => self.parse_switch_statement(),
+            TokenKind::Struct => self.parse_struct_statement(),
+            TokenKind::Enum => self.parse_enum_statement(),
+            TokenKind::Label => self.parse_labeled_statement(),
+            TokenKind::Break => self.parse_break_statement(),
+            TokenKind::Continue => self.parse_continue_statement(),
+            TokenKind::At => self.parse_attributed_statement(),
             TokenKind::Identifier => {
                 if self.next.kind == TokenKind::Assign {
                     self.parse_assign_statement()
@@ -97,50 +407,402 @@ impl<'a> Parser<'a> {
 
     pub fn parse_var_statement(&mut self) -> Result<Statement, ParserError> {
         let kind = self.cur.kind.clone();
+        if matches!(self.next.kind, TokenKind::LeftSquare | TokenKind::LeftBrace) {
+            return self.parse_destructure_statement(kind);
+        }
+
         let name = self.expect_token(TokenKind::Identifier)?;
         self.expect_token(TokenKind::Assign)?;
         let expr = self.parse_expression(0, false)?;
-        self.expect_token(TokenKind::Semicolon)?;
+        self.end_statement()?;
 
         Ok(Statement::VarStatement {
             kind,
             name: name.literal.clone(),
             value: expr,
+            deprecated: None,
+        })
+    }
+
+    /// Parses a binding like `let [a, b, rest...] = value;` or `let {name, age: a} = value;`. Patterns that can fail on a value of the right shape,
+    /// like literals, aren't allowed.
+    fn parse_destructure_statement(&mut self, kind: TokenKind) -> Result<Statement, ParserError> {
+        let pattern = self.parse_pattern()?;
+        if !pattern.binds_only() {
+            return Err(ParserError::SyntaxError(format!(
+                "Only identifiers, `_`, arrays and hash maps can be destructured by `{kind}`, got `{pattern}`"
+            )));
+        }
+
+        self.expect_token(TokenKind::Assign)?;
+        let value = self.parse_expression(0, false)?;
+        self.end_statement()?;
+
+        Ok(Statement::DestructureStatement {
+            kind,
+            pattern,
+            value,
         })
     }
 
+    /// Parses an attribute like `@deprecated("use draw2")`, followed by the function declaration it applies to.
+    fn parse_attributed_statement(&mut self) -> Result<Statement, ParserError> {
+        let attribute = self.expect_token(TokenKind::Identifier)?;
+        if attribute.literal != "deprecated" {
+            return Err(ParserError::SyntaxError(format!(
+                "Unknown attribute `@{}`",
+                attribute.literal
+            )));
+        }
+
+        self.expect_token(TokenKind::LeftParen)?;
+        let note = self.expect_token(TokenKind::String)?.literal.clone();
+        self.expect_token(TokenKind::RightParen)?;
+        self.eat_token();
+
+        match self.parse_statement()? {
+            Statement::VarStatement {
+                kind,
+                name,
+                value: value @ Expression::FunctionExpression { .. },
+                deprecated: None,
+            } => Ok(Statement::VarStatement {
+                kind,
+                name,
+                value,
+                deprecated: Some(note),
+            }),
+            _ => Err(ParserError::SyntaxError(
+                "`@deprecated` only applies to function declarations".to_owned(),
+            )),
+        }
+    }
+
     pub fn parse_return_statement(&mut self) -> Result<Statement, ParserError> {
-        if self.next.kind == TokenKind::Semicolon {
-            self.eat_token();
+        if self.at_statement_end() {
+            self.end_statement()?;
             Ok(Statement::ReturnStatement(None))
         } else {
             let expr = self.parse_expression(0, false)?;
-            self.expect_token(TokenKind::Semicolon)?;
+            self.end_statement()?;
             Ok(Statement::ReturnStatement(Some(expr)))
         }
     }
 
+    /// Parses the optional label after `break` or `continue`,
+    /// returning it along with the kind of the loop it refers to.
+    fn loop_target(&mut self) -> Result<(Option<String>, TokenKind), ParserError> {
+        let Some((kind, _)) = self.loops.last().cloned() else {
+            return Err(ParserError::OutsideLoop(self.cur.clone()));
+        };
+        if self.next.kind != TokenKind::Label {
+            return Ok((None, kind));
+        }
+
+        self.eat_token();
+        let label = &self.cur.literal[1..];
+        let (kind, label) = self
+            .loops
+            .iter()
+            .rev()
+            .find(|(_, name)| name.as_deref() == Some(label))
+            .cloned()
+            .ok_or_else(|| ParserError::UndefinedLabel(self.cur.clone()))?;
+
+        Ok((label, kind))
+    }
+
+    pub fn parse_break_statement(&mut self) -> Result<Statement, ParserError> {
+        let (label, kind) = self.loop_target()?;
+
+        if self.at_statement_end() {
+            self.end_statement()?;
+            return Ok(Statement::BreakStatement { label, value: None });
+        }
+        if kind != TokenKind::Loop {
+            return Err(ParserError::SyntaxError(format!(
+                "Only `loop` can be exited with a value, not `{kind}`"
+            )));
+        }
+
+        let expr = self.parse_expression(0, false)?;
+        self.end_statement()?;
+        Ok(Statement::BreakStatement {
+            label,
+            value: Some(expr),
+        })
+    }
+
+    pub fn parse_continue_statement(&mut self) -> Result<Statement, ParserError> {
+        let (label, _) = self.loop_target()?;
+
+        self.end_statement()?;
+        Ok(Statement::ContinueStatement(label))
+    }
+
+    /// Parses a loop preceded by a label, like `'outer: for x in xs { ... }`.
+    pub fn parse_labeled_statement(&mut self) -> Result<Statement, ParserError> {
+        let label = self.cur.literal[1..].to_owned();
+        self.expect_token(TokenKind::Colon)?;
+        self.eat_token();
+
+        match self.cur.kind {
+            TokenKind::For => self.parse_for_statement(Some(label)),
+            TokenKind::Do => self.parse_do_while_statement(Some(label)),
+            TokenKind::Loop => {
+                let expr = self.parse_loop_expression(Some(label))?;
+
+                // make semicolons optional
+                if self.next.kind == TokenKind::Semicolon {
+                    self.eat_token();
+                }
+
+                Ok(Statement::ExpressionStatement(expr))
+            }
+            _ => Err(ParserError::SyntaxError(format!(
+                "Only `for`, `do` and `loop` can be labeled, not `{}`",
+                self.cur.literal
+            ))),
+        }
+    }
+
+    pub fn parse_loop_expression(
+        &mut self,
+        label: Option<String>,
+    ) -> Result<Expression, ParserError> {
+        let body = Box::new(self.loop_body(TokenKind::Loop, label.clone())?);
+
+        Ok(Expression::LoopExpression { label, body })
+    }
+
     pub fn parse_assign_statement(&mut self) -> Result<Statement, ParserError> {
         let name = self.cur.literal.clone();
         self.expect_token(TokenKind::Assign)?;
         let expr = self.parse_expression(0, false)?;
-        self.expect_token(TokenKind::Semicolon)?;
+        self.end_statement()?;
 
         Ok(Statement::AssignStatement { name, value: expr })
     }
 
     pub fn parse_block_statement(&mut self) -> Result<Statement, ParserError> {
-        // consume {
-        self.eat_token();
-        let mut statements: Vec<Statement> = vec![];
+        self.nested(|parser| parser.delimited(Self::parse_block_body))
+    }
+
+    fn parse_block_body(&mut self) -> Result<Statement, ParserError> {
+        let block = self.spanned(|parser| {
+            // consume {
+            parser.eat_token();
+            let mut statements: Vec<Located> = vec![];
+
+            while parser.cur.kind != TokenKind::RightBrace {
+                let statement = parser.spanned(Self::parse_statement)?;
+                statements.push(statement);
+                parser.eat_token();
+            }
+
+            Ok(Statement::BlockStatement(statements))
+        })?;
+
+        Ok(block.statement)
+    }
+
+    /// Parses a statement or a block with `parse`, recording its span in `spans`
+    /// before the spans of the statements nested in it.
+    fn spanned(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Statement, ParserError>,
+    ) -> Result<Located, ParserError> {
+        let index = self.spans.len();
+        self.spans.push(self.cur.span);
+
+        let statement = parse(self)?;
+        self.spans[index].end = self.cur.span.end;
+
+        Ok(Located {
+            statement,
+            span: self.spans[index],
+        })
+    }
+
+    pub fn parse_for_statement(&mut self, label: Option<String>) -> Result<Statement, ParserError> {
+        let variable = self.expect_token(TokenKind::Identifier)?.literal.clone();
+        self.expect_token(TokenKind::In)?;
+        let iterable = self.parse_condition()?;
+        let body = Box::new(self.loop_body(TokenKind::For, label.clone())?);
+
+        // make semicolons optional
+        if self.next.kind == TokenKind::Semicolon {
+            self.eat_token();
+        }
+
+        Ok(Statement::ForStatement {
+            label,
+            variable,
+            iterable,
+            body,
+        })
+    }
+
+    pub fn parse_do_while_statement(
+        &mut self,
+        label: Option<String>,
+    ) -> Result<Statement, ParserError> {
+        let body = Box::new(self.loop_body(TokenKind::Do, label.clone())?);
+        self.expect_token(TokenKind::While)?;
+        let condition = self.parse_expression(0, false)?;
+        self.end_statement()?;
+
+        Ok(Statement::DoWhileStatement {
+            label,
+            body,
+            condition,
+        })
+    }
+
+    pub fn parse_switch_statement(&mut self) -> Result<Statement, ParserError> {
+        let switch = self.cur.clone();
+        let value = self.parse_condition()?;
+        self.expect_token(TokenKind::LeftBrace)?;
+
+        let mut arms: Vec<SwitchArm> = vec![];
+        let default = loop {
+            match self.next.kind {
+                TokenKind::Identifier if self.next.literal == "_" => {
+                    self.eat_token();
+                    self.expect_token(TokenKind::FatArrow)?;
+                    break self.parse_arm_body()?;
+                }
+                TokenKind::RightBrace => return Err(ParserError::MissingDefaultArm(switch)),
+                _ => {}
+            }
+
+            let case = self.parse_expression(0, false)?;
+            self.expect_token(TokenKind::FatArrow)?;
+            let body = self.parse_arm_body()?;
+            arms.push(SwitchArm { case, body });
+        };
+
+        // the default arm is the last one
+        self.expect_token(TokenKind::RightBrace)?;
+
+        // make semicolons optional
+        if self.next.kind == TokenKind::Semicolon {
+            self.eat_token();
+        }
+
+        Ok(Statement::SwitchStatement {
+            value,
+            arms,
+            default: Box::new(default),
+        })
+    }
+
+    pub fn parse_struct_statement(&mut self) -> Result<Statement, ParserError> {
+        let name = self.expect_token(TokenKind::Identifier)?.literal.clone();
+        if !is_struct_name(&name) {
+            return Err(ParserError::SyntaxError(format!(
+                "Struct names start with an uppercase letter, like `Point`, not `{name}`"
+            )));
+        }
+        self.expect_token(TokenKind::LeftBrace)?;
+        let fields = self.parse_field_names(&name, TokenKind::RightBrace)?;
+
+        // make semicolons optional
+        if self.next.kind == TokenKind::Semicolon {
+            self.eat_token();
+        }
+
+        Ok(Statement::StructStatement { name, fields })
+    }
+
+    /// Parses the comma-separated field names of `owner`, until the `end` token.
+    fn parse_field_names(
+        &mut self,
+        owner: &str,
+        end: TokenKind,
+    ) -> Result<Vec<String>, ParserError> {
+        let mut fields: Vec<String> = vec![];
+        while self.next.kind != end {
+            let field = self.expect_token(TokenKind::Identifier)?.literal.clone();
+            if fields.contains(&field) {
+                return Err(repeated_field(owner, &field));
+            }
+            fields.push(field);
+
+            if self.next.kind == TokenKind::Comma {
+                self.eat_token();
+            } else if self.next.kind != end {
+                return Err(ParserError::SyntaxError(
+                    "Expected comma between fields".to_owned(),
+                ));
+            }
+        }
+        self.expect_token(end)?;
+
+        Ok(fields)
+    }
+
+    pub fn parse_enum_statement(&mut self) -> Result<Statement, ParserError> {
+        let name = self.expect_token(TokenKind::Identifier)?.literal.clone();
+        self.expect_token(TokenKind::LeftBrace)?;
+
+        let mut variants: Vec<EnumVariant> = vec![];
+        while self.next.kind != TokenKind::RightBrace {
+            let variant = self.expect_token(TokenKind::Identifier)?.literal.clone();
+            if variants.iter().any(|existing| existing.name == variant) {
+                return Err(ParserError::SyntaxError(format!(
+                    "The variant `{variant}` of `{name}` is repeated"
+                )));
+            }
+
+            let mut fields: Vec<String> = vec![];
+            if self.next.kind == TokenKind::LeftParen {
+                self.eat_token();
+                if self.next.kind == TokenKind::RightParen {
+                    return Err(ParserError::SyntaxError(format!(
+                        "Variants without fields are written without parentheses, like `{variant}`"
+                    )));
+                }
+                fields = self.parse_field_names(&variant, TokenKind::RightParen)?;
+            }
+            variants.push(EnumVariant {
+                name: variant,
+                fields,
+            });
+
+            if self.next.kind == TokenKind::Comma {
+                self.eat_token();
+            } else if self.next.kind != TokenKind::RightBrace {
+                return Err(ParserError::SyntaxError(
+                    "Expected comma between variants".to_owned(),
+                ));
+            }
+        }
+        self.expect_token(TokenKind::RightBrace)?;
+
+        // make semicolons optional
+        if self.next.kind == TokenKind::Semicolon {
+            self.eat_token();
+        }
+
+        Ok(Statement::EnumStatement { name, variants })
+    }
+
+    /// Parses the body of a `match` or `switch` arm, which is a block or an expression,
+    /// optionally followed by a comma or a semicolon.
+    fn parse_arm_body(&mut self) -> Result<Statement, ParserError> {
+        let body = if self.next.kind == TokenKind::LeftBrace {
+            self.eat_token();
+            self.parse_block_statement()?
+        } else {
+            Statement::ExpressionStatement(self.parse_expression(0, false)?)
+        };
 
-        while self.cur.kind != TokenKind::RightBrace {
-            let statement = self.parse_statement()?;
-            statements.push(statement);
+        if self.next.kind == TokenKind::Comma || self.next.kind == TokenKind::Semicolon {
             self.eat_token();
         }
 
-        Ok(Statement::BlockStatement(statements))
+        Ok(body)
     }
 
     pub fn parse_expression_statement(&mut self) -> Result<Statement, ParserError> {
@@ -156,35 +818,56 @@ impl<'a> Parser<'a> {
 
     fn infix_precedence(op: &TokenKind) -> Option<Precedence> {
         match op {
-            TokenKind::AndAnd | TokenKind::OrOr => Some(Precedence::Infix(1, 2)),
+            // binds the loosest, so that `xs |> take(n + 1)` pipes the whole left side
+            TokenKind::Pipeline => Some(Precedence::Infix(0, 1)),
+
+            TokenKind::DotDot | TokenKind::DotDotEqual => Some(Precedence::Infix(1, 2)),
 
-            TokenKind::Equal | TokenKind::NotEqual => Some(Precedence::Infix(3, 4)),
+            TokenKind::AndAnd | TokenKind::OrOr => Some(Precedence::Infix(3, 4)),
+
+            TokenKind::Equal | TokenKind::NotEqual => Some(Precedence::Infix(5, 6)),
 
             TokenKind::LessThan
             | TokenKind::GreaterThan
             | TokenKind::LessThanEqual
-            | TokenKind::GreaterThanEqual => Some(Precedence::Infix(5, 6)),
+            | TokenKind::GreaterThanEqual => Some(Precedence::Infix(7, 8)),
+
+            // bitwise operators bind tighter than comparisons, so `x & 1 == 0` is `(x & 1) == 0`
+            TokenKind::Pipe => Some(Precedence::Infix(9, 10)),
+
+            TokenKind::Caret => Some(Precedence::Infix(11, 12)),
+
+            TokenKind::Ampersand => Some(Precedence::Infix(13, 14)),
 
-            TokenKind::Plus | TokenKind::Minus => Some(Precedence::Infix(7, 8)),
+            TokenKind::ShiftLeft | TokenKind::ShiftRight => Some(Precedence::Infix(15, 16)),
+
+            TokenKind::Plus | TokenKind::Minus => Some(Precedence::Infix(17, 18)),
 
             TokenKind::Asterisk | TokenKind::Slash | TokenKind::Percentage => {
-                Some(Precedence::Infix(9, 10))
+                Some(Precedence::Infix(19, 20))
             }
 
+            // right associative, and binds tighter than prefix operators: `-2 ** 2` is `-(2 ** 2)`
+            TokenKind::DoubleAsterisk => Some(Precedence::Infix(23, 22)),
+
             _ => None,
         }
     }
 
     fn prefix_precedence(op: &TokenKind) -> Option<Precedence> {
         match op {
-            TokenKind::Bang | TokenKind::Minus => Some(Precedence::Prefix(11)),
+            TokenKind::Bang | TokenKind::Tilde | TokenKind::Minus | TokenKind::Plus => {
+                Some(Precedence::Prefix(21))
+            }
             _ => None,
         }
     }
 
     fn postfix_precedence(op: &TokenKind) -> Option<Precedence> {
         match op {
-            TokenKind::LeftSquare | TokenKind::LeftParen => Some(Precedence::Postfix(12)),
+            TokenKind::LeftSquare | TokenKind::LeftParen | TokenKind::Dot | TokenKind::Question => {
+                Some(Precedence::Postfix(24))
+            }
             _ => None,
         }
     }
@@ -196,16 +879,40 @@ impl<'a> Parser<'a> {
         &mut self,
         min_prec: u8,
         skip_eating: bool,
+    ) -> Result<Expression, ParserError> {
+        self.nested(|parser| parser.parse_pratt_expression(min_prec, skip_eating))
+    }
+
+    fn parse_pratt_expression(
+        &mut self,
+        min_prec: u8,
+        skip_eating: bool,
     ) -> Result<Expression, ParserError> {
         if !skip_eating {
             self.eat_token();
         }
+        let start = self.cur.span.start;
+
+        // struct literals are always allowed inside delimiters. The flag is set here rather than with
+        // `delimited`, which would add stack frames to every nested expression
+        let restricted = self.no_struct_literals;
+        if matches!(
+            self.cur.kind,
+            TokenKind::LeftSquare | TokenKind::LeftParen | TokenKind::LeftBrace
+        ) {
+            self.no_struct_literals = false;
+        }
 
         let mut expr = match self.cur.kind {
-            TokenKind::Integer => Expression::IntegerLiteral(self.cur.literal.parse::<i32>()?),
+            TokenKind::Integer => {
+                Expression::IntegerLiteral(parse_integer(&self.cur.literal, false)?)
+            }
+            TokenKind::Float => Expression::FloatLiteral(self.cur.literal.parse::<f64>()?),
             TokenKind::True => Expression::BooleanLiteral(true),
             TokenKind::False => Expression::BooleanLiteral(false),
+            TokenKind::Null => Expression::NullLiteral,
             TokenKind::String => Expression::StringLiteral(self.cur.literal.clone()),
+            TokenKind::Identifier if self.starts_struct_literal() => self.parse_struct_literal()?,
             TokenKind::Identifier => Expression::Identifier(self.cur.literal.clone()),
 
             TokenKind::LeftSquare => {
@@ -214,19 +921,35 @@ impl<'a> Parser<'a> {
 
             TokenKind::LeftParen => self.parse_grouped_expression()?,
 
-            TokenKind::LeftBrace => self.parse_map_expression()?,
+            TokenKind::LeftBrace if self.starts_map_literal() => self.parse_map_expression()?,
+            TokenKind::LeftBrace => self.parse_block_expression()?,
 
             // parse unary expressions based on prefix token precedences
-            TokenKind::Bang | TokenKind::Minus => self.parse_unary_expression()?,
+            TokenKind::Bang | TokenKind::Tilde | TokenKind::Minus | TokenKind::Plus => {
+                self.parse_unary_expression()?
+            }
 
             TokenKind::If => self.parse_if_expression()?,
 
             TokenKind::Function => self.parse_function_expression()?,
 
+            TokenKind::Match => self.parse_match_expression()?,
+            TokenKind::Loop | TokenKind::Label if self.expressions_only => {
+                return Err(ParserError::StatementNotAllowed(self.cur.clone()));
+            }
+            TokenKind::Loop => self.parse_loop_expression(None)?,
+            TokenKind::Label => {
+                let label = self.cur.literal[1..].to_owned();
+                self.expect_token(TokenKind::Colon)?;
+                self.expect_token(TokenKind::Loop)?;
+                self.parse_loop_expression(Some(label))?
+            }
+
             _ => {
                 return Err(ParserError::UnexpectedToken(self.cur.clone()));
             }
         };
+        self.no_struct_literals = restricted;
 
         // Pratt parsing uses both a *loop* and *recursion* to handle grouping based on precedences.
         loop {
@@ -239,41 +962,10 @@ impl<'a> Parser<'a> {
                 }
 
                 self.eat_token();
+                expr = self.parse_postfix_expression(expr, start)?;
 
-                expr = match self.cur.kind {
-                    TokenKind::LeftSquare => {
-                        if self.next.kind == TokenKind::RightSquare {
-                            return Err(ParserError::SyntaxError(
-                                "Define a valid index to access this structure (e.g., array[0])."
-                                    .to_owned(),
-                            ));
-                        }
-
-                        let index = Box::new(self.parse_expression(0, false)?);
-                        self.expect_token(TokenKind::RightSquare)?;
-
-                        Expression::IndexExpression {
-                            value: Box::new(expr),
-                            index,
-                        }
-                    }
-
-                    TokenKind::LeftParen => {
-                        let arguments = self.parse_expression_list(TokenKind::RightParen)?;
-
-                        Expression::CallExpression {
-                            path: Box::new(expr),
-                            arguments,
-                        }
-                    }
-
-                    _ => {
-                        return Err(ParserError::UnexpectedToken(self.cur.clone()));
-                    }
-                };
-
-                continue;
-            }
+                continue;
+            }
 
             // parse binary expressions based on infix operators precedences
             if let Some(Precedence::Infix(left_prec, right_prec)) =
@@ -291,6 +983,7 @@ impl<'a> Parser<'a> {
                     | TokenKind::Minus
                     | TokenKind::Slash
                     | TokenKind::Asterisk
+                    | TokenKind::DoubleAsterisk
                     | TokenKind::Percentage
                     | TokenKind::Equal
                     | TokenKind::NotEqual
@@ -299,18 +992,19 @@ impl<'a> Parser<'a> {
                     | TokenKind::LessThanEqual
                     | TokenKind::GreaterThanEqual
                     | TokenKind::AndAnd
-                    | TokenKind::OrOr => {
+                    | TokenKind::OrOr
+                    | TokenKind::Ampersand
+                    | TokenKind::Pipe
+                    | TokenKind::Caret
+                    | TokenKind::ShiftLeft
+                    | TokenKind::ShiftRight
+                    | TokenKind::DotDot
+                    | TokenKind::DotDotEqual => {
                         let right = self.parse_expression(right_prec, false)?;
 
-                        Expression::BinaryExpression {
-                            left: Box::new(expr),
-                            operator,
-                            right: Box::new(right),
-                        }
-                    }
-                    _ => {
-                        return Err(ParserError::UnexpectedToken(self.cur.clone()));
+                        Self::build_binary_expression(expr, operator, right)
                     }
+                    _ => self.parse_pipeline(expr, right_prec, start)?,
                 };
 
                 continue;
@@ -322,6 +1016,195 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    /// Desugars `value |> target` into a call of `target` with `value` as its first argument:
+    /// `xs |> f(1)` is `f(xs, 1)`, `xs |> m.f(1)` is `m.f(xs, 1)` and `xs |> f` is `f(xs)`.
+    /// Kept apart from `parse_pratt_expression`, whose stack frame is part of every nested expression.
+    fn parse_pipeline(
+        &mut self,
+        value: Expression,
+        precedence: u8,
+        start: usize,
+    ) -> Result<Expression, ParserError> {
+        if self.cur.kind != TokenKind::Pipeline {
+            return Err(ParserError::UnexpectedToken(self.cur.clone()));
+        }
+
+        match self.parse_expression(precedence, false)? {
+            Expression::CallExpression {
+                callee,
+                mut arguments,
+                named,
+                span,
+            } => {
+                arguments.insert(0, value);
+                Ok(Expression::CallExpression {
+                    callee,
+                    arguments,
+                    named,
+                    span,
+                })
+            }
+            Expression::MethodCall(mut call) => {
+                call.arguments.insert(0, value);
+                Ok(Expression::MethodCall(call))
+            }
+            callee @ (Expression::Identifier(_)
+            | Expression::FieldAccess { .. }
+            | Expression::IndexExpression { .. }
+            | Expression::GroupedExpression(_)
+            | Expression::FunctionExpression { .. }) => Ok(Expression::CallExpression {
+                callee: Box::new(callee),
+                arguments: vec![value],
+                named: vec![],
+                span: Span {
+                    start,
+                    end: self.cur.span.end,
+                },
+            }),
+            _ => Err(ParserError::SyntaxError(
+                "The right side of `|>` must be a function or a call, like `f` or `f(x)`"
+                    .to_owned(),
+            )),
+        }
+    }
+
+    fn is_relational_operator(op: &TokenKind) -> bool {
+        matches!(
+            op,
+            TokenKind::LessThan
+                | TokenKind::GreaterThan
+                | TokenKind::LessThanEqual
+                | TokenKind::GreaterThanEqual
+        )
+    }
+
+    /// Builds a binary expression, turning chained relational operators
+    /// (e.g. `1 < x < 10`) into a single comparison chain.
+    fn build_binary_expression(
+        left: Expression,
+        operator: TokenKind,
+        right: Expression,
+    ) -> Expression {
+        if !Self::is_relational_operator(&operator) {
+            return Expression::BinaryExpression {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            };
+        }
+
+        match left {
+            Expression::BinaryExpression {
+                left: first,
+                operator: first_operator,
+                right: middle,
+            } if Self::is_relational_operator(&first_operator) => Expression::ComparisonChain {
+                operands: vec![*first, *middle, right],
+                operators: vec![first_operator, operator],
+            },
+            Expression::ComparisonChain {
+                mut operands,
+                mut operators,
+            } => {
+                operands.push(right);
+                operators.push(operator);
+                Expression::ComparisonChain {
+                    operands,
+                    operators,
+                }
+            }
+            left => Expression::BinaryExpression {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            },
+        }
+    }
+
+    /// Parses the postfix operator at the current token, applied to `expr` which starts at `start`.
+    /// Kept apart from `parse_pratt_expression`, whose stack frame is part of every nested expression.
+    fn parse_postfix_expression(
+        &mut self,
+        expr: Expression,
+        start: usize,
+    ) -> Result<Expression, ParserError> {
+        // indices and arguments are delimited, so struct literals are allowed in them
+        let restricted = std::mem::replace(&mut self.no_struct_literals, false);
+        let expr = match self.cur.kind {
+            TokenKind::LeftSquare => {
+                if self.next.kind == TokenKind::RightSquare {
+                    return Err(ParserError::SyntaxError(
+                        "Define a valid index to access this structure (e.g., array[0])."
+                            .to_owned(),
+                    ));
+                }
+
+                let index = Box::new(self.parse_expression(0, false)?);
+                self.expect_token(TokenKind::RightSquare)?;
+
+                Expression::IndexExpression {
+                    value: Box::new(expr),
+                    index,
+                }
+            }
+
+            TokenKind::LeftParen => {
+                let (arguments, named) = self.parse_call_arguments()?;
+
+                Expression::CallExpression {
+                    callee: Box::new(expr),
+                    arguments,
+                    named,
+                    span: Span {
+                        start,
+                        end: self.cur.span.end,
+                    },
+                }
+            }
+
+            TokenKind::Dot => {
+                let field = self.expect_token(TokenKind::Identifier)?.literal.clone();
+
+                if self.next.kind == TokenKind::LeftParen {
+                    self.eat_token();
+                    let (arguments, named) = self.parse_call_arguments()?;
+
+                    Expression::MethodCall(Box::new(MethodCall {
+                        receiver: expr,
+                        method: field,
+                        arguments,
+                        named,
+                        span: Span {
+                            start,
+                            end: self.cur.span.end,
+                        },
+                    }))
+                } else {
+                    Expression::FieldAccess {
+                        value: Box::new(expr),
+                        field,
+                    }
+                }
+            }
+
+            TokenKind::Question => Expression::UnaryExpression {
+                operator: TokenKind::Question,
+                value: Box::new(expr),
+                span: Span {
+                    start,
+                    end: self.cur.span.start,
+                },
+            },
+
+            _ => {
+                return Err(ParserError::UnexpectedToken(self.cur.clone()));
+            }
+        };
+        self.no_struct_literals = restricted;
+
+        Ok(expr)
+    }
+
     pub fn parse_grouped_expression(&mut self) -> Result<Expression, ParserError> {
         self.eat_token();
         let expr = match self.cur.kind {
@@ -340,9 +1223,61 @@ impl<'a> Parser<'a> {
         Ok(Expression::GroupedExpression(Box::new(expr)))
     }
 
+    /// Whether the current identifier starts a struct literal like `Point { x: 1 }`:
+    /// struct names are capitalized, so that `x {` can still be followed by a block.
+    fn starts_struct_literal(&self) -> bool {
+        !self.no_struct_literals
+            && self.next.kind == TokenKind::LeftBrace
+            && is_struct_name(&self.cur.literal)
+    }
+
+    fn parse_struct_literal(&mut self) -> Result<Expression, ParserError> {
+        let name = self.cur.literal.clone();
+        self.expect_token(TokenKind::LeftBrace)?;
+
+        let mut fields: Vec<(String, Expression)> = vec![];
+        while self.next.kind != TokenKind::RightBrace {
+            let field = self.expect_token(TokenKind::Identifier)?.literal.clone();
+            if fields.iter().any(|(existing, _)| *existing == field) {
+                return Err(repeated_field(&name, &field));
+            }
+            self.expect_token(TokenKind::Colon)?;
+            fields.push((field, self.parse_expression(0, false)?));
+
+            if self.next.kind == TokenKind::Comma {
+                self.eat_token();
+            } else if self.next.kind != TokenKind::RightBrace {
+                return Err(ParserError::SyntaxError(
+                    "Expected comma between fields".to_owned(),
+                ));
+            }
+        }
+        self.expect_token(TokenKind::RightBrace)?;
+
+        Ok(Expression::StructLiteral { name, fields })
+    }
+
+    fn parse_block_expression(&mut self) -> Result<Expression, ParserError> {
+        Ok(Expression::BlockExpression(Box::new(
+            self.parse_block_statement()?,
+        )))
+    }
+
+    /// Whether the current `{` starts a map literal rather than a block: maps are either empty,
+    /// or start with a string key followed by `:`.
+    fn starts_map_literal(&self) -> bool {
+        match self.next.kind {
+            TokenKind::RightBrace => true,
+            TokenKind::String => self.lexer.clone().next_token().kind == TokenKind::Colon,
+            _ => false,
+        }
+    }
+
     pub fn parse_map_expression(&mut self) -> Result<Expression, ParserError> {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         let end = TokenKind::RightBrace;
+        // spans of the statements in each value, reordered by key once the map is parsed
+        let mut value_spans = BTreeMap::new();
 
         while self.next.kind != end {
             let key = match self.parse_expression(0, false)? {
@@ -355,7 +1290,9 @@ impl<'a> Parser<'a> {
             };
 
             self.expect_token(TokenKind::Colon)?;
+            let value_start = self.spans.len();
             let value = self.parse_expression(0, false)?;
+            value_spans.insert(key.clone(), self.spans.split_off(value_start));
             map.insert(key, value);
 
             if self.next.kind == TokenKind::Comma {
@@ -368,6 +1305,7 @@ impl<'a> Parser<'a> {
         }
 
         self.expect_token(end)?;
+        self.spans.extend(value_spans.into_values().flatten());
 
         Ok(Expression::MapLiteral(map))
     }
@@ -393,20 +1331,77 @@ impl<'a> Parser<'a> {
         Ok(expressions)
     }
 
+    /// Parses the arguments of a call, where the named ones like `x: 1` come after the positional ones.
+    fn parse_call_arguments(&mut self) -> Result<CallArguments, ParserError> {
+        let mut arguments: Vec<Expression> = vec![];
+        let mut named: Vec<(String, Expression)> = vec![];
+
+        while self.next.kind != TokenKind::RightParen {
+            match self.parse_expression(0, false)? {
+                Expression::Identifier(name) if self.next.kind == TokenKind::Colon => {
+                    self.eat_token();
+                    named.push((name, self.parse_expression(0, false)?));
+                }
+                argument if named.is_empty() => arguments.push(argument),
+                _ => {
+                    return Err(ParserError::SyntaxError(
+                        "Positional arguments must come before named ones".to_owned(),
+                    ))
+                }
+            }
+
+            if self.next.kind == TokenKind::Comma {
+                self.eat_token();
+            } else if self.next.kind != TokenKind::RightParen {
+                return Err(ParserError::SyntaxError(
+                    "Expected comma between arguments".to_owned(),
+                ));
+            }
+        }
+
+        self.expect_token(TokenKind::RightParen)?;
+
+        Ok((arguments, named))
+    }
+
     pub fn parse_unary_expression(&mut self) -> Result<Expression, ParserError> {
         let operator = self.cur.kind.clone();
 
-        let Some(Precedence::Prefix(prefix_prec)) = Self::prefix_precedence(&self.cur.kind) else {
+        // negative integers are parsed as a single literal, so that `-9223372036854775808` fits in an `i64`
+        if operator == TokenKind::Minus && self.next.kind == TokenKind::Integer {
+            self.eat_token();
+
+            if self.next.kind != TokenKind::DoubleAsterisk {
+                let literal = parse_integer(&self.cur.literal, true)?;
+                return Ok(Expression::IntegerLiteral(literal));
+            }
+        }
+
+        let Some(Precedence::Prefix(prefix_prec)) = Self::prefix_precedence(&operator) else {
             unreachable!();
         };
 
-        let value = Box::new(self.parse_expression(prefix_prec, false)?);
-
-        Ok(Expression::UnaryExpression { operator, value })
+        // the operand has already been eaten if it's an integer followed by `**`
+        let skip_eating = self.cur.kind == TokenKind::Integer;
+        let start = if skip_eating {
+            self.cur.span.start
+        } else {
+            self.next.span.start
+        };
+        let value = Box::new(self.parse_expression(prefix_prec, skip_eating)?);
+
+        Ok(Expression::UnaryExpression {
+            operator,
+            value,
+            span: Span {
+                start,
+                end: self.cur.span.end,
+            },
+        })
     }
 
     pub fn parse_if_expression(&mut self) -> Result<Expression, ParserError> {
-        let condition = self.parse_expression(0, false)?;
+        let condition = self.parse_condition()?;
         self.expect_token(TokenKind::LeftBrace)?;
         let consequence = self.parse_block_statement()?;
 
@@ -429,24 +1424,268 @@ impl<'a> Parser<'a> {
         self.expect_token(TokenKind::LeftParen)?;
 
         let mut parameters: Vec<String> = vec![];
+        let mut rest = None;
         while self.next.kind != TokenKind::RightParen {
-            if self.next.kind != TokenKind::Identifier && self.next.kind != TokenKind::Comma {
-                break;
+            if rest.is_some() {
+                return Err(ParserError::SyntaxError(
+                    "The rest parameter must be the last one".to_owned(),
+                ));
             }
 
-            self.expect_token(TokenKind::Identifier)?;
-            parameters.push(self.cur.literal.clone());
+            if self.next.kind == TokenKind::Ellipsis {
+                self.eat_token();
+                self.expect_token(TokenKind::Identifier)?;
+                rest = Some(self.cur.literal.clone());
+            } else {
+                self.expect_token(TokenKind::Identifier)?;
+                parameters.push(self.cur.literal.clone());
+            }
 
             if self.next.kind == TokenKind::Comma {
                 self.eat_token();
+            } else if self.next.kind != TokenKind::RightParen {
+                return Err(ParserError::SyntaxError(
+                    "Expected comma between parameters".to_owned(),
+                ));
             }
         }
 
         self.expect_token(TokenKind::RightParen)?;
         self.expect_token(TokenKind::LeftBrace)?;
-        let body = Box::new(self.parse_block_statement()?);
 
-        Ok(Expression::FunctionExpression { parameters, body })
+        // the loops around the function can't be exited from its body,
+        // which can declare its own bindings even in a standalone expression
+        let loops = std::mem::take(&mut self.loops);
+        let expressions_only = std::mem::replace(&mut self.expressions_only, false);
+        let body = self.parse_block_statement();
+        self.expressions_only = expressions_only;
+        self.loops = loops;
+        let body = Box::new(body?);
+
+        Ok(Expression::FunctionExpression {
+            parameters,
+            rest,
+            body,
+        })
+    }
+
+    pub fn parse_match_expression(&mut self) -> Result<Expression, ParserError> {
+        let value = self.parse_condition()?;
+        self.expect_token(TokenKind::LeftBrace)?;
+
+        let mut arms: Vec<MatchArm> = vec![];
+        while self.next.kind != TokenKind::RightBrace {
+            self.expect_token(TokenKind::Case)?;
+            let pattern = self.parse_pattern()?;
+            let guard = if self.next.kind == TokenKind::If {
+                self.eat_token();
+                Some(self.parse_expression(0, false)?)
+            } else {
+                None
+            };
+            self.expect_token(TokenKind::Colon)?;
+            let body = self.parse_arm_body()?;
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+        }
+
+        self.expect_token(TokenKind::RightBrace)?;
+
+        Ok(Expression::MatchExpression {
+            value: Box::new(value),
+            arms,
+        })
+    }
+
+    /// Parse a pattern, including alternatives separated by `|`.
+    pub fn parse_pattern(&mut self) -> Result<Pattern, ParserError> {
+        self.nested(Self::parse_pattern_alternatives)
+    }
+
+    fn parse_pattern_alternatives(&mut self) -> Result<Pattern, ParserError> {
+        let mut patterns = vec![self.parse_single_pattern()?];
+
+        while self.next.kind == TokenKind::Pipe {
+            self.eat_token();
+            patterns.push(self.parse_single_pattern()?);
+        }
+
+        if patterns.len() == 1 {
+            Ok(patterns.remove(0))
+        } else {
+            Ok(Pattern::Or(patterns))
+        }
+    }
+
+    /// Parses a pattern matching a variant of an enum, like `Color.Red` or `Color.Custom(r, g, b)`.
+    /// The variants of the prelude are written on their own, like `Some(x)` or `None`.
+    fn parse_variant_pattern(&mut self) -> Result<Pattern, ParserError> {
+        let (name, variant) = match prelude_enum(&self.cur.literal) {
+            Some(name) => (name.to_owned(), self.cur.literal.clone()),
+            None => {
+                let name = self.cur.literal.clone();
+                self.expect_token(TokenKind::Dot)?;
+                (
+                    name,
+                    self.expect_token(TokenKind::Identifier)?.literal.clone(),
+                )
+            }
+        };
+
+        let mut fields: Vec<Pattern> = vec![];
+        if self.next.kind == TokenKind::LeftParen {
+            self.eat_token();
+            while self.next.kind != TokenKind::RightParen {
+                fields.push(self.parse_pattern()?);
+
+                if self.next.kind == TokenKind::Comma {
+                    self.eat_token();
+                } else if self.next.kind != TokenKind::RightParen {
+                    return Err(ParserError::SyntaxError(
+                        "Expected comma between patterns".to_owned(),
+                    ));
+                }
+            }
+            self.expect_token(TokenKind::RightParen)?;
+        }
+
+        Ok(Pattern::Variant {
+            name,
+            variant,
+            fields,
+        })
+    }
+
+    fn parse_single_pattern(&mut self) -> Result<Pattern, ParserError> {
+        self.eat_token();
+
+        let pattern = match self.cur.kind {
+            TokenKind::Identifier if self.cur.literal == "_" => Pattern::Wildcard,
+            TokenKind::Identifier
+                if self.next.kind == TokenKind::Dot
+                    || prelude_enum(&self.cur.literal).is_some() =>
+            {
+                self.parse_variant_pattern()?
+            }
+            TokenKind::Identifier => Pattern::Identifier(self.cur.literal.clone()),
+            TokenKind::Integer => Pattern::Literal(Expression::IntegerLiteral(parse_integer(
+                &self.cur.literal,
+                false,
+            )?)),
+            TokenKind::Minus => {
+                let lit = self.expect_token(TokenKind::Integer)?;
+                Pattern::Literal(Expression::IntegerLiteral(parse_integer(
+                    &lit.literal,
+                    true,
+                )?))
+            }
+            TokenKind::True => Pattern::Literal(Expression::BooleanLiteral(true)),
+            TokenKind::False => Pattern::Literal(Expression::BooleanLiteral(false)),
+            TokenKind::Null => Pattern::Literal(Expression::NullLiteral),
+            TokenKind::String => {
+                Pattern::Literal(Expression::StringLiteral(self.cur.literal.clone()))
+            }
+            TokenKind::LeftSquare => {
+                let mut elements: Vec<Pattern> = vec![];
+                let mut rest = None;
+
+                while self.next.kind != TokenKind::RightSquare {
+                    if self.next.kind == TokenKind::DotDot {
+                        self.eat_token();
+
+                        rest = if self.next.kind == TokenKind::Identifier {
+                            Some(Box::new(self.parse_single_pattern()?))
+                        } else {
+                            Some(Box::new(Pattern::Wildcard))
+                        };
+
+                        // the rest pattern must be the last one
+                        if self.next.kind == TokenKind::Comma {
+                            self.eat_token();
+                        }
+                        break;
+                    }
+
+                    let element = self.parse_pattern()?;
+
+                    // the rest can also be written after its name, e.g. `rest...`
+                    if self.next.kind == TokenKind::Ellipsis {
+                        if !matches!(element, Pattern::Identifier(_) | Pattern::Wildcard) {
+                            return Err(ParserError::SyntaxError(format!(
+                                "Only an identifier or `_` can bind the rest of an array, got `{element}`"
+                            )));
+                        }
+                        self.eat_token();
+                        rest = Some(Box::new(element));
+
+                        if self.next.kind == TokenKind::Comma {
+                            self.eat_token();
+                        }
+                        break;
+                    }
+                    elements.push(element);
+
+                    if self.next.kind == TokenKind::Comma {
+                        self.eat_token();
+                    } else if self.next.kind != TokenKind::RightSquare {
+                        return Err(ParserError::SyntaxError(
+                            "Expected comma between patterns".to_owned(),
+                        ));
+                    }
+                }
+
+                self.expect_token(TokenKind::RightSquare)?;
+
+                Pattern::Array { elements, rest }
+            }
+            TokenKind::LeftBrace => {
+                let mut entries: Vec<(String, Pattern)> = vec![];
+
+                while self.next.kind != TokenKind::RightBrace {
+                    self.eat_token();
+                    let key = match self.cur.kind {
+                        TokenKind::Identifier | TokenKind::String => self.cur.literal.clone(),
+                        _ => return Err(ParserError::UnexpectedToken(self.cur.clone())),
+                    };
+
+                    let pattern = if self.next.kind == TokenKind::Colon {
+                        self.eat_token();
+                        self.parse_pattern()?
+                    } else if self.cur.kind == TokenKind::Identifier {
+                        Pattern::Identifier(key.clone())
+                    } else {
+                        return Err(ParserError::SyntaxError(format!(
+                            "The key \"{key}\" must be followed by the pattern of its value, e.g. `\"{key}\": value`"
+                        )));
+                    };
+
+                    if entries.iter().any(|(other, _)| *other == key) {
+                        return Err(ParserError::SyntaxError(format!(
+                            "The key \"{key}\" appears more than once in the pattern"
+                        )));
+                    }
+                    entries.push((key, pattern));
+
+                    if self.next.kind == TokenKind::Comma {
+                        self.eat_token();
+                    } else if self.next.kind != TokenKind::RightBrace {
+                        return Err(ParserError::SyntaxError(
+                            "Expected comma between patterns".to_owned(),
+                        ));
+                    }
+                }
+
+                self.expect_token(TokenKind::RightBrace)?;
+
+                Pattern::Map { entries }
+            }
+            _ => return Err(ParserError::UnexpectedToken(self.cur.clone())),
+        };
+
+        Ok(pattern)
     }
 }
 
@@ -460,7 +1699,7 @@ mod tests {
             let five = 5;
             let taken = false;
             let temp = taken;
-            let seven = five + 2 * 1;
+            var seven = five + 2 * 1;
         "#;
 
         let num_vars = input.lines().count() - 2;
@@ -482,6 +1721,66 @@ mod tests {
         parser.parse_return_statement().unwrap();
     }
 
+    #[test]
+    fn parse_optional_semicolons() {
+        let tests = vec![
+            // the last statement of a program or of a block can leave out its semicolon
+            ("let x = 1", Ok("let x = 1;")),
+            ("var y = 2; y = 3", Ok("var y = 2;y = 3;")),
+            ("{ let z = 1 }", Ok("{let z = 1;}")),
+            ("let [a, b] = [1, 2]", Ok("let [a, b] = [1, 2];")),
+            ("fn() { return 1 }", Ok("fn() {return 1;}")),
+            ("fn() { return }", Ok("fn() {return;}")),
+            ("loop { break }", Ok("loop {break;}")),
+            ("loop { break 'a }", Err("The label `'a` isn't defined")),
+            ("'a: loop { break 'a 1 }", Ok("'a: loop {break 'a 1;}")),
+            ("for x in xs { continue }", Ok("for x in xs {continue;}")),
+            ("do { x } while x < 1", Ok("do {x} while (x < 1);")),
+            // elsewhere, a line break doesn't end a statement
+            ("let x = 1 let y = 2", Err("Unexpected token")),
+            ("let x = 1\nlet y = 2;", Err("Unexpected token")),
+            ("fn() { return\n1 }", Ok("fn() {return 1;}")),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            match expected {
+                Ok(expected) => assert_eq!(result.as_deref(), Ok(expected), "{input}"),
+                Err(expected) => assert!(result.unwrap_err().contains(expected), "{input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_newline_terminated_statements() {
+        let tests = vec![
+            (
+                "let x = 1\nlet y = x\ny = 2",
+                Ok("let x = 1;let y = x;y = 2;"),
+            ),
+            ("fn() {\n  return\n  1\n}", Ok("fn() {return;1}")),
+            ("loop {\n  break\n}", Ok("loop {break;}")),
+            // expressions can still span several lines
+            ("let x = 1 +\n  2\nx", Ok("let x = (1 + 2);x")),
+            ("let xs = [\n  1,\n  2,\n]", Ok("let xs = [1, 2];")),
+            ("let x = 1 let y = 2", Err("Unexpected token")),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::interactive(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            match expected {
+                Ok(expected) => assert_eq!(result.as_deref(), Ok(expected), "{input}"),
+                Err(expected) => assert!(result.unwrap_err().contains(expected), "{input}"),
+            }
+        }
+    }
+
     #[test]
     fn parse_expression_statement() {
         let input = r#"
@@ -513,6 +1812,138 @@ mod tests {
         parser.parse_block_statement().unwrap();
     }
 
+    #[test]
+    fn parse_for_statement() {
+        let input = r#"
+            for x in [1, 2, 3] {
+                println(x);
+            }
+        "#;
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program().unwrap();
+        assert_eq!(program.to_string(), "for x in [1, 2, 3] {println(x)}");
+    }
+
+    #[test]
+    fn parse_keyword_typos() {
+        let mut parser = Parser::new("lett x = 5;");
+        let err = parser.parse_program().unwrap_err();
+        assert!(matches!(
+            &err,
+            ParserError::KeywordTypo(token, TokenKind::Let) if token.literal == "lett"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unexpected identifier `lett`, did you mean the keyword `let`?"
+        );
+
+        // parsing continues after the typo, so later errors are reported too
+        let mut parser = Parser::new("let f = fn() { retun x; }; vat y = 2; let = 3;");
+        let Err(ParserError::Multiple(errors)) = parser.parse_program() else {
+            panic!("expected multiple errors");
+        };
+        assert!(matches!(
+            errors[0],
+            ParserError::KeywordTypo(_, TokenKind::Return)
+        ));
+        assert!(matches!(
+            errors[1],
+            ParserError::KeywordTypo(_, TokenKind::Var)
+        ));
+        assert!(matches!(errors[2], ParserError::UnexpectedToken(_)));
+
+        // identifiers that are valid expressions aren't touched
+        let mut parser = Parser::new("lets; ret + 1; fort(1)");
+        parser.parse_program().unwrap();
+    }
+
+    #[test]
+    fn parse_max_depth() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+        let input = nested(100);
+        let mut parser = Parser::new(&input);
+        parser.parse_program().unwrap();
+
+        let input = nested(100_000);
+        let mut parser = Parser::new(&input);
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParserError::NestingTooDeep(DEFAULT_MAX_DEPTH))
+        ));
+
+        let input = format!("{}1", "-".repeat(10));
+        let mut parser = Parser::new(&input);
+        parser.max_depth = 5;
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParserError::NestingTooDeep(5))
+        ));
+
+        let input = format!("{}{}", "{".repeat(10), "}".repeat(10));
+        let mut parser = Parser::new(&input);
+        parser.max_depth = 5;
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParserError::NestingTooDeep(5))
+        ));
+    }
+
+    #[test]
+    fn parse_radix_integers() {
+        let tests = vec![
+            ("0x1F", Ok(31)),
+            ("0xff", Ok(255)),
+            ("0o17", Ok(15)),
+            ("0b1010", Ok(10)),
+            ("007", Ok(7)),
+            ("-0x8000000000000000", Ok(i64::MIN)),
+            ("0x1G", Err("Malformed number literal `0x1G`: `G` isn't a valid hexadecimal digit")),
+            ("0o8", Err("Malformed number literal `0o8`: `8` isn't a valid octal digit")),
+            ("0b", Err("Malformed number literal `0b`: binary literals need at least one digit")),
+            ("0x8000000000000000", Err("Malformed number literal `0x8000000000000000`: the number doesn't fit in a 64 bit integer")),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let result = parser
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            let expected = expected
+                .map(|value: i64| value.to_string())
+                .map_err(str::to_owned);
+            assert_eq!(result, expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn parse_comments() {
+        let input = r#"
+            // the answer
+            let a = 40 + /* inline */ 2; // trailing
+            /*
+                let b = 1;
+            */
+        "#;
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program().unwrap();
+        assert_eq!(program.to_string(), "let a = (40 + 2);");
+
+        let mut parser = Parser::new("let a = 1; /* let b = 2;");
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParserError::UnterminatedComment(Span { start: 11, .. }))
+        ));
+
+        let mut parser = Parser::new("let a = /* 1;");
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ParserError::Multiple(errors)) if matches!(errors[0], ParserError::UnterminatedComment(_))
+        ));
+    }
+
     #[test]
     fn parse_program() {
         let input = r#"
@@ -535,9 +1966,23 @@ mod tests {
             ("a * b * c", "((a * b) * c)"),
             ("a * b / c", "((a * b) / c)"),
             ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
-            ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+            ("3 + 4; -5 * 5", "(3 + 4)(-5 * 5)"),
+            ("+a * -b", "((+a) * (-b))"),
+            ("+5 - -5", "((+5) - -5)"),
+            ("-9223372036854775808", "-9223372036854775808"),
             ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
             ("5 > 4 != 3 < 4", "((5 > 4) != (3 < 4))"),
+            ("1 <= 2 == 3 >= 4", "((1 <= 2) == (3 >= 4))"),
+            ("a % b + c % d", "((a % b) + (c % d))"),
+            ("a * b % c", "((a * b) % c)"),
+            ("a + 1 <= b - 1", "((a + 1) <= (b - 1))"),
+            ("a != b && c == d", "((a != b) && (c == d))"),
+            ("1 < x < 10", "(1 < x < 10)"),
+            ("a <= b < c >= d", "(a <= b < c >= d)"),
+            ("(1 < x) < 10", "((1 < x) < 10)"),
+            ("1 < x < 10 == true", "((1 < x < 10) == true)"),
+            ("0..n + 1", "(0 .. (n + 1))"),
+            ("-1..=len(a) * 2", "(-1 ..= (len(a) * 2))"),
             (
                 "3 + 4 * 5 == 3 * 1 + 4 * 5",
                 "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
@@ -569,6 +2014,15 @@ mod tests {
                 "add(a * b[2], b[1], 2 * [1, 2][1])",
                 "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
             ),
+            ("-fs.read(a).b + 1", "((-fs.read(a).b) + 1)"),
+            ("2 ** 3 ** 2", "(2 ** (3 ** 2))"),
+            ("-2 ** 2", "(-(2 ** 2))"),
+            ("-x ** 2 * 3", "((-(x ** 2)) * 3)"),
+            ("2 ** -1", "(2 ** -1)"),
+            ("a[0] ** f(2)", "((a[0]) ** f(2))"),
+            ("a | b ^ c & d << 1 + 2", "(a | (b ^ (c & (d << (1 + 2)))))"),
+            ("x & 1 == 0", "((x & 1) == 0)"),
+            ("~a >> 2", "((~a) >> 2)"),
         ];
 
         for test in tests {
@@ -596,6 +2050,287 @@ mod tests {
         parser.parse_program().unwrap();
     }
 
+    #[test]
+    fn parse_rest_parameters() {
+        let tests = vec![
+            ("fn(...args) { args }", Ok("fn(...args) {args}")),
+            ("fn(a, b, ...rest) { rest }", Ok("fn(a, b, ...rest) {rest}")),
+            (
+                "fn(...rest, a) { a }",
+                Err("Syntax error: The rest parameter must be the last one"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(str::to_owned).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+
+        assert!(Parser::new("fn(...) { 1 }").parse_program().is_err());
+    }
+
+    #[test]
+    fn parse_deprecated_attribute() {
+        let tests = vec![
+            (
+                r#"@deprecated("use draw2") let draw = fn(x) { x };"#,
+                Ok(r#"@deprecated("use draw2") let draw = fn(x) {x};"#),
+            ),
+            (
+                r#"@deprecated("use x2") let x = 1;"#,
+                Err("Syntax error: `@deprecated` only applies to function declarations"),
+            ),
+            (
+                r#"@inline("always") let f = fn() { 1 };"#,
+                Err("Syntax error: Unknown attribute `@inline`"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(str::to_owned).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_destructure_statements() {
+        let tests = vec![
+            ("let [a, b] = pair;", Ok("let [a, b] = pair;")),
+            ("var [a, _, rest...] = arr;", Ok("var [a, _, ..rest] = arr;")),
+            ("let [[a, b], ..] = arr;", Ok("let [[a, b], .._] = arr;")),
+            (
+                "let {name, age: a, \"first name\": f} = person;",
+                Ok("let {name, age: a, \"first name\": f} = person;"),
+            ),
+            ("let {pos: [x, y]} = p;", Ok("let {pos: [x, y]} = p;")),
+            (
+                "let [a, 1] = arr;",
+                Err("Syntax error: Only identifiers, `_`, arrays and hash maps can be destructured by `let`, got `[a, 1]`"),
+            ),
+            (
+                "let {\"first name\"} = person;",
+                Err("Syntax error: The key \"first name\" must be followed by the pattern of its value, e.g. `\"first name\": value`"),
+            ),
+            (
+                "let {a, a: b} = m;",
+                Err("Syntax error: The key \"a\" appears more than once in the pattern"),
+            ),
+            (
+                "let [a, [b]...] = arr;",
+                Err("Syntax error: Only an identifier or `_` can bind the rest of an array, got `[b]`"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(str::to_owned).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+
+        // rest patterns can be written both ways in `match` too
+        assert!(Parser::new("match x { case [a, rest...]: rest }")
+            .parse_program()
+            .is_ok());
+        assert_eq!(
+            Parser::new("match x { case {\"kind\": \"circle\", r}: r }")
+                .parse_program()
+                .unwrap()
+                .to_string(),
+            "match x {case {kind: \"circle\", r}: r;}"
+        );
+    }
+
+    #[test]
+    fn parse_block_expressions() {
+        let tests = vec![
+            (
+                "let x = { let a = compute(); a * 2 };",
+                "let x = {let a = compute();(a * 2)};",
+            ),
+            ("let x = 1 + { 2 };", "let x = (1 + {2});"),
+            ("let x = {};", "let x = {};"),
+            ("let x = {\"a\": 1};", "let x = {\"a\": 1};"),
+            ("let x = { \"a\" };", "let x = {\"a\"};"),
+            ("f({ let y = 1; y })", "f({let y = 1;y})"),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(input).parse_program().unwrap();
+            assert_eq!(program.to_string(), expected, "{input}");
+        }
+
+        assert!(matches!(
+            Parser::new("{}").parse_standalone_expression(),
+            Ok(Expression::MapLiteral(_))
+        ));
+        assert!(matches!(
+            Parser::new("{ 1 }").parse_standalone_expression(),
+            Ok(Expression::BlockExpression(_))
+        ));
+        // blocks and the bodies of `if` can't bind or assign identifiers in a standalone expression
+        for input in [
+            "{ a = 5; a }",
+            "{ let z = 3; z }",
+            "if a { var b = 1; b } else { 0 }",
+            "match a { case _: { a = 1; } }",
+            "1 + { struct P { x } 2 }",
+        ] {
+            assert!(
+                matches!(
+                    Parser::new(input).parse_standalone_expression(),
+                    Err(ParserError::StatementNotAllowed(_))
+                ),
+                "{input}"
+            );
+        }
+        assert!(Parser::new("fn(x) { let y = { let z = x; z }; y }(1)")
+            .parse_standalone_expression()
+            .is_ok());
+    }
+
+    #[test]
+    fn parse_loops() {
+        let tests = vec![
+            (
+                "let x = loop { if done() { break 1; } continue; };",
+                Ok("let x = loop {if done() {break 1;}continue;};"),
+            ),
+            ("for x in xs { if x { break; } }", Ok("for x in xs {if x {break;}}")),
+            (
+                "loop { for x in xs { break; } break; }",
+                Ok("loop {for x in xs {break;}break;}"),
+            ),
+            (
+                "break;",
+                Err("`break` can only be used inside a loop, and not from a function defined in it"),
+            ),
+            (
+                "if x { continue; }",
+                Err("`continue` can only be used inside a loop, and not from a function defined in it"),
+            ),
+            (
+                "loop { let f = fn() { break; }; }",
+                Err("`break` can only be used inside a loop, and not from a function defined in it"),
+            ),
+            (
+                "loop { for x in xs { break x; } }",
+                Err("Syntax error: Only `loop` can be exited with a value, not `for`"),
+            ),
+            (
+                "'outer: for x in xs { for y in ys { if y { continue 'outer; } break 'outer; } }",
+                Ok("'outer: for x in xs {for y in ys {if y {continue 'outer;}break 'outer;}}"),
+            ),
+            (
+                "let v = 'a: loop { for x in xs { break 'a x; } };",
+                Ok("let v = 'a: loop {for x in xs {break 'a x;}};"),
+            ),
+            ("'a: loop { break 'a; }", Ok("'a: loop {break 'a;}")),
+            (
+                "'a: loop { break 'b; }",
+                Err("The label `'b` isn't defined by any loop enclosing it"),
+            ),
+            (
+                "'a: for x in xs { loop { break 'a 1; } }",
+                Err("Syntax error: Only `loop` can be exited with a value, not `for`"),
+            ),
+            (
+                "'a: loop { let f = fn() { break 'a; }; }",
+                Err("`break` can only be used inside a loop, and not from a function defined in it"),
+            ),
+            (
+                "'a: if x { 1 }",
+                Err("Syntax error: Only `for`, `do` and `loop` can be labeled, not `if`"),
+            ),
+            (
+                "do { x = x + 1; if x > 5 { break; } continue; } while x < 10;",
+                Ok("do {x = (x + 1);if (x > 5) {break;}continue;} while (x < 10);"),
+            ),
+            (
+                "'a: do { for y in ys { continue 'a; } } while f(x);",
+                Ok("'a: do {for y in ys {continue 'a;}} while f(x);"),
+            ),
+            (
+                "do { break 1; } while true;",
+                Err("Syntax error: Only `loop` can be exited with a value, not `do`"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(str::to_owned).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+
+        // standalone expressions refuse loops like statements, except in the bodies of functions
+        for input in [
+            "break;",
+            "1 + loop { break 1; }",
+            "'a: loop { break 'a 1; }",
+        ] {
+            assert!(
+                matches!(
+                    Parser::new(input).parse_standalone_expression(),
+                    Err(ParserError::StatementNotAllowed(_))
+                ),
+                "{input}"
+            );
+        }
+        assert!(Parser::new("fn() { loop { break 1; } }()")
+            .parse_standalone_expression()
+            .is_ok());
+    }
+
+    #[test]
+    fn parse_named_arguments() {
+        let tests = vec![
+            ("draw(x: 1, y: 2)", Ok("draw(x: 1, y: 2)")),
+            ("draw(1, y: a + 1)", Ok("draw(1, y: (a + 1))")),
+            ("draw(x)", Ok("draw(x)")),
+            (
+                "draw(x: 1, 2)",
+                Err("Syntax error: Positional arguments must come before named ones"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(str::to_owned).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+    }
+
     #[test]
     fn parse_function_expression() {
         let input = r#"
@@ -612,6 +2347,275 @@ mod tests {
         parser.parse_program().unwrap();
     }
 
+    #[test]
+    fn parse_match_expression() {
+        let input = r#"
+            match x {
+                case 1 | 2 | 3: "small",
+                case [first, ..rest] if first > 0: { first }
+                case [..]: -1
+                case _: 0
+            }
+        "#;
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program().unwrap();
+        assert_eq!(
+            program.to_string(),
+            r#"match x {case 1 | 2 | 3: "small";case [first, ..rest] if (first > 0): {first};case [.._]: -1;case _: 0;}"#
+        );
+    }
+
+    #[test]
+    fn parse_switch_statement() {
+        let tests = vec![
+            (
+                r#"switch x { 1 => "one", 1 + 1 => { "two" } _ => "many" }"#,
+                Ok(r#"switch x {1 => "one", (1 + 1) => {"two"}, _ => "many"}"#),
+            ),
+            ("switch f(x) { _ => {} };", Ok("switch f(x) {_ => {}}")),
+            (
+                "switch x { 1 => 2 }",
+                Err("`switch` must end with a default `_` arm, which runs when no other arm matches"),
+            ),
+            ("switch x { _ => 0, 1 => 2 }", Err("Unexpected token")),
+            ("switch x { 1: 2, _ => 0 }", Err("Unexpected token")),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            match expected {
+                Ok(expected) => assert_eq!(result.as_deref(), Ok(expected), "{input}"),
+                Err(expected) => assert!(result.unwrap_err().starts_with(expected), "{input}"),
+            }
+        }
+
+        assert!(matches!(
+            Parser::new("switch x { _ => 0 }").parse_standalone_expression(),
+            Err(ParserError::StatementNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn parse_structs() {
+        let tests = vec![
+            ("struct Point { x, y }", Ok("struct Point { x, y }")),
+            ("struct Unit {};", Ok("struct Unit {}")),
+            (
+                "Point { x: 1 + 1, y: f(2) }",
+                Ok("Point { x: (1 + 1), y: f(2) }"),
+            ),
+            ("let u = Unit {};", Ok("let u = Unit {};")),
+            // struct literals are allowed in conditions inside delimiters
+            (
+                "if p == (Point { x: 1 }) { 1 }",
+                Ok("if (p == Point { x: 1 }) {1}"),
+            ),
+            (
+                "for p in [P { x: 1 }] { p }",
+                Ok("for p in [P { x: 1 }] {p}"),
+            ),
+            // lowercase names are followed by a block
+            ("if ready { 1 }", Ok("if ready {1}")),
+            (
+                "struct point { x }",
+                Err("Struct names start with an uppercase letter, like `Point`, not `point`"),
+            ),
+            ("struct P { x y }", Err("Expected comma between fields")),
+            ("P { x: 1, x: 2 }", Err("The field `x` of `P` is repeated")),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            match expected {
+                Ok(expected) => assert_eq!(result.as_deref(), Ok(expected), "{input}"),
+                Err(expected) => assert!(result.unwrap_err().contains(expected), "{input}"),
+            }
+        }
+
+        // in a condition, the name is the value and the braces are the body
+        assert!(Parser::new("if p == Point { x: 1 } { 1 }")
+            .parse_program()
+            .is_err());
+    }
+
+    #[test]
+    fn parse_enums() {
+        let tests = vec![
+            (
+                "enum Color { Red, Green, Custom(r, g, b), }",
+                Ok("enum Color { Red, Green, Custom(r, g, b) }"),
+            ),
+            ("enum Empty {};", Ok("enum Empty {}")),
+            (
+                "match c { case Color.Red | Color.Custom(255, _, [b]): b, case _: 0 }",
+                Ok("match c {case Color.Red | Color.Custom(255, _, [b]): b;case _: 0;}"),
+            ),
+            ("Color.Custom(1, 2, 3)", Ok("Color.Custom(1, 2, 3)")),
+            // the variants of the prelude are matched without their enum
+            (
+                "match r { case Ok(x) | Some(x): x, case Err(_) | None: 0 }",
+                Ok("match r {case Ok(x) | Some(x): x;case Err(_) | None: 0;}"),
+            ),
+            ("-f(x)?.y?", Ok("(-((f(x)?).y?))")),
+            (
+                "enum Color { Red, Red }",
+                Err("The variant `Red` of `Color` is repeated"),
+            ),
+            (
+                "enum Color { Custom(r, r) }",
+                Err("The field `r` of `Custom` is repeated"),
+            ),
+            (
+                "enum Color { Red() }",
+                Err("Variants without fields are written without parentheses, like `Red`"),
+            ),
+            (
+                "enum Color { Red Green }",
+                Err("Expected comma between variants"),
+            ),
+            (
+                "match c { case Color.Custom(r g): r }",
+                Err("Expected comma between patterns"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            match expected {
+                Ok(expected) => assert_eq!(result.as_deref(), Ok(expected), "{input}"),
+                Err(expected) => assert!(result.unwrap_err().contains(expected), "{input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_method_calls() {
+        let tests = vec![
+            ("items.len()", "items.len()"),
+            (
+                r#""a".pad_left(3, fill: "0")"#,
+                r#""a".pad_left(3, fill: "0")"#,
+            ),
+            ("xs.sorted().unique()[0]", "(xs.sorted().unique()[0])"),
+            ("-a.b.c(1 + 2)", "(-a.b.c((1 + 2)))"),
+            // fields aren't called unless followed by parentheses
+            ("p.f", "p.f"),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(input).parse_program().unwrap();
+            assert_eq!(program.to_string(), expected, "{input}");
+        }
+
+        let program = Parser::new("xs.len(1)").parse_program().unwrap();
+        let Statement::ExpressionStatement(Expression::MethodCall(call)) = &program.0[0].statement
+        else {
+            panic!("expected a method call");
+        };
+        assert_eq!(call.receiver, Expression::Identifier("xs".to_owned()));
+        assert_eq!(call.method, "len");
+        assert_eq!(call.arguments, vec![Expression::IntegerLiteral(1)]);
+        assert_eq!(call.span, Span { start: 0, end: 9 });
+    }
+
+    #[test]
+    fn parse_pipelines() {
+        let tests = vec![
+            ("xs |> f", Ok("f(xs)")),
+            ("xs |> f(1) |> g()", Ok("g(f(xs, 1))")),
+            ("xs |> f(by: 2)", Ok("f(xs, by: 2)")),
+            ("xs |> sorted() |> len()", Ok("len(sorted(xs))")),
+            ("xs |> m.f(1)", Ok("m.f(xs, 1)")),
+            ("xs |> m.f", Ok("m.f(xs)")),
+            ("xs |> (make(1))", Ok("make(1)(xs)")),
+            ("a + 1 |> f(b + 2)", Ok("f((a + 1), (b + 2))")),
+            ("let n = xs |> len();", Ok("let n = len(xs);")),
+            (
+                "xs |> 1",
+                Err("The right side of `|>` must be a function or a call, like `f` or `f(x)`"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = Parser::new(input)
+                .parse_program()
+                .map(|program| program.to_string())
+                .map_err(|err| err.to_string());
+            match expected {
+                Ok(expected) => assert_eq!(result.as_deref(), Ok(expected), "{input}"),
+                Err(expected) => assert!(result.unwrap_err().contains(expected), "{input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_grouped_expression() {
+        let tests = vec![
+            ("(1 + 2) * 3", "((1 + 2) * 3)"),
+            ("((1 + 2)) * ((3))", "((1 + 2) * 3)"),
+            ("-(a + b) * c", "((-(a + b)) * c)"),
+            ("(a)(b)", "a(b)"),
+            ("makeAdder(2)(3)", "makeAdder(2)(3)"),
+            ("arr[0](1)[2]", "((arr[0])(1)[2])"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            assert_eq!(parser.parse_program().unwrap().to_string(), expected);
+        }
+
+        for input in ["()", "(1 + 2", "(1 + 2))"] {
+            let mut parser = Parser::new(input);
+            assert!(parser.parse_program().is_err(), "{input} should not parse");
+        }
+    }
+
+    #[test]
+    fn parse_trailing_commas() {
+        let tests = vec![
+            ("add(1, 2,)", "add(1, 2)"),
+            ("[1, 2,]", "[1, 2]"),
+            (r#"let m = {"a": 1,};"#, r#"let m = {"a": 1};"#),
+            ("fn(x, y,) { x }", "fn(x, y) {x}"),
+            (
+                r#"
+                [
+                    1,
+                    2,
+                ]
+                "#,
+                "[1, 2]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            assert_eq!(parser.parse_program().unwrap().to_string(), expected);
+        }
+
+        for input in [
+            "add(,)",
+            "[1 2]",
+            "[,]",
+            r#"let m = {"a": 1 "b": 2};"#,
+            "fn(x y) { x }",
+            "fn(,) { 1 }",
+        ] {
+            let mut parser = Parser::new(input);
+            assert!(parser.parse_program().is_err(), "{input} should not parse");
+        }
+    }
+
     #[test]
     fn parse_array_expression() {
         let input = r#"
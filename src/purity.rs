@@ -0,0 +1,492 @@
+use std::{cell::RefCell, collections::HashMap, mem, rc::Rc};
+
+use crate::{
+    ast::{Expression, Located, Pattern, Program, Statement},
+    environment::Environment,
+    object::{BuiltinFunction, Closure, Object},
+    token::TokenKind,
+};
+
+/// Whether calling `function` has no effects besides its result: it doesn't perform IO,
+/// nor assigns identifiers declared outside of it, nor calls functions that might.
+/// Native functions are assumed to have effects, while values that aren't functions are pure.
+///
+/// The analysis is conservative: functions calling a function they received as an argument,
+/// or returned by another call, are considered impure.
+pub fn is_pure(function: &Object) -> bool {
+    match function {
+        Object::FunctionValue(closure) => Analysis::new(None).closure(closure),
+        Object::BuiltinValue(builtin) => is_pure_builtin(builtin),
+        Object::NativeFunctionValue(_) | Object::NativeValue(_) | Object::ModuleValue { .. } => {
+            false
+        }
+        _ => true,
+    }
+}
+
+/// Whether evaluating `program` in `env` has no effects besides its result, like `is_pure`.
+/// Top-level bindings are effects too, as they are visible to the host.
+pub fn is_pure_program(program: &Program, env: &Rc<RefCell<Environment>>) -> bool {
+    let mut analysis = Analysis::new(Some(env.clone()));
+
+    program.0.iter().all(|located| match &located.statement {
+        Statement::VarStatement { .. }
+        | Statement::DestructureStatement {
+            kind: TokenKind::Var,
+            ..
+        }
+        | Statement::AssignStatement { .. } => false,
+        statement => analysis.statement(statement),
+    })
+}
+
+fn is_pure_builtin(builtin: &BuiltinFunction) -> bool {
+    !matches!(
+        builtin,
+        BuiltinFunction::Print
+            | BuiltinFunction::Println
+            | BuiltinFunction::Log
+            | BuiltinFunction::ReadLine
+            | BuiltinFunction::Freeze
+            | BuiltinFunction::PushFront
+            | BuiltinFunction::PushBack
+            | BuiltinFunction::PopFront
+            | BuiltinFunction::PopBack
+            // the spawned function may have effects
+            | BuiltinFunction::Spawn
+            | BuiltinFunction::Actor
+            | BuiltinFunction::Tell
+            | BuiltinFunction::Ask
+            // the callbacks are scheduled on the event loop of the evaluator
+            | BuiltinFunction::SetTimeout
+            | BuiltinFunction::SetInterval
+            | BuiltinFunction::ClearTimer
+            | BuiltinFunction::WhenDone
+            | BuiltinFunction::YieldNow
+    )
+}
+
+/// How an identifier was bound by the analyzed code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Local {
+    /// A function declared with `let`, whose body is analyzed at its declaration.
+    Function,
+    /// A binding declared with `var`, which can be assigned.
+    Mutable,
+    /// Any other binding, like parameters, whose value isn't known.
+    Value,
+}
+
+struct Analysis {
+    /// Environment in which the identifiers that aren't bound by the analyzed code are resolved.
+    env: Option<Rc<RefCell<Environment>>>,
+    scopes: Vec<HashMap<String, Local>>,
+    /// Environments of the closures being analyzed, so that recursive calls are analyzed only once.
+    visiting: Vec<*const RefCell<Environment>>,
+}
+
+impl Analysis {
+    fn new(env: Option<Rc<RefCell<Environment>>>) -> Self {
+        Analysis {
+            env,
+            scopes: vec![HashMap::new()],
+            visiting: vec![],
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn bind(&mut self, name: &str, local: Local) {
+        // there's always at least the outermost scope
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), local);
+        }
+    }
+
+    fn scoped(
+        &mut self,
+        bindings: HashMap<String, Local>,
+        analyze: impl FnOnce(&mut Self) -> bool,
+    ) -> bool {
+        self.scopes.push(bindings);
+        let pure = analyze(self);
+        self.scopes.pop();
+        pure
+    }
+
+    fn closure(&mut self, closure: &Closure) -> bool {
+        let id = Rc::as_ptr(&closure.env);
+        // a recursive call is as pure as the function being analyzed
+        if self.visiting.contains(&id) {
+            return true;
+        }
+
+        self.visiting.push(id);
+        let env = self.env.replace(closure.env.clone());
+        let scopes = mem::replace(
+            &mut self.scopes,
+            vec![parameters(&closure.parameters, &closure.rest)],
+        );
+
+        let pure = self.statement(&closure.body);
+
+        self.scopes = scopes;
+        self.env = env;
+        self.visiting.pop();
+        pure
+    }
+
+    /// Identifiers that aren't bound by the analyzed code are as pure as their value.
+    fn global(&mut self, name: &str) -> bool {
+        // built-in functions take precedence over the environment when called
+        if let Ok(Object::BuiltinValue(builtin)) = BuiltinFunction::lookup_function(name) {
+            return is_pure_builtin(&builtin);
+        }
+
+        let value = self
+            .env
+            .as_ref()
+            .and_then(|env| env.borrow().get(name).ok());
+
+        match value {
+            Some(Object::FunctionValue(closure)) => self.closure(&closure),
+            Some(value) => is_pure(&value),
+            // evaluating it fails
+            None => true,
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Identifier(name) => self.local(name).is_some() || self.global(name),
+
+            Expression::IntegerLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::NullLiteral
+            | Expression::StringLiteral(_) => true,
+
+            Expression::ArrayLiteral(elements) => {
+                elements.iter().all(|element| self.expression(element))
+            }
+            Expression::MapLiteral(map) => map.values().all(|value| self.expression(value)),
+            Expression::StructLiteral { name, fields } => {
+                (self.local(name).is_some() || self.global(name))
+                    && fields.iter().all(|(_, value)| self.expression(value))
+            }
+            Expression::ComparisonChain { operands, .. } => {
+                operands.iter().all(|operand| self.expression(operand))
+            }
+
+            Expression::BinaryExpression { left, right, .. } => {
+                self.expression(left) && self.expression(right)
+            }
+            Expression::IndexExpression { value, index } => {
+                self.expression(value) && self.expression(index)
+            }
+            Expression::UnaryExpression { value, .. }
+            | Expression::FieldAccess { value, .. }
+            | Expression::GroupedExpression(value) => self.expression(value),
+
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                let callee = match callee.as_ref() {
+                    // built-in functions are called even if a local binding has their name, like at runtime
+                    Expression::Identifier(name)
+                        if BuiltinFunction::lookup_function(name).is_ok() =>
+                    {
+                        self.global(name)
+                    }
+                    Expression::Identifier(name) => match self.local(name) {
+                        Some(local) => local == Local::Function,
+                        None => self.global(name),
+                    },
+                    callee @ Expression::FunctionExpression { .. } => self.expression(callee),
+                    // the function is only known once evaluated
+                    _ => false,
+                };
+
+                callee
+                    && arguments
+                        .iter()
+                        .chain(named.iter().map(|(_, argument)| argument))
+                        .all(|argument| self.expression(argument))
+            }
+            // the method is only known once the receiver is evaluated
+            Expression::MethodCall(_) => false,
+
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.expression(condition)
+                    && self.statement(consequence)
+                    && alternative
+                        .as_ref()
+                        .is_none_or(|alternative| self.statement(alternative))
+            }
+
+            // the body is analyzed where the function is declared, rather than where it's called
+            Expression::FunctionExpression {
+                parameters: names,
+                rest,
+                body,
+            } => self.scoped(parameters(names, rest), |analysis| analysis.statement(body)),
+
+            Expression::MatchExpression { value, arms } => {
+                self.expression(value)
+                    && arms.iter().all(|arm| {
+                        let mut bindings = HashMap::new();
+                        self.pattern(&arm.pattern, &mut bindings)
+                            && self.scoped(bindings, |analysis| {
+                                arm.guard
+                                    .as_ref()
+                                    .is_none_or(|guard| analysis.expression(guard))
+                                    && analysis.statement(&arm.body)
+                            })
+                    })
+            }
+            Expression::LoopExpression { body, .. } | Expression::BlockExpression(body) => {
+                self.statement(body)
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, bindings: &mut HashMap<String, Local>) -> bool {
+        match pattern {
+            Pattern::Wildcard => true,
+            Pattern::Identifier(name) => {
+                bindings.insert(name.clone(), Local::Value);
+                true
+            }
+            Pattern::Literal(literal) => self.expression(literal),
+            Pattern::Array { elements, rest } => {
+                elements
+                    .iter()
+                    .all(|element| self.pattern(element, bindings))
+                    && rest
+                        .as_ref()
+                        .is_none_or(|rest| self.pattern(rest, bindings))
+            }
+            Pattern::Map { entries } => entries
+                .iter()
+                .all(|(_, pattern)| self.pattern(pattern, bindings)),
+            Pattern::Variant { name, fields, .. } => {
+                (self.local(name).is_some() || self.global(name))
+                    && fields.iter().all(|pattern| self.pattern(pattern, bindings))
+            }
+            Pattern::Or(patterns) => patterns
+                .iter()
+                .all(|pattern| self.pattern(pattern, bindings)),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> bool {
+        match statement {
+            Statement::VarStatement {
+                kind, name, value, ..
+            } => {
+                let local = match (kind, value) {
+                    (TokenKind::Var, _) => Local::Mutable,
+                    (_, Expression::FunctionExpression { .. }) => Local::Function,
+                    _ => Local::Value,
+                };
+                // functions can refer to the name they're bound to
+                if local == Local::Function {
+                    self.bind(name, local);
+                }
+
+                let pure = self.expression(value);
+                self.bind(name, local);
+                pure
+            }
+            Statement::DestructureStatement {
+                kind,
+                pattern,
+                value,
+            } => {
+                let mut bindings = HashMap::new();
+                let pure = self.expression(value) && self.pattern(pattern, &mut bindings);
+                for name in bindings.keys() {
+                    match kind {
+                        TokenKind::Var => self.bind(name, Local::Mutable),
+                        _ => self.bind(name, Local::Value),
+                    }
+                }
+                pure
+            }
+            // assigning a binding that isn't local mutates an outer scope
+            Statement::AssignStatement { name, value } => {
+                self.local(name).is_some() && self.expression(value)
+            }
+            Statement::ReturnStatement(value) => {
+                value.as_ref().is_none_or(|value| self.expression(value))
+            }
+            Statement::ExpressionStatement(expression) => self.expression(expression),
+            Statement::BlockStatement(statements) => self.scoped(hoisted(statements), |analysis| {
+                statements
+                    .iter()
+                    .all(|located| analysis.statement(&located.statement))
+            }),
+            Statement::ForStatement {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expression(iterable)
+                    && self.scoped(
+                        parameters(std::slice::from_ref(variable), &None),
+                        |analysis| analysis.statement(body),
+                    )
+            }
+            Statement::BreakStatement { value, .. } => {
+                value.as_ref().is_none_or(|value| self.expression(value))
+            }
+            Statement::ContinueStatement(_) => true,
+            Statement::StructStatement { name, .. } | Statement::EnumStatement { name, .. } => {
+                self.bind(name, Local::Value);
+                true
+            }
+            Statement::DoWhileStatement {
+                body, condition, ..
+            } => self.statement(body) && self.expression(condition),
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                self.expression(value)
+                    && arms
+                        .iter()
+                        .all(|arm| self.expression(&arm.case) && self.statement(&arm.body))
+                    && self.statement(default)
+            }
+        }
+    }
+}
+
+fn parameters(names: &[String], rest: &Option<String>) -> HashMap<String, Local> {
+    names
+        .iter()
+        .chain(rest)
+        .map(|name| (name.clone(), Local::Value))
+        .collect()
+}
+
+/// Functions hoisted by a block, which can be called before their declaration.
+fn hoisted(statements: &[Located]) -> HashMap<String, Local> {
+    statements
+        .iter()
+        .filter_map(|located| match &located.statement {
+            Statement::VarStatement {
+                kind: TokenKind::Let,
+                name,
+                value: Expression::FunctionExpression { .. },
+                ..
+            } => Some((name.clone(), Local::Function)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{evaluator::Evaluator, native::NativeFunction, parser::Parser};
+
+    #[test]
+    fn analyze_functions() {
+        let tests = vec![
+            ("fn(x) { x * 2 }", true),
+            (
+                "fn(arr) { let total = 0; var n = 0; for x in arr { n = n + x; } n }",
+                true,
+            ),
+            (
+                "fn(n) { if n < 2 { n } else { len([n]) + fib(n - 1) } }",
+                true,
+            ),
+            ("fn(n) { even(n) }", true),
+            ("fn(x) { println(x); x }", false),
+            ("fn(x) { let log = fn(y) { print(y) }; x }", false),
+            ("fn(x) { outer = x; }", false),
+            ("fn(x) { uses_outer(x) }", false),
+            ("fn(x) { io.write(x) }", false),
+            ("fn(f, x) { f(x) }", false),
+            // the built-in function is called, rather than the local binding
+            ("fn(x) { let println = fn(y) { y }; println(x) }", false),
+            ("fn(println) { println(1) }", false),
+            ("fn(len) { len([1]) }", true),
+            (
+                "fn(x) { match x { case [a, ..rest]: freeze(a), case _: 0 } }",
+                false,
+            ),
+        ];
+
+        let globals = "
+            var outer = 0;
+            let fib = fn(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+            let even = fn(n) { if n == 0 { true } else { odd(n - 1) } };
+            let odd = fn(n) { if n == 0 { false } else { even(n - 1) } };
+            let uses_outer = fn(x) { outer = outer + x; };
+        ";
+
+        for (function, expected) in tests {
+            let input = format!("{globals}{function}");
+            let mut evaluator = Evaluator::new(&input);
+            evaluator.register_module(
+                "io",
+                [NativeFunction::new("write", |_| Ok(Object::UnitValue))],
+            );
+
+            let function = evaluator.eval_program().unwrap().pop().unwrap();
+            assert_eq!(is_pure(&function), expected, "{function}");
+        }
+        assert!(is_pure(&Object::BuiltinValue(BuiltinFunction::Len)));
+        assert!(!is_pure(&Object::BuiltinValue(BuiltinFunction::Println)));
+        assert!(!is_pure(&Object::BuiltinValue(BuiltinFunction::Log)));
+        assert!(!is_pure(&Object::NativeFunctionValue(NativeFunction::new(
+            "noop",
+            |_| Ok(Object::UnitValue)
+        ))));
+    }
+
+    #[test]
+    fn analyze_programs() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.borrow_mut()
+            .set("limit".to_owned(), Object::IntegerValue(3));
+        Evaluator::with_env(
+            "let square = fn(x) { x * x }; let show = fn(x) { println(x) };",
+            env.clone(),
+        )
+        .eval_program()
+        .unwrap();
+
+        let tests = vec![
+            ("square(limit) > 5", true),
+            ("fn(n) { let a = [n]; a }(limit)", true),
+            ("{ let a = 1; a + limit }", true),
+            ("show(limit)", false),
+            ("{ let print = fn(x) { x }; print(limit) }", false),
+            ("let a = 1;", false),
+            ("var n = 0; fn() { n = 1; }()", false),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(input).parse_program().unwrap();
+            assert_eq!(is_pure_program(&program, &env), expected, "{input}");
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    time::Instant,
+};
+
+use crate::{
+    ast::{Located, Program},
+    evaluator::Evaluator,
+    formatter::format_program,
+    object::{EvalError, Object},
+    parser::Parser,
+};
+
+/// Inputs of an interactive session, evaluated one after the other in the same environment.
+/// The statements of the inputs that were evaluated successfully are kept, so that the session
+/// can be saved as a script.
+#[derive(Debug)]
+pub struct Session {
+    evaluator: Evaluator<'static>,
+    statements: Vec<Located>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            evaluator: Evaluator::new(""),
+            statements: vec![],
+        }
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Evaluates an input, then runs the callbacks scheduled by the session that are ready,
+    /// like the timers of `set_timeout` that are due.
+    pub fn eval(&mut self, input: &str) -> Result<Vec<Object>, EvalError> {
+        let program = Parser::interactive(input).parse_program()?;
+        let objects = self.evaluator.eval_parsed_program(program.clone())?;
+        self.statements.extend(program.0);
+        self.evaluator.poll()?;
+
+        Ok(objects)
+    }
+
+    /// Script replaying the inputs that were evaluated successfully, in the canonical format.
+    /// Inputs that failed are left out, even if some of their statements were evaluated before the error.
+    pub fn script(&self) -> String {
+        format_program(&Program(self.statements.clone()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.script())
+    }
+}
+
+/// What the session shows about each input besides its value, toggled by `:debug`.
+#[derive(Debug, Clone, Copy, Default)]
+struct DebugOptions {
+    /// Shows the statements the input was parsed to, with their grouping made explicit.
+    ast: bool,
+    /// Shows how long the evaluation of the input took.
+    time: bool,
+}
+
+impl DebugOptions {
+    /// Toggles the option named `name`, returning a description of its new state.
+    fn toggle(&mut self, name: &str) -> Option<String> {
+        let (option, description) = match name {
+            "ast" => (&mut self.ast, "Showing the AST"),
+            "time" => (&mut self.time, "Showing the evaluation time"),
+            _ => return None,
+        };
+        *option = !*option;
+
+        Some(format!("{description}: {}", on_off(*option)))
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Runs an interactive session, reading a line of code at a time from `input`.
+/// Lines starting with `:` are commands: `:save <file>` writes the script of the session, `:quit` ends it,
+/// and `:debug ast` or `:debug time` toggles showing the parsed statements or the evaluation time of each input.
+pub fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut session = Session::new();
+    let mut debug = DebugOptions::default();
+
+    write!(output, ">> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(command, argument)| (command, argument.trim()));
+
+        match command {
+            "" => {}
+            ":quit" => break,
+            ":save" if argument.is_empty() => writeln!(output, "Usage: :save <file>")?,
+            ":save" => match session.save(argument) {
+                Ok(()) => writeln!(output, "Saved the session to {argument}")?,
+                Err(err) => writeln!(output, "Failed to save the session: {err}")?,
+            },
+            ":debug" if argument.is_empty() => writeln!(
+                output,
+                "ast: {}, time: {}",
+                on_off(debug.ast),
+                on_off(debug.time)
+            )?,
+            // the language has no bytecode: programs are evaluated by walking their AST
+            ":debug" if argument == "bytecode" => writeln!(
+                output,
+                "Qalo evaluates the AST directly, there's no bytecode to show"
+            )?,
+            ":debug" => match debug.toggle(argument) {
+                Some(state) => writeln!(output, "{state}")?,
+                None => writeln!(output, "Usage: :debug [ast|time]")?,
+            },
+            command if command.starts_with(':') => {
+                writeln!(output, "Unknown command `{command}`")?;
+            }
+            _ => {
+                if debug.ast {
+                    // errors are reported by the evaluation, which parses the input again
+                    if let Ok(program) = Parser::interactive(line).parse_program() {
+                        for statement in &program.0 {
+                            writeln!(output, "ast: {statement}")?;
+                        }
+                    }
+                }
+
+                let start = Instant::now();
+                let result = session.eval(line);
+                let elapsed = start.elapsed();
+                match result {
+                    Ok(objects) => match objects.last() {
+                        Some(Object::UnitValue) | None => {}
+                        Some(obj) => writeln!(output, "{obj}")?,
+                    },
+                    Err(err) => writeln!(output, "| Qalo Error {} |\n{err}", err.code())?,
+                }
+
+                if debug.time {
+                    writeln!(output, "time: {elapsed:.2?}")?;
+                }
+            }
+        }
+
+        write!(output, ">> ")?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_script() {
+        let mut session = Session::new();
+
+        session.eval("let add = fn(x, y) { x + y };").unwrap();
+        assert!(session.eval("add(1, 2) +").is_err());
+        assert!(session.eval("add(1, unknown)").is_err());
+        session.eval("var total = add(1, 2);").unwrap();
+        session.eval("total = total * 2;").unwrap();
+        // a line break ends a statement, and the last one can leave out its semicolon
+        session.eval("let a = 1\nlet b = a").unwrap();
+        assert_eq!(
+            session.eval("total").unwrap(),
+            vec![Object::IntegerValue(6)]
+        );
+
+        assert_eq!(
+            session.script(),
+            "let add = fn(x, y) {\n  x + y\n};\nvar total = add(1, 2);\ntotal = total * 2;\nlet a = 1;\nlet b = a;\ntotal;\n"
+        );
+        // the script replays the session
+        let result = Evaluator::new(&session.script())
+            .eval_program()
+            .unwrap()
+            .pop();
+        assert_eq!(result, Some(Object::IntegerValue(6)));
+    }
+
+    #[test]
+    fn run_commands() {
+        let path = std::env::temp_dir().join(format!("qalo-session-{}.ql", std::process::id()));
+        let input = format!(
+            "let x = 2;\nx * 3\n1 / 0\n:save {}\n:save\n:undo\n:quit\nx\n",
+            path.display()
+        );
+
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                ">> >> 6\n>> | Qalo Error E1007 |\nDivision by zero isn't allowed\n>> Saved the session to {}\n>> Usage: :save <file>\n>> Unknown command `:undo`\n>> ",
+                path.display()
+            )
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "let x = 2;\nx * 3;\n");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn run_debug_commands() {
+        let input = ":debug ast\nlet x = 1 + 2 * 3\n:debug\n:debug bytecode\n:debug stack\n:debug ast\n:debug time\nx\n";
+
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let (output, time) = output.rsplit_once("time: ").unwrap();
+        assert_eq!(
+            output,
+            ">> Showing the AST: on\n>> ast: let x = (1 + (2 * 3));\n\
+             >> ast: on, time: off\n>> Qalo evaluates the AST directly, there's no bytecode to show\n\
+             >> Usage: :debug [ast|time]\n>> Showing the AST: off\n>> Showing the evaluation time: on\n>> 7\n"
+        );
+        assert!(time.ends_with("s\n>> "), "{time}");
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    rc::Rc,
+};
+
+use thiserror::Error;
+
+use crate::{json::Json, object::Object};
+
+/// Input of a script that can change from one run to the next, which `Evaluator::record_inputs` logs
+/// and `Evaluator::replay_inputs` gives back in the same order, to reproduce a run exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Line read by `read_line`, `None` at the end of the input.
+    ReadLine(Option<String>),
+    /// Result of a call to a native function registered by the host, with the name it was called by.
+    NativeCall {
+        name: String,
+        result: Result<Object, String>,
+    },
+    /// Result of a native function the log couldn't save, like a native handle: replaying it fails.
+    Unrecorded { name: String, type_name: String },
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::ReadLine(_) => write!(f, "a line with `read_line`"),
+            Input::NativeCall { name, .. } | Input::Unrecorded { name, .. } => {
+                write!(f, "the result of `{name}`")
+            }
+        }
+    }
+}
+
+/// Whether an evaluator logs the inputs of its run, or reads them back from a log.
+#[derive(Debug)]
+pub enum ReplayMode {
+    Recording(InputLog),
+    Replaying(InputLog),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    #[error("The replayed run reads {0}, but the log has {1} next")]
+    Diverged(String, String),
+    #[error("The replayed run reads {0}, but the log has no input left")]
+    Exhausted(String),
+    #[error("The recorded call to `{0}` failed: {1}")]
+    NativeCallFailed(String, String),
+    #[error("The log can't replay the value of type `{1}` returned by `{0}`")]
+    Unreplayable(String, String),
+    #[error("Invalid input log at line {0}: {1}")]
+    InvalidLog(usize, String),
+}
+
+/// Inputs of a run in the order they were read, shared with its clones: install a clone in the evaluator,
+/// and save the original once the run is over. Saved logs have a JSON object per line.
+#[derive(Debug, Clone, Default)]
+pub struct InputLog(Rc<RefCell<VecDeque<Input>>>);
+
+impl InputLog {
+    pub fn push(&self, input: Input) {
+        self.0.borrow_mut().push_back(input);
+    }
+
+    /// Removes the next input to replay.
+    pub fn pop(&self) -> Option<Input> {
+        self.0.borrow_mut().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Reads a log saved by `to_json_lines`, ignoring blank lines.
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let log = InputLog::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let input = Json::parse(line)
+                .map_err(|err| err.to_string())
+                .and_then(|json| input_from_json(&json))
+                .map_err(|message| ReplayError::InvalidLog(i + 1, message))?;
+            log.push(input);
+        }
+
+        Ok(log)
+    }
+
+    pub fn to_json_lines(&self) -> String {
+        self.0
+            .borrow()
+            .iter()
+            .map(|input| format!("{}\n", input_to_json(input)))
+            .collect()
+    }
+}
+
+/// Logs a line as `{"read_line":"text"}`, and a native call as `{"native":"name","value":...}`,
+/// `{"native":"name","error":"message"}` or `{"native":"name","unsupported":"type"}`.
+fn input_to_json(input: &Input) -> Json {
+    let member = |key: &str, value: Json| (key.to_owned(), value);
+    match input {
+        Input::ReadLine(line) => Json::Object(vec![member("read_line", line.clone().into())]),
+        Input::NativeCall { name, result } => {
+            let outcome = match result {
+                Ok(value) => match value_to_json(value) {
+                    Some(json) => member("value", json),
+                    None => member("unsupported", value.type_name().into()),
+                },
+                Err(message) => member("error", message.as_str().into()),
+            };
+            Json::Object(vec![member("native", name.as_str().into()), outcome])
+        }
+        Input::Unrecorded { name, type_name } => Json::Object(vec![
+            member("native", name.as_str().into()),
+            member("unsupported", type_name.as_str().into()),
+        ]),
+    }
+}
+
+fn input_from_json(json: &Json) -> Result<Input, String> {
+    if let Some(line) = json.get("read_line") {
+        return match line {
+            Json::Null => Ok(Input::ReadLine(None)),
+            Json::String(line) => Ok(Input::ReadLine(Some(line.clone()))),
+            _ => Err("`read_line` must be a string or null".to_owned()),
+        };
+    }
+
+    let name = json
+        .get("native")
+        .and_then(Json::as_str)
+        .ok_or("expected a `read_line` or `native` member")?
+        .to_owned();
+    if let Some(value) = json.get("value") {
+        let value = value_from_json(value).ok_or("unknown value encoding")?;
+        Ok(Input::NativeCall {
+            name,
+            result: Ok(value),
+        })
+    } else if let Some(message) = json.get("error").and_then(Json::as_str) {
+        Ok(Input::NativeCall {
+            name,
+            result: Err(message.to_owned()),
+        })
+    } else if let Some(type_name) = json.get("unsupported").and_then(Json::as_str) {
+        Ok(Input::Unrecorded {
+            name,
+            type_name: type_name.to_owned(),
+        })
+    } else {
+        Err("expected a `value`, `error` or `unsupported` member".to_owned())
+    }
+}
+
+/// Encodes the values JSON can't tell apart in an object with a single member, like `{"int":"42"}`,
+/// so that they're replayed with the same type. Values other than data, like functions, aren't encoded.
+fn value_to_json(value: &Object) -> Option<Json> {
+    let tagged = |tag: &str, json: Json| Json::Object(vec![(tag.to_owned(), json)]);
+    let json = match value {
+        Object::NullValue => Json::Null,
+        Object::BooleanValue(b) => Json::Bool(*b),
+        Object::StringValue(s) => Json::String(s.clone()),
+        Object::IntegerValue(n) => tagged("int", n.to_string().into()),
+        // as text, for the digits to be kept exactly and for NaN and the infinities
+        Object::FloatValue(x) => tagged("float", x.to_string().into()),
+        Object::UnitValue => tagged("unit", Json::Null),
+        Object::ArrayValue(elements) => {
+            Json::Array(elements.iter().map(value_to_json).collect::<Option<_>>()?)
+        }
+        Object::DequeValue(elements) => {
+            Json::Array(elements.iter().map(value_to_json).collect::<Option<_>>()?)
+        }
+        Object::MapValue(map) => {
+            let members = map
+                .iter()
+                .map(|(key, value)| Some((key.clone(), value_to_json(value)?)))
+                .collect::<Option<_>>()?;
+            tagged("map", Json::Object(members))
+        }
+        _ => return None,
+    };
+
+    Some(json)
+}
+
+fn value_from_json(json: &Json) -> Option<Object> {
+    let value = match json {
+        Json::Null => Object::NullValue,
+        Json::Bool(b) => Object::BooleanValue(*b),
+        Json::String(s) => Object::StringValue(s.clone()),
+        Json::Array(elements) => Object::ArrayValue(
+            elements
+                .iter()
+                .map(value_from_json)
+                .collect::<Option<_>>()?,
+        ),
+        Json::Object(members) if members.len() == 1 => match &members[0] {
+            (tag, value) if tag == "int" => Object::IntegerValue(value.as_str()?.parse().ok()?),
+            (tag, value) if tag == "float" => Object::FloatValue(value.as_str()?.parse().ok()?),
+            (tag, Json::Null) if tag == "unit" => Object::UnitValue,
+            (tag, Json::Object(members)) if tag == "map" => {
+                let map: Option<BTreeMap<String, Object>> = members
+                    .iter()
+                    .map(|(key, value)| Some((key.clone(), value_from_json(value)?)))
+                    .collect();
+                Object::MapValue(map?)
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_parse_input_logs() {
+        let log = InputLog::default();
+        log.push(Input::ReadLine(Some("say \"hi\"".to_owned())));
+        log.push(Input::ReadLine(None));
+        let mut map = BTreeMap::new();
+        map.insert("n".to_owned(), Object::IntegerValue(-3));
+        map.insert("x".to_owned(), Object::FloatValue(0.1));
+        log.push(Input::NativeCall {
+            name: "sensor.read".to_owned(),
+            result: Ok(Object::ArrayValue(vec![
+                Object::MapValue(map),
+                Object::UnitValue,
+                Object::BooleanValue(true),
+            ])),
+        });
+        log.push(Input::NativeCall {
+            name: "now".to_owned(),
+            result: Err("clock unavailable".to_owned()),
+        });
+
+        let text = log.to_json_lines();
+        assert_eq!(
+            text,
+            "{\"read_line\":\"say \\\"hi\\\"\"}\n\
+             {\"read_line\":null}\n\
+             {\"native\":\"sensor.read\",\"value\":[{\"map\":{\"n\":{\"int\":\"-3\"},\"x\":{\"float\":\"0.1\"}}},{\"unit\":null},true]}\n\
+             {\"native\":\"now\",\"error\":\"clock unavailable\"}\n"
+        );
+
+        let parsed = InputLog::parse(&text).unwrap();
+        assert_eq!(parsed.len(), 4);
+        while let Some(input) = log.pop() {
+            assert_eq!(parsed.pop(), Some(input));
+        }
+        assert!(parsed.is_empty());
+    }
+
+    #[test]
+    fn save_unsupported_values() {
+        let log = InputLog::default();
+        log.push(Input::NativeCall {
+            name: "open".to_owned(),
+            result: Ok(Object::RangeValue {
+                start: 0,
+                end: 2,
+                inclusive: false,
+            }),
+        });
+
+        let parsed = InputLog::parse(&log.to_json_lines()).unwrap();
+        assert_eq!(
+            parsed.pop(),
+            Some(Input::Unrecorded {
+                name: "open".to_owned(),
+                type_name: "range".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_invalid_input_logs() {
+        assert_eq!(
+            InputLog::parse("{\"read_line\":null}\n\n{\"native\":\"f\"}").unwrap_err(),
+            ReplayError::InvalidLog(
+                3,
+                "expected a `value`, `error` or `unsupported` member".to_owned()
+            )
+        );
+        assert!(matches!(
+            InputLog::parse("{\"native\":\"f\",\"value\":1}"),
+            Err(ReplayError::InvalidLog(1, message)) if message == "unknown value encoding"
+        ));
+        assert!(matches!(
+            InputLog::parse("[1"),
+            Err(ReplayError::InvalidLog(1, _))
+        ));
+    }
+}
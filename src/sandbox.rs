@@ -0,0 +1,392 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{Expression, Located, MatchArm, Pattern, Statement},
+    object::{prelude, BuiltinFunction, EvalError},
+    suggestion::closest_matches,
+    token::TokenKind,
+};
+
+/// Whitelist of the identifiers and built-in functions an expression may reference,
+/// to safely evaluate expressions written by users (e.g. filters) with `eval_expression_str_sandboxed`.
+/// Identifiers bound by the expression itself, like function parameters, are always allowed.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    allowed: HashSet<String>,
+}
+
+impl Sandbox {
+    pub fn new<'a>(allowed: impl IntoIterator<Item = &'a str>) -> Self {
+        Sandbox {
+            allowed: allowed.into_iter().map(str::to_owned).collect(),
+        }
+    }
+
+    /// Resolves every identifier referenced by `expression`, without evaluating it,
+    /// and fails on the first one that is neither bound by the expression nor whitelisted.
+    pub fn check(&self, expression: &Expression) -> Result<(), EvalError> {
+        Resolver {
+            sandbox: self,
+            scopes: vec![HashSet::new()],
+        }
+        .expression(expression)
+    }
+}
+
+struct Resolver<'a> {
+    sandbox: &'a Sandbox,
+    /// Identifiers bound by the expression, one set for each nested scope.
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&self, name: &str) -> Result<(), EvalError> {
+        // the values of the prelude can't do anything on their own
+        if self.sandbox.allowed.contains(name)
+            || self.scopes.iter().any(|scope| scope.contains(name))
+            || prelude(name).is_some()
+        {
+            return Ok(());
+        }
+
+        Err(self.not_allowed(name))
+    }
+
+    /// Resolves a built-in function being called, which the evaluator looks up before the identifiers
+    /// bound by the expression: a parameter or a `let` with its name doesn't shadow it.
+    fn resolve_builtin(&self, name: &str) -> Result<(), EvalError> {
+        if self.sandbox.allowed.contains(name) {
+            Ok(())
+        } else {
+            Err(self.not_allowed(name))
+        }
+    }
+
+    fn not_allowed(&self, name: &str) -> EvalError {
+        let suggestions = closest_matches(name, self.sandbox.allowed.iter().map(String::as_str));
+        EvalError::IdentifierNotAllowed(name.to_owned(), suggestions)
+    }
+
+    fn bind(&mut self, name: &str) {
+        // there's always at least the outermost scope
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned());
+        }
+    }
+
+    fn scoped(
+        &mut self,
+        bindings: HashSet<String>,
+        resolve: impl FnOnce(&mut Self) -> Result<(), EvalError>,
+    ) -> Result<(), EvalError> {
+        self.scopes.push(bindings);
+        let result = resolve(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Result<(), EvalError> {
+        match expression {
+            Expression::Identifier(name) => self.resolve(name),
+
+            Expression::IntegerLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::NullLiteral
+            | Expression::StringLiteral(_) => Ok(()),
+
+            Expression::ArrayLiteral(elements) => elements
+                .iter()
+                .try_for_each(|element| self.expression(element)),
+            Expression::MapLiteral(map) => {
+                map.values().try_for_each(|value| self.expression(value))
+            }
+            Expression::StructLiteral { name, fields } => {
+                self.resolve(name)?;
+                fields
+                    .iter()
+                    .try_for_each(|(_, value)| self.expression(value))
+            }
+            Expression::ComparisonChain { operands, .. } => operands
+                .iter()
+                .try_for_each(|operand| self.expression(operand)),
+
+            Expression::BinaryExpression { left, right, .. } => {
+                self.expression(left)?;
+                self.expression(right)
+            }
+            Expression::IndexExpression { value, index } => {
+                self.expression(value)?;
+                self.expression(index)
+            }
+            Expression::UnaryExpression { value, .. }
+            | Expression::FieldAccess { value, .. }
+            | Expression::GroupedExpression(value) => self.expression(value),
+
+            Expression::CallExpression {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                match callee.as_ref() {
+                    Expression::Identifier(name)
+                        if BuiltinFunction::lookup_function(name).is_ok() =>
+                    {
+                        self.resolve_builtin(name)?;
+                    }
+                    callee => self.expression(callee)?,
+                }
+                arguments
+                    .iter()
+                    .chain(named.iter().map(|(_, argument)| argument))
+                    .try_for_each(|argument| self.expression(argument))
+            }
+            Expression::MethodCall(call) => {
+                // methods named like built-in functions may be dispatched to them
+                if BuiltinFunction::lookup_function(&call.method).is_ok() {
+                    self.resolve_builtin(&call.method)?;
+                }
+                self.expression(&call.receiver)?;
+                call.arguments
+                    .iter()
+                    .chain(call.named.iter().map(|(_, argument)| argument))
+                    .try_for_each(|argument| self.expression(argument))
+            }
+
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.expression(condition)?;
+                self.statement(consequence)?;
+                match alternative {
+                    Some(alternative) => self.statement(alternative),
+                    None => Ok(()),
+                }
+            }
+
+            Expression::FunctionExpression {
+                parameters,
+                rest,
+                body,
+            } => self.scoped(
+                parameters.iter().chain(rest).cloned().collect(),
+                |resolver| resolver.statement(body),
+            ),
+
+            Expression::MatchExpression { value, arms } => {
+                self.expression(value)?;
+                arms.iter().try_for_each(|arm| self.match_arm(arm))
+            }
+            Expression::LoopExpression { body, .. } | Expression::BlockExpression(body) => {
+                self.statement(body)
+            }
+        }
+    }
+
+    fn match_arm(&mut self, arm: &MatchArm) -> Result<(), EvalError> {
+        let mut bindings = HashSet::new();
+        self.pattern(&arm.pattern, &mut bindings)?;
+
+        self.scoped(bindings, |resolver| {
+            if let Some(guard) = &arm.guard {
+                resolver.expression(guard)?;
+            }
+            resolver.statement(&arm.body)
+        })
+    }
+
+    fn pattern(
+        &mut self,
+        pattern: &Pattern,
+        bindings: &mut HashSet<String>,
+    ) -> Result<(), EvalError> {
+        match pattern {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Identifier(name) => {
+                bindings.insert(name.clone());
+                Ok(())
+            }
+            Pattern::Literal(literal) => self.expression(literal),
+            Pattern::Array { elements, rest } => {
+                for element in elements {
+                    self.pattern(element, bindings)?;
+                }
+                match rest {
+                    Some(rest) => self.pattern(rest, bindings),
+                    None => Ok(()),
+                }
+            }
+            Pattern::Map { entries } => entries
+                .iter()
+                .try_for_each(|(_, pattern)| self.pattern(pattern, bindings)),
+            Pattern::Variant { name, fields, .. } => {
+                self.resolve(name)?;
+                fields
+                    .iter()
+                    .try_for_each(|pattern| self.pattern(pattern, bindings))
+            }
+            Pattern::Or(patterns) => patterns
+                .iter()
+                .try_for_each(|pattern| self.pattern(pattern, bindings)),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), EvalError> {
+        match statement {
+            Statement::VarStatement { name, value, .. } => {
+                // functions can refer to the name they're bound to
+                if matches!(value, Expression::FunctionExpression { .. }) {
+                    self.bind(name);
+                }
+                self.expression(value)?;
+                self.bind(name);
+                Ok(())
+            }
+            Statement::DestructureStatement { pattern, value, .. } => {
+                self.expression(value)?;
+                let mut bindings = HashSet::new();
+                self.pattern(pattern, &mut bindings)?;
+                bindings.iter().for_each(|name| self.bind(name));
+                Ok(())
+            }
+            Statement::AssignStatement { name, value } => {
+                self.resolve(name)?;
+                self.expression(value)
+            }
+            Statement::ContinueStatement(_) => Ok(()),
+            Statement::StructStatement { name, .. } | Statement::EnumStatement { name, .. } => {
+                self.bind(name);
+                Ok(())
+            }
+            Statement::DoWhileStatement {
+                body, condition, ..
+            } => {
+                self.statement(body)?;
+                self.expression(condition)
+            }
+            Statement::SwitchStatement {
+                value,
+                arms,
+                default,
+            } => {
+                self.expression(value)?;
+                for arm in arms {
+                    self.expression(&arm.case)?;
+                    self.statement(&arm.body)?;
+                }
+                self.statement(default)
+            }
+            Statement::ReturnStatement(value) | Statement::BreakStatement { value, .. } => {
+                match value {
+                    Some(value) => self.expression(value),
+                    None => Ok(()),
+                }
+            }
+            Statement::ExpressionStatement(expression) => self.expression(expression),
+            Statement::BlockStatement(statements) => self.scoped(hoisted(statements), |resolver| {
+                statements
+                    .iter()
+                    .try_for_each(|located| resolver.statement(&located.statement))
+            }),
+            Statement::ForStatement {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expression(iterable)?;
+                self.scoped(HashSet::from([variable.clone()]), |resolver| {
+                    resolver.statement(body)
+                })
+            }
+        }
+    }
+}
+
+/// Names of the functions hoisted by a block, which can be referenced before their declaration.
+fn hoisted(statements: &[Located]) -> HashSet<String> {
+    statements
+        .iter()
+        .filter_map(|located| match &located.statement {
+            Statement::VarStatement {
+                kind: TokenKind::Let,
+                name,
+                value: Expression::FunctionExpression { .. },
+                ..
+            } => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn check_sandboxed_expressions() {
+        let sandbox = Sandbox::new(["price", "tags", "len", "sorted"]);
+
+        let tests = vec![
+            ("price * 2 > 10", Ok(())),
+            ("len(tags) > 0 && sorted(tags)[0] == \"a\"", Ok(())),
+            ("fn(x) { let y = x * price; y }(2)", Ok(())),
+            (
+                "match tags { case [first, ..rest]: len(rest), case _: 0 }",
+                Ok(()),
+            ),
+            (
+                "fn() { if price > 0 { let f = fn(n) { if n == 0 { 0 } else { f(n - 1) } }; f(3) } }()",
+                Ok(()),
+            ),
+            (
+                "prce * 2",
+                Err("The identifier `prce` isn't allowed here. Did you mean \"price\"?"),
+            ),
+            (
+                "println(price)",
+                Err("The identifier `println` isn't allowed here"),
+            ),
+            (
+                "fn(x) { x + secret }(1)",
+                Err("The identifier `secret` isn't allowed here"),
+            ),
+            // bindings don't leak out of their scope
+            (
+                "fn() { if true { let y = 1; y } else { 0 } + y }()",
+                Err("The identifier `y` isn't allowed here"),
+            ),
+            ("fn() { price = 0; }", Ok(())),
+            ("tags.sorted().len()", Ok(())),
+            (
+                "tags.unique()",
+                Err("The identifier `unique` isn't allowed here"),
+            ),
+            (
+                "fn() { let a = is_b(); let is_b = fn() { true }; a }",
+                Ok(()),
+            ),
+            // bindings named like a built-in function don't shadow it when called
+            (
+                "fn(println) { println(\"escaped\") }(1)",
+                Err("The identifier `println` isn't allowed here"),
+            ),
+            (
+                "fn(x) { let println = fn(y) { y }; println(x) }(1)",
+                Err("The identifier `println` isn't allowed here"),
+            ),
+            ("fn(println) { [println, len(tags)] }(1)", Ok(())),
+            ("fn(len) { len(tags) }(1)", Ok(())),
+        ];
+
+        for (input, expected) in tests {
+            let expression = Parser::new(input).parse_standalone_expression().unwrap();
+            let result = sandbox.check(&expression).map_err(|err| err.to_string());
+            assert_eq!(result, expected.map_err(str::to_owned), "{input}");
+        }
+    }
+}
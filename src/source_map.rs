@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::{ast::ParserError, token::Span};
+
+/// Position in a source file, with lines and columns starting from 1.
+/// Columns count chars, not bytes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// Finds the position of the byte `offset` of `source`.
+    pub fn in_source(file: &str, source: &str, offset: usize) -> Self {
+        Location {
+            file: file.to_owned(),
+            line: 1,
+            column: 1,
+        }
+        .advance(&source[..floor_char_boundary(source, offset)])
+    }
+
+    /// Moves the location past `text`.
+    fn advance(mut self, text: &str) -> Self {
+        match text.rfind('\n') {
+            Some(newline) => {
+                self.line += text.matches('\n').count();
+                self.column = text[newline + 1..].chars().count() + 1;
+            }
+            None => self.column += text.chars().count(),
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Maps a source generated by a host (from a template or a DSL) back to the files it came from,
+/// so that diagnostics point to the original positions.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    /// Name used for the positions of the generated source that aren't mapped.
+    generated: String,
+    segments: Vec<(Span, Location)>,
+}
+
+impl SourceMap {
+    pub fn new(generated: &str) -> Self {
+        SourceMap {
+            generated: generated.to_owned(),
+            segments: vec![],
+        }
+    }
+
+    /// Declares that the `span` of the generated source was copied verbatim from `original`,
+    /// the position of its first char. When spans overlap, the last added one wins.
+    pub fn add(&mut self, span: Span, original: Location) {
+        self.segments.push((span, original));
+    }
+
+    /// Translates the byte `offset` of the generated `source` to its original position,
+    /// or to its position in the generated source if it isn't mapped.
+    pub fn locate(&self, source: &str, offset: usize) -> Location {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|(span, _)| span.start <= offset && offset < span.end);
+
+        match segment {
+            Some((span, original)) => {
+                let offset = floor_char_boundary(source, offset).max(span.start);
+                original.clone().advance(&source[span.start..offset])
+            }
+            None => Location::in_source(&self.generated, source, offset),
+        }
+    }
+
+    /// Formats the diagnostics of `err` as `file:line:column: message`, one per line.
+    /// Diagnostics without a position are kept as they are.
+    pub fn render(&self, source: &str, err: &ParserError) -> String {
+        match err {
+            ParserError::Multiple(errors) => errors
+                .iter()
+                .map(|err| self.render(source, err))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            err => match err.span() {
+                Some(span) => format!("{}: {err}", self.locate(source, span.start)),
+                None => err.to_string(),
+            },
+        }
+    }
+}
+
+/// Clamps `offset` to the closest char boundary before it, so that it can be used to slice `text`.
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn location(file: &str, line: usize, column: usize) -> Location {
+        Location {
+            file: file.to_owned(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn locate_in_source() {
+        let source = "let a = 1;\nlet é = 2;\n";
+
+        assert_eq!(
+            Location::in_source("a.ql", source, 0),
+            location("a.ql", 1, 1)
+        );
+        assert_eq!(
+            Location::in_source("a.ql", source, 4),
+            location("a.ql", 1, 5)
+        );
+        assert_eq!(
+            Location::in_source("a.ql", source, 11),
+            location("a.ql", 2, 1)
+        );
+        // `é` takes two bytes, but a single column
+        assert_eq!(
+            Location::in_source("a.ql", source, 17),
+            location("a.ql", 2, 6)
+        );
+        assert_eq!(
+            Location::in_source("a.ql", source, 99),
+            location("a.ql", 3, 1)
+        );
+    }
+
+    #[test]
+    fn locate_with_source_map() {
+        // generated from the template `<p>{{ user.name + }}</p>` on line 3 of `page.html`,
+        // where the expression starts at column 7
+        let source = "let out = \"\";\nout = out + (user.name + );\n";
+        let expression = source.find("user").unwrap();
+        let mut map = SourceMap::new("<generated>");
+        map.add(
+            Span {
+                start: expression,
+                end: expression + "user.name + ".len(),
+            },
+            location("page.html", 3, 7),
+        );
+
+        assert_eq!(map.locate(source, expression), location("page.html", 3, 7));
+        assert_eq!(
+            map.locate(source, expression + 5),
+            location("page.html", 3, 12)
+        );
+        assert_eq!(map.locate(source, 0), location("<generated>", 1, 1));
+
+        let err = Parser::new(source).parse_program().unwrap_err();
+        assert_eq!(map.render(source, &err), format!("<generated>:2:26: {err}"));
+
+        let source = "user.name lett x = 1;";
+        let mut map = SourceMap::new("<generated>");
+        map.add(
+            Span {
+                start: 0,
+                end: source.len(),
+            },
+            location("page.html", 1, 4),
+        );
+        let err = Parser::new(source).parse_program().unwrap_err();
+        assert_eq!(map.render(source, &err), format!("page.html:1:14: {err}"));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    cell::RefCell,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use thiserror::Error;
+
+use crate::{evaluator::Evaluator, object::EvalError};
+
+/// Comment giving the next line the script should print.
+const EXPECT: &str = "// expect:";
+/// Comment giving the error the script should fail with.
+const EXPECT_ERROR: &str = "// expect-error:";
+
+/// Behavior a spec script describes with comments: `// expect: 7` for each line it prints, in order,
+/// and `// expect-error: DivisionByZero` if it fails, naming the error either like its variant or by its code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Spec {
+    /// Lines the script prints, with the line of the comment expecting them.
+    pub output: Vec<(usize, String)>,
+    /// Error the script fails with, with the line of the comment expecting it.
+    pub error: Option<(usize, String)>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SpecFailure {
+    #[error("Line {0} expects the output `{1}`, got `{2}`")]
+    WrongOutput(usize, String, String),
+    #[error("Line {0} expects the output `{1}`, but nothing else was printed")]
+    MissingOutput(usize, String),
+    #[error("The script printed `{0}`, which no line expects")]
+    UnexpectedOutput(String),
+    #[error("Line {0} expects the error `{1}`, but the script succeeded")]
+    MissingError(usize, String),
+    #[error("Line {0} expects the error `{1}`, got {2} ({3}): {4}")]
+    WrongError(usize, String, String, &'static str, String),
+    #[error("The script failed with {0} ({1}): {2}")]
+    UnexpectedError(String, &'static str, String),
+}
+
+impl Spec {
+    /// Reads the expectations of a script. Only comments written with `//` count, at the end of a line or on their own.
+    pub fn parse(source: &str) -> Self {
+        let mut spec = Spec::default();
+        for (i, line) in source.lines().enumerate() {
+            if let Some((_, expected)) = line.split_once(EXPECT) {
+                spec.output.push((i + 1, expected.trim().to_owned()));
+            } else if let Some((_, expected)) = line.split_once(EXPECT_ERROR) {
+                spec.error = Some((i + 1, expected.trim().to_owned()));
+            }
+        }
+
+        spec
+    }
+
+    /// Evaluates `source`, running the callbacks it schedules, and checks it behaves as described.
+    /// The output is compared before the error, so a script failing too early reports the lines it didn't print.
+    pub fn check(&self, source: &str) -> Result<(), SpecFailure> {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut evaluator = Evaluator::new(source);
+        evaluator.capture_output(output.clone());
+        let result = evaluator
+            .eval_program()
+            .and_then(|_| evaluator.run_event_loop());
+
+        let output = output.borrow();
+        let mut printed = output.lines();
+        for (line, expected) in &self.output {
+            match printed.next() {
+                Some(actual) if actual == expected => {}
+                Some(actual) => {
+                    return Err(SpecFailure::WrongOutput(
+                        *line,
+                        expected.clone(),
+                        actual.to_owned(),
+                    ))
+                }
+                None if result.is_err() && self.error.is_none() => {
+                    let err = result.unwrap_err();
+                    return Err(SpecFailure::UnexpectedError(
+                        error_name(&err),
+                        err.code(),
+                        err.to_string(),
+                    ));
+                }
+                None => return Err(SpecFailure::MissingOutput(*line, expected.clone())),
+            }
+        }
+        if let Some(actual) = printed.next() {
+            return Err(SpecFailure::UnexpectedOutput(actual.to_owned()));
+        }
+
+        match (result, &self.error) {
+            (Ok(()), None) => Ok(()),
+            (Ok(()), Some((line, expected))) => {
+                Err(SpecFailure::MissingError(*line, expected.clone()))
+            }
+            (Err(err), None) => Err(SpecFailure::UnexpectedError(
+                error_name(&err),
+                err.code(),
+                err.to_string(),
+            )),
+            (Err(err), Some((line, expected))) => {
+                let name = error_name(&err);
+                if *expected == name || expected == err.code() {
+                    Ok(())
+                } else {
+                    Err(SpecFailure::WrongError(
+                        *line,
+                        expected.clone(),
+                        name,
+                        err.code(),
+                        err.to_string(),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Name of the variant of the innermost error, the one of the parser error for scripts that don't parse.
+pub fn error_name(err: &EvalError) -> String {
+    let debug = match err.root_cause() {
+        EvalError::ParsingError(err) => format!("{err:?}"),
+        err => format!("{err:?}"),
+    };
+    debug
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+/// Checks the spec scripts, the `.ql` files of `dir` and its subdirectories, in the order of their paths.
+pub fn run_specs(dir: &Path) -> io::Result<Vec<(PathBuf, Result<(), SpecFailure>)>> {
+    let mut results = vec![];
+    for path in spec_files(dir)? {
+        let source = fs::read_to_string(&path)?;
+        let result = Spec::parse(&source).check(&source);
+        results.push((path, result));
+    }
+
+    Ok(results)
+}
+
+fn spec_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(spec_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "ql") {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        let source =
+            "println(1); // expect: 1\n\n// expect: 2\nprintln(2);\n1 / 0; // expect-error: E1007";
+        assert_eq!(
+            Spec::parse(source),
+            Spec {
+                output: vec![(1, "1".to_owned()), (3, "2".to_owned())],
+                error: Some((5, "E1007".to_owned())),
+            }
+        );
+    }
+
+    #[test]
+    fn check_spec() {
+        let tests = vec![
+            ("println(1 + 2); // expect: 3", Ok(())),
+            (
+                "println(1); 1 / 0; // expect: 1\n// expect-error: DivisionByZero",
+                Ok(()),
+            ),
+            ("1 / 0; // expect-error: E1007", Ok(())),
+            ("let x = ; // expect-error: UnexpectedToken", Ok(())),
+            (
+                "println(2); // expect: 3",
+                Err(SpecFailure::WrongOutput(1, "3".to_owned(), "2".to_owned())),
+            ),
+            (
+                "// expect: 1",
+                Err(SpecFailure::MissingOutput(1, "1".to_owned())),
+            ),
+            (
+                "println(1);",
+                Err(SpecFailure::UnexpectedOutput("1".to_owned())),
+            ),
+            (
+                "1; // expect-error: DivisionByZero",
+                Err(SpecFailure::MissingError(1, "DivisionByZero".to_owned())),
+            ),
+            (
+                "1 % 0; // expect-error: DivisionByZero",
+                Err(SpecFailure::WrongError(
+                    1,
+                    "DivisionByZero".to_owned(),
+                    "ModuloByZero".to_owned(),
+                    "E1008",
+                    "Modulo of zero isn't allowed".to_owned(),
+                )),
+            ),
+        ];
+
+        for (source, expected) in tests {
+            assert_eq!(Spec::parse(source).check(source), expected, "{source}");
+        }
+
+        // an error cutting the output short is reported rather than the missing lines
+        let source = "x; // expect: 1";
+        assert!(matches!(
+            Spec::parse(source).check(source),
+            Err(SpecFailure::UnexpectedError(name, "E1001", _)) if name == "IdentifierNotFound"
+        ));
+    }
+
+    #[test]
+    fn language_specs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("spec");
+        let results = run_specs(&dir).unwrap();
+        assert!(!results.is_empty());
+
+        let failures: Vec<String> = results
+            .into_iter()
+            .filter_map(|(path, result)| Some(format!("{}: {}", path.display(), result.err()?)))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
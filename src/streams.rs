@@ -0,0 +1,185 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, BufRead, Read, Write},
+    rc::Rc,
+    sync::mpsc::Sender,
+};
+
+/// Streams the built-in functions of a script use: `print` and `println` write to `stdout`,
+/// `read_line` reads from `stdin`, and the records of `log` go to `stderr` unless a log sink is installed.
+/// They are the streams of the process by default, and hosts can replace them to capture or feed the script.
+/// Tasks and actors run on other threads: what they write is forwarded to the streams of the evaluator
+/// that started them, and they can't read its input.
+pub struct Streams {
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+    pub stdin: Box<dyn BufRead>,
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Streams {
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            stdin: Box::new(io::BufReader::new(io::stdin())),
+        }
+    }
+}
+
+impl fmt::Debug for Streams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Streams").finish_non_exhaustive()
+    }
+}
+
+impl Streams {
+    /// Reads a line of `stdin` without its line ending, or `None` at the end of the input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.stdin.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let content = line.strip_suffix('\n').unwrap_or(&line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        Ok(Some(content.to_owned()))
+    }
+
+    /// Streams of a task or an actor, sending what it writes to `output`.
+    pub(crate) fn forwarded(output: Sender<Output>) -> Self {
+        Streams {
+            stdout: Box::new(ForwardedStream {
+                output: output.clone(),
+                stream: Output::Stdout,
+            }),
+            stderr: Box::new(ForwardedStream {
+                output,
+                stream: Output::Stderr,
+            }),
+            stdin: Box::new(NoInput),
+        }
+    }
+}
+
+/// Text written by a task or an actor, which the evaluator that started it writes to its own streams.
+#[derive(Debug)]
+pub(crate) enum Output {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// Writer sending what a task or an actor writes to one of the streams of the evaluator that started it.
+/// Once that evaluator is dropped, the text is discarded.
+struct ForwardedStream {
+    output: Sender<Output>,
+    stream: fn(String) -> Output,
+}
+
+impl Write for ForwardedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf).into_owned();
+        let _ = self.output.send((self.stream)(text));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Input of tasks and actors, which fails to read as the input of the host can't be shared with other threads.
+struct NoInput;
+
+impl NoInput {
+    fn error() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "tasks and actors can't read the input of the host",
+        )
+    }
+}
+
+impl Read for NoInput {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(Self::error())
+    }
+}
+
+impl BufRead for NoInput {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Err(Self::error())
+    }
+
+    fn consume(&mut self, _: usize) {}
+}
+
+/// Writer appending to a string shared with the host, which reads what the script wrote from it.
+/// Bytes that aren't valid UTF-8 are replaced.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(pub Rc<RefCell<String>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().push_str(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_lines() {
+        let mut streams = Streams {
+            stdin: Box::new("first\r\nsecond\n\nlast".as_bytes()),
+            ..Streams::default()
+        };
+
+        let lines: Vec<Option<String>> = (0..5).map(|_| streams.read_line().unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                Some("first".to_owned()),
+                Some("second".to_owned()),
+                Some(String::new()),
+                Some("last".to_owned()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn write_shared_buffer() {
+        let buffer = SharedBuffer::default();
+        let mut writer = buffer.clone();
+        write!(writer, "a{}", 1).unwrap();
+        writer.write_all(&[b'b', 0xFF]).unwrap();
+
+        assert_eq!(*buffer.0.borrow(), "a1b\u{FFFD}");
+    }
+
+    #[test]
+    fn forward_streams() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut streams = Streams::forwarded(sender);
+        write!(streams.stdout, "out").unwrap();
+        write!(streams.stderr, "err").unwrap();
+
+        let output: Vec<String> = receiver
+            .try_iter()
+            .map(|output| format!("{output:?}"))
+            .collect();
+        assert_eq!(output, vec!["Stdout(\"out\")", "Stderr(\"err\")"]);
+        assert!(streams.read_line().is_err());
+
+        // the text written once the receiver is gone is discarded
+        drop(receiver);
+        write!(streams.stdout, "lost").unwrap();
+    }
+}
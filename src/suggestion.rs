@@ -0,0 +1,101 @@
+/// Maximum number of suggestions shown in a diagnostic.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Computes the Levenshtein edit distance between two strings, counting chars rather than bytes.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    // distances between the current prefix of `a` and every prefix of `b`
+    let mut row: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b_ch) in b_chars.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_ch != *b_ch);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b_chars.len()]
+}
+
+/// Returns the candidates that are close enough to `target` to be plausible typos,
+/// sorted from the closest to the farthest.
+pub fn closest_matches<'a>(
+    target: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    // roughly one edit every three chars, so that swapped letters in short words still match
+    let max_distance = target.chars().count().div_ceil(3).max(1);
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(target, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    // ties are broken alphabetically to keep diagnostics deterministic
+    matches.sort();
+
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_owned())
+        .collect()
+}
+
+/// Formats suggestions as a hint appended to error messages.
+pub fn format_suggestions(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [suggestion] => format!(". Did you mean \"{suggestion}\"?"),
+        _ => {
+            let quoted = suggestions
+                .iter()
+                .map(|suggestion| format!("\"{suggestion}\""))
+                .collect::<Vec<String>>()
+                .join(", ");
+            format!(". Did you mean one of {quoted}?")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance() {
+        let tests = vec![
+            ("", "", 0),
+            ("abc", "", 3),
+            ("", "abc", 3),
+            ("name", "name", 0),
+            ("nmae", "name", 2),
+            ("kitten", "sitting", 3),
+            ("lett", "let", 1),
+            ("héllo", "hello", 1),
+        ];
+
+        for (a, b, expected) in tests {
+            assert_eq!(levenshtein(a, b), expected, "{a} -> {b}");
+        }
+    }
+
+    #[test]
+    fn suggestions() {
+        let candidates = ["name", "age", "names", "email"];
+
+        assert_eq!(closest_matches("nam", candidates), vec!["name"]);
+        assert_eq!(closest_matches("namee", candidates), vec!["name", "names"]);
+        assert!(closest_matches("zzz", candidates).is_empty());
+
+        assert_eq!(format_suggestions(&[]), "");
+        assert_eq!(
+            format_suggestions(&["a".to_owned(), "b".to_owned()]),
+            ". Did you mean one of \"a\", \"b\"?"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    ast::ParserError,
+    environment::Environment,
+    evaluator::Evaluator,
+    lexer::Lexer,
+    object::{Context, EvalError, Object},
+    source_map::Location,
+    token::{Span, TokenKind},
+};
+
+/// Renders `text`, replacing every `{{ expr }}` placeholder with the value of `expr` evaluated in `env`.
+/// Strings are inserted without quotes, while `null` and unit values are rendered as nothing.
+pub fn render_template(text: &str, env: Rc<RefCell<Environment>>) -> Result<String, EvalError> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = 0;
+
+    while let Some(open) = text[rest..].find("{{") {
+        let start = rest + open;
+        output.push_str(&text[rest..start]);
+
+        let source_start = start + "{{".len();
+        let Some(source_end) = placeholder_end(&text[source_start..]) else {
+            return Err(ParserError::UnterminatedPlaceholder(Span {
+                start,
+                end: text.len(),
+            })
+            .into());
+        };
+        let source = &text[source_start..source_start + source_end];
+
+        let value = Evaluator::with_env(source, env.clone())
+            .eval_program()
+            .with_context(|| {
+                let location = Location::in_source("", text, start);
+                format!(
+                    "while rendering the placeholder `{{{{{source}}}}}` at {}:{}",
+                    location.line, location.column
+                )
+            })?;
+
+        match value.last() {
+            Some(Object::StringValue(text)) => output.push_str(text),
+            Some(Object::NullValue | Object::UnitValue) | None => {}
+            Some(value) => output.push_str(&value.to_string()),
+        }
+
+        rest = source_start + source_end + "}}".len();
+    }
+
+    output.push_str(&text[rest..]);
+    Ok(output)
+}
+
+/// Finds the `}}` closing a placeholder, skipping the braces of maps, blocks and strings within it.
+fn placeholder_end(source: &str) -> Option<usize> {
+    let mut lexer = Lexer::new(source);
+    let mut depth = 0usize;
+
+    loop {
+        let token = lexer.next_token();
+
+        match token.kind {
+            TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightBrace if depth == 0 => {
+                return source[token.span.end..]
+                    .starts_with('}')
+                    .then_some(token.span.start);
+            }
+            TokenKind::RightBrace => depth -= 1,
+            TokenKind::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_templates() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.borrow_mut()
+            .set("name".to_owned(), Object::StringValue("Ada".to_owned()));
+        env.borrow_mut().set(
+            "items".to_owned(),
+            Object::ArrayValue(vec![Object::IntegerValue(1), Object::IntegerValue(2)]),
+        );
+
+        let tests = vec![
+            ("Hello {{ name }}!", Ok("Hello Ada!")),
+            ("{{ len(items) }} items, first {{items[0]}}", Ok("2 items, first 1")),
+            ("no placeholders", Ok("no placeholders")),
+            ("{{ ({\"a\": {\"b\": 1}})[\"a\"][\"b\"] }}", Ok("1")),
+            ("{{ \"}}\" }}", Ok("}}")),
+            ("[{{ null }}{{ }}]", Ok("[]")),
+            ("{{ let x = 2; x * 3 }}", Ok("6")),
+            (
+                "Hello {{ name !",
+                Err("Parsing error: Unterminated template placeholder starting at byte 6, it must be closed with `}}`"),
+            ),
+            (
+                "line\n  {{ nme }}",
+                Err("Identifier not found: nme\n    while rendering the placeholder `{{ nme }}` at 2:3"),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let result = render_template(input, env.clone()).map_err(|err| err.to_string());
+            assert_eq!(
+                result,
+                expected.map(str::to_owned).map_err(str::to_owned),
+                "{input}"
+            );
+        }
+
+        // bindings made by placeholders are visible to the host
+        render_template("{{ let shown = true; }}", env.clone()).unwrap();
+        assert_eq!(
+            env.borrow().get("shown").unwrap(),
+            Object::BooleanValue(true)
+        );
+    }
+}
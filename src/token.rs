@@ -4,24 +4,37 @@ use std::fmt;
 pub struct Token {
     pub kind: TokenKind,
     pub literal: String,
-    // TODO: add source location for more accurate debugging info
-    // source: Source,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Location of a token in the source, as a range of byte offsets.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum TokenKind {
     Illegal,
     Eof,
 
     Identifier,
+    /// Name of a loop, like `'outer`.
+    Label,
     Integer,
+    Float,
     String,
 
     Assign,
     Plus,
     Minus,
     Bang,
+    Tilde,
+    /// `?`, returning `Err` and `None` values from the enclosing function.
+    Question,
     Asterisk,
+    DoubleAsterisk,
     Slash,
     Percentage,
 
@@ -33,10 +46,23 @@ pub enum TokenKind {
     GreaterThanEqual,
     AndAnd,
     OrOr,
+    Ampersand,
+    Pipe,
+    /// `|>`, passing a value as the first argument of a call.
+    Pipeline,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
 
     Comma,
     Semicolon,
     Colon,
+    FatArrow,
+    DotDot,
+    DotDotEqual,
+    Ellipsis,
+    Dot,
+    At,
 
     LeftParen,
     RightParen,
@@ -47,26 +73,59 @@ pub enum TokenKind {
 
     Function,
     Let,
+    Var,
     True,
     False,
+    Null,
     If,
     Else,
     Return,
+    For,
+    In,
+    Match,
+    Case,
+    Loop,
+    Break,
+    Continue,
+    Do,
+    While,
+    Switch,
+    Struct,
+    Enum,
 }
 
+/// Keywords recognized by the lexer, which can't be used as identifiers.
+pub const KEYWORDS: [(&str, TokenKind); 21] = [
+    ("fn", TokenKind::Function),
+    ("let", TokenKind::Let),
+    ("var", TokenKind::Var),
+    ("true", TokenKind::True),
+    ("false", TokenKind::False),
+    ("null", TokenKind::Null),
+    ("if", TokenKind::If),
+    ("else", TokenKind::Else),
+    ("return", TokenKind::Return),
+    ("for", TokenKind::For),
+    ("in", TokenKind::In),
+    ("match", TokenKind::Match),
+    ("case", TokenKind::Case),
+    ("loop", TokenKind::Loop),
+    ("break", TokenKind::Break),
+    ("continue", TokenKind::Continue),
+    ("do", TokenKind::Do),
+    ("while", TokenKind::While),
+    ("switch", TokenKind::Switch),
+    ("struct", TokenKind::Struct),
+    ("enum", TokenKind::Enum),
+];
+
 impl TokenKind {
     /// Matches keywords.
     pub fn lookup_identifier(identifier: &str) -> TokenKind {
-        match identifier {
-            "fn" => TokenKind::Function,
-            "let" => TokenKind::Let,
-            "true" => TokenKind::True,
-            "false" => TokenKind::False,
-            "if" => TokenKind::If,
-            "else" => TokenKind::Else,
-            "return" => TokenKind::Return,
-            _ => TokenKind::Identifier,
-        }
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == identifier)
+            .map_or(TokenKind::Identifier, |(_, kind)| kind.clone())
     }
 }
 
@@ -77,14 +136,19 @@ impl fmt::Display for TokenKind {
             TokenKind::Eof => write!(f, "eof"),
 
             TokenKind::Identifier => write!(f, "identifier"),
+            TokenKind::Label => write!(f, "label"),
             TokenKind::Integer => write!(f, "integer"),
+            TokenKind::Float => write!(f, "float"),
             TokenKind::String => write!(f, "string"),
 
             TokenKind::Assign => write!(f, "="),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::Minus => write!(f, "-"),
             TokenKind::Bang => write!(f, "!"),
+            TokenKind::Tilde => write!(f, "~"),
+            TokenKind::Question => write!(f, "?"),
             TokenKind::Asterisk => write!(f, "*"),
+            TokenKind::DoubleAsterisk => write!(f, "**"),
             TokenKind::Slash => write!(f, "/"),
             TokenKind::Percentage => write!(f, "%"),
 
@@ -96,10 +160,22 @@ impl fmt::Display for TokenKind {
             TokenKind::GreaterThanEqual => write!(f, ">="),
             TokenKind::AndAnd => write!(f, "&&"),
             TokenKind::OrOr => write!(f, "||"),
+            TokenKind::Ampersand => write!(f, "&"),
+            TokenKind::Pipe => write!(f, "|"),
+            TokenKind::Pipeline => write!(f, "|>"),
+            TokenKind::Caret => write!(f, "^"),
+            TokenKind::ShiftLeft => write!(f, "<<"),
+            TokenKind::ShiftRight => write!(f, ">>"),
 
             TokenKind::Comma => write!(f, ","),
             TokenKind::Semicolon => write!(f, ";"),
             TokenKind::Colon => write!(f, ":"),
+            TokenKind::FatArrow => write!(f, "=>"),
+            TokenKind::DotDot => write!(f, ".."),
+            TokenKind::DotDotEqual => write!(f, "..="),
+            TokenKind::Ellipsis => write!(f, "..."),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::At => write!(f, "@"),
             TokenKind::LeftParen => write!(f, "("),
             TokenKind::RightParen => write!(f, ")"),
             TokenKind::LeftBrace => write!(f, "{{"),
@@ -109,17 +185,25 @@ impl fmt::Display for TokenKind {
 
             TokenKind::Function => write!(f, "fn"),
             TokenKind::Let => write!(f, "let"),
+            TokenKind::Var => write!(f, "var"),
             TokenKind::True => write!(f, "true"),
             TokenKind::False => write!(f, "false"),
+            TokenKind::Null => write!(f, "null"),
             TokenKind::If => write!(f, "if"),
             TokenKind::Else => write!(f, "else"),
             TokenKind::Return => write!(f, "return"),
+            TokenKind::For => write!(f, "for"),
+            TokenKind::In => write!(f, "in"),
+            TokenKind::Match => write!(f, "match"),
+            TokenKind::Case => write!(f, "case"),
+            TokenKind::Loop => write!(f, "loop"),
+            TokenKind::Break => write!(f, "break"),
+            TokenKind::Continue => write!(f, "continue"),
+            TokenKind::Do => write!(f, "do"),
+            TokenKind::While => write!(f, "while"),
+            TokenKind::Switch => write!(f, "switch"),
+            TokenKind::Struct => write!(f, "struct"),
+            TokenKind::Enum => write!(f, "enum"),
         }
     }
 }
-
-// #[derive(Debug, PartialEq, Eq)]
-// pub struct Source {
-//     filename: String,
-//     pos: (u32, u32),
-// }
@@ -0,0 +1,45 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits a string into extended grapheme clusters (what users perceive as characters), following UAX #29.
+pub fn graphemes(text: &str) -> Vec<&str> {
+    text.graphemes(true).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grapheme_clusters() {
+        let tests = vec![
+            ("", vec![]),
+            ("abc", vec!["a", "b", "c"]),
+            ("e\u{301}a", vec!["e\u{301}", "a"]),
+            ("a\r\nb", vec!["a", "\r\n", "b"]),
+            ("👍🏽!", vec!["👍🏽", "!"]),
+            ("👨‍👩‍👧", vec!["👨‍👩‍👧"]),
+            // a joiner only joins emoji, it stays with the letter before it
+            ("a\u{200D}b", vec!["a\u{200D}", "b"]),
+            ("🇮🇹🇫🇷", vec!["🇮🇹", "🇫🇷"]),
+            // consonants joined by a virama form a single conjunct cluster
+            ("नमस्ते", vec!["न", "म", "स्ते"]),
+            // marks of any script, spacing or not, extend the previous character
+            ("নমস্কার", vec!["ন", "ম", "স্কা", "র"]),
+            ("தமிழ்", vec!["த", "மி", "ழ்"]),
+            ("a\u{1AB0}\u{0F71}b", vec!["a\u{1AB0}\u{0F71}", "b"]),
+            // decomposed Hangul syllables are single clusters, like precomposed ones
+            (
+                "\u{1100}\u{1161}\u{11A8}\u{1100}",
+                vec!["\u{1100}\u{1161}\u{11A8}", "\u{1100}"],
+            ),
+            ("한\u{11A8}글", vec!["한\u{11A8}", "글"]),
+            ("각\u{1161}", vec!["각", "\u{1161}"]),
+            // digits, underscores and middle dots don't extend the previous character
+            ("a1_·", vec!["a", "1", "_", "·"]),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(graphemes(input), expected, "{input}");
+        }
+    }
+}
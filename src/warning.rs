@@ -0,0 +1,34 @@
+use std::fmt;
+
+use crate::token::Span;
+
+/// Diagnostic reported while evaluating a program, which doesn't stop the evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// A function declared with `@deprecated`, or a deprecated native function, was called.
+    /// Only the first call of each function is reported.
+    DeprecatedCall {
+        function: String,
+        note: String,
+        span: Span,
+    },
+}
+
+impl Warning {
+    /// Location of the code that caused the warning.
+    pub fn span(&self) -> Span {
+        match self {
+            Warning::DeprecatedCall { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::DeprecatedCall { function, note, .. } => {
+                write!(f, "`{function}` is deprecated: {note}")
+            }
+        }
+    }
+}